@@ -0,0 +1,56 @@
+//! Map, query, protect and unmap a page with the easy page table on every memory backend.
+use hvisor_pt::common::frame::{MemAttr, MemType};
+use hvisor_pt::memory::{PooledPageTableMem, SimPageTableMem, StdLog, SIM_PMEM_BASE, SIM_POOL_SIZE};
+use hvisor_pt::{EasyPageTable, EasyTLB, PageTableApi, PageTableMemExec};
+
+fn main() {
+    println!("running Test...");
+    test::<PooledPageTableMem<StdLog>>();
+    test::<SimPageTableMem>();
+}
+
+fn test<M: PageTableMemExec>() {
+    // A frame of the simulated physical memory past its table pool, which host memory covers too
+    let paddr = SIM_PMEM_BASE + SIM_POOL_SIZE;
+    // Memory reserved by the host must never be mapped
+    let reserved = paddr + 0x10000;
+    let mut pt = EasyPageTable::<M>::new(&[(reserved, 0x1000)]).expect("out of page table memory");
+    println!("PageTable Inited, root at {:#x}", pt.root());
+    let r1 = pt.map(0x1000, paddr, 4096, MemAttr::default());
+    assert!(r1.is_ok());
+    println!("map ok");
+    let r2 = pt.query(0x1020);
+    assert!(r2.is_ok());
+    assert!(r2.unwrap() == (0x1000, paddr, 4096, MemAttr::default()));
+    println!("query_after_map ok");
+    let mut tlb = EasyTLB::new();
+    let attr = MemAttr {
+        writable: false,
+        ..MemAttr::default()
+    };
+    let r3 = pt.protect(0x1000, attr, &mut tlb);
+    assert!(r3.is_ok());
+    assert!(pt.query(0x1020).unwrap() == (0x1000, paddr, 4096, attr));
+    println!("protect ok");
+    // Changing the memory type in place would need break-before-make
+    let device = MemAttr {
+        mem_type: MemType::DeviceNGnRE,
+        ..attr
+    };
+    assert!(pt.protect(0x1000, device, &mut tlb).is_err());
+    assert!(pt.query(0x1020).unwrap() == (0x1000, paddr, 4096, attr));
+    println!("protect_mem_type rejected");
+    let r4 = pt.unmap(0x1000, &mut tlb);
+    assert!(r4.is_ok());
+    println!("unmap ok");
+    let r5 = pt.query(0x1010);
+    assert!(r5.is_err());
+    println!("query_after_unmap ok ");
+    let r6 = pt.map(0x1000, reserved, 4096, MemAttr::default());
+    assert!(r6.is_err());
+    println!("map_reserved rejected");
+    // Nor may the page tables themselves
+    let r7 = pt.map(0x1000, pt.root(), 4096, MemAttr::default());
+    assert!(r7.is_err());
+    println!("map_page_table rejected");
+}
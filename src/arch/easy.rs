@@ -1,4 +1,5 @@
 //! A toy page table implementation for testing.
+use alloc::{vec, vec::Vec};
 use vstd::prelude::*;
 
 use super::PageTableApi;
@@ -7,33 +8,291 @@ use crate::{
     common::{
         addr::{PAddr, PAddrExec, VAddrExec},
         arch::{PTArchExec, PTArchLevelExec},
-        frame::{FrameExec, FrameSize, MemAttr},
+        frame::{FrameExec, FrameSize, MemAttr, MemType, PageState, Shareability, SwBits},
         pte::{ExecPTE, GhostPTE},
         PagingResult,
     },
-    spec::memory::PageTableMemExec,
+    spec::memory::{PageTableMemExec, TLBExec, TLBOp, TLBTag},
     imp::interface::PTConstantsExec,
 };
 
 verus! {
 
+/// Mask of the address bits of an easy PTE.
+pub const EASY_ADDR_MASK: u64 = 0x000f_ffff_ffff_f000;
+
+/// Memory type selected by the 3-bit attribute index of an easy PTE (like a MAIR index).
+pub open spec fn spec_mem_type_from_bits(bits: u64) -> MemType {
+    if bits == 0 {
+        MemType::NormalWriteBack
+    } else if bits == 1 {
+        MemType::NormalWriteThrough
+    } else if bits == 2 {
+        MemType::NormalNonCacheable
+    } else if bits == 3 {
+        MemType::DeviceNGnRnE
+    } else {
+        MemType::DeviceNGnRE
+    }
+}
+
+/// Attribute index of `mem_type` in an easy PTE.
+pub open spec fn spec_mem_type_to_bits(mem_type: MemType) -> u64 {
+    match mem_type {
+        MemType::NormalWriteBack => 0,
+        MemType::NormalWriteThrough => 1,
+        MemType::NormalNonCacheable => 2,
+        MemType::DeviceNGnRnE => 3,
+        MemType::DeviceNGnRE => 4,
+    }
+}
+
+/// Shareability encoded by the 2-bit SH field of an easy PTE. The reserved value `0b01` is
+/// treated as non-shareable.
+pub open spec fn spec_shareability_from_bits(bits: u64) -> Shareability {
+    if bits == 0b10 {
+        Shareability::OuterShareable
+    } else if bits == 0b11 {
+        Shareability::InnerShareable
+    } else {
+        Shareability::NonShareable
+    }
+}
+
+/// SH field of `shareability` in an easy PTE.
+pub open spec fn spec_shareability_to_bits(shareability: Shareability) -> u64 {
+    match shareability {
+        Shareability::NonShareable => 0b00,
+        Shareability::OuterShareable => 0b10,
+        Shareability::InnerShareable => 0b11,
+    }
+}
+
+/// Software bits encoded by the 4-bit software field of an easy PTE.
+pub open spec fn spec_sw_bits_from_bits(bits: u64) -> SwBits {
+    let state = if bits & 0b11 == 0 {
+        PageState::Owned
+    } else if bits & 0b11 == 1 {
+        PageState::SharedOwned
+    } else if bits & 0b11 == 2 {
+        PageState::SharedBorrowed
+    } else {
+        PageState::Donated
+    };
+    SwBits { state, pinned: bits & 0b100 != 0, mmio_emulated: bits & 0b1000 != 0 }
+}
+
+/// Software field of `sw_bits` in an easy PTE.
+pub open spec fn spec_sw_bits_to_bits(sw_bits: SwBits) -> u64 {
+    let a: u64 = match sw_bits.state {
+        PageState::Owned => 0,
+        PageState::SharedOwned => 1,
+        PageState::SharedBorrowed => 2,
+        PageState::Donated => 3,
+    };
+    let b: u64 = if sw_bits.pinned {
+        0b100
+    } else {
+        0
+    };
+    let c: u64 = if sw_bits.mmio_emulated {
+        0b1000
+    } else {
+        0
+    };
+    a | b | c
+}
+
+/// (EXEC-MODE) Memory type selected by the attribute index of an easy PTE.
+fn mem_type_from_bits(bits: u64) -> (res: MemType)
+    ensures
+        res == spec_mem_type_from_bits(bits),
+{
+    if bits == 0 {
+        MemType::NormalWriteBack
+    } else if bits == 1 {
+        MemType::NormalWriteThrough
+    } else if bits == 2 {
+        MemType::NormalNonCacheable
+    } else if bits == 3 {
+        MemType::DeviceNGnRnE
+    } else {
+        MemType::DeviceNGnRE
+    }
+}
+
+/// (EXEC-MODE) Attribute index of `mem_type` in an easy PTE.
+fn mem_type_to_bits(mem_type: MemType) -> (res: u64)
+    ensures
+        res == spec_mem_type_to_bits(mem_type),
+{
+    match mem_type {
+        MemType::NormalWriteBack => 0,
+        MemType::NormalWriteThrough => 1,
+        MemType::NormalNonCacheable => 2,
+        MemType::DeviceNGnRnE => 3,
+        MemType::DeviceNGnRE => 4,
+    }
+}
+
+/// (EXEC-MODE) Shareability encoded by the SH field of an easy PTE.
+fn shareability_from_bits(bits: u64) -> (res: Shareability)
+    ensures
+        res == spec_shareability_from_bits(bits),
+{
+    if bits == 0b10 {
+        Shareability::OuterShareable
+    } else if bits == 0b11 {
+        Shareability::InnerShareable
+    } else {
+        Shareability::NonShareable
+    }
+}
+
+/// (EXEC-MODE) SH field of `shareability` in an easy PTE.
+fn shareability_to_bits(shareability: Shareability) -> (res: u64)
+    ensures
+        res == spec_shareability_to_bits(shareability),
+{
+    match shareability {
+        Shareability::NonShareable => 0b00,
+        Shareability::OuterShareable => 0b10,
+        Shareability::InnerShareable => 0b11,
+    }
+}
+
+/// (EXEC-MODE) Software bits encoded by the software field of an easy PTE.
+fn sw_bits_from_bits(bits: u64) -> (res: SwBits)
+    ensures
+        res == spec_sw_bits_from_bits(bits),
+{
+    let state = if bits & 0b11 == 0 {
+        PageState::Owned
+    } else if bits & 0b11 == 1 {
+        PageState::SharedOwned
+    } else if bits & 0b11 == 2 {
+        PageState::SharedBorrowed
+    } else {
+        PageState::Donated
+    };
+    SwBits { state, pinned: bits & 0b100 != 0, mmio_emulated: bits & 0b1000 != 0 }
+}
+
+/// (EXEC-MODE) Software field of `sw_bits` in an easy PTE.
+fn sw_bits_to_bits(sw_bits: SwBits) -> (res: u64)
+    ensures
+        res == spec_sw_bits_to_bits(sw_bits),
+{
+    let a: u64 = match sw_bits.state {
+        PageState::Owned => 0,
+        PageState::SharedOwned => 1,
+        PageState::SharedBorrowed => 2,
+        PageState::Donated => 3,
+    };
+    let b: u64 = if sw_bits.pinned {
+        0b100
+    } else {
+        0
+    };
+    let c: u64 = if sw_bits.mmio_emulated {
+        0b1000
+    } else {
+        0
+    };
+    a | b | c
+}
+
+/// Lemma. The software field decodes back to its state, pinned and MMIO-emulated parts.
+proof fn lemma_sw_bits_fields(state: u64, pinned: u64, mmio: u64)
+    by (bit_vector)
+    requires
+        state < 4,
+        pinned == 0 || pinned == 0b100,
+        mmio == 0 || mmio == 0b1000,
+    ensures
+        (state | pinned | mmio) < 16,
+        (state | pinned | mmio) & 0b11 == state,
+        ((state | pinned | mmio) & 0b100 != 0) == (pinned != 0),
+        ((state | pinned | mmio) & 0b1000 != 0) == (mmio != 0),
+{
+}
+
+/// Lemma. Every attribute field of an encoded easy PTE decodes back to the value encoded.
+///
+/// `val` is the encoding of `to_u64`, with one argument per field.
+proof fn lemma_attr_fields(
+    val: u64,
+    addr: u64,
+    readable: u64,
+    writable: u64,
+    executable: u64,
+    user: u64,
+    mem_type: u64,
+    huge: u64,
+    valid: u64,
+    accessed: u64,
+    dirty: u64,
+    sh: u64,
+    global: u64,
+    sw: u64,
+)
+    by (bit_vector)
+    requires
+        readable == 0 || readable == 0b100,
+        writable == 0 || writable == 0b1000,
+        executable == 0 || executable == 0b10000,
+        user == 0 || user == 0b100000,
+        mem_type < 8,
+        huge == 0 || huge == 0b10,
+        valid == 0 || valid == 0b1,
+        accessed == 0 || accessed == 0b10000000,
+        dirty == 0 || dirty == 0b100000000,
+        sh < 4,
+        global == 0 || global == 0b1000000,
+        sw < 16,
+        val == (addr & 0x000f_ffff_ffff_f000u64) | readable | writable | executable | user | (
+        mem_type << 9) | huge | valid | accessed | dirty | (sh << 52) | global | (sw << 54),
+    ensures
+        (val & 0b100 != 0) == (readable != 0),
+        (val & 0b1000 != 0) == (writable != 0),
+        (val & 0b10000 != 0) == (executable != 0),
+        (val & 0b100000 != 0) == (user != 0),
+        (val >> 9) & 0b111 == mem_type,
+        (val >> 52) & 0b11 == sh,
+        (val & 0b1000000 != 0) == (global != 0),
+        (val >> 54) & 0b1111 == sw,
+{
+}
+
 /// Easy ghost PTE.
 ///
-/// |addr: 63-12||padding: 11-7||attr: 6-2||huge: 1||valid: 0|
+/// |padding: 63-58||sw: 57-54||sh: 53-52||addr: 51-12||memtype: 11-9||dirty: 8||accessed: 7|
+/// |global: 6||attr: 5-2||huge: 1||valid: 0|
+///
+/// The software bits hold |mmio_emulated: 57||pinned: 56||state: 55-54|.
 pub struct EasyGhostPTE {
     pub addr: PAddr,
     pub attr: MemAttr,
     pub huge: bool,
     pub valid: bool,
+    pub accessed: bool,
+    pub dirty: bool,
 }
 
 impl GhostPTE for EasyGhostPTE {
     open spec fn new(addr: PAddr, attr: MemAttr, huge: bool) -> Self {
-        Self { addr, attr, huge, valid: true }
+        Self { addr, attr, huge, valid: true, accessed: false, dirty: false }
     }
 
     open spec fn empty() -> Self {
-        Self { addr: PAddr(0nat), attr: MemAttr::spec_default(), huge: false, valid: false }
+        Self {
+            addr: PAddr(0nat),
+            attr: MemAttr::spec_default(),
+            huge: false,
+            valid: false,
+            accessed: false,
+            dirty: false,
+        }
     }
 
     open spec fn addr(self) -> PAddr {
@@ -44,6 +303,14 @@ impl GhostPTE for EasyGhostPTE {
         self.attr
     }
 
+    open spec fn global(self) -> bool {
+        self.attr.global
+    }
+
+    open spec fn sw_bits(self) -> SwBits {
+        self.attr.sw_bits
+    }
+
     open spec fn valid(self) -> bool {
         self.valid
     }
@@ -52,25 +319,61 @@ impl GhostPTE for EasyGhostPTE {
         self.huge
     }
 
+    open spec fn accessed(self) -> bool {
+        self.accessed
+    }
+
+    open spec fn dirty(self) -> bool {
+        self.dirty
+    }
+
+    open spec fn set_accessed(self) -> Self {
+        Self { accessed: true, ..self }
+    }
+
+    open spec fn set_dirty(self) -> Self {
+        Self { dirty: true, ..self }
+    }
+
+    open spec fn clear_dirty(self) -> Self {
+        Self { dirty: false, ..self }
+    }
+
     open spec fn from_u64(val: u64) -> Self {
-        let addr = PAddr((val >> 12 << 12) as nat);
+        let addr = PAddr((val & EASY_ADDR_MASK) as nat);
         let readable = val & 0b100 != 0;
         let writable = val & 0b1000 != 0;
         let executable = val & 0b10000 != 0;
         let user_accessible = val & 0b100000 != 0;
-        let device = val & 0b1000000 != 0;
+        let mem_type = spec_mem_type_from_bits((val >> 9) & 0b111);
+        let shareability = spec_shareability_from_bits((val >> 52) & 0b11);
+        let global = val & 0b1000000 != 0;
+        let sw_bits = spec_sw_bits_from_bits((val >> 54) & 0b1111);
         let huge = val & 0b10 != 0;
         let valid = val & 0b1 != 0;
+        let accessed = val & 0b10000000 != 0;
+        let dirty = val & 0b100000000 != 0;
         Self {
             addr,
-            attr: MemAttr { readable, writable, executable, user_accessible, device },
+            attr: MemAttr {
+                readable,
+                writable,
+                executable,
+                user_accessible,
+                mem_type,
+                shareability,
+                global,
+                sw_bits,
+            },
             huge,
             valid,
+            accessed,
+            dirty,
         }
     }
 
     open spec fn to_u64(self) -> u64 {
-        let a = self.addr.0 as u64 >> 12 << 12;
+        let a = self.addr.0 as u64 & EASY_ADDR_MASK;
         let b = if self.attr.readable {
             0b100
         } else {
@@ -91,11 +394,7 @@ impl GhostPTE for EasyGhostPTE {
         } else {
             0
         };
-        let f = if self.attr.device {
-            0b1000000
-        } else {
-            0
-        };
+        let f = spec_mem_type_to_bits(self.attr.mem_type) << 9;
         let g = if self.huge {
             0b10
         } else {
@@ -106,14 +405,31 @@ impl GhostPTE for EasyGhostPTE {
         } else {
             0
         };
-        a | b | c | d | e | f | g | h
+        let i = if self.accessed {
+            0b10000000
+        } else {
+            0
+        };
+        let j = if self.dirty {
+            0b100000000
+        } else {
+            0
+        };
+        let k = spec_shareability_to_bits(self.attr.shareability) << 52;
+        let l = if self.attr.global {
+            0b1000000
+        } else {
+            0
+        };
+        let m = spec_sw_bits_to_bits(self.attr.sw_bits) << 54;
+        a | b | c | d | e | f | g | h | i | j | k | l | m
     }
 
     proof fn lemma_empty_invalid() {
     }
 
     proof fn lemma_from_0_invalid() {
-        admit()
+        assert(0u64 & 0b1 == 0) by (bit_vector);
     }
 
     proof fn lemma_from_to_u64_inverse(val: u64) {
@@ -123,33 +439,132 @@ impl GhostPTE for EasyGhostPTE {
     proof fn lemma_new_keeps_value(addr: PAddr, attr: MemAttr, huge: bool) {
     }
 
+    proof fn lemma_encoding_keeps_attr(addr: PAddr, attr: MemAttr, huge: bool) {
+        let val = Self::new(addr, attr, huge).to_u64();
+        let state: u64 = match attr.sw_bits.state {
+            PageState::Owned => 0,
+            PageState::SharedOwned => 1,
+            PageState::SharedBorrowed => 2,
+            PageState::Donated => 3,
+        };
+        let pinned: u64 = if attr.sw_bits.pinned {
+            0b100
+        } else {
+            0
+        };
+        let mmio: u64 = if attr.sw_bits.mmio_emulated {
+            0b1000
+        } else {
+            0
+        };
+        lemma_sw_bits_fields(state, pinned, mmio);
+        let sw = spec_sw_bits_to_bits(attr.sw_bits);
+        assert(spec_sw_bits_from_bits(sw) == attr.sw_bits);
+        lemma_attr_fields(
+            val,
+            addr.0 as u64,
+            if attr.readable {
+                0b100
+            } else {
+                0
+            },
+            if attr.writable {
+                0b1000
+            } else {
+                0
+            },
+            if attr.executable {
+                0b10000
+            } else {
+                0
+            },
+            if attr.user_accessible {
+                0b100000
+            } else {
+                0
+            },
+            spec_mem_type_to_bits(attr.mem_type),
+            if huge {
+                0b10
+            } else {
+                0
+            },
+            0b1,
+            0,
+            0,
+            spec_shareability_to_bits(attr.shareability),
+            if attr.global {
+                0b1000000
+            } else {
+                0
+            },
+            sw,
+        );
+        // The memory type and shareability encodings are inverted by their decodings
+        assert(spec_mem_type_from_bits(spec_mem_type_to_bits(attr.mem_type)) == attr.mem_type);
+        assert(spec_shareability_from_bits(spec_shareability_to_bits(attr.shareability))
+            == attr.shareability);
+        assert(Self::from_u64(val).attr() == attr);
+    }
+
+    proof fn lemma_sw_bits_in_attr(pte: Self) {
+    }
+
     proof fn lemma_eq_by_u64(pte1: Self, pte2: Self) {
         admit()
     }
+
+    proof fn lemma_set_accessed(pte: Self) {
+    }
+
+    proof fn lemma_set_dirty(pte: Self) {
+    }
+
+    proof fn lemma_clear_dirty(pte: Self) {
+    }
 }
 
 /// Easy executable PTE implementation.
 ///
-/// |addr: 63-12||padding: 11-7||attr: 6-2||huge: 1||valid: 0|
+/// |padding: 63-58||sw: 57-54||sh: 53-52||addr: 51-12||memtype: 11-9||dirty: 8||accessed: 7|
+/// |global: 6||attr: 5-2||huge: 1||valid: 0|
+///
+/// The software bits hold |mmio_emulated: 57||pinned: 56||state: 55-54|.
 #[derive(Clone)]
 pub struct EasyExecPTE {
     pub addr: PAddrExec,
     pub attr: MemAttr,
     pub huge: bool,
     pub valid: bool,
+    pub accessed: bool,
+    pub dirty: bool,
 }
 
 impl ExecPTE<EasyGhostPTE> for EasyExecPTE {
     open spec fn view(self) -> EasyGhostPTE {
-        EasyGhostPTE { addr: self.addr@, attr: self.attr, huge: self.huge, valid: self.valid }
+        EasyGhostPTE {
+            addr: self.addr@,
+            attr: self.attr,
+            huge: self.huge,
+            valid: self.valid,
+            accessed: self.accessed,
+            dirty: self.dirty,
+        }
     }
 
     fn new(addr: PAddrExec, attr: MemAttr, huge: bool) -> Self {
-        Self { addr, attr, huge, valid: true }
+        Self { addr, attr, huge, valid: true, accessed: false, dirty: false }
     }
 
     fn empty() -> Self {
-        Self { addr: PAddrExec(0), attr: MemAttr::default(), huge: false, valid: false }
+        Self {
+            addr: PAddrExec(0),
+            attr: MemAttr::default(),
+            huge: false,
+            valid: false,
+            accessed: false,
+            dirty: false,
+        }
     }
 
     fn addr(&self) -> (res: PAddrExec) {
@@ -164,29 +579,72 @@ impl ExecPTE<EasyGhostPTE> for EasyExecPTE {
         self.huge
     }
 
+    fn global(&self) -> (res: bool) {
+        self.attr.global
+    }
+
+    fn sw_bits(&self) -> (res: SwBits) {
+        self.attr.sw_bits
+    }
+
     fn valid(&self) -> (res: bool) {
         self.valid
     }
 
+    fn accessed(&self) -> (res: bool) {
+        self.accessed
+    }
+
+    fn dirty(&self) -> (res: bool) {
+        self.dirty
+    }
+
+    fn clear_dirty(&self) -> (pte: Self) {
+        Self {
+            addr: self.addr,
+            attr: self.attr,
+            huge: self.huge,
+            valid: self.valid,
+            accessed: self.accessed,
+            dirty: false,
+        }
+    }
+
     fn from_u64(val: u64) -> (pte: Self) {
-        let addr = PAddrExec((val >> 12 << 12) as usize);
+        let addr = PAddrExec((val & EASY_ADDR_MASK) as usize);
         let readable = val & 0b100 != 0;
         let writable = val & 0b1000 != 0;
         let executable = val & 0b10000 != 0;
         let user_accessible = val & 0b100000 != 0;
-        let device = val & 0b1000000 != 0;
+        let mem_type = mem_type_from_bits((val >> 9) & 0b111);
+        let shareability = shareability_from_bits((val >> 52) & 0b11);
+        let global = val & 0b1000000 != 0;
+        let sw_bits = sw_bits_from_bits((val >> 54) & 0b1111);
         let huge = val & 0b10 != 0;
         let valid = val & 0b1 != 0;
+        let accessed = val & 0b10000000 != 0;
+        let dirty = val & 0b100000000 != 0;
         Self {
             addr,
-            attr: MemAttr { readable, writable, executable, user_accessible, device },
+            attr: MemAttr {
+                readable,
+                writable,
+                executable,
+                user_accessible,
+                mem_type,
+                shareability,
+                global,
+                sw_bits,
+            },
             huge,
             valid,
+            accessed,
+            dirty,
         }
     }
 
     fn to_u64(&self) -> (res: u64) {
-        let a = self.addr.0 as u64 >> 12 << 12;
+        let a = self.addr.0 as u64 & EASY_ADDR_MASK;
         let b = if self.attr.readable {
             0b100
         } else {
@@ -207,11 +665,7 @@ impl ExecPTE<EasyGhostPTE> for EasyExecPTE {
         } else {
             0
         };
-        let f = if self.attr.device {
-            0b1000000
-        } else {
-            0
-        };
+        let f = mem_type_to_bits(self.attr.mem_type) << 9;
         let g = if self.huge {
             0b10
         } else {
@@ -222,14 +676,74 @@ impl ExecPTE<EasyGhostPTE> for EasyExecPTE {
         } else {
             0
         };
-        a | b | c | d | e | f | g | h
+        let i = if self.accessed {
+            0b10000000
+        } else {
+            0
+        };
+        let j = if self.dirty {
+            0b100000000
+        } else {
+            0
+        };
+        let k = shareability_to_bits(self.attr.shareability) << 52;
+        let l = if self.attr.global {
+            0b1000000
+        } else {
+            0
+        };
+        let m = sw_bits_to_bits(self.attr.sw_bits) << 54;
+        a | b | c | d | e | f | g | h | i | j | k | l | m
+    }
+}
+
+/// TLB of the easy architecture.
+///
+/// The easy architecture caches no translations, so maintenance operations only need to be
+/// recorded.
+pub struct EasyTLB {
+    /// Operations issued so far.
+    pub issued: Ghost<Seq<TLBOp>>,
+}
+
+impl EasyTLB {
+    /// Construct a TLB with no operations issued.
+    pub fn new() -> (res: Self)
+        ensures
+            res.issued() == Seq::<TLBOp>::empty(),
+    {
+        Self { issued: Ghost(Seq::empty()) }
+    }
+}
+
+impl TLBExec for EasyTLB {
+    open spec fn issued(self) -> Seq<TLBOp> {
+        self.issued@
+    }
+
+    fn invalidate_va(&mut self, tag: usize, vaddr: VAddrExec) {
+        self.issued = Ghost(self.issued@.push(TLBOp::VA(TLBTag(tag as nat), vaddr@)));
+    }
+
+    fn invalidate_range(&mut self, tag: usize, start: VAddrExec, size: usize) {
+        self.issued = Ghost(
+            self.issued@.push(TLBOp::Range(TLBTag(tag as nat), start@, size as nat)),
+        );
+    }
+
+    fn invalidate_tag(&mut self, tag: usize) {
+        self.issued = Ghost(self.issued@.push(TLBOp::Tag(TLBTag(tag as nat))));
+    }
+
+    fn invalidate_all(&mut self) {
+        self.issued = Ghost(self.issued@.push(TLBOp::All));
     }
 }
 
 } // verus!
 
 /// Easy Page Table Architecture: 3-level, each level 512 entries.
-fn easy_pt_arch() -> PTArchExec {
+pub fn easy_pt_arch() -> PTArchExec {
     PTArchExec(vec![
         PTArchLevelExec {
             entry_count: 512,
@@ -246,26 +760,32 @@ fn easy_pt_arch() -> PTArchExec {
     ])
 }
 
-/// Physical memory lower and upper bounds.
-const EASY_PMEM_LB: usize = 0b1000;
-const EASY_PMEM_UB: usize = 0b100000000;
-
 /// Easy Page Table Implementation.
 /// 
 /// The underlying page table memory can be any type that implements `PageTableMemExec`.
 pub struct EasyPageTable<M: PageTableMemExec>(PageTableExec<M, EasyGhostPTE, EasyExecPTE>);
 
 impl<M> PageTableApi for EasyPageTable<M> where M: PageTableMemExec {
-    fn new() -> Self {
+    fn new(reserved: &[(usize, usize)]) -> Option<Self> {
         let arch = easy_pt_arch();
-        Self(PageTableExec::new(
-            M::new_init(arch.clone()),
+        let pt_mem = M::new_init(arch.clone())?;
+        // Mapped frames must lie in the physical memory of the backend
+        let (pmem_base, pmem_size) = pt_mem.pmem()?;
+        let pmem_ub = pmem_base.0.checked_add(pmem_size)?;
+        // Page tables must never be mapped, so their pool is reserved too
+        let mut reserved: Vec<_> =
+            reserved.iter().map(|&(base, size)| (PAddrExec(base), size)).collect();
+        reserved.extend(pt_mem.pool());
+        Some(Self(PageTableExec::new(
+            pt_mem,
             PTConstantsExec {
                 arch,
-                pmem_lb: PAddrExec(EASY_PMEM_LB),
-                pmem_ub: PAddrExec(EASY_PMEM_UB),
+                pmem_lb: pmem_base,
+                pmem_ub: PAddrExec(pmem_ub),
+                reserved,
+                tag: 0,
             },
-        ))
+        )))
     }
 
     fn root(&self) -> usize {
@@ -280,6 +800,20 @@ impl<M> PageTableApi for EasyPageTable<M> where M: PageTableMemExec {
         } else {
             FrameSize::Size4K
         };
+        let end = paddr.checked_add(size.as_usize()).ok_or(())?;
+        // The frame must lie in physical memory
+        let constants = &self.0.constants;
+        if paddr < constants.pmem_lb.0 || end > constants.pmem_ub.0 {
+            return Err(());
+        }
+        // Reserved memory must never be mapped. A region that ends past the address space
+        // overlaps everything above its base.
+        let reserved = &constants.reserved;
+        if reserved.iter().any(|(base, len)| {
+            base.0 < end && base.0.checked_add(*len).map_or(true, |res_end| paddr < res_end)
+        }) {
+            return Err(());
+        }
         self.0.map(
             VAddrExec(vbase),
             FrameExec {
@@ -290,8 +824,12 @@ impl<M> PageTableApi for EasyPageTable<M> where M: PageTableMemExec {
         )
     }
 
-    fn unmap(&mut self, vaddr: usize) -> PagingResult {
-        self.0.unmap(VAddrExec(vaddr))
+    fn unmap<T: TLBExec>(&mut self, vaddr: usize, tlb: &mut T) -> PagingResult {
+        self.0.unmap(VAddrExec(vaddr), tlb)
+    }
+
+    fn protect<T: TLBExec>(&mut self, vaddr: usize, attr: MemAttr, tlb: &mut T) -> PagingResult {
+        self.0.protect(VAddrExec(vaddr), attr, tlb)
     }
 
     fn query(&self, vaddr: usize) -> PagingResult<(usize, usize, usize, MemAttr)> {
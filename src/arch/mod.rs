@@ -1,12 +1,20 @@
 //! Provides page table management functions for different architectures.
+#[cfg(feature = "arch-easy")]
 pub mod easy;
 
 use crate::common::{frame::MemAttr, PagingResult};
+use crate::spec::memory::TLBExec;
 
 /// Page Table API.
 pub trait PageTableApi {
-    /// Creates an empty page table.
-    fn new() -> Self;
+    /// Creates an empty page table that never maps the `(base, size)` physical regions in
+    /// `reserved`.
+    ///
+    /// The memory the page tables are allocated from is reserved as well, so guests can never
+    /// reach the page tables. Returns `None` if the root table cannot be allocated.
+    fn new(reserved: &[(usize, usize)]) -> Option<Self>
+    where
+        Self: Sized;
 
     /// Returns the root page table address.
     fn root(&self) -> usize;
@@ -14,10 +22,17 @@ pub trait PageTableApi {
     /// Maps a virtual address to a physical address.
     fn map(&mut self, vaddr: usize, paddr: usize, size: usize, attr: MemAttr) -> PagingResult;
 
-    /// Unmaps a virtual address.
-    fn unmap(&mut self, vaddr: usize) -> PagingResult;
+    /// Unmaps a virtual address, and invalidates it in the TLB through `tlb`.
+    fn unmap<T: TLBExec>(&mut self, vaddr: usize, tlb: &mut T) -> PagingResult;
+
+    /// Changes the attributes of the page or block mapped at `vaddr`, and invalidates it in
+    /// the TLB through `tlb`. The mapping stays valid throughout, so the memory type and
+    /// shareability cannot change.
+    fn protect<T: TLBExec>(&mut self, vaddr: usize, attr: MemAttr, tlb: &mut T) -> PagingResult;
 
     /// Given a virtual address, returns the virtual base addree, physical address,
     /// frame size, and the attributes of the mapping.
+    ///
+    /// The attributes include the global flag and the software bits given to `map`.
     fn query(&self, vaddr: usize) -> PagingResult<(usize, usize, usize, MemAttr)>;
 }
@@ -6,6 +6,54 @@ verus! {
 /// Word size.
 pub spec const WORD_SIZE: nat = 8;
 
+/// Size of a memory access.
+///
+/// Accesses are little-endian. A naturally aligned access never spans two words.
+pub enum AccessSize {
+    /// 1 byte
+    Size1,
+    /// 2 bytes
+    Size2,
+    /// 4 bytes
+    Size4,
+    /// 8 bytes, a whole word
+    Size8,
+}
+
+impl AccessSize {
+    /// Convert to nat.
+    pub open spec fn as_nat(self) -> nat {
+        match self {
+            AccessSize::Size1 => 1,
+            AccessSize::Size2 => 2,
+            AccessSize::Size4 => 4,
+            AccessSize::Size8 => 8,
+        }
+    }
+
+    /// Mask of the low `self.as_nat()` bytes.
+    pub open spec fn mask(self) -> u64 {
+        match self {
+            AccessSize::Size1 => 0xff,
+            AccessSize::Size2 => 0xffff,
+            AccessSize::Size4 => 0xffff_ffff,
+            AccessSize::Size8 => 0xffff_ffff_ffff_ffff,
+        }
+    }
+
+    /// Bytes `[offset, offset + self.as_nat())` of `word`, zero-extended.
+    pub open spec fn extract(self, word: u64, offset: nat) -> u64 {
+        (word >> (offset * 8) as u64) & self.mask()
+    }
+
+    /// `word` with bytes `[offset, offset + self.as_nat())` replaced by the low bytes of
+    /// `value`. The other byte lanes are kept.
+    pub open spec fn insert(self, word: u64, offset: nat, value: u64) -> u64 {
+        let shift = (offset * 8) as u64;
+        (word & !(self.mask() << shift)) | ((value & self.mask()) << shift)
+    }
+}
+
 /// Representing virtual address.
 pub struct VAddr(pub nat);
 
@@ -35,6 +83,11 @@ impl VAddr {
         VIdx(self.0 / WORD_SIZE)
     }
 
+    /// Byte offset of `self` in its word.
+    pub open spec fn word_offset(self) -> nat {
+        self.0 % WORD_SIZE
+    }
+
     /// If virtual page base `vbase` maps to physical page base `pbase`, calculate the
     /// physical address that `self` maps to.
     pub open spec fn map(self, vbase: Self, pbase: PAddr) -> PAddr
@@ -64,6 +117,25 @@ impl PAddr {
         overlap(base1.0, size1, base2.0, size2)
     }
 
+    /// If physical region (base, size) overlaps any `(base, size)` region in `regions`.
+    pub open spec fn overlap_any(base: Self, size: nat, regions: Seq<(Self, nat)>) -> bool {
+        exists|i|
+            0 <= i < regions.len() && #[trigger] Self::overlap(
+                base,
+                size,
+                regions[i].0,
+                regions[i].1,
+            )
+    }
+
+    /// If physical region (base, size) lies inside some `(base, size)` region in `regions`.
+    pub open spec fn inside_any(base: Self, size: nat, regions: Seq<(Self, nat)>) -> bool {
+        exists|i|
+            #![trigger regions[i]]
+            0 <= i < regions.len() && regions[i].0.0 <= base.0 && base.0 + size <= regions[i].0.0
+                + regions[i].1
+    }
+
     /// Offset `self` by `offset` bytes.
     pub open spec fn offset(self, offset: nat) -> PAddr {
         PAddr(self.0 + offset)
@@ -73,6 +145,11 @@ impl PAddr {
     pub open spec fn idx(self) -> PIdx {
         PIdx(self.0 / WORD_SIZE)
     }
+
+    /// Byte offset of `self` in its word.
+    pub open spec fn word_offset(self) -> nat {
+        self.0 % WORD_SIZE
+    }
 }
 
 /// Index used to access virtual memory by 8-byte word.
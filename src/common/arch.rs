@@ -3,6 +3,7 @@
 //! Page table architecture specifies the hierarchical structure of a page table, including the
 //! number of query levels, the number of entries at each level, and the frame size associated
 //! with a block/page descriptor.
+use alloc::{vec, vec::Vec};
 use vstd::prelude::*;
 
 use super::{
@@ -75,6 +76,11 @@ impl PTArch {
         choose|level: nat| level < self.level_count() && self.frame_size(level) == size
     }
 
+    /// Architecture of the tables below a root entry, which has every level but the root.
+    pub open spec fn below_root(self) -> PTArch {
+        PTArch(self.0.subrange(1, self.0.len() as int))
+    }
+
     /// Calculates the page table entry index for a virtual address at the specified level.
     pub open spec fn pte_index(self, vaddr: VAddr, level: nat) -> nat
         recommends
@@ -182,6 +188,58 @@ impl PTArch {
         }
     }
 
+    /// Lemma. The tables below a root entry form a valid architecture with the same leaf
+    /// level, supporting every frame size but the one of the root level.
+    pub proof fn lemma_below_root(self)
+        requires
+            self.valid(),
+            self.level_count() > 1,
+        ensures
+            self.below_root().valid(),
+            self.below_root().level_count() == self.level_count() - 1,
+            self.below_root().leaf_frame_size() == self.leaf_frame_size(),
+            forall|level: nat|
+                level < self.below_root().level_count() ==> #[trigger] self.below_root().0[level
+                    as int] == self.0[level + 1],
+            forall|size: FrameSize|
+                self.is_valid_frame_size(size) && size != self.frame_size(0)
+                    ==> #[trigger] self.below_root().is_valid_frame_size(size),
+    {
+        let below = self.below_root();
+        assert forall|level: nat| level < below.level_count() implies below.entry_count(level)
+            > 1 by {
+            assert(below.0[level as int] == self.0[level + 1]);
+        }
+        assert forall|level: nat| 1 <= level < below.level_count() implies below.frame_size(
+            (level - 1) as nat,
+        ).as_nat() == below.frame_size(level).as_nat() * below.entry_count(level) by {
+            assert(below.0[level - 1] == self.0[level as int]);
+            assert(below.0[level as int] == self.0[level + 1]);
+        }
+        assert forall|size: FrameSize|
+            self.is_valid_frame_size(size) && size != self.frame_size(0) implies #[trigger]
+            below.is_valid_frame_size(size) by {
+            let level = self.level_of_frame_size(size);
+            assert(level >= 1);
+            // The size is supported on a level below the root
+            assert(below.frame_size((level - 1) as nat) == size);
+        }
+    }
+
+    /// Lemma. `pte_index` is within the entry count of the level.
+    pub proof fn lemma_pte_index_bound(self, vaddr: VAddr, level: nat)
+        requires
+            self.valid(),
+            level < self.level_count(),
+        ensures
+            self.pte_index(vaddr, level) < self.entry_count(level),
+    {
+        vstd::arithmetic::div_mod::lemma_mod_pos_bound(
+            (vaddr.0 / self.frame_size(level).as_nat()) as int,
+            self.entry_count(level) as int,
+        );
+    }
+
     /// Lemma. `vbase` has fixed range and alignment.
     pub proof fn lemma_vbase_range_and_alignment(self, vaddr: VAddr, level: nat)
         by (nonlinear_arith)
@@ -195,6 +253,62 @@ impl PTArch {
             self.vbase(vaddr, level).aligned(self.frame_size(level).as_nat()),
     {
     }
+
+    /// Lemma. Every address within an aligned page or block at `level` has it as `vbase`.
+    pub proof fn lemma_vbase_within(self, vbase: VAddr, vaddr: VAddr, level: nat)
+        requires
+            self.valid(),
+            level < self.level_count(),
+            vbase.aligned(self.frame_size(level).as_nat()),
+            vaddr.within(vbase, self.frame_size(level).as_nat()),
+        ensures
+            self.vbase(vaddr, level) == vbase,
+    {
+        let size = self.frame_size(level).as_nat() as int;
+        vstd::arithmetic::div_mod::lemma_fundamental_div_mod(vbase.0 as int, size);
+        vstd::arithmetic::div_mod::lemma_fundamental_div_mod_converse(
+            vaddr.0 as int,
+            size,
+            vbase.0 as int / size,
+            vaddr.0 - vbase.0,
+        );
+    }
+
+    /// Lemma. Addresses in the same page or block at `level` have the same index on `level`
+    /// and every lower level.
+    pub proof fn lemma_same_vbase_same_pte_index(
+        self,
+        v1: VAddr,
+        v2: VAddr,
+        level: nat,
+        level2: nat,
+    )
+        requires
+            self.valid(),
+            level2 <= level < self.level_count(),
+            self.vbase(v1, level) == self.vbase(v2, level),
+        ensures
+            self.pte_index(v1, level2) == self.pte_index(v2, level2),
+    {
+        let size = self.frame_size(level).as_nat();
+        let size2 = self.frame_size(level2).as_nat();
+        assert(v1.0 / size == v2.0 / size) by (nonlinear_arith)
+            requires
+                size > 0,
+                v1.0 / size * size == v2.0 / size * size,
+        ;
+        // `size2` is a multiple of `size`, so dividing by `size2` divides by `size` first
+        self.lemma_frame_size_aligned(level, level2);
+        vstd::arithmetic::div_mod::lemma_fundamental_div_mod(size2 as int, size as int);
+        let k = size2 / size;
+        assert(k > 0) by (nonlinear_arith)
+            requires
+                size2 == size * k + 0,
+                size2 > 0,
+        ;
+        vstd::arithmetic::div_mod::lemma_div_denominator(v1.0 as int, size as int, k as int);
+        vstd::arithmetic::div_mod::lemma_div_denominator(v2.0 as int, size as int, k as int);
+    }
 }
 
 /// **EXEC MODE** Represents a single level in a hierarchical page table structure.
@@ -254,6 +368,17 @@ impl PTArchExec {
         self.0[level].entry_count
     }
 
+    /// The size of a table at the given level.
+    pub fn table_size(&self, level: usize) -> (res: usize)
+        requires
+            level < self@.level_count(),
+            self@.table_size(level as nat) <= usize::MAX,
+        ensures
+            res == self@.table_size(level as nat),
+    {
+        self.entry_count(level) * 8
+    }
+
     /// Computes the page table entry index for `vaddr` at the specified level.
     pub fn pte_index(&self, vaddr: VAddrExec, level: usize) -> (res: usize)
         requires
@@ -283,6 +408,35 @@ impl PTArchExec {
         VAddrExec(vaddr.0 / fsize * fsize)
     }
 
+    /// Architecture of the tables below a root entry.
+    pub fn below_root(&self) -> (res: PTArchExec)
+        requires
+            self@.level_count() > 0,
+        ensures
+            res@ == self@.below_root(),
+    {
+        let mut levels = Vec::new();
+        let mut i = 1;
+        while i < self.0.len()
+            invariant
+                1 <= i <= self.0.len(),
+                levels.len() == i - 1,
+                forall|j: int| 0 <= j < levels.len() ==> #[trigger] levels[j]@ == self.0[j + 1]@,
+            decreases self.0.len() - i,
+        {
+            levels.push(
+                PTArchLevelExec {
+                    entry_count: self.0[i].entry_count,
+                    frame_size: self.0[i].frame_size,
+                },
+            );
+            i += 1;
+        }
+        let res = PTArchExec(levels);
+        assert(res@.0 =~= self@.below_root().0);
+        res
+    }
+
     /// Get the corresponding level of a frame size.
     #[verifier::external_body]
     pub fn level_of_frame_size(&self, size: FrameSize) -> (res: usize)
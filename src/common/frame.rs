@@ -1,30 +1,53 @@
 //! Physical frame defination.
 use vstd::prelude::*;
 
-use super::addr::{PAddr, PAddrExec};
+use super::{
+    addr::{PAddr, PAddrExec},
+    Privilege,
+};
 
 verus! {
 
-/// Page & Block size supported by VMSA-v8.
+/// Page, block & table size supported by VMSA-v8 and RISC-V.
 ///
 /// - For 4KB granule, support: 4K, 2M, 1G, 512G.
 /// - For 16KB granule, support: 16K, 32M, 64G.
+/// - For 64KB granule, support: 64K, 512M, 4T.
+/// - Concatenated and enlarged root tables (e.g. RISC-V x4 roots) take 8K to 1M.
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum FrameSize {
     /// 4 KiB
     Size4K,
+    /// 8 KiB
+    Size8K,
     /// 16 KiB
     Size16K,
+    /// 32 KiB
+    Size32K,
+    /// 64 KiB
+    Size64K,
+    /// 128 KiB
+    Size128K,
+    /// 256 KiB
+    Size256K,
+    /// 512 KiB
+    Size512K,
+    /// 1 MiB
+    Size1M,
     /// 2 MiB
     Size2M,
     /// 32 MiB
     Size32M,
+    /// 512 MiB
+    Size512M,
     /// 1 GiB
     Size1G,
     /// 64 GiB
     Size64G,
     /// 512 GiB
     Size512G,
+    /// 4 TiB
+    Size4T,
 }
 
 impl FrameSize {
@@ -32,12 +55,21 @@ impl FrameSize {
     pub open spec fn as_nat(self) -> nat {
         match self {
             FrameSize::Size4K => 0x1000,
+            FrameSize::Size8K => 0x2000,
             FrameSize::Size16K => 0x4000,
+            FrameSize::Size32K => 0x8000,
+            FrameSize::Size64K => 0x10000,
+            FrameSize::Size128K => 0x20000,
+            FrameSize::Size256K => 0x40000,
+            FrameSize::Size512K => 0x80000,
+            FrameSize::Size1M => 0x100000,
             FrameSize::Size2M => 0x200000,
             FrameSize::Size32M => 0x2000000,
+            FrameSize::Size512M => 0x20000000,
             FrameSize::Size1G => 0x40000000,
             FrameSize::Size64G => 0x1000000000,
             FrameSize::Size512G => 0x8000000000,
+            FrameSize::Size4T => 0x40000000000,
         }
     }
 
@@ -48,14 +80,182 @@ impl FrameSize {
     {
         match self {
             FrameSize::Size4K => 0x1000,
+            FrameSize::Size8K => 0x2000,
             FrameSize::Size16K => 0x4000,
+            FrameSize::Size32K => 0x8000,
+            FrameSize::Size64K => 0x10000,
+            FrameSize::Size128K => 0x20000,
+            FrameSize::Size256K => 0x40000,
+            FrameSize::Size512K => 0x80000,
+            FrameSize::Size1M => 0x100000,
             FrameSize::Size2M => 0x200000,
             FrameSize::Size32M => 0x2000000,
+            FrameSize::Size512M => 0x20000000,
             FrameSize::Size1G => 0x40000000,
             FrameSize::Size64G => 0x1000000000,
             FrameSize::Size512G => 0x8000000000,
+            FrameSize::Size4T => 0x40000000000,
         }
     }
+
+    /// The frame size of `size` bytes, if any.
+    pub fn from_usize(size: usize) -> (res: Option<FrameSize>)
+        ensures
+            res is Some ==> res->Some_0.as_nat() == size as nat,
+    {
+        if size == 0x1000 {
+            Some(FrameSize::Size4K)
+        } else if size == 0x2000 {
+            Some(FrameSize::Size8K)
+        } else if size == 0x4000 {
+            Some(FrameSize::Size16K)
+        } else if size == 0x8000 {
+            Some(FrameSize::Size32K)
+        } else if size == 0x10000 {
+            Some(FrameSize::Size64K)
+        } else if size == 0x20000 {
+            Some(FrameSize::Size128K)
+        } else if size == 0x40000 {
+            Some(FrameSize::Size256K)
+        } else if size == 0x80000 {
+            Some(FrameSize::Size512K)
+        } else if size == 0x100000 {
+            Some(FrameSize::Size1M)
+        } else if size == 0x200000 {
+            Some(FrameSize::Size2M)
+        } else if size == 0x2000000 {
+            Some(FrameSize::Size32M)
+        } else if size == 0x20000000 {
+            Some(FrameSize::Size512M)
+        } else if size == 0x40000000 {
+            Some(FrameSize::Size1G)
+        } else if size == 0x1000000000 {
+            Some(FrameSize::Size64G)
+        } else if size == 0x8000000000 {
+            Some(FrameSize::Size512G)
+        } else if size == 0x40000000000 {
+            Some(FrameSize::Size4T)
+        } else {
+            None
+        }
+    }
+
+    /// Lemma. Every frame size is a multiple of 4K, so an address aligned to a frame size is
+    /// also aligned to 4K.
+    pub proof fn lemma_aligned_implies_4k_aligned(self, addr: nat)
+        requires
+            addr % self.as_nat() == 0,
+        ensures
+            addr % FrameSize::Size4K.as_nat() == 0,
+    {
+        let k = self.as_nat() / 0x1000;
+        assert(self.as_nat() == 0x1000 * k);
+        vstd::arithmetic::div_mod::lemma_mod_mod(addr as int, 0x1000, k as int);
+    }
+}
+
+/// Memory type of a frame, with the cacheability of normal memory.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum MemType {
+    /// Normal memory, write-back cacheable.
+    NormalWriteBack,
+    /// Normal memory, write-through cacheable.
+    NormalWriteThrough,
+    /// Normal memory, non-cacheable.
+    NormalNonCacheable,
+    /// Device memory, non-Gathering, non-Reordering, no Early write acknowledgement.
+    DeviceNGnRnE,
+    /// Device memory, non-Gathering, non-Reordering, Early write acknowledgement.
+    DeviceNGnRE,
+}
+
+impl MemType {
+    /// If the memory type is a device memory type.
+    pub open spec fn is_device(self) -> bool {
+        self is DeviceNGnRnE || self is DeviceNGnRE
+    }
+
+    /// If `self` and `other` are the same memory type.
+    pub fn same_as(self, other: Self) -> (res: bool)
+        ensures
+            res == (self == other),
+    {
+        match (self, other) {
+            (MemType::NormalWriteBack, MemType::NormalWriteBack) => true,
+            (MemType::NormalWriteThrough, MemType::NormalWriteThrough) => true,
+            (MemType::NormalNonCacheable, MemType::NormalNonCacheable) => true,
+            (MemType::DeviceNGnRnE, MemType::DeviceNGnRnE) => true,
+            (MemType::DeviceNGnRE, MemType::DeviceNGnRE) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Shareability domain of a frame.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum Shareability {
+    /// Not shared with other observers.
+    NonShareable,
+    /// Coherent in the outer shareable domain.
+    OuterShareable,
+    /// Coherent in the inner shareable domain.
+    InnerShareable,
+}
+
+impl Shareability {
+    /// If `self` and `other` are the same shareability domain.
+    pub fn same_as(self, other: Self) -> (res: bool)
+        ensures
+            res == (self == other),
+    {
+        match (self, other) {
+            (Shareability::NonShareable, Shareability::NonShareable) => true,
+            (Shareability::OuterShareable, Shareability::OuterShareable) => true,
+            (Shareability::InnerShareable, Shareability::InnerShareable) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Ownership state of a page, from the point of view of the page table that maps it.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum PageState {
+    /// Owned exclusively.
+    Owned,
+    /// Owned, and shared with another page table.
+    SharedOwned,
+    /// Borrowed from the owner that shares it.
+    SharedBorrowed,
+    /// Donated by its previous owner.
+    Donated,
+}
+
+/// Software-available bits of an entry, kept for hypervisor bookkeeping.
+///
+/// The MMU ignores them.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct SwBits {
+    /// Ownership state of the page.
+    pub state: PageState,
+    /// Whether the mapping must not be removed.
+    pub pinned: bool,
+    /// Whether accesses to the page are emulated by the hypervisor.
+    pub mmio_emulated: bool,
+}
+
+impl SwBits {
+    /// Default software bits: owned/unpinned/not emulated.
+    pub fn default() -> (res: Self)
+        ensures
+            res == Self::spec_default(),
+    {
+        Self { state: PageState::Owned, pinned: false, mmio_emulated: false }
+    }
+
+    /// Spec-mode default software bits: owned/unpinned/not emulated.
+    pub open spec fn spec_default() -> Self {
+        Self { state: PageState::Owned, pinned: false, mmio_emulated: false }
+    }
 }
 
 /// Frame attributes. Defination consistent with `hvisor::memory::MemFlags`.
@@ -69,8 +269,17 @@ pub struct MemAttr {
     pub executable: bool,
     /// Whether the memory is user accessible.
     pub user_accessible: bool,
-    /// Whether the memory is used for device mapping.
-    pub device: bool,
+    /// Memory type of the frame.
+    pub mem_type: MemType,
+    /// Shareability of the frame.
+    pub shareability: Shareability,
+    /// Whether the mapping is global, i.e. not tagged with an address space (nG clear).
+    ///
+    /// The TLB model tags every entry with the address space it was filled in, which treats
+    /// global mappings as non-global.
+    pub global: bool,
+    /// Software-available bits.
+    pub sw_bits: SwBits,
 }
 
 impl MemAttr {
@@ -80,12 +289,33 @@ impl MemAttr {
         writable: bool,
         executable: bool,
         user_accessible: bool,
-        device: bool,
+        mem_type: MemType,
+        shareability: Shareability,
+        global: bool,
+        sw_bits: SwBits,
     ) -> (res: Self)
         ensures
-            res == Self::spec_new(readable, writable, executable, user_accessible, device),
+            res == Self::spec_new(
+                readable,
+                writable,
+                executable,
+                user_accessible,
+                mem_type,
+                shareability,
+                global,
+                sw_bits,
+            ),
     {
-        Self { readable, writable, executable, user_accessible, device }
+        Self {
+            readable,
+            writable,
+            executable,
+            user_accessible,
+            mem_type,
+            shareability,
+            global,
+            sw_bits,
+        }
     }
 
     /// Spec-mode constructor.
@@ -94,26 +324,112 @@ impl MemAttr {
         writable: bool,
         executable: bool,
         user_accessible: bool,
-        device: bool,
+        mem_type: MemType,
+        shareability: Shareability,
+        global: bool,
+        sw_bits: SwBits,
     ) -> Self {
-        Self { readable, writable, executable, user_accessible, device }
+        Self {
+            readable,
+            writable,
+            executable,
+            user_accessible,
+            mem_type,
+            shareability,
+            global,
+            sw_bits,
+        }
     }
 
     /// Default attributes for a frame.
     ///
-    /// readable/writable/executable/user_accessible/non-device.
+    /// readable/writable/executable/user_accessible/normal write-back/inner shareable/
+    /// non-global/default software bits.
     pub fn default() -> (res: Self)
         ensures
             res == Self::spec_default(),
     {
-        Self::new(true, true, true, true, false)
+        Self::new(
+            true,
+            true,
+            true,
+            true,
+            MemType::NormalWriteBack,
+            Shareability::InnerShareable,
+            false,
+            SwBits::default(),
+        )
     }
 
     /// Spec-mode default attributes for a frame.
     ///
-    /// readable/writable/executable/user_accessible/non-device.
+    /// readable/writable/executable/user_accessible/normal write-back/inner shareable/
+    /// non-global/default software bits.
     pub open spec fn spec_default() -> Self {
-        Self::spec_new(true, true, true, true, false)
+        Self::spec_new(
+            true,
+            true,
+            true,
+            true,
+            MemType::NormalWriteBack,
+            Shareability::InnerShareable,
+            false,
+            SwBits::spec_default(),
+        )
+    }
+
+    /// If `self` and `other` have the same memory type and shareability.
+    ///
+    /// Changing either of them on a valid descriptor requires break-before-make, as the old
+    /// and the new translation may be cached with mismatched attributes.
+    pub open spec fn spec_same_memory_type(self, other: Self) -> bool {
+        &&& self.mem_type == other.mem_type
+        &&& self.shareability == other.shareability
+    }
+
+    /// If `self` and `other` have the same memory type and shareability.
+    pub fn same_memory_type(&self, other: &Self) -> (res: bool)
+        ensures
+            res == self.spec_same_memory_type(*other),
+    {
+        self.mem_type.same_as(other.mem_type) && self.shareability.same_as(other.shareability)
+    }
+
+    /// `self` with the global flag and the software bits replaced, the fields that the MMU
+    /// does not interpret.
+    pub open spec fn with_sw(self, global: bool, sw_bits: SwBits) -> Self {
+        Self { global, sw_bits, ..self }
+    }
+
+    /// The attributes the MMU interprets, with the global flag and the software bits reset.
+    ///
+    /// Two attributes with the same `hw` differ only in fields the MMU ignores.
+    pub open spec fn hw(self) -> Self {
+        self.with_sw(false, SwBits::spec_default())
+    }
+
+    /// Whether the memory is used for device mapping.
+    ///
+    /// Accesses to device memory are forwarded to the device (MMIO) instead of physical memory.
+    pub open spec fn device(self) -> bool {
+        self.mem_type.is_device()
+    }
+
+    /// If an access at `privilege` may read memory with these attributes.
+    pub open spec fn permits_read(self, privilege: Privilege) -> bool {
+        self.readable && privilege.may_access(self.user_accessible)
+    }
+
+    /// If an access at `privilege` may write memory with these attributes.
+    pub open spec fn permits_write(self, privilege: Privilege) -> bool {
+        self.writable && privilege.may_access(self.user_accessible)
+    }
+
+    /// If an access at `privilege` may fetch instructions from memory with these attributes.
+    ///
+    /// Instructions are never fetched from device memory.
+    pub open spec fn permits_exec(self, privilege: Privilege) -> bool {
+        self.executable && !self.device() && privilege.may_execute(self.user_accessible)
     }
 }
 
@@ -127,7 +443,15 @@ pub struct Frame {
     pub attr: MemAttr,
 }
 
+impl Frame {
+    /// The frame as the MMU interprets it (`MemAttr::hw`).
+    pub open spec fn hw(self) -> Self {
+        Frame { attr: self.attr.hw(), ..self }
+    }
+}
+
 /// (EXEC-MODE) represents a physical memory frame (Page or Block).
+#[derive(Clone, Copy)]
 pub struct FrameExec {
     /// The base address of the frame.
     pub base: PAddrExec,
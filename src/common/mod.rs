@@ -6,17 +6,107 @@ pub mod arch;
 pub mod frame;
 pub mod pte;
 
+use addr::{AccessSize, PAddr};
+
 verus! {
 
 /// Result type returned by paging operations (map, unmap, query).
 pub type PagingResult<T = ()> = Result<T, ()>;
 
-/// Result type returned by memory operations (read, write).
+/// Kind of a page fault, as reported to the fault handler.
+pub enum FaultKind {
+    /// No valid descriptor translates the address.
+    Translation,
+    /// The mapping does not permit the read.
+    PermissionRead,
+    /// The mapping does not permit the write.
+    PermissionWrite,
+    /// The mapping does not permit the instruction fetch.
+    PermissionExec,
+    /// The Access flag of the descriptor is clear.
+    ///
+    /// Not raised by the specified hardware, which sets the Access flag itself.
+    AccessFlag,
+    /// The output address is outside physical memory.
+    AddressSize,
+}
+
+/// Privilege level a memory access is performed at.
+pub enum Privilege {
+    /// Unprivileged software (EL0, U-mode).
+    User,
+    /// Privileged software (EL1, S-mode).
+    ///
+    /// With `pan` (Privileged Access Never) set, data accesses to user pages fault.
+    Kernel { pan: bool },
+}
+
+impl Privilege {
+    /// If a data access at this privilege may touch a page that is user accessible or not.
+    pub open spec fn may_access(self, user_page: bool) -> bool {
+        match self {
+            Privilege::User => user_page,
+            Privilege::Kernel { pan } => !(pan && user_page),
+        }
+    }
+
+    /// If an instruction fetch at this privilege may touch a page that is user accessible or
+    /// not.
+    ///
+    /// Privileged software never executes from user pages, whatever the PAN setting.
+    pub open spec fn may_execute(self, user_page: bool) -> bool {
+        match self {
+            Privilege::User => user_page,
+            Privilege::Kernel { .. } => !user_page,
+        }
+    }
+}
+
+/// Access forwarded to a device by a memory operation on a device mapping (MMIO).
+///
+/// Devices are outside the model. A device read returns whatever value the device chooses and
+/// may have side effects on it, and a device write is not guaranteed to read back.
+pub enum MmioAccess {
+    /// Read of `size` bytes at `paddr`, answered with `value` by the device.
+    Read { paddr: PAddr, size: AccessSize, value: u64 },
+    /// Write of the low `size` bytes of `value` to `paddr`.
+    Write { paddr: PAddr, size: AccessSize, value: u64 },
+}
+
+/// Result type returned by memory operations (read, write, fetch).
 pub enum MemoryResult<T> {
     /// Success.
     Ok(T),
-    /// Page fault.
-    PageFault,
+    /// Page fault of kind `kind`, raised by the descriptor on level `level`.
+    PageFault { kind: FaultKind, level: nat },
+    /// The access is not naturally aligned. Raised before translation.
+    AlignmentFault,
+}
+
+impl<T> MemoryResult<T> {
+    /// If `self` is a page fault of kind `kind` raised on level `level`.
+    pub open spec fn is_fault(self, kind: FaultKind, level: nat) -> bool {
+        self === MemoryResult::PageFault { kind, level }
+    }
+
+    /// If `self` is a page fault of kind `kind`.
+    pub open spec fn is_fault_kind(self, kind: FaultKind) -> bool {
+        match self {
+            MemoryResult::PageFault { kind: kind2, .. } => kind2 == kind,
+            _ => false,
+        }
+    }
+
+    /// Level reported by a page fault.
+    pub open spec fn fault_level(self) -> nat
+        recommends
+            self is PageFault,
+    {
+        match self {
+            MemoryResult::PageFault { level, .. } => level,
+            _ => 0,
+        }
+    }
 }
 
 } // verus!
@@ -1,7 +1,7 @@
 //! Page table entry specification defined by Rust trait.
 use crate::common::{
     addr::{PAddr, PAddrExec},
-    frame::{FrameSize, MemAttr},
+    frame::{FrameSize, MemAttr, SwBits},
 };
 use vstd::prelude::*;
 
@@ -27,12 +27,33 @@ pub trait GhostPTE: Sized {
     /// Returns the attributes of this entry.
     spec fn attr(self) -> MemAttr;
 
+    /// Returns whether this entry maps a global translation.
+    spec fn global(self) -> bool;
+
+    /// Returns the software-available bits of this entry.
+    spec fn sw_bits(self) -> SwBits;
+
     /// Returns whether this entry is valid.
     spec fn valid(self) -> bool;
 
     /// Returns whether this entry maps to a huge frame.
     spec fn huge(self) -> bool;
 
+    /// Returns whether the Access flag is set.
+    spec fn accessed(self) -> bool;
+
+    /// Returns whether the Dirty flag is set.
+    spec fn dirty(self) -> bool;
+
+    /// Set the Access flag, as the MMU does on the first access through this entry.
+    spec fn set_accessed(self) -> Self;
+
+    /// Set the Dirty flag, as the MMU does on the first write through this entry.
+    spec fn set_dirty(self) -> Self;
+
+    /// Clear the Dirty flag.
+    spec fn clear_dirty(self) -> Self;
+
     /// PTE constructed by `new` keeps the same value.
     broadcast proof fn lemma_new_keeps_value(addr: PAddr, attr: MemAttr, huge: bool)
         requires
@@ -44,6 +65,23 @@ pub trait GhostPTE: Sized {
             }),
     ;
 
+    /// The global flag and the software bits are stored in the attributes of the entry.
+    broadcast proof fn lemma_sw_bits_in_attr(pte: Self)
+        ensures
+            ({
+                let attr = #[trigger] pte.attr();
+                pte.global() == attr.global && pte.sw_bits() == attr.sw_bits
+            }),
+    ;
+
+    /// Encoding an entry keeps its attributes, including the memory type and shareability.
+    broadcast proof fn lemma_encoding_keeps_attr(addr: PAddr, attr: MemAttr, huge: bool)
+        requires
+            addr.aligned(FrameSize::Size4K.as_nat()),
+        ensures
+            #[trigger] Self::from_u64(Self::new(addr, attr, huge).to_u64()).attr() == attr,
+    ;
+
     /// `PTE::empty().valid()` is false.
     broadcast proof fn lemma_empty_invalid()
         ensures
@@ -69,6 +107,69 @@ pub trait GhostPTE: Sized {
         ensures
             #[trigger] Self::from_u64(val).to_u64() == val,
     ;
+
+    /// `set_accessed` sets the Access flag and keeps the translation.
+    broadcast proof fn lemma_set_accessed(pte: Self)
+        ensures
+            ({
+                let pte2 = #[trigger] pte.set_accessed();
+                &&& pte2.accessed()
+                &&& pte2.dirty() == pte.dirty()
+                &&& same_translation(pte, pte2)
+            }),
+    ;
+
+    /// `set_dirty` sets the Dirty flag and keeps the translation.
+    broadcast proof fn lemma_set_dirty(pte: Self)
+        ensures
+            ({
+                let pte2 = #[trigger] pte.set_dirty();
+                &&& pte2.dirty()
+                &&& pte2.accessed() == pte.accessed()
+                &&& same_translation(pte, pte2)
+            }),
+    ;
+
+    /// `clear_dirty` clears the Dirty flag and keeps the translation.
+    broadcast proof fn lemma_clear_dirty(pte: Self)
+        ensures
+            ({
+                let pte2 = #[trigger] pte.clear_dirty();
+                &&& !pte2.dirty()
+                &&& pte2.accessed() == pte.accessed()
+                &&& same_translation(pte, pte2)
+            }),
+    ;
+}
+
+/// If two entries translate addresses the same way.
+///
+/// They may still differ in the Access and Dirty flags, which the MMU updates.
+pub open spec fn same_translation<G: GhostPTE>(pte1: G, pte2: G) -> bool {
+    &&& pte1.valid() == pte2.valid()
+    &&& pte1.huge() == pte2.huge()
+    &&& pte1.addr() == pte2.addr()
+    &&& pte1.attr() == pte2.attr()
+}
+
+/// If two entries translate addresses the same way for the MMU.
+///
+/// Unlike `same_translation`, they may also differ in the global flag and the software bits,
+/// which the MMU does not interpret.
+pub open spec fn same_hw_translation<G: GhostPTE>(pte1: G, pte2: G) -> bool {
+    &&& pte1.valid() == pte2.valid()
+    &&& pte1.huge() == pte2.huge()
+    &&& pte1.addr() == pte2.addr()
+    &&& pte1.attr().hw() == pte2.attr().hw()
+}
+
+/// If two entries link the same table or frame in the same way.
+///
+/// They may still differ in the attributes, e.g. the access permissions.
+pub open spec fn same_target<G: GhostPTE>(pte1: G, pte2: G) -> bool {
+    &&& pte1.valid() == pte2.valid()
+    &&& pte1.huge() == pte2.huge()
+    &&& pte1.addr() == pte2.addr()
 }
 
 /// Executable Page Table Entry interface.
@@ -114,6 +215,18 @@ pub trait ExecPTE<G>: Sized where G: GhostPTE {
             res == self@.attr(),
     ;
 
+    /// Returns whether this entry maps a global translation.
+    fn global(&self) -> (res: bool)
+        ensures
+            res == self@.global(),
+    ;
+
+    /// Returns the software-available bits of this entry.
+    fn sw_bits(&self) -> (res: SwBits)
+        ensures
+            res == self@.sw_bits(),
+    ;
+
     /// Returns whether this entry is valid.
     fn valid(&self) -> (res: bool)
         ensures
@@ -127,6 +240,24 @@ pub trait ExecPTE<G>: Sized where G: GhostPTE {
         ensures
             res == self@.huge(),
     ;
+
+    /// Returns whether the Access flag is set.
+    fn accessed(&self) -> (res: bool)
+        ensures
+            res == self@.accessed(),
+    ;
+
+    /// Returns whether the Dirty flag is set.
+    fn dirty(&self) -> (res: bool)
+        ensures
+            res == self@.dirty(),
+    ;
+
+    /// Returns a copy of this entry with the Dirty flag cleared.
+    fn clear_dirty(&self) -> (pte: Self)
+        ensures
+            pte@ == self@.clear_dirty(),
+    ;
 }
 
 /// Broadcasted lemmas for GhostPTE.
@@ -136,6 +267,11 @@ pub broadcast group group_pte_lemmas {
     GhostPTE::lemma_eq_by_u64,
     GhostPTE::lemma_from_to_u64_inverse,
     GhostPTE::lemma_new_keeps_value,
+    GhostPTE::lemma_encoding_keeps_attr,
+    GhostPTE::lemma_sw_bits_in_attr,
+    GhostPTE::lemma_set_accessed,
+    GhostPTE::lemma_set_dirty,
+    GhostPTE::lemma_clear_dirty,
 }
 
 } // verus!
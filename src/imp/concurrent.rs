@@ -0,0 +1,899 @@
+//! Thread-safe page table.
+//!
+//! Each root table entry links the table of one region of `PageTableSM`. The tables below a
+//! root entry are a page table memory of their own, built for the architecture without the
+//! root level, and are protected by the lock of the region together with its `regions` token.
+//! Operations on different regions take different locks, and queries only take a read lock.
+//! Every update steps the state machine while holding the write lock, so it is linearizable
+//! against the `PageTableState` transitions of its region, and thus of the whole page table.
+//!
+//! The root table is written once when the page table is constructed and never changes. It is
+//! only read by the hardware, and is not part of the proof.
+//!
+//! Queries can also bypass the lock through a `PageTableWalkerExec`. Each region has a count
+//! next to its lock, which a writer makes odd before updating the tables and even again after,
+//! publishing the updated page table memory with the new count. A lock-free query walks the
+//! memory published with the count it loads first, and falls back to the read lock if the count
+//! is odd or has changed by the end of the walk. Tables are only deallocated and reused under
+//! an odd count, so a walk never answers from a reused table.
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use vstd::atomic_ghost::{AtomicInvariantPredicate, AtomicU64};
+use vstd::atomic_with_ghost;
+use vstd::prelude::*;
+use vstd::rwlock::{RwLock, RwLockPredicate};
+
+use super::interface::{PTConstantsExec, PageTableInterface};
+use crate::common::{
+    addr::{PAddr, PAddrExec, VAddr, VAddrExec},
+    arch::PTArchExec,
+    frame::{Frame, FrameExec, FrameSize, MemAttr},
+    pte::{ExecPTE, GhostPTE},
+    PagingResult,
+};
+use crate::spec::{
+    concurrent::{query_linearizable, PageTableSM},
+    memory::{PageTableMemExec, TLBExec, TLBOp, TLBTag},
+    page_table::PageTableState,
+};
+
+verus! {
+
+broadcast use crate::spec::memory::group_pt_mem_lemmas;
+
+/// Atomic loads of page table entries for lock-free walks.
+///
+/// A walker reads each entry with a single atomic load while a writer may be updating the
+/// tables. It walks the page table memory published with the count of the region it reads
+/// before the walk, and the walk only counts if the count is unchanged after it. Writers
+/// change the count around every update, including the deallocation of emptied tables, so a
+/// walk that read a table being updated or reused is discarded.
+pub trait PageTableWalkerExec: Sized {
+    /// Load the entry at `index` of the table at `base` with a single atomic load.
+    ///
+    /// `res.1@` records the count of the region at the time of the load. If it is still the
+    /// count `published` was published with, the load reads the published memory.
+    ///
+    /// Assumption: Writers only write the tables of a region while its count is odd, and
+    /// store the next even count with release ordering once the writes are complete. A load
+    /// outside the memory tables are allocated from returns 0 instead of faulting.
+    fn load(
+        &self,
+        published: Tracked<&PageTableSM::published>,
+        base: PAddrExec,
+        index: usize,
+    ) -> (res: (u64, Tracked<PageTableSM::seen>))
+        ensures
+            res.1@.instance_id() == published@.instance_id(),
+            res.1@.element().0 == published@.key().0,
+            res.1@.element().1 >= published@.key().1,
+            res.1@.element().1 == published@.key().1 && published@.value().1.accessible(
+                base@,
+                index as nat,
+            ) ==> res.0 == published@.value().1.read(base@, index as nat),
+    ;
+}
+
+/// Page table memory of a region, protected by the lock of the region.
+pub struct LockedPageTable<M: PageTableMemExec> {
+    /// Page table memory below the root entry of the region.
+    pub pt_mem: M,
+    /// Token of the region mappings.
+    pub region: Tracked<PageTableSM::regions>,
+    /// Token of the region count.
+    pub writer: Tracked<PageTableSM::writer>,
+    /// Count of the region.
+    pub count: u64,
+}
+
+/// Lock invariant: the token always holds the mappings of the page table memory.
+pub struct LockedPageTablePred<M, G, PI> {
+    /// Page table constants of the region.
+    pub constants: PTConstantsExec,
+    /// State machine instance.
+    pub instance: PageTableSM::Instance,
+    /// Index of the region.
+    pub index: nat,
+    /// Root of the tables of the region, which walkers start from.
+    pub root: PAddr,
+    /// Phantom data.
+    pub _phantom: PhantomData<(M, G, PI)>,
+}
+
+impl<M, G, PI> RwLockPredicate<LockedPageTable<M>> for LockedPageTablePred<M, G, PI> where
+    M: PageTableMemExec,
+    G: GhostPTE,
+    PI: PageTableInterface<M, G>,
+ {
+    open spec fn inv(self, v: LockedPageTable<M>) -> bool {
+        &&& PI::invariants(v.pt_mem, self.constants)
+        &&& v.region@.instance_id() == self.instance.id()
+        &&& v.region@.key() == self.index
+        &&& v.region@.value() == v.pt_mem@.interpret::<G>()
+        &&& v.writer@.instance_id() == self.instance.id()
+        &&& v.writer@.key() == self.index
+        &&& v.writer@.value() == v.count as nat
+    }
+}
+
+/// Ghost state of the count of a region.
+pub struct RegionCount {
+    /// Token of the count.
+    pub counts: PageTableSM::counts,
+    /// Token of the page table memory published with the count, once published.
+    pub published: Option<PageTableSM::published>,
+}
+
+/// Count invariant: at an even count, the published memory satisfies the invariants of `PI`.
+pub struct RegionCountPred<M, G, PI> {
+    /// Phantom data.
+    pub _phantom: PhantomData<(M, G, PI)>,
+}
+
+impl<M, G, PI> AtomicInvariantPredicate<
+    LockedPageTablePred<M, G, PI>,
+    u64,
+    RegionCount,
+> for RegionCountPred<M, G, PI> where
+    M: PageTableMemExec,
+    G: GhostPTE,
+    PI: PageTableInterface<M, G>,
+ {
+    open spec fn atomic_inv(k: LockedPageTablePred<M, G, PI>, v: u64, g: RegionCount) -> bool {
+        &&& g.counts.instance_id() == k.instance.id()
+        &&& g.counts.key() == k.index
+        &&& g.counts.value() == v as nat
+        &&& v % 2 == 0 ==> g.published is Some && {
+            let published = g.published->Some_0;
+            &&& published.instance_id() == k.instance.id()
+            &&& published.key() == (k.index, v as nat)
+            &&& published.value().1.root() == k.root
+            &&& exists|pt_mem: M|
+                #[trigger] PI::invariants(pt_mem, k.constants) && pt_mem@ == published.value().1
+                    && pt_mem@.interpret::<G>() == published.value().0
+        }
+    }
+}
+
+/// Lock and count of a region.
+pub struct Region<M, G, PI> where
+    M: PageTableMemExec,
+    G: GhostPTE,
+    PI: PageTableInterface<M, G>,
+ {
+    /// Lock of the page table memory of the region.
+    pub lock: RwLock<LockedPageTable<M>, LockedPageTablePred<M, G, PI>>,
+    /// Count of the region, odd while a writer updates its tables.
+    pub count: AtomicU64<LockedPageTablePred<M, G, PI>, RegionCount, RegionCountPred<M, G, PI>>,
+    /// Root of the tables of the region.
+    pub root: PAddrExec,
+}
+
+/// Thread-safe page table built on a page table implementation `PI`.
+///
+/// `E` encodes the root table entries that link the regions.
+pub struct ConcurrentPageTable<M, G, E, PI> where
+    M: PageTableMemExec,
+    G: GhostPTE,
+    E: ExecPTE<G>,
+    PI: PageTableInterface<M, G>,
+ {
+    /// Root table, whose entries link the tables of the regions.
+    pub root: M,
+    /// Regions, one per root table entry.
+    pub regions: Vec<Region<M, G, PI>>,
+    /// Page table constants.
+    pub constants: PTConstantsExec,
+    /// Page table constants of the regions, whose architecture has no root level.
+    pub region_constants: PTConstantsExec,
+    /// State machine instance.
+    pub instance: Tracked<PageTableSM::Instance>,
+    /// Phantom data.
+    pub _phantom: PhantomData<E>,
+}
+
+impl<M, G, E, PI> ConcurrentPageTable<M, G, E, PI> where
+    M: PageTableMemExec,
+    G: GhostPTE,
+    E: ExecPTE<G>,
+    PI: PageTableInterface<M, G>,
+ {
+    /// If `region_constants` are `constants` for the tables below a root entry.
+    pub open spec fn below_root(
+        constants: PTConstantsExec,
+        region_constants: PTConstantsExec,
+    ) -> bool {
+        &&& region_constants@.arch == constants@.arch.below_root()
+        &&& region_constants@.pmem_lb == constants@.pmem_lb
+        &&& region_constants@.pmem_ub == constants@.pmem_ub
+        &&& region_constants@.reserved == constants@.reserved
+        &&& region_constants.tag == constants.tag
+    }
+
+    /// Well-formedness.
+    pub open spec fn wf(self) -> bool {
+        &&& self.constants@.arch.valid()
+        &&& self.constants@.arch.level_count() > 1
+        &&& Self::below_root(self.constants, self.region_constants)
+        &&& self.root@.invariants()
+        &&& self.root@.arch == self.constants@.arch
+        &&& self.instance@.constants() == self.constants@
+        &&& self.regions@.len() == self.constants@.arch.entry_count(0)
+        &&& forall|i: int|
+            #![trigger self.regions@[i]]
+            0 <= i < self.regions@.len() ==> {
+                let pred = self.regions@[i].lock.pred();
+                &&& pred.constants == self.region_constants
+                &&& pred.instance == self.instance@
+                &&& pred.index == i as nat
+                &&& pred.root == self.regions@[i].root@
+                &&& self.regions@[i].count.well_formed()
+                &&& self.regions@[i].count.constant() == pred
+            }
+    }
+
+    /// If `token` owns `frame` in this page table.
+    pub open spec fn owns(self, token: PageTableSM::frames, frame: Frame) -> bool {
+        &&& token.instance_id() == self.instance@.id()
+        &&& token.element() == frame
+    }
+
+    /// Construct a thread-safe page table, allocating the table of every region.
+    ///
+    /// Returns the tokens of `frames`, which callers need to map them, or `None` if a table
+    /// cannot be allocated or is not aligned for a root table entry.
+    pub fn new(constants: PTConstantsExec, frames: Ghost<Set<Frame>>) -> (res: Option<
+        (Self, Tracked<Map<Frame, PageTableSM::frames>>),
+    >)
+        requires
+            constants@.arch.valid(),
+            constants@.arch.level_count() > 1,
+            forall|frame1: Frame, frame2: Frame|
+                #[trigger] frames@.contains(frame1) && #[trigger] frames@.contains(frame2) && frame1
+                    != frame2 ==> !PAddr::overlap(
+                    frame1.base,
+                    frame1.size.as_nat(),
+                    frame2.base,
+                    frame2.size.as_nat(),
+                ),
+        ensures
+            res is Some ==> {
+                let (pt, tokens) = res->Some_0;
+                &&& pt.wf()
+                &&& pt.constants == constants
+                &&& forall|frame: Frame| #[trigger]
+                    frames@.contains(frame) ==> tokens@.contains_key(frame) && pt.owns(
+                        tokens@[frame],
+                        frame,
+                    )
+            },
+    {
+        proof {
+            constants@.arch.lemma_below_root();
+        }
+        let mut region_constants = constants.clone();
+        region_constants.arch = constants.arch.below_root();
+        let mut root = match M::new_init(constants.clone().arch) {
+            Some(root) => root,
+            None => return None,
+        };
+        let root_base = root.root();
+        let tracked (
+            Tracked(instance),
+            Tracked(region_tokens),
+            Tracked(writer_tokens),
+            Tracked(count_tokens),
+            Tracked(_),
+            Tracked(_),
+            Tracked(frame_tokens),
+        ) = PageTableSM::Instance::initialize(constants@, frames@);
+        let tracked mut region_tokens = region_tokens;
+        let tracked mut writer_tokens = writer_tokens;
+        let tracked mut count_tokens = count_tokens;
+
+        let entry_count = constants.arch.entry_count(0);
+        let mut regions = Vec::new();
+        let mut i = 0;
+        while i < entry_count
+            invariant
+                constants@.arch.valid(),
+                constants@.arch.level_count() > 1,
+                Self::below_root(constants, region_constants),
+                instance.constants() == constants@,
+                entry_count == constants@.arch.entry_count(0),
+                0 <= i <= entry_count,
+                root@.invariants(),
+                root@.arch == constants@.arch,
+                root@.root() == root_base@,
+                root@.contains_table(root_base@),
+                root@.table(root_base@).level == 0,
+                regions@.len() == i,
+                forall|j: int|
+                    #![trigger regions@[j]]
+                    0 <= j < i ==> {
+                        let pred = regions@[j].lock.pred();
+                        &&& pred.constants == region_constants
+                        &&& pred.instance == instance
+                        &&& pred.index == j as nat
+                        &&& pred.root == regions@[j].root@
+                        &&& regions@[j].count.well_formed()
+                        &&& regions@[j].count.constant() == pred
+                    },
+                forall|j: nat|
+                    i <= j < entry_count ==> {
+                        &&& #[trigger] region_tokens.contains_key(j)
+                        &&& region_tokens[j].instance_id() == instance.id()
+                        &&& region_tokens[j].key() == j
+                        &&& region_tokens[j].value() === Map::empty()
+                        &&& writer_tokens.contains_key(j)
+                        &&& writer_tokens[j].instance_id() == instance.id()
+                        &&& writer_tokens[j].key() == j
+                        &&& writer_tokens[j].value() == 1
+                        &&& count_tokens.contains_key(j)
+                        &&& count_tokens[j].instance_id() == instance.id()
+                        &&& count_tokens[j].key() == j
+                        &&& count_tokens[j].value() == 1
+                    },
+            decreases entry_count - i,
+        {
+            let pt_mem = match M::new_init(region_constants.clone().arch) {
+                Some(pt_mem) => pt_mem,
+                None => return None,
+            };
+            let base = pt_mem.root();
+            // A table descriptor only holds a 4K-aligned address
+            if base.0 % FrameSize::Size4K.as_usize() != 0 {
+                return None;
+            }
+            let entry = E::new(base, MemAttr::default(), false).to_u64();
+            root.write(root_base, i, entry);
+            proof {
+                PI::init_implies_invariants(pt_mem, region_constants);
+                pt_mem@.lemma_init_implies_empty::<G>();
+            }
+            let tracked token = region_tokens.tracked_remove(i as nat);
+            // Publish the empty tables with count 2
+            let tracked (Tracked(writer), Tracked(counts), Tracked(published)) = instance.publish(
+                i as nat,
+                pt_mem@,
+                &token,
+                writer_tokens.tracked_remove(i as nat),
+                count_tokens.tracked_remove(i as nat),
+            );
+            let ghost pred = LockedPageTablePred::<M, G, PI> {
+                constants: region_constants,
+                instance,
+                index: i as nat,
+                root: base@,
+                _phantom: PhantomData,
+            };
+            proof {
+                assert(PI::invariants(pt_mem, region_constants));
+            }
+            let tracked count = RegionCount { counts, published: Some(published) };
+            let locked = LockedPageTable {
+                pt_mem,
+                region: Tracked(token),
+                writer: Tracked(writer),
+                count: 2,
+            };
+            regions.push(
+                Region {
+                    lock: RwLock::new(locked, Ghost(pred)),
+                    count: AtomicU64::new(Ghost(pred), 2, Tracked(count)),
+                    root: base,
+                },
+            );
+            i += 1;
+        }
+        Some(
+            (
+                Self {
+                    root,
+                    regions,
+                    constants,
+                    region_constants,
+                    instance: Tracked(instance),
+                    _phantom: PhantomData,
+                },
+                Tracked(frame_tokens),
+            ),
+        )
+    }
+
+    /// Physical address of the root table.
+    pub fn root(&self) -> (res: PAddrExec)
+        requires
+            self.wf(),
+        ensures
+            res@ == self.root@.root(),
+    {
+        self.root.root()
+    }
+
+    /// Make the count of region `index` odd before updating its tables.
+    ///
+    /// The count of a region whose update could not be published is already odd, and stays so.
+    fn begin_update(
+        &self,
+        index: usize,
+        count: u64,
+        writer: Tracked<PageTableSM::writer>,
+    ) -> (res: (u64, Tracked<PageTableSM::writer>))
+        requires
+            self.wf(),
+            index < self.regions@.len(),
+            writer@.instance_id() == self.instance@.id(),
+            writer@.key() == index as nat,
+            writer@.value() == count as nat,
+        ensures
+            res.0 % 2 == 1,
+            res.1@.instance_id() == self.instance@.id(),
+            res.1@.key() == index as nat,
+            res.1@.value() == res.0 as nat,
+    {
+        let tracked mut writer = writer.get();
+        if count % 2 == 1 {
+            return (count, Tracked(writer));
+        }
+        atomic_with_ghost!(&self.regions[index].count => store(count + 1); ghost g => {
+            let tracked RegionCount { counts, published } = g;
+            let tracked (Tracked(new_writer), Tracked(new_counts)) =
+                self.instance.borrow().begin(index as nat, writer, counts);
+            writer = new_writer;
+            g = RegionCount { counts: new_counts, published };
+        });
+        (count + 1, Tracked(writer))
+    }
+
+    /// Make the count of region `index` even after updating its tables, publishing them.
+    ///
+    /// The count stays odd if it would overflow, or if the root of the tables has moved, and
+    /// lock-free queries of the region then always fall back to the lock.
+    fn end_update(
+        &self,
+        index: usize,
+        count: u64,
+        pt_mem: &M,
+        Tracked(region): Tracked<&PageTableSM::regions>,
+        writer: Tracked<PageTableSM::writer>,
+    ) -> (res: (u64, Tracked<PageTableSM::writer>))
+        requires
+            self.wf(),
+            index < self.regions@.len(),
+            count % 2 == 1,
+            PI::invariants(*pt_mem, self.region_constants),
+            region.instance_id() == self.instance@.id(),
+            region.key() == index as nat,
+            region.value() == pt_mem@.interpret::<G>(),
+            writer@.instance_id() == self.instance@.id(),
+            writer@.key() == index as nat,
+            writer@.value() == count as nat,
+        ensures
+            res.1@.instance_id() == self.instance@.id(),
+            res.1@.key() == index as nat,
+            res.1@.value() == res.0 as nat,
+    {
+        let tracked mut writer = writer.get();
+        proof {
+            // Any query shows the page table memory invariants
+            PI::lemma_walk_answers_query(*pt_mem, self.region_constants, VAddr(0));
+        }
+        if count == u64::MAX || pt_mem.root().0 != self.regions[index].root.0 {
+            return (count, Tracked(writer));
+        }
+        atomic_with_ghost!(&self.regions[index].count => store(count + 1); ghost g => {
+            let tracked RegionCount { counts, published: _ } = g;
+            let tracked (Tracked(new_writer), Tracked(new_counts), Tracked(published)) =
+                self.instance.borrow().publish(index as nat, pt_mem@, region, writer, counts);
+            writer = new_writer;
+            g = RegionCount { counts: new_counts, published: Some(published) };
+            assert(PI::invariants(*pt_mem, self.region_constants));
+        });
+        (count + 1, Tracked(writer))
+    }
+
+    /// Map a frame owned by the caller.
+    ///
+    /// The frame token is consumed if mapping succeeds, and returned otherwise. The operation
+    /// takes effect atomically as the transition from `res.2@.0` to `res.2@.1`, which are the
+    /// states of the region token of `vbase` before and after the `map` step.
+    ///
+    /// Blocks of the root level span a whole region and cannot be mapped, which fails as if
+    /// the page table ran out of memory.
+    pub fn map(
+        &self,
+        vbase: VAddrExec,
+        frame: FrameExec,
+        frame_token: Tracked<PageTableSM::frames>,
+    ) -> (res: (
+        PagingResult,
+        Tracked<Option<PageTableSM::frames>>,
+        Ghost<(PageTableState, PageTableState)>,
+    ))
+        requires
+            self.wf(),
+            self.owns(frame_token@, frame@),
+            PageTableState::new(Map::empty(), self.constants@).map_pre(vbase@, frame@),
+        ensures
+            PageTableState::map(res.2@.0, res.2@.1, vbase@, frame@, res.0),
+            res.0 is Ok <==> res.1@ is None,
+            res.1@ is Some ==> self.owns(res.1@->Some_0, frame@),
+    {
+        let index = self.constants.arch.pte_index(vbase, 0);
+        let (locked, handle) = self.regions[index].lock.acquire_write();
+        let pt_mem = locked.pt_mem;
+        let tracked mut region = locked.region.get();
+        let tracked frame_token = frame_token.get();
+        let ghost s1 = PageTableState::new(region.value(), self.constants@);
+        proof {
+            // An owned frame does not overlap any mapping.
+            self.instance.borrow().frame_unmapped(index as nat, frame@, &region, &frame_token);
+            self.constants@.arch.lemma_below_root();
+            self.constants@.arch.lemma_frame_size_inversion(0);
+        }
+        if self.constants.arch.level_of_frame_size(frame.size) == 0 {
+            handle.release_write(
+                LockedPageTable {
+                    pt_mem,
+                    region: Tracked(region),
+                    writer: locked.writer,
+                    count: locked.count,
+                },
+            );
+            return (Err(()), Tracked(Some(frame_token)), Ghost((s1, s1)));
+        }
+        let (count, writer) = self.begin_update(index, locked.count, locked.writer);
+        let tracked writer = writer.get();
+        let (res, pt_mem) = PI::map(pt_mem, self.region_constants.clone(), vbase, frame);
+        let tracked mut remaining = None;
+        if res.is_ok() {
+            // Linearization point
+            proof {
+                region = self.instance.borrow().map(
+                    index as nat,
+                    vbase@,
+                    frame@,
+                    pt_mem@.interpret::<G>(),
+                    region,
+                    &writer,
+                    frame_token,
+                );
+            }
+        } else {
+            proof {
+                remaining = Some(frame_token);
+            }
+        }
+        let ghost s2 = PageTableState::new(region.value(), self.constants@);
+        let (count, writer) = self.end_update(
+            index,
+            count,
+            &pt_mem,
+            Tracked(&region),
+            Tracked(writer),
+        );
+        handle.release_write(LockedPageTable { pt_mem, region: Tracked(region), writer, count });
+        (res, Tracked(remaining), Ghost((s1, s2)))
+    }
+
+    /// Unmap a virtual address.
+    ///
+    /// The unmapped page is invalidated through `tlb`, and the token of the unmapped frame is
+    /// returned to the caller. The operation takes effect atomically as the transition from
+    /// `res.2@.0` to `res.2@.1`, which are the states of the region token of `vbase` before and
+    /// after the `unmap` step.
+    pub fn unmap<T: TLBExec>(&self, vbase: VAddrExec, tlb: &mut T) -> (res: (
+        PagingResult,
+        Tracked<Option<PageTableSM::frames>>,
+        Ghost<(PageTableState, PageTableState)>,
+    ))
+        requires
+            self.wf(),
+            PageTableState::new(Map::empty(), self.constants@).unmap_pre(vbase@),
+        ensures
+            PageTableState::unmap(res.2@.0, res.2@.1, vbase@, res.0),
+            res.0 is Ok <==> res.1@ is Some,
+            res.0 is Ok ==> self.owns(res.1@->Some_0, res.2@.0.mappings[vbase@]),
+            res.0 is Ok ==> tlb.issued() == old(tlb).issued().push(
+                TLBOp::VA(TLBTag(self.constants.tag as nat), vbase@),
+            ),
+            res.0 is Err ==> tlb.issued() == old(tlb).issued(),
+    {
+        let index = self.constants.arch.pte_index(vbase, 0);
+        let (locked, handle) = self.regions[index].lock.acquire_write();
+        let pt_mem = locked.pt_mem;
+        let tracked mut region = locked.region.get();
+        let ghost s1 = PageTableState::new(region.value(), self.constants@);
+        proof {
+            // Regions have the leaf level of the whole page table
+            self.constants@.arch.lemma_below_root();
+        }
+        let (count, writer) = self.begin_update(index, locked.count, locked.writer);
+        let tracked writer = writer.get();
+        let (res, pt_mem) = PI::unmap(pt_mem, self.region_constants.clone(), vbase, tlb);
+        let tracked mut unmapped = None;
+        if res.is_ok() {
+            // Linearization point
+            proof {
+                let tracked (Tracked(new_region), Tracked(frame_token)) =
+                    self.instance.borrow().unmap(
+                    index as nat,
+                    vbase@,
+                    pt_mem@.interpret::<G>(),
+                    region,
+                    &writer,
+                );
+                region = new_region;
+                unmapped = Some(frame_token);
+            }
+        }
+        let ghost s2 = PageTableState::new(region.value(), self.constants@);
+        let (count, writer) = self.end_update(
+            index,
+            count,
+            &pt_mem,
+            Tracked(&region),
+            Tracked(writer),
+        );
+        handle.release_write(LockedPageTable { pt_mem, region: Tracked(region), writer, count });
+        (res, Tracked(unmapped), Ghost((s1, s2)))
+    }
+
+    /// Query a virtual address.
+    ///
+    /// Walks the tables of the region of `vaddr` under its read lock. The result is the one of
+    /// `PageTableState` at `res.1@`, the state of the region token at which the query takes
+    /// effect (`lemma_query_in_region`).
+    pub fn query(&self, vaddr: VAddrExec) -> (res: (
+        PagingResult<(VAddrExec, FrameExec)>,
+        Ghost<PageTableState>,
+    ))
+        requires
+            self.wf(),
+            PageTableState::new(Map::empty(), self.constants@).query_pre(vaddr@),
+        ensures
+            res.1@.constants == self.constants@,
+            PageTableState::query(
+                res.1@,
+                res.1@,
+                vaddr@,
+                match res.0 {
+                    Ok((vaddr, frame)) => Ok((vaddr@, frame@)),
+                    Err(()) => Err(()),
+                },
+            ),
+    {
+        let index = self.constants.arch.pte_index(vaddr, 0);
+        let handle = self.regions[index].lock.acquire_read();
+        let locked = handle.borrow();
+        let pt_mem = &locked.pt_mem;
+        let ghost s = PageTableState::new(locked.region@.value(), self.constants@);
+        proof {
+            PI::lemma_walk_answers_query(*pt_mem, self.region_constants, vaddr@);
+        }
+        let res = Self::walk(&self.region_constants.arch, pt_mem, vaddr);
+        handle.release_read();
+        (res, Ghost(s))
+    }
+
+    /// Walk the page table memory of a region from its root for `vaddr`.
+    fn walk(arch: &PTArchExec, pt_mem: &M, vaddr: VAddrExec) -> (res: PagingResult<
+        (VAddrExec, FrameExec),
+    >)
+        requires
+            pt_mem@.invariants(),
+            pt_mem@.walkable::<G>(),
+            pt_mem@.arch == arch@,
+        ensures
+            match res {
+                Ok((vaddr, frame)) => Ok((vaddr@, frame@)),
+                Err(()) => Err(()),
+            } == pt_mem@.query_walk::<G>(vaddr@),
+    {
+        proof {
+            pt_mem@.lemma_contains_root();
+        }
+        let level_count = arch.level_count();
+        let mut base = pt_mem.root();
+        let mut level = 0;
+        loop
+            invariant
+                pt_mem@.invariants(),
+                pt_mem@.walkable::<G>(),
+                pt_mem@.arch == arch@,
+                level_count == arch@.level_count(),
+                level < level_count,
+                pt_mem@.contains_table(base@),
+                pt_mem@.table(base@).level == level,
+                pt_mem@.walk_from::<G>(vaddr@, base@, level as nat) == pt_mem@.walk_from::<G>(
+                    vaddr@,
+                    pt_mem@.root(),
+                    0,
+                ),
+            decreases level_count - level,
+        {
+            let pte = E::from_u64(pt_mem.read(base, arch.pte_index(vaddr, level)));
+            if level + 1 < level_count && pte.valid() && !pte.huge() {
+                // Descend into the next-level table
+                base = pte.addr();
+                level += 1;
+            } else if pte.valid() {
+                let frame = FrameExec {
+                    base: pte.addr(),
+                    size: arch.frame_size(level),
+                    attr: pte.attr(),
+                };
+                return Ok((arch.vbase(vaddr, level), frame));
+            } else {
+                return Err(());
+            }
+        }
+    }
+
+    /// Walk the page table memory published with a count through `walker` for `vaddr`.
+    ///
+    /// `res.1@` records the latest count any load was made at. The walk answers from the
+    /// published memory if no load was made after the count changed.
+    fn walk_published<W: PageTableWalkerExec>(
+        walker: &W,
+        arch: &PTArchExec,
+        root: PAddrExec,
+        vaddr: VAddrExec,
+        Tracked(published): Tracked<&PageTableSM::published>,
+        seen: Tracked<PageTableSM::seen>,
+    ) -> (res: (PagingResult<(VAddrExec, FrameExec)>, Tracked<PageTableSM::seen>))
+        requires
+            published.value().1.invariants(),
+            published.value().1.walkable::<G>(),
+            published.value().1.arch == arch@,
+            published.value().1.root() == root@,
+            seen@.instance_id() == published.instance_id(),
+            seen@.element() == published.key(),
+        ensures
+            res.1@.instance_id() == published.instance_id(),
+            res.1@.element().0 == published.key().0,
+            res.1@.element().1 >= published.key().1,
+            res.1@.element().1 == published.key().1 ==> match res.0 {
+                Ok((vaddr, frame)) => Ok((vaddr@, frame@)),
+                Err(()) => Err(()),
+            } == published.value().1.query_walk::<G>(vaddr@),
+    {
+        let ghost mem = published.value().1;
+        let ghost count = published.key().1;
+        proof {
+            mem.lemma_contains_root();
+        }
+        let tracked mut latest = seen.get();
+        let level_count = arch.level_count();
+        let mut base = root;
+        let mut level = 0;
+        loop
+            invariant
+                mem.invariants(),
+                mem.walkable::<G>(),
+                mem.arch == arch@,
+                level_count == arch@.level_count(),
+                level < level_count,
+                latest.instance_id() == published.instance_id(),
+                latest.element().0 == published.key().0,
+                latest.element().1 >= count,
+                latest.element().1 == count ==> {
+                    &&& mem.contains_table(base@)
+                    &&& mem.table(base@).level == level
+                    &&& mem.walk_from::<G>(vaddr@, base@, level as nat) == mem.walk_from::<G>(
+                        vaddr@,
+                        mem.root(),
+                        0,
+                    )
+                },
+            decreases level_count - level,
+        {
+            let (entry, seen) = walker.load(Tracked(published), base, arch.pte_index(vaddr, level));
+            proof {
+                // Keep a later count once the walk has seen one
+                if seen@.element().1 != count {
+                    latest = seen.get();
+                }
+            }
+            let pte = E::from_u64(entry);
+            if level + 1 < level_count && pte.valid() && !pte.huge() {
+                // Descend into the next-level table
+                base = pte.addr();
+                level += 1;
+            } else if pte.valid() {
+                let frame = FrameExec {
+                    base: pte.addr(),
+                    size: arch.frame_size(level),
+                    attr: pte.attr(),
+                };
+                return (Ok((arch.vbase(vaddr, level), frame)), Tracked(latest));
+            } else {
+                return (Err(()), Tracked(latest));
+            }
+        }
+    }
+
+    /// Query a virtual address through `walker`, without taking the lock.
+    ///
+    /// The walk reads the tables published with the count of the region of `vaddr`, and falls
+    /// back to `query` if the count is odd or changes before the walk ends. The result is the
+    /// one of `PageTableState` at `res.1@.0`, which equals `res.1@.1`.
+    pub fn query_lockfree<W: PageTableWalkerExec>(&self, walker: &W, vaddr: VAddrExec) -> (res: (
+        PagingResult<(VAddrExec, FrameExec)>,
+        Ghost<(PageTableState, PageTableState)>,
+    ))
+        requires
+            self.wf(),
+            PageTableState::new(Map::empty(), self.constants@).query_pre(vaddr@),
+        ensures
+            res.1@.0.constants == self.constants@,
+            query_linearizable(
+                res.1@.0,
+                res.1@.1,
+                vaddr@,
+                match res.0 {
+                    Ok((vaddr, frame)) => Ok((vaddr@, frame@)),
+                    Err(()) => Err(()),
+                },
+            ),
+    {
+        let index = self.constants.arch.pte_index(vaddr, 0);
+        let region = &self.regions[index];
+        let tracked mut published: Option<PageTableSM::published> = None;
+        let tracked mut seen: Option<PageTableSM::seen> = None;
+        let count = atomic_with_ghost!(&region.count => load(); returning count; ghost g => {
+            if count % 2 == 0 {
+                published = Some(g.published.tracked_borrow().clone());
+                seen = Some(self.instance.borrow().observe(index as nat, &g.counts));
+            }
+            assert(count % 2 == 0 ==> published is Some && {
+                let published = published->Some_0;
+                &&& published.instance_id() == self.instance@.id()
+                &&& published.key() == (index as nat, count as nat)
+                &&& published.value().1.root() == region.root@
+                &&& seen is Some
+                &&& seen->Some_0.instance_id() == self.instance@.id()
+                &&& seen->Some_0.element() == published.key()
+                &&& exists|pt_mem: M|
+                    #[trigger] PI::invariants(pt_mem, self.region_constants) && pt_mem@
+                        == published.value().1 && pt_mem@.interpret::<G>()
+                        == published.value().0
+            });
+        });
+        if count % 2 == 1 {
+            // A writer is updating the tables
+            let (res, s) = self.query(vaddr);
+            return (res, Ghost((s@, s@)));
+        }
+        let tracked published = published.tracked_unwrap();
+        let ghost s = PageTableState::new(published.value().0, self.constants@);
+        proof {
+            let pt_mem = choose|pt_mem: M|
+                #[trigger] PI::invariants(pt_mem, self.region_constants) && pt_mem@
+                    == published.value().1 && pt_mem@.interpret::<G>() == published.value().0;
+            PI::lemma_walk_answers_query(pt_mem, self.region_constants, vaddr@);
+            self.constants@.arch.lemma_below_root();
+        }
+        let (res, latest) = Self::walk_published(
+            walker,
+            &self.region_constants.arch,
+            region.root,
+            vaddr,
+            Tracked(&published),
+            Tracked(seen.tracked_unwrap()),
+        );
+        let tracked latest = latest.get();
+        let current = atomic_with_ghost!(&region.count => load(); returning current; ghost g => {
+            self.instance.borrow().seen_bound(index as nat, latest.element().1, &g.counts, &latest);
+        });
+        if current != count {
+            // The tables changed during the walk
+            let (res, s) = self.query(vaddr);
+            return (res, Ghost((s@, s@)));
+        }
+        (res, Ghost((s, s)))
+    }
+}
+
+} // verus!
@@ -2,16 +2,18 @@
 //!
 //! Concrete page table must implement this interface to satisfy the page table specification
 //! defined in `spec::page_table`.
+use alloc::vec::Vec;
 use vstd::prelude::*;
 
 use crate::common::{
-    addr::{PAddrExec, VAddrExec},
+    addr::{PAddrExec, VAddr, VAddrExec},
     arch::PTArchExec,
     frame::FrameExec,
+    pte::GhostPTE,
     PagingResult,
 };
 use crate::spec::{
-    memory::PageTableMemExec,
+    memory::{PageTableMemExec, TLBExec, TLBOp, TLBTag},
     page_table::{PTConstants, PageTableState},
 };
 
@@ -25,12 +27,41 @@ pub struct PTConstantsExec {
     pub pmem_lb: PAddrExec,
     /// Physical memory upper bound.
     pub pmem_ub: PAddrExec,
+    /// Physical regions reserved by the hypervisor, as `(base, size)` pairs.
+    pub reserved: Vec<(PAddrExec, usize)>,
+    /// TLB tag of the address space (ASID for stage-1, VMID for stage-2).
+    pub tag: usize,
 }
 
 impl PTConstantsExec {
     /// View as `PTConstants`
     pub open spec fn view(self) -> PTConstants {
-        PTConstants { arch: self.arch@, pmem_lb: self.pmem_lb@, pmem_ub: self.pmem_ub@ }
+        PTConstants {
+            arch: self.arch@,
+            pmem_lb: self.pmem_lb@,
+            pmem_ub: self.pmem_ub@,
+            reserved: Seq::new(
+                self.reserved.len() as nat,
+                |i| (self.reserved[i].0@, self.reserved[i].1 as nat),
+            ),
+        }
+    }
+
+    /// Clone the constants.
+    ///
+    /// Assumption: Cloning yields constants indistinguishable from the original.
+    #[verifier::external_body]
+    pub fn clone(&self) -> (res: Self)
+        ensures
+            res == *self,
+    {
+        Self {
+            arch: self.arch.clone(),
+            pmem_lb: self.pmem_lb,
+            pmem_ub: self.pmem_ub,
+            reserved: self.reserved.clone(),
+            tag: self.tag,
+        }
     }
 }
 
@@ -40,12 +71,15 @@ impl PTConstantsExec {
 /// - `map` specifies the pre and post conditions for the `map` operation.
 /// - `unmap` specifies the pre and post conditions for the `unmap` operation.
 /// - `query` specifies the pre and post conditions for the `query` operation.
+/// - `lemma_walk_answers_query` proves queries can be answered by walking the page table memory.
+///
+/// `G` is the descriptor format the hardware uses to interpret the page table memory.
 ///
 /// If a concrete implementation refines this specification (i.e. `impl PageTableInterface`),
 /// along with the assumptions we make about the hardware and the remaining system, we can
 /// conclude that the whole system refines the low-level specification, thus refines the
 /// high-level specification.
-pub trait PageTableInterface<M> where Self: Sized, M: PageTableMemExec {
+pub trait PageTableInterface<M, G> where Self: Sized, M: PageTableMemExec, G: GhostPTE {
     /// Invariants that must be implied at initial state and preseved after each operation.
     spec fn invariants(pt_mem: M, constants: PTConstantsExec) -> bool;
 
@@ -58,6 +92,25 @@ pub trait PageTableInterface<M> where Self: Sized, M: PageTableMemExec {
             Self::invariants(pt_mem, constants),
     ;
 
+    /// Prove the walk from the root answers queries.
+    ///
+    /// Callers that cannot hand over `pt_mem`, like `ConcurrentPageTable`, walk it themselves.
+    proof fn lemma_walk_answers_query(pt_mem: M, constants: PTConstantsExec, vaddr: VAddr)
+        requires
+            Self::invariants(pt_mem, constants),
+            PageTableState::new(pt_mem@.interpret::<G>(), constants@).query_pre(vaddr),
+        ensures
+            pt_mem@.invariants(),
+            pt_mem@.walkable::<G>(),
+            pt_mem@.arch == constants@.arch,
+            PageTableState::query(
+                PageTableState::new(pt_mem@.interpret::<G>(), constants@),
+                PageTableState::new(pt_mem@.interpret::<G>(), constants@),
+                vaddr,
+                pt_mem@.query_walk::<G>(vaddr),
+            ),
+    ;
+
     /// Map a virtual address to a physical frame.
     ///
     /// Implementation must ensure the postconditions are satisfied.
@@ -67,12 +120,12 @@ pub trait PageTableInterface<M> where Self: Sized, M: PageTableMemExec {
     ))
         requires
             Self::invariants(pt_mem, constants),
-            PageTableState::new(pt_mem@.interpret(), constants@).map_pre(vbase@, frame@),
+            PageTableState::new(pt_mem@.interpret::<G>(), constants@).map_pre(vbase@, frame@),
         ensures
             Self::invariants(res.1, constants),
             PageTableState::map(
-                PageTableState::new(pt_mem@.interpret(), constants@),
-                PageTableState::new(res.1@.interpret(), constants@),
+                PageTableState::new(pt_mem@.interpret::<G>(), constants@),
+                PageTableState::new(res.1@.interpret::<G>(), constants@),
                 vbase@,
                 frame@,
                 res.0,
@@ -81,19 +134,29 @@ pub trait PageTableInterface<M> where Self: Sized, M: PageTableMemExec {
 
     /// Unmap a virtual address.
     ///
-    /// Implementation must ensure the postconditions are satisfied.
-    fn unmap(pt_mem: M, constants: PTConstantsExec, vbase: VAddrExec) -> (res: (PagingResult, M))
+    /// Implementation must ensure the postconditions are satisfied, and invalidate the unmapped
+    /// page in the TLB through `tlb` once it is removed from the page table.
+    fn unmap<T: TLBExec>(
+        pt_mem: M,
+        constants: PTConstantsExec,
+        vbase: VAddrExec,
+        tlb: &mut T,
+    ) -> (res: (PagingResult, M))
         requires
             Self::invariants(pt_mem, constants),
-            PageTableState::new(pt_mem@.interpret(), constants@).unmap_pre(vbase@),
+            PageTableState::new(pt_mem@.interpret::<G>(), constants@).unmap_pre(vbase@),
         ensures
             Self::invariants(res.1, constants),
             PageTableState::unmap(
-                PageTableState::new(pt_mem@.interpret(), constants@),
-                PageTableState::new(res.1@.interpret(), constants@),
+                PageTableState::new(pt_mem@.interpret::<G>(), constants@),
+                PageTableState::new(res.1@.interpret::<G>(), constants@),
                 vbase@,
                 res.0,
             ),
+            res.0 is Ok ==> tlb.issued() == old(tlb).issued().push(
+                TLBOp::VA(TLBTag(constants.tag as nat), vbase@),
+            ),
+            res.0 is Err ==> tlb.issued() == old(tlb).issued(),
     ;
 
     /// Query a virtual address, return the mapped physical frame.
@@ -105,12 +168,12 @@ pub trait PageTableInterface<M> where Self: Sized, M: PageTableMemExec {
     ))
         requires
             Self::invariants(pt_mem, constants),
-            PageTableState::new(pt_mem@.interpret(), constants@).query_pre(vaddr@),
+            PageTableState::new(pt_mem@.interpret::<G>(), constants@).query_pre(vaddr@),
         ensures
             Self::invariants(res.1, constants),
             PageTableState::query(
-                PageTableState::new(pt_mem@.interpret(), constants@),
-                PageTableState::new(res.1@.interpret(), constants@),
+                PageTableState::new(pt_mem@.interpret::<G>(), constants@),
+                PageTableState::new(res.1@.interpret::<G>(), constants@),
                 vaddr@,
                 match res.0 {
                     Ok((vaddr, frame)) => Ok((vaddr@, frame@)),
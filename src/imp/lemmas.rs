@@ -57,6 +57,31 @@ pub proof fn lemma_map_eq_pair<K, V>(m1: Map<K, V>, m2: Map<K, V>)
     }
 }
 
+/// Lemma. The submap relation is transitive.
+pub proof fn lemma_submap_trans<K, V>(m1: Map<K, V>, m2: Map<K, V>, m3: Map<K, V>)
+    requires
+        m1.submap_of(m2),
+        m2.submap_of(m3),
+    ensures
+        m1.submap_of(m3),
+{
+    assert forall|k| #[trigger] m1.contains_key(k) implies m3.contains_key(k) && m1[k]
+        === m3[k] by {
+        assert(m2.contains_key(k));
+    }
+}
+
+/// Lemma. Two maps that are submaps of each other are equal.
+pub proof fn lemma_submap_antisym<K, V>(m1: Map<K, V>, m2: Map<K, V>)
+    requires
+        m1.submap_of(m2),
+        m2.submap_of(m1),
+    ensures
+        m1 === m2,
+{
+    lemma_map_eq_key_value(m1, m2);
+}
+
 /// Lemma. VA alignment to FrameSize ensures alignment to WORD_SIZE.
 pub proof fn lemma_va_align_frame_size_must_align_word_size(vaddr: VAddr, fsize: FrameSize)
     by (nonlinear_arith)
@@ -96,6 +121,37 @@ pub proof fn lemma_vaddr_in_vpage_implies_paddr_in_pframe(vaddr: VAddr, vbase: V
 {
 }
 
+/// Lemma. The word containing `paddr` lies in any word-aligned region that contains `paddr`.
+pub proof fn lemma_word_within_aligned_region(paddr: PAddr, base: PAddr, size: nat)
+    by (nonlinear_arith)
+    requires
+        paddr.within(base, size),
+        base.aligned(WORD_SIZE),
+    ensures
+        paddr.idx().addr().within(base, size),
+{
+}
+
+/// Lemma. The word containing `vaddr` lies in the same word-aligned page, and maps to the word
+/// containing the physical address of `vaddr` at the same byte offset.
+pub proof fn lemma_vaddr_word_maps_to_paddr_word(
+    vaddr: VAddr,
+    vbase: VAddr,
+    pbase: PAddr,
+    size: nat,
+)
+    by (nonlinear_arith)
+    requires
+        vaddr.within(vbase, size),
+        vbase.aligned(WORD_SIZE),
+        pbase.aligned(WORD_SIZE),
+    ensures
+        vaddr.idx().addr().within(vbase, size),
+        vaddr.idx().addr().map(vbase, pbase).idx() == vaddr.map(vbase, pbase).idx(),
+        vaddr.map(vbase, pbase).word_offset() == vaddr.word_offset(),
+{
+}
+
 /// Lemma. `a % WORD_SIZE == 0` and `b % WORD_SIZE == 0` implies `(a + b) % WORD_SIZE == 0`.
 pub proof fn lemma_sum_align_word_size(a: nat, b: nat)
     by (nonlinear_arith)
@@ -3,24 +3,30 @@ use vstd::prelude::*;
 
 use super::lemmas::*;
 use crate::common::{
-    addr::{PAddr, VAddr, VIdx, WORD_SIZE},
+    addr::{AccessSize, PAddr, PIdx, VAddr, VIdx, WORD_SIZE},
     frame::Frame,
-    MemoryResult, PagingResult,
+    pte::{same_hw_translation, GhostPTE},
+    FaultKind, MemoryResult, MmioAccess, PagingResult, Privilege,
+};
+use crate::spec::{
+    hardware::HardwareState,
+    high_level::HighLevelState,
+    low_level::LowLevelState,
+    memory::{PageTableMem, TLBOp, TLBTag, Table},
 };
-use crate::spec::{high_level::HighLevelState, low_level::LowLevelState};
 
 verus! {
 
 /// Lemma. If the TLB is a subset of the page table, then the interpreted page table
 /// is equal to the all mappings (page table & TLB).
-proof fn lemma_pt_interpret_equals_all_mappings(st: LowLevelState)
+proof fn lemma_pt_interpret_equals_all_mappings<G: GhostPTE>(st: LowLevelState<G>)
     requires
         st.tlb_is_submap_of_pt(),
     ensures
-        st.pt.interpret() === st.all_mappings(),
+        st.pt.interpret::<G>() === st.all_mappings(),
 {
-    let interp_pt = st.pt.interpret();
-    let tlb = st.tlb;
+    let interp_pt = st.pt.interpret::<G>();
+    let (tlb, tag) = (st.tlb, st.constants.tag);
     let all_mappings = st.all_mappings();
 
     // 1. Any mapping in `all_mappings` is also in `interp_pt`.
@@ -30,8 +36,8 @@ proof fn lemma_pt_interpret_equals_all_mappings(st: LowLevelState)
     // 2. Any mapping in `interp_pt` is also in `all_mappings`.
     assert forall|vbase, frame| #[trigger]
         interp_pt.contains_pair(vbase, frame) implies all_mappings.contains_pair(vbase, frame) by {
-        if tlb.contains_base(vbase) {
-            assert(all_mappings.contains_pair(vbase, tlb.index(vbase)));
+        if tlb.contains_base(tag, vbase) {
+            assert(all_mappings.contains_pair(vbase, tlb.index(tag, vbase)));
         } else {
             assert(all_mappings.contains_pair(vbase, interp_pt[vbase]));
         }
@@ -43,23 +49,23 @@ proof fn lemma_pt_interpret_equals_all_mappings(st: LowLevelState)
 
 /// Lemma. If there is no overlap in the virtual memory space, then there is at most
 /// one mapping containing a virtual address.
-proof fn lemma_at_most_one_mapping_for_vaddr(st: LowLevelState, vaddr: VAddr)
+proof fn lemma_at_most_one_mapping_for_vaddr<G: GhostPTE>(st: LowLevelState<G>, vaddr: VAddr)
     requires
         st.mappings_nonoverlap_in_vmem(),
     ensures
         forall|vbase1, frame1, vbase2, frame2|
             {
-                &&& #[trigger] st.pt.interpret().contains_pair(vbase1, frame1)
+                &&& #[trigger] st.pt.interpret::<G>().contains_pair(vbase1, frame1)
                 &&& vaddr.within(vbase1, frame1.size.as_nat())
-                &&& #[trigger] st.pt.interpret().contains_pair(vbase2, frame2)
+                &&& #[trigger] st.pt.interpret::<G>().contains_pair(vbase2, frame2)
                 &&& vaddr.within(vbase2, frame2.size.as_nat())
             } ==> vbase1 == vbase2,
 {
     if exists|vbase1, frame1, vbase2, frame2|
         {
-            &&& #[trigger] st.pt.interpret().contains_pair(vbase1, frame1)
+            &&& #[trigger] st.pt.interpret::<G>().contains_pair(vbase1, frame1)
             &&& vaddr.within(vbase1, frame1.size.as_nat())
-            &&& #[trigger] st.pt.interpret().contains_pair(vbase2, frame2)
+            &&& #[trigger] st.pt.interpret::<G>().contains_pair(vbase2, frame2)
             &&& vaddr.within(vbase2, frame2.size.as_nat())
             &&& vbase1 != vbase2
         } {
@@ -72,9 +78,9 @@ proof fn lemma_at_most_one_mapping_for_vaddr(st: LowLevelState, vaddr: VAddr)
             frame2,
         |
             {
-                &&& #[trigger] st.pt.interpret().contains_pair(base1, frame1)
+                &&& #[trigger] st.pt.interpret::<G>().contains_pair(base1, frame1)
                 &&& vaddr.within(base1, frame1.size.as_nat())
-                &&& #[trigger] st.pt.interpret().contains_pair(base2, frame2)
+                &&& #[trigger] st.pt.interpret::<G>().contains_pair(base2, frame2)
                 &&& vaddr.within(base2, frame2.size.as_nat())
                 &&& base1 != base2
             };
@@ -84,7 +90,7 @@ proof fn lemma_at_most_one_mapping_for_vaddr(st: LowLevelState, vaddr: VAddr)
 
 /// Lemma. If the TLB has a mapping for a virtual address, then the page table also has a
 /// mapping for that virtual address, and the two mappings are equal.
-proof fn lemma_mapping_in_both_tlb_and_pt(st: LowLevelState, vaddr: VAddr)
+proof fn lemma_mapping_in_both_tlb_and_pt<G: GhostPTE>(st: LowLevelState<G>, vaddr: VAddr)
     requires
         st.tlb_is_submap_of_pt(),
         st.mappings_nonoverlap_in_vmem(),
@@ -99,7 +105,7 @@ proof fn lemma_mapping_in_both_tlb_and_pt(st: LowLevelState, vaddr: VAddr)
 
     let (vbase, frame) = st.hw_state().tlb_mapping_for(vaddr);
     // TLB is submap of PT.
-    assert(st.pt.interpret().contains_pair(vbase, frame));
+    assert(st.pt.interpret::<G>().contains_pair(vbase, frame));
     assert(st.hw_state().pt_has_mapping_for(vaddr));
     // Lemma ensures that the two mappings are equal.
     lemma_at_most_one_mapping_for_vaddr(st, vaddr);
@@ -110,7 +116,11 @@ proof fn lemma_mapping_in_both_tlb_and_pt(st: LowLevelState, vaddr: VAddr)
 
 /// Lemma. If there is no overlap in the physical memory space, then 2 different virtual
 /// indexes cannot map to the same physical index.
-proof fn lemma_different_pidxs_for_different_vidxs(st: LowLevelState, vidx1: VIdx, vidx2: VIdx)
+proof fn lemma_different_pidxs_for_different_vidxs<G: GhostPTE>(
+    st: LowLevelState<G>,
+    vidx1: VIdx,
+    vidx2: VIdx,
+)
     requires
         st.mappings_nonoverlap_in_pmem(),
         st.mappings_aligned(),
@@ -118,9 +128,9 @@ proof fn lemma_different_pidxs_for_different_vidxs(st: LowLevelState, vidx1: VId
     ensures
         forall|vbase1: VAddr, frame1: Frame, vbase2: VAddr, frame2: Frame|
             {
-                &&& #[trigger] st.pt.interpret().contains_pair(vbase1, frame1)
+                &&& #[trigger] st.pt.interpret::<G>().contains_pair(vbase1, frame1)
                 &&& vidx1.addr().within(vbase1, frame1.size.as_nat())
-                &&& #[trigger] st.pt.interpret().contains_pair(vbase2, frame2)
+                &&& #[trigger] st.pt.interpret::<G>().contains_pair(vbase2, frame2)
                 &&& vidx2.addr().within(vbase2, frame2.size.as_nat())
             } ==> vidx1.addr().map(vbase1, frame1.base).idx() != vidx2.addr().map(
                 vbase2,
@@ -129,9 +139,9 @@ proof fn lemma_different_pidxs_for_different_vidxs(st: LowLevelState, vidx1: VId
 {
     if exists|vbase1, frame1, vbase2, frame2|
         {
-            &&& #[trigger] st.pt.interpret().contains_pair(vbase1, frame1)
+            &&& #[trigger] st.pt.interpret::<G>().contains_pair(vbase1, frame1)
             &&& vidx1.addr().within(vbase1, frame1.size.as_nat())
-            &&& #[trigger] st.pt.interpret().contains_pair(vbase2, frame2)
+            &&& #[trigger] st.pt.interpret::<G>().contains_pair(vbase2, frame2)
             &&& vidx2.addr().within(vbase2, frame2.size.as_nat())
             &&& vidx1.addr().map(vbase1, frame1.base).idx() == vidx2.addr().map(
                 vbase2,
@@ -146,9 +156,9 @@ proof fn lemma_different_pidxs_for_different_vidxs(st: LowLevelState, vidx1: VId
             frame2,
         |
             {
-                &&& #[trigger] st.pt.interpret().contains_pair(vbase1, frame1)
+                &&& #[trigger] st.pt.interpret::<G>().contains_pair(vbase1, frame1)
                 &&& vidx1.addr().within(vbase1, frame1.size.as_nat())
-                &&& #[trigger] st.pt.interpret().contains_pair(vbase2, frame2)
+                &&& #[trigger] st.pt.interpret::<G>().contains_pair(vbase2, frame2)
                 &&& vidx2.addr().within(vbase2, frame2.size.as_nat())
                 &&& vidx1.addr().map(vbase1, frame1.base).idx() == vidx2.addr().map(
                     vbase2,
@@ -166,6 +176,489 @@ proof fn lemma_different_pidxs_for_different_vidxs(st: LowLevelState, vidx1: VId
     }
 }
 
+/// Lemma. A physical word reached through a frame that does not overlap page tables never
+/// lies in page table memory.
+proof fn lemma_frame_pidx_not_in_table<G: GhostPTE>(
+    st: LowLevelState<G>,
+    vbase: VAddr,
+    frame: Frame,
+    vaddr: VAddr,
+)
+    requires
+        frame.base.aligned(frame.size.as_nat()),
+        !st.overlaps_tables(frame),
+        vaddr.within(vbase, frame.size.as_nat()),
+    ensures
+        !st.pt.in_table(vaddr.map(vbase, frame.base).idx()),
+{
+    let paddr = vaddr.map(vbase, frame.base);
+    lemma_vaddr_in_vpage_implies_paddr_in_pframe(vaddr, vbase, frame);
+    lemma_pa_align_frame_size_must_align_word_size(frame.base, frame.size);
+    lemma_word_within_aligned_region(paddr, frame.base, frame.size.as_nat());
+    if st.pt.in_table(paddr.idx()) {
+        // Proof by contradiction. The word lies in both the frame and a table.
+        let i = choose|i|
+            0 <= i < st.pt.tables.len() && paddr.idx().addr().within(
+                #[trigger] st.pt.tables[i].base,
+                st.pt.tables[i].size.as_nat(),
+            );
+        assert(PAddr::overlap(
+            frame.base,
+            frame.size.as_nat(),
+            st.pt.tables[i].base,
+            st.pt.tables[i].size.as_nat(),
+        ));
+        assert(st.overlaps_tables(frame));
+    }
+}
+
+/// Lemma. If all tables lie in reserved memory, a frame that does not overlap reserved memory
+/// does not overlap any table.
+proof fn lemma_unreserved_frame_not_in_tables<G: GhostPTE>(st: LowLevelState<G>, frame: Frame)
+    requires
+        st.tables_reserved(),
+        !PAddr::overlap_any(frame.base, frame.size.as_nat(), st.constants.reserved),
+    ensures
+        !st.overlaps_tables(frame),
+{
+    let reserved = st.constants.reserved;
+    if st.overlaps_tables(frame) {
+        // Proof by contradiction. The table lies in a reserved region, so the frame
+        // overlaps that region.
+        let i = choose|i|
+            0 <= i < st.pt.tables.len() && PAddr::overlap(
+                frame.base,
+                frame.size.as_nat(),
+                #[trigger] st.pt.tables[i].base,
+                st.pt.tables[i].size.as_nat(),
+            );
+        let table = st.pt.tables[i];
+        assert(PAddr::inside_any(table.base, table.size.as_nat(), reserved));
+        let j = choose|j|
+            #![trigger reserved[j]]
+            0 <= j < reserved.len() && reserved[j].0.0 <= table.base.0 && table.base.0
+                + table.size.as_nat() <= reserved[j].0.0 + reserved[j].1;
+        assert(PAddr::overlap(frame.base, frame.size.as_nat(), reserved[j].0, reserved[j].1));
+    }
+}
+
+/// Lemma. Tables are allocated from the pool, so if the pool lies in reserved memory, all
+/// tables lie in reserved memory.
+proof fn lemma_pool_implies_tables_reserved<G: GhostPTE>(st: LowLevelState<G>)
+    requires
+        st.pt.invariants(),
+        st.pool_reserved(),
+    ensures
+        st.tables_reserved(),
+{
+    let pool = st.pt.pool;
+    let reserved = st.constants.reserved;
+    assert forall|i| 0 <= i < st.pt.tables.len() implies PAddr::inside_any(
+        #[trigger] st.pt.tables[i].base,
+        st.pt.tables[i].size.as_nat(),
+        reserved,
+    ) by {
+        let table = st.pt.tables[i];
+        assert(st.pt.table_in_pool(table));
+        let j = choose|j|
+            #![trigger pool[j]]
+            0 <= j < pool.len() && pool[j].0.0 <= table.base.0 && table.base.0
+                + table.size.as_nat() <= pool[j].0.0 + pool[j].1;
+        assert(PAddr::inside_any(pool[j].0, pool[j].1, reserved));
+        let k = choose|k|
+            #![trigger reserved[k]]
+            0 <= k < reserved.len() && reserved[k].0.0 <= pool[j].0.0 && pool[j].0.0 + pool[j].1
+                <= reserved[k].0.0 + reserved[k].1;
+        assert(reserved[k].0.0 <= table.base.0 && table.base.0 + table.size.as_nat()
+            <= reserved[k].0.0 + reserved[k].1);
+    }
+}
+
+/// Lemma. If all tables lie in reserved memory and no mapping overlaps reserved memory, then
+/// no mapping covers page table memory.
+proof fn lemma_reserved_implies_tables_unmapped<G: GhostPTE>(st: LowLevelState<G>)
+    requires
+        st.mappings_unreserved(),
+        st.tables_reserved(),
+    ensures
+        st.tables_unmapped(),
+{
+    assert forall|vbase: VAddr, frame: Frame| #[trigger]
+        st.pt.interpret::<G>().contains_pair(vbase, frame) implies !st.overlaps_tables(frame) by {
+        lemma_unreserved_frame_not_in_tables(st, frame);
+    }
+}
+
+/// Lemma. A memory write access never modifies page table memory, so the page table
+/// interpretation is unchanged.
+///
+/// Only requires the TLB entries to be safe, so the write may go through a stale entry.
+proof fn lemma_write_preserves_pt_interpret<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
+    vaddr: VAddr,
+    size: AccessSize,
+    value: u64,
+    privilege: Privilege,
+    res: MemoryResult<()>,
+    mmio: Option<MmioAccess>,
+)
+    requires
+        s1.pt_invariants(),
+        s1.tlb_unreserved(),
+        s1.constants === s2.constants,
+        HardwareState::write_access(
+            s1.hw_state(),
+            s2.hw_state(),
+            vaddr,
+            size,
+            value,
+            privilege,
+            res,
+            mmio,
+        ),
+    ensures
+        s2.pt.tables == s1.pt.tables,
+        s2.pt.arch == s1.pt.arch,
+        s2.pt.mem === s2.mem,
+        s2.mem.lb() == s1.mem.lb(),
+        s2.mem.ub() == s1.mem.ub(),
+        forall|base: PAddr| #[trigger] s2.pt.table_view(base) == s1.pt.table_view(base),
+        s2.pt.interpret::<G>() == s1.pt.interpret::<G>(),
+{
+    if s2.mem === s1.mem {
+        assert(s2.pt == s1.pt);
+    } else {
+        // The written word is reached through a TLB entry or a page table mapping, neither of
+        // which covers reserved memory
+        let (vbase, frame) = if s1.hw_state().tlb_has_mapping_for(vaddr) {
+            s1.hw_state().tlb_mapping_for(vaddr)
+        } else {
+            s1.hw_state().pt_mapping_for(vaddr)
+        };
+        assert(frame.base.aligned(frame.size.as_nat()));
+        assert(!PAddr::overlap_any(frame.base, frame.size.as_nat(), s1.constants.reserved));
+        let paddr = vaddr.map(vbase, frame.base);
+        let pidx = paddr.idx();
+        assert(s2.mem === s1.mem.write(
+            pidx,
+            size.insert(s1.mem.read(pidx), paddr.word_offset(), value),
+        ));
+        lemma_unreserved_frame_not_in_tables(s1, frame);
+        lemma_frame_pidx_not_in_table(s1, vbase, frame, vaddr);
+        // Table contents are unchanged
+        assert forall|base: PAddr| s1.pt.contains_table(base) implies #[trigger]
+            s2.pt.table_view(base) == s1.pt.table_view(base) by {
+            assert forall|i: int| 0 <= i < s1.pt.table_view(base).len() implies #[trigger]
+                s2.pt.table_view(base)[i] == s1.pt.table_view(base)[i] by {
+                s1.pt.lemma_entry_in_mem(base, i as nat);
+                s1.pt.lemma_entry_in_table(base, i as nat);
+            }
+            assert(s2.pt.table_view(base) =~= s1.pt.table_view(base));
+        }
+        s1.pt.lemma_same_tables_same_interpret::<G>(s2.pt);
+        assert forall|base: PAddr| #[trigger]
+            s2.pt.table_view(base) == s1.pt.table_view(base) by {
+            if !s1.pt.contains_table(base) {
+                assert(!s2.pt.contains_table(base));
+            }
+        }
+    }
+}
+
+/// Lemma. A page table operation that keeps the mappings does not change the interpreted
+/// memory, since it only modifies page table memory.
+proof fn lemma_pt_op_preserves_interpret_mem<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
+)
+    requires
+        s1.invariants(),
+        s2.invariants(),
+        s1.all_mappings() == s2.all_mappings(),
+        HardwareState::pt_op(s1.hw_state(), s2.hw_state()),
+    ensures
+        s1.interpret_mem() == s2.interpret_mem(),
+{
+    lemma_pt_interpret_equals_all_mappings(s1);
+    lemma_pt_interpret_equals_all_mappings(s2);
+    assert forall|vidx: VIdx| #[trigger]
+        s1.interpret_mem().contains_key(vidx) implies s1.interpret_mem()[vidx]
+        == s2.interpret_mem()[vidx] by {
+        let (vbase, frame) = choose|vbase: VAddr, frame: Frame|
+            {
+                &&& #[trigger] s1.all_mappings().contains_pair(vbase, frame)
+                &&& !frame.attr.device()
+                &&& vidx.addr().within(vbase, frame.size.as_nat())
+            };
+        // The word is not in page table memory of either state
+        assert(s1.pt.interpret::<G>().contains_pair(vbase, frame));
+        assert(s2.pt.interpret::<G>().contains_pair(vbase, frame));
+        lemma_frame_pidx_not_in_table(s1, vbase, frame, vidx.addr());
+        lemma_frame_pidx_not_in_table(s2, vbase, frame, vidx.addr());
+        let paddr = vidx.addr().map(vbase, frame.base);
+        // Prove `paddr` is within physical memory.
+        lemma_vaddr_in_vpage_implies_paddr_in_pframe(vidx.addr(), vbase, frame);
+        lemma_pa_align_frame_size_must_align_word_size(frame.base, frame.size);
+        lemma_sum_align_word_size(frame.base.0, frame.size.as_nat());
+        lemma_paddr_neq_implies_pidx_neq(paddr, frame.base.offset(frame.size.as_nat()));
+        assert(s1.mem.contains(paddr.idx()));
+        assert(s2.mem.read(paddr.idx()) == s1.mem.read(paddr.idx()));
+    }
+    assert(s1.interpret_mem() =~= s2.interpret_mem());
+}
+
+/// Lemma. A table of `pt1` that contains physical word `pidx` is a table of `pt2` as well.
+proof fn lemma_in_table_subset(pt1: PageTableMem, pt2: PageTableMem, pidx: PIdx)
+    requires
+        pt1.in_table(pidx),
+        forall|table: Table| pt1.tables.contains(table) ==> #[trigger] pt2.tables.contains(table),
+    ensures
+        pt2.in_table(pidx),
+{
+    let i = choose|i|
+        0 <= i < pt1.tables.len() && pidx.addr().within(
+            #[trigger] pt1.tables[i].base,
+            pt1.tables[i].size.as_nat(),
+        );
+    assert(pt1.tables.contains(pt1.tables[i]));
+    let j = choose|j| 0 <= j < pt2.tables.len() && pt2.tables[j] == pt1.tables[i];
+    assert(pidx.addr().within(pt2.tables[j].base, pt2.tables[j].size.as_nat()));
+}
+
+/// Lemma. A single step of a page table operation keeps the TLB and the page table
+/// invariants, and only writes words of the tables it touches.
+///
+/// With `grow` the step is a write or an allocation, otherwise a write or a deallocation.
+proof fn lemma_pt_step_is_pt_op<G: GhostPTE>(
+    s1: HardwareState<G>,
+    s2: HardwareState<G>,
+    grow: bool,
+)
+    requires
+        s1.pt.invariants(),
+        s1.pt.mem === s1.mem,
+        ({
+            ||| HardwareState::pt_bbm_write(s1, s2)
+            ||| grow && exists|level: nat, table: Table|
+                HardwareState::pt_alloc(s1, s2, level, table)
+            ||| !grow && exists|base: PAddr| HardwareState::pt_dealloc(s1, s2, base)
+        }),
+    ensures
+        HardwareState::pt_op(s1, s2),
+        s2.tlb === s1.tlb,
+        s2.pt.invariants(),
+        s2.pt.arch == s1.pt.arch,
+        s2.pt.pool == s1.pt.pool,
+        grow ==> forall|table: Table|
+            s1.pt.tables.contains(table) ==> #[trigger] s2.pt.tables.contains(table),
+        !grow ==> forall|table: Table|
+            s2.pt.tables.contains(table) ==> #[trigger] s1.pt.tables.contains(table),
+{
+    if HardwareState::pt_bbm_write(s1, s2) {
+        let (base, index, entry) = choose|base: PAddr, index: nat, entry: u64|
+            {
+                &&& s1.write_is_bbm(base, index, entry)
+                &&& #[trigger] HardwareState::pt_write(s1, s2, base, index, entry)
+            };
+        s1.pt.lemma_write_preserves_invariants(base, index, entry);
+        s1.pt.lemma_entry_in_table(base, index);
+        s1.pt.lemma_entry_in_mem(base, index);
+        // Only the entry is written, and it lies in a table
+        assert forall|pidx: PIdx|
+            s1.mem.contains(pidx) && !s1.pt.in_table(pidx) && !s2.pt.in_table(
+                pidx,
+            ) implies #[trigger] s2.mem.read(pidx) == s1.mem.read(pidx) by {
+            assert(pidx != s1.pt.entry_addr(base, index).idx());
+        }
+        assert(s2.pt.tables == s1.pt.tables);
+    } else if grow {
+        let (level, table) = choose|level: nat, table: Table|
+            HardwareState::pt_alloc(s1, s2, level, table);
+        s1.pt.lemma_alloc_table_preserves_invariants(level, s2.pt, table);
+        // Only the new table is written, and it is a table of `s2`
+        let last = s2.pt.tables.len() - 1;
+        assert(s2.pt.tables[last] == table);
+        assert forall|pidx: PIdx|
+            s1.mem.contains(pidx) && !s1.pt.in_table(pidx) && !s2.pt.in_table(
+                pidx,
+            ) implies #[trigger] s2.mem.read(pidx) == s1.mem.read(pidx) by {
+            if pidx.addr().within(table.base, table.size.as_nat()) {
+                assert(s2.pt.in_table(pidx));
+            }
+        }
+        assert forall|table2: Table| s1.pt.tables.contains(table2) implies #[trigger]
+            s2.pt.tables.contains(table2) by {
+            let j = choose|j| 0 <= j < s1.pt.tables.len() && s1.pt.tables[j] == table2;
+            assert(s2.pt.tables[j] == table2);
+        }
+    } else {
+        let base = choose|base: PAddr| HardwareState::pt_dealloc(s1, s2, base);
+        s1.pt.lemma_dealloc_table_preserves_invariants(base);
+        s1.pt.dealloc_table_facts(base);
+    }
+}
+
+/// Lemma. The steps of a page table operation summarize to `pt_op`, and keep the TLB and
+/// the page table invariants.
+///
+/// With `grow` the operation only allocates tables, otherwise it only deallocates tables, so
+/// a word that lies in no table of `s1` and `s2` lies in no table on the way either.
+proof fn lemma_pt_steps_is_pt_op<G: GhostPTE>(
+    s1: HardwareState<G>,
+    s2: HardwareState<G>,
+    path: Seq<HardwareState<G>>,
+    grow: bool,
+)
+    requires
+        s1.pt.invariants(),
+        s1.pt.mem === s1.mem,
+        grow ==> HardwareState::pt_grow_steps(s1, s2, path),
+        !grow ==> HardwareState::pt_shrink_steps(s1, s2, path),
+    ensures
+        HardwareState::pt_op(s1, s2),
+        s2.tlb === s1.tlb,
+        s2.pt.invariants(),
+        s2.pt.arch == s1.pt.arch,
+        s2.pt.pool == s1.pt.pool,
+        grow ==> forall|table: Table|
+            s1.pt.tables.contains(table) ==> #[trigger] s2.pt.tables.contains(table),
+        !grow ==> forall|table: Table|
+            s2.pt.tables.contains(table) ==> #[trigger] s1.pt.tables.contains(table),
+    decreases path.len(),
+{
+    if path.len() == 1 {
+        assert(s2 === s1);
+    } else {
+        // The steps up to the last one
+        let init = path.drop_last();
+        let mid = init.last();
+        assert(forall|i: int| 0 <= i < init.len() ==> init[i] === path[i]);
+        if grow {
+            assert(HardwareState::pt_grow_steps(s1, mid, init));
+        } else {
+            assert(HardwareState::pt_shrink_steps(s1, mid, init));
+        }
+        lemma_pt_steps_is_pt_op(s1, mid, init, grow);
+        // The last step
+        let i = path.len() - 2;
+        assert(path[i] === mid && path[i + 1] === s2);
+        lemma_pt_step_is_pt_op(mid, s2, grow);
+        assert forall|pidx: PIdx|
+            s1.mem.contains(pidx) && !s1.pt.in_table(pidx) && !s2.pt.in_table(
+                pidx,
+            ) implies #[trigger] s2.mem.read(pidx) == s1.mem.read(pidx) by {
+            // Tables only grow or only shrink, so `pidx` lies in no table of `mid` either
+            if mid.pt.in_table(pidx) {
+                if grow {
+                    lemma_in_table_subset(mid.pt, s2.pt, pidx);
+                } else {
+                    lemma_in_table_subset(mid.pt, s1.pt, pidx);
+                }
+            }
+            assert(mid.mem.read(pidx) == s1.mem.read(pidx));
+        }
+    }
+}
+
+/// Lemma. The steps of a map operation keep the TLB, and summarize to `pt_op`.
+proof fn lemma_map_steps_is_pt_op<G: GhostPTE>(s1: HardwareState<G>, s2: HardwareState<G>)
+    requires
+        s1.pt.invariants(),
+        s1.pt.mem === s1.mem,
+        LowLevelState::<G>::map_steps(s1, s2),
+    ensures
+        HardwareState::pt_op(s1, s2),
+        s2.tlb === s1.tlb,
+{
+    let path = choose|path: Seq<HardwareState<G>>|
+        {
+            &&& HardwareState::pt_grow_steps(s1, s2, path)
+            &&& forall|i: int|
+                0 <= i < path.len() ==> LowLevelState::<G>::mappings_between(
+                    s1.pt,
+                    #[trigger] path[i].pt,
+                    s2.pt,
+                )
+        };
+    lemma_pt_steps_is_pt_op(s1, s2, path, true);
+}
+
+/// Lemma. The steps of an unmap operation keep the TLB, and summarize to `pt_op`.
+proof fn lemma_unmap_steps_is_pt_op<G: GhostPTE>(s1: HardwareState<G>, s2: HardwareState<G>)
+    requires
+        s1.pt.invariants(),
+        s1.pt.mem === s1.mem,
+        LowLevelState::<G>::unmap_steps(s1, s2),
+    ensures
+        HardwareState::pt_op(s1, s2),
+        s2.tlb === s1.tlb,
+{
+    let path = choose|path: Seq<HardwareState<G>>|
+        {
+            &&& HardwareState::pt_shrink_steps(s1, s2, path)
+            &&& forall|i: int|
+                0 <= i < path.len() ==> LowLevelState::<G>::mappings_between(
+                    s2.pt,
+                    #[trigger] path[i].pt,
+                    s1.pt,
+                )
+        };
+    lemma_pt_steps_is_pt_op(s1, s2, path, false);
+}
+
+/// Lemma. The MMU setting the Access and Dirty flags keeps the mappings and only writes page
+/// table memory, so it preserves the invariants and the high-level view.
+proof fn lemma_set_flags_preserves_view<G: GhostPTE>(
+    st: LowLevelState<G>,
+    vaddr: VAddr,
+    dirty: bool,
+)
+    requires
+        st.pt_invariants(),
+    ensures
+        st.set_flags(vaddr, dirty).hw_state() === st.hw_state().set_flags(vaddr, dirty),
+        st.set_flags(vaddr, dirty).constants === st.constants,
+        st.set_flags(vaddr, dirty).tlb === st.tlb,
+        st.set_flags(vaddr, dirty).pt_invariants(),
+        st.set_flags(vaddr, dirty).pt.interpret::<G>() == st.pt.interpret::<G>(),
+        st.invariants() ==> st.set_flags(vaddr, dirty).invariants(),
+        st.invariants() ==> st.set_flags(vaddr, dirty)@ == st@,
+{
+    let st2 = st.set_flags(vaddr, dirty);
+    st.pt.lemma_set_flags_keeps_interpret::<G>(vaddr, dirty);
+    assert(st2.pt_in_mem());
+    assert(st2.tables_reserved());
+    assert(st2.pt_invariants());
+    if st.invariants() {
+        assert(st2.tlb_is_submap_of_pt());
+        lemma_pt_interpret_equals_all_mappings(st);
+        lemma_pt_interpret_equals_all_mappings(st2);
+        // Mapped words are not in page table memory, so they keep their values
+        assert forall|vidx: VIdx| #[trigger]
+            st.interpret_mem().contains_key(vidx) implies st.interpret_mem()[vidx]
+            == st2.interpret_mem()[vidx] by {
+            let (vbase, frame) = choose|vbase: VAddr, frame: Frame|
+                {
+                    &&& #[trigger] st.all_mappings().contains_pair(vbase, frame)
+                    &&& !frame.attr.device()
+                    &&& vidx.addr().within(vbase, frame.size.as_nat())
+                };
+            assert(st.pt.interpret::<G>().contains_pair(vbase, frame));
+            lemma_frame_pidx_not_in_table(st, vbase, frame, vidx.addr());
+            let paddr = vidx.addr().map(vbase, frame.base);
+            // Prove `paddr` is within physical memory.
+            lemma_vaddr_in_vpage_implies_paddr_in_pframe(vidx.addr(), vbase, frame);
+            lemma_pa_align_frame_size_must_align_word_size(frame.base, frame.size);
+            lemma_sum_align_word_size(frame.base.0, frame.size.as_nat());
+            lemma_paddr_neq_implies_pidx_neq(paddr, frame.base.offset(frame.size.as_nat()));
+            assert(st.mem.contains(paddr.idx()));
+            assert(st2.mem.read(paddr.idx()) == st.mem.read(paddr.idx()));
+        }
+        assert(st.interpret_mem() =~= st2.interpret_mem());
+    }
+}
+
 /// Lemma. If there is no overlap in the physical memory space, adding a new mapping that
 /// does not overlap with existing mappings preserves the non-overlap property.
 proof fn lemma_add_mapping_preserves_nonoverlap(
@@ -242,8 +735,104 @@ proof fn lemma_add_mapping_preserves_nonoverlap(
     }
 }
 
+/// Lemma. The MMU never interprets the global flag and the software bits of a mapping, so
+/// accesses through two mappings that agree up to them (`Frame::hw`) have the same outcomes.
+pub proof fn lemma_sw_bits_not_interpreted<G: GhostPTE>(
+    s1: HardwareState<G>,
+    s2: HardwareState<G>,
+    vaddr: VAddr,
+    size: AccessSize,
+    value: u64,
+    privilege: Privilege,
+    base: VAddr,
+    frame: Frame,
+    frame2: Frame,
+)
+    requires
+        frame.hw() == frame2.hw(),
+    ensures
+        ({
+            &&& forall|res: MemoryResult<u64>, mmio: Option<MmioAccess>|
+                #[trigger] s1.read_through(vaddr, size, privilege, base, frame, res, mmio)
+                    == s1.read_through(vaddr, size, privilege, base, frame2, res, mmio)
+            &&& forall|res: MemoryResult<u64>|
+                #[trigger] s1.fetch_through(vaddr, size, privilege, base, frame, res)
+                    == s1.fetch_through(vaddr, size, privilege, base, frame2, res)
+            &&& forall|res: MemoryResult<()>, mmio: Option<MmioAccess>|
+                #[trigger] s1.write_through(
+                    s2,
+                    vaddr,
+                    size,
+                    value,
+                    privilege,
+                    base,
+                    frame,
+                    res,
+                    mmio,
+                ) == s1.write_through(s2, vaddr, size, value, privilege, base, frame2, res, mmio)
+        }),
+{
+    // Every field but the global flag and the software bits is equal
+    assert(frame.base == frame2.base && frame.size == frame2.size);
+    assert(frame.attr.hw() == frame2.attr.hw());
+}
+
+/// Theorem. A page table write that changes only the global flag and the software bits of an
+/// entry leaves every translation unchanged for the MMU.
+///
+/// Every address is mapped after the write iff it was before, through a mapping with the same
+/// base that agrees up to those bits, so accesses through it have the same outcomes
+/// (`lemma_sw_bits_not_interpreted`). Walks that fault still fault on the same level.
+pub proof fn theorem_sw_bits_write_keeps_translation<G: GhostPTE>(
+    s1: HardwareState<G>,
+    s2: HardwareState<G>,
+    b: PAddr,
+    i: nat,
+    entry: u64,
+    vaddr: VAddr,
+)
+    requires
+        s1.pt.invariants(),
+        s1.pt.walkable::<G>(),
+        s1.pt.accessible(b, i),
+        same_hw_translation(G::from_u64(s1.pt.read(b, i)), G::from_u64(entry)),
+        s2.pt == s1.pt.write(b, i, entry),
+    ensures
+        s2.pt_has_mapping_for(vaddr) == s1.pt_has_mapping_for(vaddr),
+        s2.walk_level(vaddr) == s1.walk_level(vaddr),
+        forall|vbase: VAddr, frame: Frame|
+            #[trigger] s1.pt.interpret::<G>().contains_pair(vbase, frame) ==> {
+                &&& s2.pt.interpret::<G>().contains_key(vbase)
+                &&& s2.pt.interpret::<G>()[vbase].hw() == frame.hw()
+            },
+        forall|vbase: VAddr, frame: Frame|
+            #[trigger] s2.pt.interpret::<G>().contains_pair(vbase, frame) ==> {
+                &&& s1.pt.interpret::<G>().contains_key(vbase)
+                &&& s1.pt.interpret::<G>()[vbase].hw() == frame.hw()
+            },
+{
+    let (m1, m2) = (s1.pt.interpret::<G>(), s2.pt.interpret::<G>());
+    s1.pt.lemma_write_same_hw_translation_keeps_translation::<G>(b, i, entry);
+    assert(s2.pt.root() == s1.pt.root());
+    assert(s2.walk_level(vaddr) == s1.walk_level(vaddr));
+    // Mappings with the same base have the same size, as `hw` keeps the size
+    assert forall|vbase: VAddr| #[trigger]
+        m1.contains_key(vbase) implies m2[vbase].size == m1[vbase].size by {
+        assert(m2[vbase].hw().size == m1[vbase].hw().size);
+    }
+    if s1.pt_has_mapping_for(vaddr) {
+        let (vbase, frame) = s1.pt_mapping_for(vaddr);
+        assert(m2.contains_pair(vbase, m2[vbase]));
+    }
+    if s2.pt_has_mapping_for(vaddr) {
+        let (vbase, frame) = s2.pt_mapping_for(vaddr);
+        assert(m1.contains_key(vbase));
+        assert(m1.contains_pair(vbase, m1[vbase]));
+    }
+}
+
 /// Theorem. The low-level init state implies the invariants.
-proof fn ll_init_implies_invariants(st: LowLevelState)
+pub proof fn ll_init_implies_invariants<G: GhostPTE>(st: LowLevelState<G>)
     requires
         st.init(),
     ensures
@@ -252,7 +841,7 @@ proof fn ll_init_implies_invariants(st: LowLevelState)
 }
 
 /// Theorem. The low-level init state implies the page table init state.
-proof fn ll_init_implies_pt_init(st: LowLevelState)
+proof fn ll_init_implies_pt_init<G: GhostPTE>(st: LowLevelState<G>)
     requires
         st.init(),
     ensures
@@ -261,7 +850,7 @@ proof fn ll_init_implies_pt_init(st: LowLevelState)
 }
 
 /// Theorem. The low-level init state refines the high-level init state.
-proof fn ll_init_refines_hl_init(st: LowLevelState)
+proof fn ll_init_refines_hl_init<G: GhostPTE>(st: LowLevelState<G>)
     requires
         st.init(),
     ensures
@@ -273,54 +862,152 @@ proof fn ll_init_refines_hl_init(st: LowLevelState)
     assert(st.interpret_mem() === Map::empty());
 }
 
+/// Lemma. A memory read keeps the page table invariants and mappings, and only fills the TLB
+/// from the page table.
+pub proof fn lemma_read_preserves_pt_invariants<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
+    vaddr: VAddr,
+    size: AccessSize,
+    privilege: Privilege,
+    res: MemoryResult<u64>,
+    mmio: Option<MmioAccess>,
+)
+    requires
+        s1.pt_invariants(),
+        LowLevelState::<G>::read(s1, s2, vaddr, size, privilege, res, mmio),
+    ensures
+        s2.pt_invariants(),
+        s2.pt.interpret::<G>() == s1.pt.interpret::<G>(),
+        s2.tlb === s1.tlb || exists|vbase: VAddr, frame: Frame|
+            {
+                &&& #[trigger] s1.pt.interpret::<G>().contains_pair(vbase, frame)
+                &&& s2.tlb === s1.tlb.update(s1.constants.tag, vbase, frame)
+            },
+{
+    // The MMU may set the Access flag first
+    lemma_set_flags_preserves_view(s1, vaddr, false);
+    let (hw1, hw2) = (s1.hw_state(), s2.hw_state());
+    let mid = if HardwareState::read_access(hw1, hw2, vaddr, size, privilege, res, mmio) {
+        s1
+    } else {
+        s1.set_flags(vaddr, false)
+    };
+    assert(HardwareState::read_access(mid.hw_state(), hw2, vaddr, size, privilege, res, mmio));
+    if s2.tlb !== s1.tlb {
+        // The TLB is filled from the page table
+        let (vbase, frame) = mid.hw_state().pt_mapping_for(vaddr);
+        assert(mid.pt.interpret::<G>().contains_pair(vbase, frame));
+        assert(s1.pt.interpret::<G>().contains_pair(vbase, frame));
+    }
+}
+
 /// Theorem. The low-level read operation preserves the invariants.
-proof fn ll_read_preserves_invariants(
-    s1: LowLevelState,
-    s2: LowLevelState,
+proof fn ll_read_preserves_invariants<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
     vaddr: VAddr,
+    size: AccessSize,
+    privilege: Privilege,
     res: MemoryResult<u64>,
+    mmio: Option<MmioAccess>,
 )
     requires
         s1.invariants(),
-        LowLevelState::read(s1, s2, vaddr, res),
+        LowLevelState::<G>::read(s1, s2, vaddr, size, privilege, res, mmio),
     ensures
         s2.invariants(),
 {
+    lemma_read_preserves_pt_invariants(s1, s2, vaddr, size, privilege, res, mmio);
+    if s2.tlb !== s1.tlb {
+        let (vbase, frame) = choose|vbase: VAddr, frame: Frame|
+            {
+                &&& #[trigger] s1.pt.interpret::<G>().contains_pair(vbase, frame)
+                &&& s2.tlb === s1.tlb.update(s1.constants.tag, vbase, frame)
+            };
+        assert(s2.pt.interpret::<G>().contains_pair(vbase, frame));
+    }
     assert(s2.tlb_is_submap_of_pt());
 }
 
 /// Theorem. The low-level read operation refines the high-level read operation.
-proof fn ll_read_refines_hl_read(
-    s1: LowLevelState,
-    s2: LowLevelState,
+///
+/// Reads of normal memory return the interpreted value, and reads of device memory are
+/// forwarded to the device unchanged.
+proof fn ll_read_refines_hl_read<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
+    vaddr: VAddr,
+    size: AccessSize,
+    privilege: Privilege,
+    res: MemoryResult<u64>,
+    mmio: Option<MmioAccess>,
+)
+    requires
+        s1.invariants(),
+        LowLevelState::<G>::read(s1, s2, vaddr, size, privilege, res, mmio),
+    ensures
+        HighLevelState::read(s1@, s2@, vaddr, size, privilege, res, mmio),
+{
+    if HardwareState::read_access(s1.hw_state(), s2.hw_state(), vaddr, size, privilege, res, mmio) {
+        lemma_read_access_refines_hl_read(s1, s2, vaddr, size, privilege, res, mmio);
+    } else {
+        // The MMU sets the Access flag first, which keeps the high-level view
+        lemma_set_flags_preserves_view(s1, vaddr, false);
+        let mid = s1.set_flags(vaddr, false);
+        lemma_read_access_refines_hl_read(mid, s2, vaddr, size, privilege, res, mmio);
+    }
+}
+
+/// Lemma. A memory read access refines the high-level read operation.
+proof fn lemma_read_access_refines_hl_read<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
     vaddr: VAddr,
+    size: AccessSize,
+    privilege: Privilege,
     res: MemoryResult<u64>,
+    mmio: Option<MmioAccess>,
 )
     requires
         s1.invariants(),
-        LowLevelState::read(s1, s2, vaddr, res),
+        s1.constants === s2.constants,
+        HardwareState::read_access(s1.hw_state(), s2.hw_state(), vaddr, size, privilege, res, mmio),
     ensures
-        HighLevelState::read(s1@, s2@, vaddr, res),
+        HighLevelState::read(s1@, s2@, vaddr, size, privilege, res, mmio),
 {
     lemma_pt_interpret_equals_all_mappings(s1);
     assert(s1@.mappings === s2@.mappings);
 
-    if s1.has_mapping_for(vaddr) {
+    if !vaddr.aligned(size.as_nat()) {
+        // Both fault before translation
+        assert(res is AlignmentFault);
+    } else if s1.has_mapping_for(vaddr) {
         if s1.hw_state().tlb_has_mapping_for(vaddr) {
             // Lemma tells us "TLB hit" and "TLB miss, PT hit" are equivalent.
             lemma_mapping_in_both_tlb_and_pt(s1, vaddr);
         }
         let (vbase, frame) = s1.mapping_for(vaddr);
         // `s1` has mapping `(vbase, frame)` which contains `op.vaddr`.
-        let pidx = vaddr.map(vbase, frame.base).idx();
-        if s1.mem.contains(pidx) && frame.attr.readable && frame.attr.user_accessible {
+        let paddr = vaddr.map(vbase, frame.base);
+        let pidx = paddr.idx();
+        if s1.mem.contains(pidx) && frame.attr.permits_read(privilege) && frame.attr.device() {
+            // Forwarded to the device through the same mapping
+            assert(res is Ok);
+        } else if s1.mem.contains(pidx) && frame.attr.permits_read(privilege) {
+            // The word containing `op.vaddr` is in the same mapping, at the same byte offset.
+            assert(s1.pt.interpret::<G>().contains_pair(vbase, frame));
+            lemma_va_align_frame_size_must_align_word_size(vbase, frame.size);
+            lemma_pa_align_frame_size_must_align_word_size(frame.base, frame.size);
+            lemma_vaddr_word_maps_to_paddr_word(vaddr, vbase, frame.base, frame.size.as_nat());
             // Values in the intepreted memory are the same as in the OS memory, because
-            // there is only one mapping for `op.vaddr` (lemma).
-            lemma_at_most_one_mapping_for_vaddr(s1, vaddr);
+            // there is only one mapping for the word (lemma).
+            lemma_at_most_one_mapping_for_vaddr(s1, vaddr.idx().addr());
             assert(s1.interpret_mem()[vaddr.idx()] === s1.mem.read(pidx));
             assert(res is Ok);
         } else {
-            assert(res is PageFault);
+            // Both report the level of the same mapping
+            assert(s1.pt.arch == s1@.constants.arch);
         }
     } else {
         if s1.hw_state().tlb_has_mapping_for(vaddr) {
@@ -328,46 +1015,372 @@ proof fn ll_read_refines_hl_read(
             lemma_mapping_in_both_tlb_and_pt(s1, vaddr);
         }
         assert(!s1.has_mapping_for(vaddr));
-        assert(res is PageFault);
+        // The walk terminates on a valid level
+        s1.pt.lemma_walk_level_bound::<G>(vaddr);
+        assert(res.is_fault_kind(FaultKind::Translation));
+    }
+}
+
+/// Lemma. An instruction fetch keeps the page table invariants and mappings, and only fills the
+/// TLB from the page table.
+pub proof fn lemma_fetch_preserves_pt_invariants<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
+    vaddr: VAddr,
+    size: AccessSize,
+    privilege: Privilege,
+    res: MemoryResult<u64>,
+)
+    requires
+        s1.pt_invariants(),
+        LowLevelState::<G>::fetch(s1, s2, vaddr, size, privilege, res),
+    ensures
+        s2.pt_invariants(),
+        s2.pt.interpret::<G>() == s1.pt.interpret::<G>(),
+        s2.tlb === s1.tlb || exists|vbase: VAddr, frame: Frame|
+            {
+                &&& #[trigger] s1.pt.interpret::<G>().contains_pair(vbase, frame)
+                &&& s2.tlb === s1.tlb.update(s1.constants.tag, vbase, frame)
+            },
+{
+    // The MMU may set the Access flag first
+    lemma_set_flags_preserves_view(s1, vaddr, false);
+    let mid = if HardwareState::fetch_access(
+        s1.hw_state(),
+        s2.hw_state(),
+        vaddr,
+        size,
+        privilege,
+        res,
+    ) {
+        s1
+    } else {
+        s1.set_flags(vaddr, false)
+    };
+    assert(HardwareState::fetch_access(mid.hw_state(), s2.hw_state(), vaddr, size, privilege, res));
+    if s2.tlb !== s1.tlb {
+        // The TLB is filled from the page table
+        let (vbase, frame) = mid.hw_state().pt_mapping_for(vaddr);
+        assert(mid.pt.interpret::<G>().contains_pair(vbase, frame));
+        assert(s1.pt.interpret::<G>().contains_pair(vbase, frame));
+    }
+}
+
+/// Theorem. The low-level fetch operation preserves the invariants.
+proof fn ll_fetch_preserves_invariants<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
+    vaddr: VAddr,
+    size: AccessSize,
+    privilege: Privilege,
+    res: MemoryResult<u64>,
+)
+    requires
+        s1.invariants(),
+        LowLevelState::<G>::fetch(s1, s2, vaddr, size, privilege, res),
+    ensures
+        s2.invariants(),
+{
+    lemma_fetch_preserves_pt_invariants(s1, s2, vaddr, size, privilege, res);
+    if s2.tlb !== s1.tlb {
+        let (vbase, frame) = choose|vbase: VAddr, frame: Frame|
+            {
+                &&& #[trigger] s1.pt.interpret::<G>().contains_pair(vbase, frame)
+                &&& s2.tlb === s1.tlb.update(s1.constants.tag, vbase, frame)
+            };
+        assert(s2.pt.interpret::<G>().contains_pair(vbase, frame));
+    }
+    assert(s2.tlb_is_submap_of_pt());
+}
+
+/// Theorem. The low-level fetch operation refines the high-level fetch operation.
+proof fn ll_fetch_refines_hl_fetch<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
+    vaddr: VAddr,
+    size: AccessSize,
+    privilege: Privilege,
+    res: MemoryResult<u64>,
+)
+    requires
+        s1.invariants(),
+        LowLevelState::<G>::fetch(s1, s2, vaddr, size, privilege, res),
+    ensures
+        HighLevelState::fetch(s1@, s2@, vaddr, size, privilege, res),
+{
+    if HardwareState::fetch_access(s1.hw_state(), s2.hw_state(), vaddr, size, privilege, res) {
+        lemma_fetch_access_refines_hl_fetch(s1, s2, vaddr, size, privilege, res);
+    } else {
+        // The MMU sets the Access flag first, which keeps the high-level view
+        lemma_set_flags_preserves_view(s1, vaddr, false);
+        lemma_fetch_access_refines_hl_fetch(
+            s1.set_flags(vaddr, false),
+            s2,
+            vaddr,
+            size,
+            privilege,
+            res,
+        );
+    }
+}
+
+/// Lemma. An instruction fetch access refines the high-level fetch operation.
+proof fn lemma_fetch_access_refines_hl_fetch<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
+    vaddr: VAddr,
+    size: AccessSize,
+    privilege: Privilege,
+    res: MemoryResult<u64>,
+)
+    requires
+        s1.invariants(),
+        s1.constants === s2.constants,
+        HardwareState::fetch_access(s1.hw_state(), s2.hw_state(), vaddr, size, privilege, res),
+    ensures
+        HighLevelState::fetch(s1@, s2@, vaddr, size, privilege, res),
+{
+    lemma_pt_interpret_equals_all_mappings(s1);
+    assert(s1@.mappings === s2@.mappings);
+
+    if !vaddr.aligned(size.as_nat()) {
+        // Both fault before translation
+        assert(res is AlignmentFault);
+    } else if s1.has_mapping_for(vaddr) {
+        if s1.hw_state().tlb_has_mapping_for(vaddr) {
+            lemma_mapping_in_both_tlb_and_pt(s1, vaddr);
+        }
+        let (vbase, frame) = s1.mapping_for(vaddr);
+        let paddr = vaddr.map(vbase, frame.base);
+        let pidx = paddr.idx();
+        if s1.mem.contains(pidx) && frame.attr.permits_exec(privilege) {
+            // Instructions come from the same word and byte lanes as reads
+            assert(s1.pt.interpret::<G>().contains_pair(vbase, frame));
+            lemma_va_align_frame_size_must_align_word_size(vbase, frame.size);
+            lemma_pa_align_frame_size_must_align_word_size(frame.base, frame.size);
+            lemma_vaddr_word_maps_to_paddr_word(vaddr, vbase, frame.base, frame.size.as_nat());
+            lemma_at_most_one_mapping_for_vaddr(s1, vaddr.idx().addr());
+            assert(s1.interpret_mem()[vaddr.idx()] === s1.mem.read(pidx));
+            assert(res is Ok);
+        } else {
+            assert(s1.pt.arch == s1@.constants.arch);
+        }
+    } else {
+        if s1.hw_state().tlb_has_mapping_for(vaddr) {
+            lemma_mapping_in_both_tlb_and_pt(s1, vaddr);
+        }
+        assert(!s1.has_mapping_for(vaddr));
+        s1.pt.lemma_walk_level_bound::<G>(vaddr);
+        assert(res.is_fault_kind(FaultKind::Translation));
+    }
+}
+
+/// Lemma. A memory write preserves the page table invariants and mappings, and only fills the
+/// TLB from the page table.
+pub proof fn lemma_write_preserves_pt_invariants<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
+    vaddr: VAddr,
+    size: AccessSize,
+    value: u64,
+    privilege: Privilege,
+    res: MemoryResult<()>,
+    mmio: Option<MmioAccess>,
+)
+    requires
+        s1.pt_invariants(),
+        s1.tlb_unreserved(),
+        LowLevelState::<G>::write(s1, s2, vaddr, size, value, privilege, res, mmio),
+    ensures
+        s2.pt_invariants(),
+        s2.pt.interpret::<G>() == s1.pt.interpret::<G>(),
+        s2.tlb === s1.tlb || exists|vbase: VAddr, frame: Frame|
+            {
+                &&& #[trigger] s1.pt.interpret::<G>().contains_pair(vbase, frame)
+                &&& s2.tlb === s1.tlb.update(s1.constants.tag, vbase, frame)
+            },
+{
+    // The MMU may set the Access and Dirty flags first
+    lemma_set_flags_preserves_view(s1, vaddr, true);
+    let (hw1, hw2) = (s1.hw_state(), s2.hw_state());
+    let mid = if HardwareState::write_access(hw1, hw2, vaddr, size, value, privilege, res, mmio) {
+        s1
+    } else {
+        s1.set_flags(vaddr, true)
+    };
+    let hw_mid = mid.hw_state();
+    assert(HardwareState::write_access(hw_mid, hw2, vaddr, size, value, privilege, res, mmio));
+    lemma_write_access_preserves_pt_invariants(mid, s2, vaddr, size, value, privilege, res, mmio);
+    if s2.tlb !== s1.tlb {
+        // The TLB is filled from the page table
+        let (vbase, frame) = mid.hw_state().pt_mapping_for(vaddr);
+        assert(mid.pt.interpret::<G>().contains_pair(vbase, frame));
+        assert(s1.pt.interpret::<G>().contains_pair(vbase, frame));
+    }
+}
+
+/// Lemma. A memory write access preserves the page table invariants.
+proof fn lemma_write_access_preserves_pt_invariants<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
+    vaddr: VAddr,
+    size: AccessSize,
+    value: u64,
+    privilege: Privilege,
+    res: MemoryResult<()>,
+    mmio: Option<MmioAccess>,
+)
+    requires
+        s1.pt_invariants(),
+        s1.tlb_unreserved(),
+        s1.constants === s2.constants,
+        HardwareState::write_access(
+            s1.hw_state(),
+            s2.hw_state(),
+            vaddr,
+            size,
+            value,
+            privilege,
+            res,
+            mmio,
+        ),
+    ensures
+        s2.pt_invariants(),
+        s2.pt.interpret::<G>() == s1.pt.interpret::<G>(),
+{
+    lemma_write_preserves_pt_interpret(s1, s2, vaddr, size, value, privilege, res, mmio);
+    // Page table memory keeps its invariants since tables are not touched
+    assert forall|i| 0 <= i < s2.pt.tables.len() implies s2.pt.table_in_mem(
+        #[trigger] s2.pt.tables[i],
+    ) by {
+        assert(s1.pt.table_in_mem(s1.pt.tables[i]));
+    }
+    // Table contents are unchanged, so are table descriptors
+    assert(s2.pt.walkable::<G>());
+    assert(s2.pt_in_mem());
+    assert(s2.mappings_unreserved());
+    assert(s2.tables_reserved());
+    assert(s2.tables_unmapped());
+}
+
+/// Lemma. If the TLB is a subset of the page table, TLB entries never cover reserved memory.
+proof fn lemma_submap_implies_tlb_unreserved<G: GhostPTE>(st: LowLevelState<G>)
+    requires
+        st.invariants(),
+    ensures
+        st.tlb_unreserved(),
+{
+    assert forall|vbase: VAddr, frame: Frame| #[trigger]
+        st.tlb.contains_mapping(st.constants.tag, vbase, frame) implies frame.base.aligned(
+        frame.size.as_nat(),
+    ) && !PAddr::overlap_any(
+        frame.base,
+        frame.size.as_nat(),
+        st.constants.reserved,
+    ) by {
+        assert(st.pt.interpret::<G>().contains_pair(vbase, frame));
     }
 }
 
 /// Theorem. The low-level write operation preserves the invariants.
-proof fn ll_write_preserves_invariants(
-    s1: LowLevelState,
-    s2: LowLevelState,
+proof fn ll_write_preserves_invariants<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
     vaddr: VAddr,
+    size: AccessSize,
     value: u64,
+    privilege: Privilege,
     res: MemoryResult<()>,
+    mmio: Option<MmioAccess>,
 )
     requires
         s1.invariants(),
-        LowLevelState::write(s1, s2, vaddr, value, res),
+        LowLevelState::<G>::write(s1, s2, vaddr, size, value, privilege, res, mmio),
     ensures
         s2.invariants(),
 {
+    lemma_submap_implies_tlb_unreserved(s1);
+    lemma_write_preserves_pt_invariants(s1, s2, vaddr, size, value, privilege, res, mmio);
+    if s2.tlb !== s1.tlb {
+        let (vbase, frame) = choose|vbase: VAddr, frame: Frame|
+            {
+                &&& #[trigger] s1.pt.interpret::<G>().contains_pair(vbase, frame)
+                &&& s2.tlb === s1.tlb.update(s1.constants.tag, vbase, frame)
+            };
+        assert(s2.pt.interpret::<G>().contains_pair(vbase, frame));
+    }
     assert(s2.tlb_is_submap_of_pt());
 }
 
 /// Theorem. The low-level write operation refines the high-level write operation.
-proof fn ll_write_refines_hl_write(
-    s1: LowLevelState,
-    s2: LowLevelState,
+///
+/// Writes of normal memory update the interpreted memory, and writes of device memory are
+/// forwarded to the device unchanged.
+proof fn ll_write_refines_hl_write<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
+    vaddr: VAddr,
+    size: AccessSize,
+    value: u64,
+    privilege: Privilege,
+    res: MemoryResult<()>,
+    mmio: Option<MmioAccess>,
+)
+    requires
+        s1.invariants(),
+        LowLevelState::<G>::write(s1, s2, vaddr, size, value, privilege, res, mmio),
+    ensures
+        HighLevelState::write(s1@, s2@, vaddr, size, value, privilege, res, mmio),
+{
+    let (hw1, hw2) = (s1.hw_state(), s2.hw_state());
+    if HardwareState::write_access(hw1, hw2, vaddr, size, value, privilege, res, mmio) {
+        lemma_write_access_refines_hl_write(s1, s2, vaddr, size, value, privilege, res, mmio);
+    } else {
+        // The MMU sets the Access and Dirty flags first, which keeps the high-level view
+        lemma_set_flags_preserves_view(s1, vaddr, true);
+        let mid = s1.set_flags(vaddr, true);
+        lemma_write_access_refines_hl_write(mid, s2, vaddr, size, value, privilege, res, mmio);
+    }
+}
+
+/// Lemma. A memory write access refines the high-level write operation.
+proof fn lemma_write_access_refines_hl_write<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
     vaddr: VAddr,
+    size: AccessSize,
     value: u64,
+    privilege: Privilege,
     res: MemoryResult<()>,
+    mmio: Option<MmioAccess>,
 )
     by (nonlinear_arith)
     requires
         s1.invariants(),
-        LowLevelState::write(s1, s2, vaddr, value, res),
+        s1.constants === s2.constants,
+        HardwareState::write_access(
+            s1.hw_state(),
+            s2.hw_state(),
+            vaddr,
+            size,
+            value,
+            privilege,
+            res,
+            mmio,
+        ),
     ensures
-        HighLevelState::write(s1@, s2@, vaddr, value, res),
+        HighLevelState::write(s1@, s2@, vaddr, size, value, privilege, res, mmio),
 {
+    lemma_submap_implies_tlb_unreserved(s1);
+    lemma_write_preserves_pt_interpret(s1, s2, vaddr, size, value, privilege, res, mmio);
     lemma_pt_interpret_equals_all_mappings(s1);
     assert(s1@.mappings === s2@.mappings);
 
-    if s1.has_mapping_for(vaddr) {
+    if !vaddr.aligned(size.as_nat()) {
+        // Both fault before translation, and memory is unchanged
+        assert(s2.interpret_mem() === s1.interpret_mem());
+        assert(res is AlignmentFault);
+    } else if s1.has_mapping_for(vaddr) {
         if s1.hw_state().tlb_has_mapping_for(vaddr) {
             // Lemma tells us "TLB hit" and "TLB miss, PT hit" are equivalent.
             lemma_mapping_in_both_tlb_and_pt(s1, vaddr);
@@ -376,29 +1389,39 @@ proof fn ll_write_refines_hl_write(
         // `s1` has mapping `(vbase, frame)` which contains `op.vaddr`.
         let vidx = vaddr.idx();
         assert(vaddr.0 >= vbase.0);
-        let pidx = vaddr.map(vbase, frame.base).idx();
-        if s1.mem.contains(pidx) && frame.attr.writable && frame.attr.user_accessible {
+        let paddr = vaddr.map(vbase, frame.base);
+        let pidx = paddr.idx();
+        if s1.mem.contains(pidx) && frame.attr.permits_write(privilege) && !frame.attr.device() {
+            // The word containing `op.vaddr` is in the same mapping, at the same byte offset.
+            assert(s1.pt.interpret::<G>().contains_pair(vbase, frame));
+            lemma_va_align_frame_size_must_align_word_size(vbase, frame.size);
+            lemma_pa_align_frame_size_must_align_word_size(frame.base, frame.size);
+            lemma_vaddr_word_maps_to_paddr_word(vaddr, vbase, frame.base, frame.size.as_nat());
+            lemma_at_most_one_mapping_for_vaddr(s1, vidx.addr());
+            assert(s1.interpret_mem()[vidx] === s1.mem.read(pidx));
+            let word = size.insert(s1.interpret_mem()[vidx], vaddr.word_offset(), value);
             // Prove that the interpreted memory is updated correctly.
             assert forall|vidx2: VIdx|
                 #![auto]
                 s2.interpret_mem().contains_key(vidx2) && s1.interpret_mem().insert(
-                    vaddr.idx(),
-                    value,
+                    vidx,
+                    word,
                 ).contains_key(vidx2) implies s2.interpret_mem()[vidx2]
-                == s1.interpret_mem().insert(vaddr.idx(), value)[vidx2] by {
-                if vidx2 == vaddr.idx() {
+                == s1.interpret_mem().insert(vidx, word)[vidx2] by {
+                if vidx2 == vidx {
                     // Prove that value at `vidx` is updated.
                     //
                     // Value updated in the physical memory is the same as in the interpreted memory,
-                    // because there is only one mapping for `op.vaddr` (lemma).
-                    lemma_at_most_one_mapping_for_vaddr(s2, vaddr);
-                    assert(s2.interpret_mem()[vidx2] == value);
+                    // because there is only one mapping for the word (lemma).
+                    lemma_at_most_one_mapping_for_vaddr(s2, vidx.addr());
+                    assert(s2.interpret_mem()[vidx2] == word);
                 } else {
                     // Prove that values at other indices are unchanged.
                     let (vbase2, frame2) = choose|vbase2: VAddr, frame2: Frame|
                         #![auto]
                         {
                             &&& s1.all_mappings().contains_pair(vbase2, frame2)
+                            &&& !frame2.attr.device()
                             &&& vidx2.addr().within(vbase2, frame2.size.as_nat())
                         };
                     let paddr2 = vidx2.addr().map(vbase2, frame2.base);
@@ -422,11 +1445,13 @@ proof fn ll_write_refines_hl_write(
                     assert(s1.mem.read(pidx2) == s2.mem.read(pidx2));
                 }
             }
-            assert(s2.interpret_mem() === s1.interpret_mem().insert(vaddr.idx(), value));
+            assert(s2.interpret_mem() === s1.interpret_mem().insert(vidx, word));
             assert(res is Ok);
         } else {
+            // A fault or a device write leaves memory unchanged
             assert(s2.interpret_mem() === s1.interpret_mem());
-            assert(res is PageFault);
+            // Both report the level of the same mapping
+            assert(s1.pt.arch == s1@.constants.arch);
         }
     } else {
         if s1.hw_state().tlb_has_mapping_for(vaddr) {
@@ -434,47 +1459,51 @@ proof fn ll_write_refines_hl_write(
             lemma_mapping_in_both_tlb_and_pt(s1, vaddr);
         }
         assert(!s1.has_mapping_for(vaddr));
-        assert(res is PageFault);
+        // The walk terminates on a valid level
+        s1.pt.lemma_walk_level_bound::<G>(vaddr);
+        assert(res.is_fault_kind(FaultKind::Translation));
     }
 }
 
 /// Theorem. The low-level map operation preserves the invariants.
-proof fn ll_map_preserves_invariants(
-    s1: LowLevelState,
-    s2: LowLevelState,
+pub proof fn ll_map_preserves_invariants<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
     vbase: VAddr,
     frame: Frame,
     res: PagingResult,
 )
     requires
         s1.invariants(),
-        LowLevelState::map(s1, s2, vbase, frame, res),
+        LowLevelState::<G>::map(s1, s2, vbase, frame, res),
     ensures
         s2.invariants(),
 {
-    if s2.pt.interpret() == s1.pt.interpret().insert(vbase, frame) {
+    // The steps of the map keep the TLB and only write page table memory
+    lemma_map_steps_is_pt_op(s1.hw_state(), s2.hw_state());
+    if s2.pt.interpret::<G>() == s1.pt.interpret::<G>().insert(vbase, frame) {
         // Prove mappings aligned to word size.
         assert forall|vbase2: VAddr, frame2: Frame| #[trigger]
-            s2.pt.interpret().contains_pair(vbase2, frame2) implies vbase2.aligned(
+            s2.pt.interpret::<G>().contains_pair(vbase2, frame2) implies vbase2.aligned(
             frame2.size.as_nat(),
         ) && frame2.base.aligned(frame2.size.as_nat()) by {
             if vbase2 == vbase {
                 assert(vbase.aligned(frame.size.as_nat()));
                 assert(frame.base.aligned(frame.size.as_nat()));
             } else {
-                assert(s1.pt.interpret().contains_pair(vbase2, frame2));
+                assert(s1.pt.interpret::<G>().contains_pair(vbase2, frame2));
             }
         }
         // Prove mappings within physical memory.
         assert forall|vbase2: VAddr, frame2: Frame| #[trigger]
-            s2.pt.interpret().contains_pair(vbase2, frame2) implies s2.mem.lb().0
+            s2.pt.interpret::<G>().contains_pair(vbase2, frame2) implies s2.mem.lb().0
             <= frame2.base.idx().0 && frame2.base.offset(frame2.size.as_nat()).idx().0
             <= s2.mem.ub().0 by {
             if vbase2 == vbase {
                 assert(s2.mem.ub().0 >= frame.base.offset(frame.size.as_nat()).idx().0
                     >= frame.base.idx().0 >= s2.mem.lb().0);
             } else {
-                assert(s1.pt.interpret().contains_pair(vbase2, frame2));
+                assert(s1.pt.interpret::<G>().contains_pair(vbase2, frame2));
             }
         }
     }
@@ -483,34 +1512,50 @@ proof fn ll_map_preserves_invariants(
 
     // Prove non-overlapping mappings in pmem and vmem.
     assert(s2.mappings_nonoverlap_in_vmem());
-    lemma_add_mapping_preserves_nonoverlap(s1.pt.interpret(), vbase, frame);
+    lemma_add_mapping_preserves_nonoverlap(s1.pt.interpret::<G>(), vbase, frame);
     assert(s2.mappings_nonoverlap_in_pmem());
 
     // Prove tlb is a subset of pt.
     assert(s1.tlb == s1.hw_state().tlb);
     assert(forall|vbase, frame|
-        s1.pt.interpret().contains_pair(vbase, frame) ==> s2.pt.interpret().contains_pair(
+        s1.pt.interpret::<G>().contains_pair(vbase, frame) ==> s2.pt.interpret::<G>().contains_pair(
             vbase,
             frame,
         ));
     assert(s2.tlb_is_submap_of_pt());
+
+    // Prove no mapping covers reserved memory or page table memory.
+    assert forall|vbase2: VAddr, frame2: Frame| #[trigger]
+        s2.pt.interpret::<G>().contains_pair(vbase2, frame2) implies !PAddr::overlap_any(
+        frame2.base,
+        frame2.size.as_nat(),
+        s2.constants.reserved,
+    ) by {
+        if vbase2 != vbase || frame2 != frame {
+            assert(s1.pt.interpret::<G>().contains_pair(vbase2, frame2));
+        }
+    }
+    assert(s2.pool_reserved());
+    lemma_pool_implies_tables_reserved(s2);
+    lemma_reserved_implies_tables_unmapped(s2);
 }
 
 /// Theorem. The low-level map operation refines the high-level map operation.
-proof fn ll_map_refines_hl_map(
-    s1: LowLevelState,
-    s2: LowLevelState,
+proof fn ll_map_refines_hl_map<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
     vbase: VAddr,
     frame: Frame,
     res: PagingResult,
 )
     requires
         s1.invariants(),
-        LowLevelState::map(s1, s2, vbase, frame, res),
+        LowLevelState::<G>::map(s1, s2, vbase, frame, res),
     ensures
         HighLevelState::map(s1@, s2@, vbase, frame, res),
 {
     lemma_pt_interpret_equals_all_mappings(s1);
+    lemma_map_steps_is_pt_op(s1.hw_state(), s2.hw_state());
     ll_map_preserves_invariants(s1, s2, vbase, frame, res);
     lemma_pt_interpret_equals_all_mappings(s2);
 
@@ -518,22 +1563,65 @@ proof fn ll_map_refines_hl_map(
     // Then updating pt_mem is equivalent to updating all_mappings.
 }
 
+/// Lemma. The invalidation issued by a successful unmap removes the unmapped page from the
+/// TLB, and the whole unmap behaves as a page table operation.
+proof fn lemma_unmap_invalidates_tlb<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
+    vbase: VAddr,
+    res: PagingResult,
+)
+    requires
+        s1.pt_in_mem(),
+        LowLevelState::<G>::unmap(s1, s2, vbase, res),
+    ensures
+        HardwareState::pt_op(s1.hw_state(), s2.hw_state()),
+        res is Ok ==> !s2.tlb.contains_base(s2.constants.tag, vbase),
+{
+    if res is Ok {
+        let tag = s1.constants.tag;
+        let mid = choose|mid: HardwareState<G>|
+            {
+                &&& LowLevelState::<G>::unmap_steps(s1.hw_state(), mid)
+                &&& HardwareState::tlb_invalidate(mid, s2.hw_state(), TLBOp::VA(tag, vbase))
+            };
+        // The steps of the unmap keep the TLB and only write page table memory
+        lemma_unmap_steps_is_pt_op(s1.hw_state(), mid);
+        // The invalidation only removes entries, including the one for `vbase`.
+        mid.tlb.lemma_invalidate_removes_only(TLBOp::VA(tag, vbase));
+        mid.tlb.lemma_invalidate_va_removes_base(tag, vbase);
+        assert forall|tag2: TLBTag, vbase2: VAddr, frame2: Frame|
+            s2.tlb.contains_mapping(tag2, vbase2, frame2) implies s1.tlb.contains_mapping(
+            tag2,
+            vbase2,
+            frame2,
+        ) by {
+            assert(mid.tlb.contains_mapping(tag2, vbase2, frame2));
+        }
+    } else {
+        lemma_unmap_steps_is_pt_op(s1.hw_state(), s2.hw_state());
+    }
+}
+
 /// Theorem. The low-level unmap operation preserves the invariants.
-proof fn ll_unmap_preserves_invariants(
-    s1: LowLevelState,
-    s2: LowLevelState,
+pub proof fn ll_unmap_preserves_invariants<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
     vbase: VAddr,
     res: PagingResult,
 )
     requires
         s1.invariants(),
-        LowLevelState::unmap(s1, s2, vbase, res),
+        LowLevelState::<G>::unmap(s1, s2, vbase, res),
     ensures
         s2.invariants(),
 {
+    // The unmapped page is invalidated in the TLB.
+    lemma_unmap_invalidates_tlb(s1, s2, vbase, res);
+
     // Prove s2.pt is a subset of s1.pt.
     assert(forall|vbase, frame| #[trigger]
-        s2.pt.interpret().contains_pair(vbase, frame) ==> s1.pt.interpret().contains_pair(
+        s2.pt.interpret::<G>().contains_pair(vbase, frame) ==> s1.pt.interpret::<G>().contains_pair(
             vbase,
             frame,
         ));
@@ -542,29 +1630,45 @@ proof fn ll_unmap_preserves_invariants(
     assert(s1.tlb == s1.hw_state().tlb);
     // s1.tlb < s1.pt ==> s2.tlb < s1.tlb\{op.vaddr} < s1.pt\{op.vaddr} = s2.pt
     assert forall|vbase, frame| #[trigger]
-        s2.tlb.contains_mapping(vbase, frame) implies s2.pt.interpret().contains_pair(
+        s2.tlb.contains_mapping(s2.constants.tag, vbase, frame) implies s2.pt.interpret::<
+            G,
+        >().contains_pair(
         vbase,
         frame,
     ) by {
-        assert(s1.pt.interpret().contains_pair(vbase, frame));
+        assert(s1.pt.interpret::<G>().contains_pair(vbase, frame));
     }
     assert(s2.tlb_is_submap_of_pt());
+
+    // Prove no mapping covers reserved memory or page table memory.
+    assert forall|vbase2: VAddr, frame2: Frame| #[trigger]
+        s2.pt.interpret::<G>().contains_pair(vbase2, frame2) implies !PAddr::overlap_any(
+        frame2.base,
+        frame2.size.as_nat(),
+        s2.constants.reserved,
+    ) by {
+        assert(s1.pt.interpret::<G>().contains_pair(vbase2, frame2));
+    }
+    assert(s2.pool_reserved());
+    lemma_pool_implies_tables_reserved(s2);
+    lemma_reserved_implies_tables_unmapped(s2);
 }
 
 /// Theorem. The low-level unmap operation refines the high-level unmap operation.
-proof fn ll_unmap_refines_hl_unmap(
-    s1: LowLevelState,
-    s2: LowLevelState,
+proof fn ll_unmap_refines_hl_unmap<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
     vbase: VAddr,
     res: PagingResult,
 )
     requires
         s1.invariants(),
-        LowLevelState::unmap(s1, s2, vbase, res),
+        LowLevelState::<G>::unmap(s1, s2, vbase, res),
     ensures
         HighLevelState::unmap(s1@, s2@, vbase, res),
 {
     lemma_pt_interpret_equals_all_mappings(s1);
+    lemma_unmap_invalidates_tlb(s1, s2, vbase, res);
     ll_unmap_preserves_invariants(s1, s2, vbase, res);
     lemma_pt_interpret_equals_all_mappings(s2);
 
@@ -573,31 +1677,43 @@ proof fn ll_unmap_refines_hl_unmap(
 }
 
 /// Theorem. The low-level query operation preserves the invariants.
-proof fn ll_query_preserves_invariants(
-    s1: LowLevelState,
-    s2: LowLevelState,
+pub proof fn ll_query_preserves_invariants<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
     vaddr: VAddr,
     res: PagingResult<(VAddr, Frame)>,
 )
     requires
         s1.invariants(),
-        LowLevelState::query(s1, s2, vaddr, res),
+        LowLevelState::<G>::query(s1, s2, vaddr, res),
     ensures
         s2.invariants(),
 {
     assert(s1.tlb == s1.hw_state().tlb);
+    // Prove no mapping covers reserved memory or page table memory.
+    assert forall|vbase2: VAddr, frame2: Frame| #[trigger]
+        s2.pt.interpret::<G>().contains_pair(vbase2, frame2) implies !PAddr::overlap_any(
+        frame2.base,
+        frame2.size.as_nat(),
+        s2.constants.reserved,
+    ) by {
+        assert(s1.pt.interpret::<G>().contains_pair(vbase2, frame2));
+    }
+    assert(s2.pool_reserved());
+    lemma_pool_implies_tables_reserved(s2);
+    lemma_reserved_implies_tables_unmapped(s2);
 }
 
 /// Theorem. The low-level query operation refines the high-level query operation.
-proof fn ll_query_refines_hl_query(
-    s1: LowLevelState,
-    s2: LowLevelState,
+proof fn ll_query_refines_hl_query<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
     vaddr: VAddr,
     res: PagingResult<(VAddr, Frame)>,
 )
     requires
         s1.invariants(),
-        LowLevelState::query(s1, s2, vaddr, res),
+        LowLevelState::<G>::query(s1, s2, vaddr, res),
     ensures
         HighLevelState::query(s1@, s2@, vaddr, res),
 {
@@ -605,35 +1721,126 @@ proof fn ll_query_refines_hl_query(
     ll_query_preserves_invariants(s1, s2, vaddr, res);
     assert(s1.pt_state().mappings == s1@.mappings);
     lemma_pt_interpret_equals_all_mappings(s2);
+    // Querying only touches page table memory, which is not mapped.
+    lemma_pt_op_preserves_interpret_mem(s1, s2);
 
     // Post condition satisfied because interpret_pt_mem equals all_mappings (lemma).
     // Then querying pt_mem (low-level) is equivalent to querying all_mappings (high-level).
 }
 
-/// Theorem. The low-level tlb evict operation preserves the invariants.
-proof fn ll_tlb_evict_preserves_invariants(s1: LowLevelState, s2: LowLevelState, vbase: VAddr)
+/// Theorem. The low-level TLB maintenance operation preserves the invariants.
+proof fn ll_tlb_invalidate_preserves_invariants<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
+    op: TLBOp,
+)
     requires
         s1.invariants(),
-        LowLevelState::tlb_evict(s1, s2, vbase),
+        LowLevelState::<G>::tlb_invalidate(s1, s2, op),
     ensures
         s2.invariants(),
 {
+    // TLB maintenance only removes entries.
+    s1.tlb.lemma_invalidate_removes_only(op);
+    assert forall|vbase, frame|
+        s2.tlb.contains_mapping(s2.constants.tag, vbase, frame) implies #[trigger]
+        s2.pt.interpret::<G>().contains_pair(vbase, frame) by {
+        assert(s1.tlb.contains_mapping(s1.constants.tag, vbase, frame));
+    }
+    assert(s2.tlb_is_submap_of_pt());
 }
 
-/// Theorem. The low-level tlb evict operation refines the high-level identity operation.
-proof fn ll_tlb_evict_refines_hl_id(s1: LowLevelState, s2: LowLevelState, vbase: VAddr)
+/// Theorem. The low-level TLB maintenance operation refines the high-level identity operation.
+proof fn ll_tlb_invalidate_refines_hl_id<G: GhostPTE>(
+    s1: LowLevelState<G>,
+    s2: LowLevelState<G>,
+    op: TLBOp,
+)
     requires
         s1.invariants(),
-        LowLevelState::tlb_evict(s1, s2, vbase),
+        LowLevelState::<G>::tlb_invalidate(s1, s2, op),
     ensures
         HighLevelState::id(s1@, s2@),
 {
     lemma_pt_interpret_equals_all_mappings(s1);
-    ll_tlb_evict_preserves_invariants(s1, s2, vbase);
+    ll_tlb_invalidate_preserves_invariants(s1, s2, op);
     lemma_pt_interpret_equals_all_mappings(s2);
 
     // Post condition satisfied because TLB is the subset of the page table (lemma).
     // Then updating TLB has no effect on the page table.
 }
 
+/// Lemma. TLB entries of other address spaces never affect the view of this address space.
+///
+/// Two states that only differ in TLB entries with other tags satisfy the same invariants
+/// and have the same high-level view.
+proof fn lemma_other_tags_irrelevant<G: GhostPTE>(s1: LowLevelState<G>, s2: LowLevelState<G>)
+    requires
+        s1.mem === s2.mem,
+        s1.pt === s2.pt,
+        s1.constants === s2.constants,
+        s1.tlb.tagged(s1.constants.tag) === s2.tlb.tagged(s2.constants.tag),
+    ensures
+        s1.invariants() == s2.invariants(),
+        s1@ === s2@,
+{
+    let tag = s1.constants.tag;
+    assert forall|vbase: VAddr, frame: Frame|
+        s1.tlb.contains_mapping(tag, vbase, frame) == s2.tlb.contains_mapping(
+            tag,
+            vbase,
+            frame,
+        ) by {
+        assert(s1.tlb.tagged(tag).contains_key(vbase) == s2.tlb.tagged(tag).contains_key(vbase));
+        if s1.tlb.contains_base(tag, vbase) {
+            assert(s1.tlb.tagged(tag)[vbase] == s2.tlb.tagged(tag)[vbase]);
+        }
+    }
+    assert(s1.tlb_is_submap_of_pt() == s2.tlb_is_submap_of_pt());
+    assert forall|vbase: VAddr| s1.tlb.contains_base(tag, vbase) implies s1.tlb.index(tag, vbase)
+        == s2.tlb.index(tag, vbase) by {
+        assert(s1.tlb.tagged(tag)[vbase] == s2.tlb.tagged(tag)[vbase]);
+    }
+    assert(s1.all_mappings() =~= s2.all_mappings());
+    assert(s1.interpret_mem() =~= s2.interpret_mem());
+}
+
+/// Theorem. No guest mapping ever reaches memory reserved by the hypervisor.
+///
+/// Every low-level transition preserves the invariants, so this holds in every reachable state.
+proof fn ll_mappings_never_reach_reserved<G: GhostPTE>(st: LowLevelState<G>)
+    requires
+        st.invariants(),
+    ensures
+        forall|vbase: VAddr, frame: Frame| #[trigger]
+            st@.mappings.contains_pair(vbase, frame) ==> !PAddr::overlap_any(
+                frame.base,
+                frame.size.as_nat(),
+                st@.constants.reserved,
+            ),
+        forall|vaddr: VAddr, i: int|
+            #![auto]
+            st@.has_mapping_for(vaddr) && 0 <= i < st@.constants.reserved.len() ==> !vaddr.map(
+                st@.mapping_for(vaddr).0,
+                st@.mapping_for(vaddr).1.base,
+            ).within(st@.constants.reserved[i].0, st@.constants.reserved[i].1),
+{
+    lemma_pt_interpret_equals_all_mappings(st);
+    let reserved = st.constants.reserved;
+    assert forall|vaddr: VAddr, i: int|
+        #![auto]
+        st@.has_mapping_for(vaddr) && 0 <= i < reserved.len() implies !vaddr.map(
+        st@.mapping_for(vaddr).0,
+        st@.mapping_for(vaddr).1.base,
+    ).within(reserved[i].0, reserved[i].1) by {
+        let (vbase, frame) = st@.mapping_for(vaddr);
+        assert(st.pt.interpret::<G>().contains_pair(vbase, frame));
+        lemma_vaddr_in_vpage_implies_paddr_in_pframe(vaddr, vbase, frame);
+        if vaddr.map(vbase, frame.base).within(reserved[i].0, reserved[i].1) {
+            // Proof by contradiction. The frame would overlap a reserved region.
+            assert(PAddr::overlap(frame.base, frame.size.as_nat(), reserved[i].0, reserved[i].1));
+        }
+    }
+}
+
 } // verus!
@@ -1,6 +1,9 @@
 mod lemmas;
 mod ll_refine_hl;
+mod multicore;
 mod tree;
 
+#[cfg(feature = "concurrent")]
+pub mod concurrent;
 pub mod interface;
 pub mod paging;
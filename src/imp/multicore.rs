@@ -0,0 +1,717 @@
+//! Prove the TLB shootdown protocol keeps the TLBs of all CPUs coherent with the page table.
+use core::marker::PhantomData;
+use vstd::prelude::*;
+
+use super::ll_refine_hl::{
+    lemma_fetch_preserves_pt_invariants, lemma_read_preserves_pt_invariants,
+    lemma_write_preserves_pt_invariants, ll_init_implies_invariants, ll_map_preserves_invariants,
+    ll_query_preserves_invariants, ll_unmap_preserves_invariants,
+};
+use crate::common::{
+    addr::{AccessSize, PAddr, VAddr},
+    frame::Frame,
+    pte::GhostPTE,
+    MemoryResult, MmioAccess, PagingResult, Privilege,
+};
+use crate::spec::{
+    hardware::HardwareState,
+    low_level::LowLevelState,
+    memory::{Table, TLBOp, TLB},
+    multicore::MultiCoreState,
+};
+
+verus! {
+
+/// The state observed by a CPU after flushing its TLB.
+spec fn flushed<G: GhostPTE>(st: LowLevelState<G>) -> LowLevelState<G> {
+    LowLevelState {
+        mem: st.mem,
+        pt: st.pt,
+        tlb: TLB(Map::empty()),
+        constants: st.constants,
+        _phantom: PhantomData,
+    }
+}
+
+/// The hardware state observed by a CPU after flushing its TLB.
+spec fn flushed_hw<G: GhostPTE>(hw: HardwareState<G>) -> HardwareState<G> {
+    HardwareState { tlb: TLB(Map::empty()), ..hw }
+}
+
+/// Lemma. A page table step does not depend on the TLB, so a CPU with a flushed TLB observes
+/// the same step.
+proof fn lemma_flushed_step<G: GhostPTE>(s1: HardwareState<G>, s2: HardwareState<G>)
+    ensures
+        HardwareState::pt_bbm_write(s1, s2) ==> HardwareState::pt_bbm_write(
+            flushed_hw(s1),
+            flushed_hw(s2),
+        ),
+        forall|level: nat, table: Table|
+            HardwareState::pt_alloc(s1, s2, level, table) ==> #[trigger] HardwareState::pt_alloc(
+                flushed_hw(s1),
+                flushed_hw(s2),
+                level,
+                table,
+            ),
+        forall|base: PAddr|
+            HardwareState::pt_dealloc(s1, s2, base) ==> #[trigger] HardwareState::pt_dealloc(
+                flushed_hw(s1),
+                flushed_hw(s2),
+                base,
+            ),
+{
+    if HardwareState::pt_bbm_write(s1, s2) {
+        let (base, index, entry) = choose|base: PAddr, index: nat, entry: u64|
+            {
+                &&& s1.write_is_bbm(base, index, entry)
+                &&& #[trigger] HardwareState::pt_write(s1, s2, base, index, entry)
+            };
+        // An empty TLB caches no translation
+        assert(flushed_hw(s1).write_is_bbm(base, index, entry));
+        assert(HardwareState::pt_write(flushed_hw(s1), flushed_hw(s2), base, index, entry));
+    }
+}
+
+/// Lemma. The steps of a map or an unmap operation are the same with the TLB flushed.
+proof fn lemma_flushed_steps<G: GhostPTE>(s1: HardwareState<G>, s2: HardwareState<G>)
+    ensures
+        LowLevelState::<G>::map_steps(s1, s2) ==> LowLevelState::<G>::map_steps(
+            flushed_hw(s1),
+            flushed_hw(s2),
+        ),
+        LowLevelState::<G>::unmap_steps(s1, s2) ==> LowLevelState::<G>::unmap_steps(
+            flushed_hw(s1),
+            flushed_hw(s2),
+        ),
+{
+    if LowLevelState::<G>::map_steps(s1, s2) {
+        let path = choose|path: Seq<HardwareState<G>>|
+            {
+                &&& HardwareState::pt_grow_steps(s1, s2, path)
+                &&& forall|i: int|
+                    0 <= i < path.len() ==> LowLevelState::<G>::mappings_between(
+                        s1.pt,
+                        #[trigger] path[i].pt,
+                        s2.pt,
+                    )
+            };
+        let fpath = path.map_values(|hw: HardwareState<G>| flushed_hw(hw));
+        assert forall|i: int| 0 <= i < fpath.len() - 1 implies {
+            ||| HardwareState::pt_bbm_write(#[trigger] fpath[i], fpath[i + 1])
+            ||| exists|level: nat, table: Table|
+                HardwareState::pt_alloc(fpath[i], fpath[i + 1], level, table)
+        } by {
+            lemma_flushed_step(path[i], path[i + 1]);
+        }
+        assert(forall|i: int| 0 <= i < fpath.len() ==> #[trigger] fpath[i].pt == path[i].pt);
+        assert(HardwareState::pt_grow_steps(flushed_hw(s1), flushed_hw(s2), fpath));
+    }
+    if LowLevelState::<G>::unmap_steps(s1, s2) {
+        let path = choose|path: Seq<HardwareState<G>>|
+            {
+                &&& HardwareState::pt_shrink_steps(s1, s2, path)
+                &&& forall|i: int|
+                    0 <= i < path.len() ==> LowLevelState::<G>::mappings_between(
+                        s2.pt,
+                        #[trigger] path[i].pt,
+                        s1.pt,
+                    )
+            };
+        let fpath = path.map_values(|hw: HardwareState<G>| flushed_hw(hw));
+        assert forall|i: int| 0 <= i < fpath.len() - 1 implies {
+            ||| HardwareState::pt_bbm_write(#[trigger] fpath[i], fpath[i + 1])
+            ||| exists|base: PAddr| HardwareState::pt_dealloc(fpath[i], fpath[i + 1], base)
+        } by {
+            lemma_flushed_step(path[i], path[i + 1]);
+        }
+        assert(forall|i: int| 0 <= i < fpath.len() ==> #[trigger] fpath[i].pt == path[i].pt);
+        assert(HardwareState::pt_shrink_steps(flushed_hw(s1), flushed_hw(s2), fpath));
+    }
+}
+
+/// Lemma. The page table invariants do not depend on the TLB, so all CPUs agree on them.
+proof fn lemma_pt_invariants_shared<G: GhostPTE>(st: MultiCoreState<G>, cpu: nat)
+    ensures
+        st.cpu(cpu).pt_invariants() == st.cpu(0).pt_invariants(),
+        flushed(st.cpu(cpu)).pt_invariants() == st.cpu(0).pt_invariants(),
+{
+}
+
+/// Lemma. A CPU with a flushed TLB satisfies the low-level invariants, and observes a page
+/// table operation the same way as with its own TLB.
+proof fn lemma_flushed_pt_op<G: GhostPTE>(
+    s1: MultiCoreState<G>,
+    s2: MultiCoreState<G>,
+    cpu: nat,
+)
+    requires
+        s1.invariants(),
+        HardwareState::pt_op(s1.cpu(cpu).hw_state(), s2.cpu(cpu).hw_state()),
+    ensures
+        flushed(s1.cpu(cpu)).invariants(),
+        HardwareState::pt_op(flushed(s1.cpu(cpu)).hw_state(), flushed(s2.cpu(cpu)).hw_state()),
+{
+    lemma_pt_invariants_shared(s1, cpu);
+    lemma_pt_invariants_shared(s2, cpu);
+}
+
+/// Lemma. TLBs stay coherent if the page table only gains mappings, the shootdown is
+/// unchanged, and every new TLB entry is filled from the page table.
+proof fn lemma_tlbs_stay_coherent<G: GhostPTE>(s1: MultiCoreState<G>, s2: MultiCoreState<G>)
+    requires
+        s1.invariants(),
+        s2.cpu(0).pt_invariants(),
+        s1.constants === s2.constants,
+        s1.cpu_count() == s2.cpu_count(),
+        s1.shootdown == s2.shootdown,
+        forall|vbase: VAddr, frame: Frame|
+            s1.pt.interpret::<G>().contains_pair(vbase, frame) ==> #[trigger]
+                s2.pt.interpret::<G>().contains_pair(vbase, frame),
+        forall|cpu: nat, vbase: VAddr, frame: Frame|
+            cpu < s2.cpu_count() && #[trigger] s2.cpu(cpu).tlb.contains_mapping(
+                s2.constants.tag,
+                vbase,
+                frame,
+            ) ==> s1.cpu(cpu).tlb.contains_mapping(s1.constants.tag, vbase, frame)
+                || s1.pt.interpret::<G>().contains_pair(vbase, frame),
+    ensures
+        s2.invariants(),
+{
+    let tag = s1.constants.tag;
+    assert forall|cpu: nat| cpu < s2.cpu_count() implies #[trigger] s2.cpu(
+        cpu,
+    ).tlb_unreserved() by {
+        assert(s1.cpu(cpu).tlb_unreserved());
+        assert forall|vbase: VAddr, frame: Frame| #[trigger]
+            s2.cpu(cpu).tlb.contains_mapping(tag, vbase, frame) implies frame.base.aligned(
+            frame.size.as_nat(),
+        ) && !PAddr::overlap_any(frame.base, frame.size.as_nat(), s2.constants.reserved) by {
+            if !s1.cpu(cpu).tlb.contains_mapping(tag, vbase, frame) {
+                // Filled from the page table
+                assert(s1.pt.interpret::<G>().contains_pair(vbase, frame));
+            }
+        }
+    }
+    assert forall|cpu: nat, vbase: VAddr, frame: Frame|
+        cpu < s2.cpu_count() && #[trigger] s2.cpu(cpu).tlb.contains_mapping(
+            tag,
+            vbase,
+            frame,
+        ) implies s2.pt.interpret::<G>().contains_pair(vbase, frame) || s2.stale_pending(
+        cpu,
+        vbase,
+        frame,
+    ) by {
+        if s1.cpu(cpu).tlb.contains_mapping(tag, vbase, frame) {
+            assert(s1.pt.interpret::<G>().contains_pair(vbase, frame) || s1.stale_pending(
+                cpu,
+                vbase,
+                frame,
+            ));
+        }
+    }
+    assert(s2.tlbs_coherent());
+}
+
+/// Lemma. A memory access on `cpu` only fills its TLB from the page table.
+proof fn lemma_access_fills_from_pt<G: GhostPTE>(
+    s1: MultiCoreState<G>,
+    s2: MultiCoreState<G>,
+    cpu: nat,
+)
+    requires
+        cpu < s1.cpu_count(),
+        s1.constants === s2.constants,
+        MultiCoreState::other_tlbs_unchanged(s1, s2, cpu),
+        s2.cpu(cpu).tlb === s1.cpu(cpu).tlb || exists|base: VAddr, frame: Frame|
+            {
+                &&& #[trigger] s1.pt.interpret::<G>().contains_pair(base, frame)
+                &&& s2.cpu(cpu).tlb === s1.cpu(cpu).tlb.update(s1.constants.tag, base, frame)
+            },
+    ensures
+        forall|c: nat, vbase: VAddr, frame: Frame|
+            c < s2.cpu_count() && #[trigger] s2.cpu(c).tlb.contains_mapping(
+                s2.constants.tag,
+                vbase,
+                frame,
+            ) ==> s1.cpu(c).tlb.contains_mapping(s1.constants.tag, vbase, frame)
+                || s1.pt.interpret::<G>().contains_pair(vbase, frame),
+{
+    let tag = s1.constants.tag;
+    assert forall|c: nat, vbase: VAddr, frame: Frame|
+        c < s2.cpu_count() && #[trigger] s2.cpu(c).tlb.contains_mapping(
+            tag,
+            vbase,
+            frame,
+        ) implies s1.cpu(c).tlb.contains_mapping(tag, vbase, frame)
+        || s1.pt.interpret::<G>().contains_pair(vbase, frame) by {
+        if c != cpu {
+            assert(s1.tlbs[c as int] === s2.tlbs[c as int]);
+        } else if s2.cpu(cpu).tlb !== s1.cpu(cpu).tlb {
+            // The only new entry is a page table mapping
+            let (base, f) = choose|base: VAddr, f: Frame|
+                {
+                    &&& #[trigger] s1.pt.interpret::<G>().contains_pair(base, f)
+                    &&& s2.cpu(cpu).tlb === s1.cpu(cpu).tlb.update(tag, base, f)
+                };
+            if vbase != base || frame != f {
+                assert(s1.cpu(cpu).tlb.contains_mapping(tag, vbase, frame));
+            }
+        }
+    }
+}
+
+/// Theorem. The multi-core init state implies the invariants.
+proof fn mc_init_implies_invariants<G: GhostPTE>(st: MultiCoreState<G>)
+    requires
+        st.init(),
+    ensures
+        st.invariants(),
+{
+    assert(st.cpu(0).init());
+    ll_init_implies_invariants(st.cpu(0));
+    // All TLBs start empty
+    assert forall|cpu: nat| cpu < st.cpu_count() implies #[trigger] st.cpu(
+        cpu,
+    ).tlb_unreserved() by {
+        assert(st.cpu(cpu).init());
+    }
+    assert forall|cpu: nat, vbase: VAddr, frame: Frame|
+        cpu < st.cpu_count() && #[trigger] st.cpu(cpu).tlb.contains_mapping(
+            st.constants.tag,
+            vbase,
+            frame,
+        ) implies st.pt.interpret::<G>().contains_pair(vbase, frame) || st.stale_pending(
+        cpu,
+        vbase,
+        frame,
+    ) by {
+        assert(st.cpu(cpu).init());
+    }
+}
+
+/// Theorem. The multi-core read operation preserves the invariants.
+proof fn mc_read_preserves_invariants<G: GhostPTE>(
+    s1: MultiCoreState<G>,
+    s2: MultiCoreState<G>,
+    cpu: nat,
+    vaddr: VAddr,
+    size: AccessSize,
+    privilege: Privilege,
+    res: MemoryResult<u64>,
+    mmio: Option<MmioAccess>,
+)
+    requires
+        s1.invariants(),
+        MultiCoreState::read(s1, s2, cpu, vaddr, size, privilege, res, mmio),
+    ensures
+        s2.invariants(),
+{
+    lemma_pt_invariants_shared(s1, cpu);
+    lemma_read_preserves_pt_invariants(
+        s1.cpu(cpu),
+        s2.cpu(cpu),
+        vaddr,
+        size,
+        privilege,
+        res,
+        mmio,
+    );
+    lemma_pt_invariants_shared(s2, cpu);
+    lemma_access_fills_from_pt(s1, s2, cpu);
+    lemma_tlbs_stay_coherent(s1, s2);
+}
+
+/// Theorem. The multi-core fetch operation preserves the invariants.
+proof fn mc_fetch_preserves_invariants<G: GhostPTE>(
+    s1: MultiCoreState<G>,
+    s2: MultiCoreState<G>,
+    cpu: nat,
+    vaddr: VAddr,
+    size: AccessSize,
+    privilege: Privilege,
+    res: MemoryResult<u64>,
+)
+    requires
+        s1.invariants(),
+        MultiCoreState::fetch(s1, s2, cpu, vaddr, size, privilege, res),
+    ensures
+        s2.invariants(),
+{
+    lemma_pt_invariants_shared(s1, cpu);
+    lemma_fetch_preserves_pt_invariants(s1.cpu(cpu), s2.cpu(cpu), vaddr, size, privilege, res);
+    lemma_pt_invariants_shared(s2, cpu);
+    lemma_access_fills_from_pt(s1, s2, cpu);
+    lemma_tlbs_stay_coherent(s1, s2);
+}
+
+/// Theorem. The multi-core write operation preserves the invariants.
+///
+/// The write may go through a stale TLB entry, but stale entries never cover page tables.
+proof fn mc_write_preserves_invariants<G: GhostPTE>(
+    s1: MultiCoreState<G>,
+    s2: MultiCoreState<G>,
+    cpu: nat,
+    vaddr: VAddr,
+    size: AccessSize,
+    value: u64,
+    privilege: Privilege,
+    res: MemoryResult<()>,
+    mmio: Option<MmioAccess>,
+)
+    requires
+        s1.invariants(),
+        MultiCoreState::write(s1, s2, cpu, vaddr, size, value, privilege, res, mmio),
+    ensures
+        s2.invariants(),
+{
+    lemma_pt_invariants_shared(s1, cpu);
+    assert(s1.cpu(cpu).tlb_unreserved());
+    lemma_write_preserves_pt_invariants(
+        s1.cpu(cpu),
+        s2.cpu(cpu),
+        vaddr,
+        size,
+        value,
+        privilege,
+        res,
+        mmio,
+    );
+    lemma_pt_invariants_shared(s2, cpu);
+    lemma_access_fills_from_pt(s1, s2, cpu);
+    lemma_tlbs_stay_coherent(s1, s2);
+}
+
+/// Theorem. The multi-core local TLB maintenance operation preserves the invariants.
+proof fn mc_tlb_invalidate_preserves_invariants<G: GhostPTE>(
+    s1: MultiCoreState<G>,
+    s2: MultiCoreState<G>,
+    cpu: nat,
+    op: TLBOp,
+)
+    requires
+        s1.invariants(),
+        MultiCoreState::tlb_invalidate(s1, s2, cpu, op),
+    ensures
+        s2.invariants(),
+{
+    lemma_pt_invariants_shared(s2, cpu);
+    s1.cpu(cpu).tlb.lemma_invalidate_removes_only(op);
+    assert forall|c: nat, vbase: VAddr, frame: Frame|
+        c < s2.cpu_count() && #[trigger] s2.cpu(c).tlb.contains_mapping(
+            s2.constants.tag,
+            vbase,
+            frame,
+        ) implies s1.cpu(c).tlb.contains_mapping(s1.constants.tag, vbase, frame) by {
+        if c != cpu {
+            assert(s1.tlbs[c as int] === s2.tlbs[c as int]);
+        }
+    }
+    lemma_tlbs_stay_coherent(s1, s2);
+}
+
+/// Theorem. The multi-core map operation preserves the invariants.
+proof fn mc_map_preserves_invariants<G: GhostPTE>(
+    s1: MultiCoreState<G>,
+    s2: MultiCoreState<G>,
+    cpu: nat,
+    vbase: VAddr,
+    frame: Frame,
+    res: PagingResult,
+)
+    requires
+        s1.invariants(),
+        MultiCoreState::map(s1, s2, cpu, vbase, frame, res),
+    ensures
+        s2.invariants(),
+{
+    // Page table invariants are checked on `cpu` with its TLB flushed.
+    let (t1, t2) = (flushed(s1.cpu(cpu)), flushed(s2.cpu(cpu)));
+    lemma_flushed_pt_op(s1, s2, cpu);
+    lemma_flushed_steps(s1.cpu(cpu).hw_state(), s2.cpu(cpu).hw_state());
+    assert(t1.hw_state() === flushed_hw(s1.cpu(cpu).hw_state()));
+    assert(t2.hw_state() === flushed_hw(s2.cpu(cpu).hw_state()));
+    assert(LowLevelState::map(t1, t2, vbase, frame, res));
+    ll_map_preserves_invariants(t1, t2, vbase, frame, res);
+    lemma_pt_invariants_shared(s2, cpu);
+
+    // Mappings are only added, TLB entries are only removed.
+    assert(forall|vbase2, frame2|
+        s1.pt.interpret::<G>().contains_pair(vbase2, frame2) ==> s2.pt.interpret::<
+            G,
+        >().contains_pair(vbase2, frame2));
+    assert forall|c: nat, vbase2: VAddr, frame2: Frame|
+        c < s2.cpu_count() && #[trigger] s2.cpu(c).tlb.contains_mapping(
+            s2.constants.tag,
+            vbase2,
+            frame2,
+        ) implies s1.cpu(c).tlb.contains_mapping(s1.constants.tag, vbase2, frame2) by {
+        assert(HardwareState::pt_op(s1.cpu(c).hw_state(), s2.cpu(c).hw_state()));
+    }
+    lemma_tlbs_stay_coherent(s1, s2);
+}
+
+/// Theorem. The multi-core unmap operation preserves the invariants.
+///
+/// The unmapped page may remain in the TLBs of all CPUs, but the shootdown started by unmap
+/// covers it.
+proof fn mc_unmap_preserves_invariants<G: GhostPTE>(
+    s1: MultiCoreState<G>,
+    s2: MultiCoreState<G>,
+    cpu: nat,
+    vbase: VAddr,
+    res: PagingResult,
+)
+    requires
+        s1.invariants(),
+        MultiCoreState::unmap(s1, s2, cpu, vbase, res),
+    ensures
+        s2.invariants(),
+{
+    let tag = s1.constants.tag;
+    // Page table invariants are checked on `cpu` with its TLB flushed.
+    let (t1, t2) = (flushed(s1.cpu(cpu)), flushed(s2.cpu(cpu)));
+    lemma_flushed_pt_op(s1, s2, cpu);
+    lemma_flushed_steps(s1.cpu(cpu).hw_state(), s2.cpu(cpu).hw_state());
+    assert(t1.hw_state() === flushed_hw(s1.cpu(cpu).hw_state()));
+    assert(t2.hw_state() === flushed_hw(s2.cpu(cpu).hw_state()));
+    if res is Ok {
+        // Invalidating an empty TLB changes nothing.
+        let op = TLBOp::VA(tag, vbase);
+        assert(t2.tlb.invalidate(op).0 =~= t2.tlb.0);
+        assert(HardwareState::tlb_invalidate(t2.hw_state(), t2.hw_state(), op));
+    }
+    assert(LowLevelState::unmap(t1, t2, vbase, res));
+    ll_unmap_preserves_invariants(t1, t2, vbase, res);
+    lemma_pt_invariants_shared(s2, cpu);
+
+    // TLB entries are only removed, and no entry was stale before.
+    assert forall|c: nat, vbase2: VAddr, frame2: Frame|
+        c < s2.cpu_count() && #[trigger] s2.cpu(c).tlb.contains_mapping(
+            tag,
+            vbase2,
+            frame2,
+        ) implies s1.cpu(c).tlb.contains_mapping(tag, vbase2, frame2)
+        && s1.pt.interpret::<G>().contains_pair(vbase2, frame2) by {
+        assert(HardwareState::pt_op(s1.cpu(c).hw_state(), s2.cpu(c).hw_state()));
+        assert(!s1.stale_pending(c, vbase2, frame2));
+    }
+    assert forall|c: nat| c < s2.cpu_count() implies #[trigger] s2.cpu(c).tlb_unreserved() by {
+        assert(s1.cpu(c).tlb_unreserved());
+        assert forall|vbase2: VAddr, frame2: Frame| #[trigger]
+            s2.cpu(c).tlb.contains_mapping(tag, vbase2, frame2) implies frame2.base.aligned(
+            frame2.size.as_nat(),
+        ) && !PAddr::overlap_any(frame2.base, frame2.size.as_nat(), s2.constants.reserved) by {
+            assert(s1.cpu(c).tlb.contains_mapping(tag, vbase2, frame2));
+        }
+    }
+    assert forall|c: nat, vbase2: VAddr, frame2: Frame|
+        c < s2.cpu_count() && #[trigger] s2.cpu(c).tlb.contains_mapping(
+            tag,
+            vbase2,
+            frame2,
+        ) implies s2.pt.interpret::<G>().contains_pair(vbase2, frame2) || s2.stale_pending(
+        c,
+        vbase2,
+        frame2,
+    ) by {
+        assert(s1.pt.interpret::<G>().contains_pair(vbase2, frame2));
+        if res is Ok && vbase2 == vbase {
+            // The unmapped page is covered by the broadcast invalidation.
+            assert(vbase.within(vbase, frame2.size.as_nat()));
+            assert(s2.shootdown->Some_0.pending.contains(c));
+        }
+    }
+    assert(s2.tlbs_coherent());
+}
+
+/// Theorem. The multi-core query operation preserves the invariants.
+proof fn mc_query_preserves_invariants<G: GhostPTE>(
+    s1: MultiCoreState<G>,
+    s2: MultiCoreState<G>,
+    cpu: nat,
+    vaddr: VAddr,
+    res: PagingResult<(VAddr, Frame)>,
+)
+    requires
+        s1.invariants(),
+        MultiCoreState::query(s1, s2, cpu, vaddr, res),
+    ensures
+        s2.invariants(),
+{
+    // Page table invariants are checked on `cpu` with its TLB flushed.
+    let (t1, t2) = (flushed(s1.cpu(cpu)), flushed(s2.cpu(cpu)));
+    lemma_flushed_pt_op(s1, s2, cpu);
+    assert(LowLevelState::query(t1, t2, vaddr, res));
+    ll_query_preserves_invariants(t1, t2, vaddr, res);
+    lemma_pt_invariants_shared(s2, cpu);
+
+    // Mappings are unchanged, TLB entries are only removed.
+    assert forall|c: nat, vbase: VAddr, frame: Frame|
+        c < s2.cpu_count() && #[trigger] s2.cpu(c).tlb.contains_mapping(
+            s2.constants.tag,
+            vbase,
+            frame,
+        ) implies s1.cpu(c).tlb.contains_mapping(s1.constants.tag, vbase, frame) by {
+        assert(HardwareState::pt_op(s1.cpu(c).hw_state(), s2.cpu(c).hw_state()));
+    }
+    lemma_tlbs_stay_coherent(s1, s2);
+}
+
+/// Theorem. Acknowledging the shootdown preserves the invariants.
+proof fn mc_shootdown_ack_preserves_invariants<G: GhostPTE>(
+    s1: MultiCoreState<G>,
+    s2: MultiCoreState<G>,
+    cpu: nat,
+)
+    requires
+        s1.invariants(),
+        MultiCoreState::shootdown_ack(s1, s2, cpu),
+    ensures
+        s2.invariants(),
+{
+    let tag = s1.constants.tag;
+    let sd = s1.shootdown->Some_0;
+    lemma_pt_invariants_shared(s2, cpu);
+    s1.cpu(cpu).tlb.lemma_invalidate_covers(sd.op);
+    assert forall|c: nat, vbase: VAddr, frame: Frame|
+        c < s2.cpu_count() && #[trigger] s2.cpu(c).tlb.contains_mapping(
+            tag,
+            vbase,
+            frame,
+        ) implies s1.cpu(c).tlb.contains_mapping(tag, vbase, frame) && (c == cpu ==> !sd.op.covers(
+        tag,
+        vbase,
+        frame,
+    )) by {
+        if c != cpu {
+            assert(s1.tlbs[c as int] === s2.tlbs[c as int]);
+        }
+    }
+    assert forall|c: nat| c < s2.cpu_count() implies #[trigger] s2.cpu(c).tlb_unreserved() by {
+        assert(s1.cpu(c).tlb_unreserved());
+        assert forall|vbase: VAddr, frame: Frame| #[trigger]
+            s2.cpu(c).tlb.contains_mapping(tag, vbase, frame) implies frame.base.aligned(
+            frame.size.as_nat(),
+        ) && !PAddr::overlap_any(frame.base, frame.size.as_nat(), s2.constants.reserved) by {
+            assert(s1.cpu(c).tlb.contains_mapping(tag, vbase, frame));
+        }
+    }
+    assert forall|c: nat, vbase: VAddr, frame: Frame|
+        c < s2.cpu_count() && #[trigger] s2.cpu(c).tlb.contains_mapping(
+            tag,
+            vbase,
+            frame,
+        ) implies s2.pt.interpret::<G>().contains_pair(vbase, frame) || s2.stale_pending(
+        c,
+        vbase,
+        frame,
+    ) by {
+        assert(s1.cpu(c).tlb.contains_mapping(tag, vbase, frame));
+        assert(s1.pt.interpret::<G>().contains_pair(vbase, frame) || s1.stale_pending(
+            c,
+            vbase,
+            frame,
+        ));
+        if c != cpu && s1.stale_pending(c, vbase, frame) {
+            // Still waiting for `c`
+            assert(sd.pending.remove(cpu).contains(c));
+        }
+    }
+    assert(s2.tlbs_coherent());
+}
+
+/// Theorem. Completing the shootdown preserves the invariants.
+proof fn mc_shootdown_finish_preserves_invariants<G: GhostPTE>(
+    s1: MultiCoreState<G>,
+    s2: MultiCoreState<G>,
+)
+    requires
+        s1.invariants(),
+        MultiCoreState::shootdown_finish(s1, s2),
+    ensures
+        s2.invariants(),
+{
+    assert forall|c: nat| c < s2.cpu_count() implies #[trigger] s2.cpu(c).tlb_unreserved() by {
+        assert(s2.cpu(c) == s1.cpu(c));
+        assert(s1.cpu(c).tlb_unreserved());
+    }
+    // Every CPU has acknowledged, so no entry is stale.
+    assert forall|c: nat, vbase: VAddr, frame: Frame|
+        c < s2.cpu_count() && #[trigger] s2.cpu(c).tlb.contains_mapping(
+            s2.constants.tag,
+            vbase,
+            frame,
+        ) implies s2.pt.interpret::<G>().contains_pair(vbase, frame) by {
+        assert(s1.cpu(c).tlb.contains_mapping(s1.constants.tag, vbase, frame));
+        assert(!s1.shootdown->Some_0.pending.contains(c));
+        assert(!s1.stale_pending(c, vbase, frame));
+    }
+    assert(s2.cpu(0) == s1.cpu(0));
+    assert(s2.tlbs_coherent());
+}
+
+/// Theorem. When no shootdown is in progress, every CPU satisfies the low-level invariants.
+///
+/// Every CPU then refines the high-level state machine by the low-level refinement proof.
+proof fn mc_cpus_coherent<G: GhostPTE>(st: MultiCoreState<G>)
+    requires
+        st.invariants(),
+        st.shootdown is None,
+    ensures
+        forall|cpu: nat| cpu < st.cpu_count() ==> #[trigger] st.cpu(cpu).invariants(),
+{
+    assert forall|cpu: nat| cpu < st.cpu_count() implies #[trigger] st.cpu(cpu).invariants() by {
+        lemma_pt_invariants_shared(st, cpu);
+        assert forall|vbase: VAddr, frame: Frame|
+            st.cpu(cpu).tlb.contains_mapping(st.constants.tag, vbase, frame) implies #[trigger]
+            st.cpu(cpu).pt.interpret::<G>().contains_pair(vbase, frame) by {
+            assert(!st.stale_pending(cpu, vbase, frame));
+        }
+        assert(st.cpu(cpu).tlb_is_submap_of_pt());
+    }
+}
+
+/// Theorem. After unmap and the shootdown it starts, no CPU can translate through the
+/// unmapped page.
+///
+/// `s3` is any state reached after the shootdown completes, as long as the page table
+/// mappings are not modified meanwhile.
+proof fn mc_unmap_shootdown_removes_translation<G: GhostPTE>(
+    s1: MultiCoreState<G>,
+    s2: MultiCoreState<G>,
+    s3: MultiCoreState<G>,
+    cpu: nat,
+    vbase: VAddr,
+)
+    requires
+        s1.invariants(),
+        MultiCoreState::unmap(s1, s2, cpu, vbase, Ok(())),
+        s3.invariants(),
+        s3.shootdown is None,
+        s3.constants === s2.constants,
+        s3.pt.interpret::<G>() == s2.pt.interpret::<G>(),
+    ensures
+        !s3.pt.interpret::<G>().contains_key(vbase),
+        forall|c: nat|
+            c < s3.cpu_count() ==> !#[trigger] s3.cpu(c).tlb.contains_base(
+                s3.constants.tag,
+                vbase,
+            ),
+{
+    assert(!s2.pt.interpret::<G>().contains_key(vbase));
+    mc_cpus_coherent(s3);
+    assert forall|c: nat| c < s3.cpu_count() implies !#[trigger] s3.cpu(c).tlb.contains_base(
+        s3.constants.tag,
+        vbase,
+    ) by {
+        if s3.cpu(c).tlb.contains_base(s3.constants.tag, vbase) {
+            // Proof by contradiction. The entry is backed by the page table.
+            let frame = s3.cpu(c).tlb.index(s3.constants.tag, vbase);
+            assert(s3.cpu(c).tlb.contains_mapping(s3.constants.tag, vbase, frame));
+            assert(s3.pt.interpret::<G>().contains_pair(vbase, frame));
+        }
+    }
+}
+
+} // verus!
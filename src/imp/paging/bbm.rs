@@ -0,0 +1,921 @@
+//! Break-before-make compliance of page table updates.
+//!
+//! The walker observes page table memory after every single step of an operation
+//! (`HardwareState::pt_write`, `pt_alloc` and `pt_dealloc`), and the low-level `map` and
+//! `unmap` transitions are stated over these steps (`LowLevelState::map_steps` and
+//! `unmap_steps`). This module enumerates the states `map` and `unmap` pass through, and
+//! proves that each of them is safe to observe:
+//!
+//! - `map` only fills invalid entries, so its states only gain mappings, from the mappings
+//!   before `map` up to the mappings after it.
+//! - `unmap` only invalidates entries before deallocating tables, so its states only lose
+//!   mappings, from the mappings before `unmap` down to the mappings after it.
+//!
+//! No valid descriptor is ever replaced by another valid one, so every write is
+//! break-before-make on the page table side. A translation cached by the TLB during an
+//! operation is a mapping of the larger of the two states. The mappings `map` adds overlap no
+//! mapping before it, so the TLB is clear for each of its writes (`theorem_insert_tlb_clear`).
+//!
+//! Splitting or merging a block is out of scope: `map` fails on an existing mapping instead,
+//! and `protect` only rewrites the attributes of a valid descriptor, which `pt_write` allows
+//! without a break, and then invalidates the old translation
+//! (`theorem_protect_changes_attr`). A change of the memory type or shareability needs a break,
+//! so `protect` rejects it. Replacing a block with a table would need a TLB invalidation
+//! between the break and the make, which no operation issues.
+use vstd::prelude::*;
+
+use super::pt::PageTable;
+use crate::{
+    common::{
+        addr::{PAddr, VAddr},
+        frame::{Frame, MemAttr},
+        pte::{same_target, GhostPTE},
+    },
+    imp::lemmas::{lemma_submap_antisym, lemma_submap_trans},
+    spec::memory::{PageTableMem, TLBTag, Table, TLB},
+};
+
+verus! {
+
+// Use page table memory related lemmas.
+broadcast use crate::spec::memory::group_pt_mem_lemmas;
+
+impl<G> PageTable<G> where G: GhostPTE {
+    /// If `insert` with the table allocations `allocs` passes through page table memory `m`.
+    pub open spec fn insert_passes_through(
+        self,
+        vbase: VAddr,
+        base: PAddr,
+        level: nat,
+        target_level: nat,
+        new_pte: G,
+        allocs: Seq<(PageTableMem, Table)>,
+        m: PageTableMem,
+    ) -> bool
+        recommends
+            self.invariants(),
+            self.pt_mem.contains_table(base),
+            self.pt_mem.table(base).level == level,
+            level <= target_level < self.constants.arch.level_count(),
+            self.pte_valid_frame(new_pte, target_level),
+        decreases target_level - level,
+    {
+        let idx = self.constants.arch.pte_index(vbase, level);
+        let pte = G::from_u64(self.pt_mem.read(base, idx));
+        ||| m == self.pt_mem
+        ||| if level >= target_level {
+            // Write the leaf entry
+            m == self.insert(vbase, base, level, target_level, new_pte, allocs).0.pt_mem
+        } else if pte.valid() {
+            // Insert at next level
+            !pte.huge() && self.insert_passes_through(
+                vbase,
+                pte.addr(),
+                level + 1,
+                target_level,
+                new_pte,
+                allocs,
+                m,
+            )
+        } else {
+            // Allocate intermediate table, write entry, and insert at next level
+            let (pt_mem, table) = allocs[0];
+            let entry = G::new(table.base, MemAttr::spec_default(), false).to_u64();
+            &&& allocs.len() > 0
+            &&& self.pt_mem.allocates(level + 1, pt_mem, table)
+            &&& {
+                ||| m == pt_mem
+                ||| Self::new(pt_mem.write(base, idx, entry), self.constants).insert_passes_through(
+                    vbase,
+                    table.base,
+                    level + 1,
+                    target_level,
+                    new_pte,
+                    allocs.drop_first(),
+                    m,
+                )
+            }
+        }
+    }
+
+    /// If `prune` passes through page table memory `m`.
+    ///
+    /// An emptied table is unlinked from its parent before it is deallocated.
+    pub open spec fn prune_passes_through(
+        self,
+        vaddr: VAddr,
+        base: PAddr,
+        level: nat,
+        m: PageTableMem,
+    ) -> bool
+        recommends
+            self.invariants(),
+            self.pt_mem.contains_table(base),
+            self.pt_mem.table(base).level == level,
+            level < self.constants.arch.level_count(),
+        decreases self.constants.arch.level_count() - level,
+    {
+        let idx = self.constants.arch.pte_index(vaddr, level);
+        let pte = G::from_u64(self.pt_mem.read(base, idx));
+        ||| m == self.pt_mem
+        ||| level < self.constants.arch.level_count() - 1 && pte.valid() && !pte.huge() && {
+            let s2 = self.prune(vaddr, pte.addr(), level + 1);
+            ||| self.prune_passes_through(vaddr, pte.addr(), level + 1, m)
+            ||| s2.is_table_empty(pte.addr()) && {
+                let pt_mem = s2.pt_mem.write(base, idx, G::empty().to_u64());
+                ||| m == pt_mem
+                ||| m == pt_mem.dealloc_table(pte.addr())
+            }
+        }
+    }
+
+    /// If unmapping `vbase` passes through page table memory `m`.
+    pub open spec fn unmap_passes_through(self, vbase: VAddr, m: PageTableMem) -> bool
+        recommends
+            self.invariants(),
+    {
+        let root = self.pt_mem.root();
+        let (s2, res) = self.remove(vbase, root, 0);
+        ||| m == self.pt_mem
+        ||| res is Ok && s2.prune_passes_through(vbase, root, 0, m)
+    }
+
+    /// Lemma. Filling an invalid entry keeps every walk that reaches a valid entry.
+    ///
+    /// `self` is the page table after writing `entry` into the invalid entry of `pre`.
+    pub proof fn lemma_fill_preserves_walk_from(
+        self,
+        pre: PageTableMem,
+        b: PAddr,
+        i: nat,
+        entry: u64,
+        vaddr: VAddr,
+        base: PAddr,
+        level: nat,
+    )
+        requires
+            self.invariants(),
+            pre.invariants(),
+            pre.accessible(b, i),
+            !G::from_u64(pre.read(b, i)).valid(),
+            self.pt_mem == pre.write(b, i, entry),
+            pre.contains_table(base),
+            level == pre.table(base).level,
+            level < self.constants.arch.level_count(),
+        ensures
+            pre.walk_from::<G>(vaddr, base, level).0.valid() ==> self.pt_mem.walk_from::<G>(
+                vaddr,
+                base,
+                level,
+            ) == pre.walk_from::<G>(vaddr, base, level),
+        decreases self.constants.arch.level_count() - level,
+    {
+        let arch = self.constants.arch;
+        let idx = arch.pte_index(vaddr, level);
+        arch.lemma_pte_index_bound(vaddr, level);
+        assert(pre.accessible(base, idx));
+        let pte = G::from_u64(pre.read(base, idx));
+
+        if base != b || idx != i {
+            // The walk does not read the filled entry at this level
+            pre.lemma_write_keeps_other_entry(b, i, entry, base, idx);
+            if self.pte_points_to_table(pte, level) {
+                // Both walks descend into the same table
+                assert(self.pt_mem.accessible(base, idx));
+                assert(pre.contains_table(pte.addr()));
+                self.lemma_fill_preserves_walk_from(pre, b, i, entry, vaddr, pte.addr(), level + 1);
+            }
+        }
+    }
+
+    /// Lemma. Invalidating an entry keeps every walk of the result that reaches a valid entry.
+    pub proof fn lemma_invalidate_preserves_walk_from(
+        self,
+        b: PAddr,
+        i: nat,
+        entry: u64,
+        vaddr: VAddr,
+        base: PAddr,
+        level: nat,
+    )
+        requires
+            self.invariants(),
+            self.pt_mem.accessible(b, i),
+            !G::from_u64(entry).valid(),
+            self.pt_mem.contains_table(base),
+            level == self.pt_mem.table(base).level,
+            level < self.constants.arch.level_count(),
+        ensures
+            ({
+                let post = self.pt_mem.write(b, i, entry);
+                post.walk_from::<G>(vaddr, base, level).0.valid() ==> post.walk_from::<G>(
+                    vaddr,
+                    base,
+                    level,
+                ) == self.pt_mem.walk_from::<G>(vaddr, base, level)
+            }),
+        decreases self.constants.arch.level_count() - level,
+    {
+        let arch = self.constants.arch;
+        let idx = arch.pte_index(vaddr, level);
+        arch.lemma_pte_index_bound(vaddr, level);
+        assert(self.pt_mem.accessible(base, idx));
+        let pte = G::from_u64(self.pt_mem.read(base, idx));
+
+        if base == b && idx == i {
+            // The walk of the result stops at the invalidated entry
+            assert(self.pt_mem.write(b, i, entry).read(b, i) == entry);
+        } else {
+            self.pt_mem.lemma_write_keeps_other_entry(b, i, entry, base, idx);
+            if self.pte_points_to_table(pte, level) {
+                // Both walks descend into the same table
+                self.lemma_invalidate_preserves_walk_from(
+                    b,
+                    i,
+                    entry,
+                    vaddr,
+                    pte.addr(),
+                    level + 1,
+                );
+            }
+        }
+    }
+
+    /// Lemma. A page table memory that has the same contents for every table of `self`
+    /// performs the same walks as `self`, whatever other tables it has.
+    pub proof fn lemma_same_tables_same_walk_from(
+        self,
+        other: PageTableMem,
+        vaddr: VAddr,
+        base: PAddr,
+        level: nat,
+    )
+        requires
+            self.invariants(),
+            other.arch == self.pt_mem.arch,
+            forall|base2: PAddr| #[trigger]
+                self.pt_mem.contains_table(base2) ==> other.table_view(base2)
+                    == self.pt_mem.table_view(base2),
+            self.pt_mem.contains_table(base),
+            level == self.pt_mem.table(base).level,
+            level < self.constants.arch.level_count(),
+        ensures
+            other.walk_from::<G>(vaddr, base, level) == self.pt_mem.walk_from::<G>(
+                vaddr,
+                base,
+                level,
+            ),
+        decreases self.constants.arch.level_count() - level,
+    {
+        let arch = self.constants.arch;
+        let idx = arch.pte_index(vaddr, level);
+        arch.lemma_pte_index_bound(vaddr, level);
+        assert(self.pt_mem.accessible(base, idx));
+        assert(other.read(base, idx) == self.pt_mem.read(base, idx));
+        let pte = G::from_u64(self.pt_mem.read(base, idx));
+        if self.pte_points_to_table(pte, level) {
+            self.lemma_same_tables_same_walk_from(other, vaddr, pte.addr(), level + 1);
+        }
+    }
+
+    /// Lemma. Filling an invalid entry only adds mappings.
+    ///
+    /// `self` is the page table after writing `entry` into the invalid entry of `pre`.
+    pub proof fn lemma_fill_grows_interpret(self, pre: PageTableMem, b: PAddr, i: nat, entry: u64)
+        requires
+            self.invariants(),
+            pre.invariants(),
+            pre.accessible(b, i),
+            !G::from_u64(pre.read(b, i)).valid(),
+            self.pt_mem == pre.write(b, i, entry),
+        ensures
+            pre.interpret::<G>().submap_of(self.pt_mem.interpret::<G>()),
+    {
+        let root = pre.root();
+        assert(self.pt_mem.root() == root);
+        assert forall|vaddr: VAddr| #[trigger]
+            pre.walk_from::<G>(vaddr, root, 0).0.valid() implies self.pt_mem.walk_from::<G>(
+            vaddr,
+            root,
+            0,
+        ) == pre.walk_from::<G>(vaddr, root, 0) by {
+            self.lemma_fill_preserves_walk_from(pre, b, i, entry, vaddr, root, 0);
+        }
+        lemma_walks_imply_submap::<G>(pre, self.pt_mem);
+    }
+
+    /// Lemma. Invalidating an entry only removes mappings.
+    pub proof fn lemma_invalidate_shrinks_interpret(self, b: PAddr, i: nat, entry: u64)
+        requires
+            self.invariants(),
+            self.pt_mem.accessible(b, i),
+            !G::from_u64(entry).valid(),
+        ensures
+            self.pt_mem.write(b, i, entry).interpret::<G>().submap_of(
+                self.pt_mem.interpret::<G>(),
+            ),
+    {
+        let post = self.pt_mem.write(b, i, entry);
+        let root = self.pt_mem.root();
+        assert(post.root() == root);
+        assert forall|vaddr: VAddr| #[trigger]
+            post.walk_from::<G>(vaddr, root, 0).0.valid() implies self.pt_mem.walk_from::<G>(
+            vaddr,
+            root,
+            0,
+        ) == post.walk_from::<G>(vaddr, root, 0) by {
+            self.lemma_invalidate_preserves_walk_from(b, i, entry, vaddr, root, 0);
+        }
+        lemma_walks_imply_submap::<G>(post, self.pt_mem);
+    }
+
+    /// Lemma. Allocating a table, yielding `pt_mem`, keeps the mappings.
+    pub proof fn lemma_alloc_keeps_interpret(self, level: nat, pt_mem: PageTableMem, table: Table)
+        requires
+            self.invariants(),
+            level < self.constants.arch.level_count(),
+            self.pt_mem.allocates(level, pt_mem, table),
+        ensures
+            pt_mem.interpret::<G>() == self.pt_mem.interpret::<G>(),
+    {
+        let root = self.pt_mem.root();
+        assert(pt_mem.root() == root);
+        assert forall|vaddr: VAddr| #[trigger]
+            pt_mem.walk_from::<G>(vaddr, root, 0) == self.pt_mem.walk_from::<G>(vaddr, root, 0) by {
+            self.lemma_same_tables_same_walk_from(pt_mem, vaddr, root, 0);
+        }
+        lemma_walks_imply_submap::<G>(self.pt_mem, pt_mem);
+        lemma_walks_imply_submap::<G>(pt_mem, self.pt_mem);
+        lemma_submap_antisym(pt_mem.interpret::<G>(), self.pt_mem.interpret::<G>());
+    }
+
+    /// Lemma. Linking an empty table into an invalid entry keeps every walk of the result that
+    /// reaches a valid entry.
+    ///
+    /// `self` is the page table after writing `entry`, which points to an empty table, into the
+    /// invalid entry of `pre`.
+    pub proof fn lemma_link_empty_table_preserves_walk_from(
+        self,
+        pre: PageTableMem,
+        b: PAddr,
+        i: nat,
+        entry: u64,
+        vaddr: VAddr,
+        base: PAddr,
+        level: nat,
+    )
+        requires
+            self.invariants(),
+            pre.invariants(),
+            pre.accessible(b, i),
+            !G::from_u64(pre.read(b, i)).valid(),
+            self.pt_mem == pre.write(b, i, entry),
+            self.pte_points_to_table(G::from_u64(entry), pre.table(b).level),
+            self.is_table_empty(G::from_u64(entry).addr()),
+            pre.contains_table(base),
+            level == pre.table(base).level,
+            level < self.constants.arch.level_count(),
+        ensures
+            self.pt_mem.walk_from::<G>(vaddr, base, level).0.valid() ==> self.pt_mem.walk_from::<
+                G,
+            >(vaddr, base, level) == pre.walk_from::<G>(vaddr, base, level),
+        decreases self.constants.arch.level_count() - level,
+    {
+        let arch = self.constants.arch;
+        let idx = arch.pte_index(vaddr, level);
+        arch.lemma_pte_index_bound(vaddr, level);
+        assert(pre.accessible(base, idx));
+
+        if base == b && idx == i {
+            // The walk of the result descends into the linked table, which has no valid entry
+            let table_base = G::from_u64(entry).addr();
+            assert(self.pt_mem.accessible(b, i));
+            assert(self.pt_mem.read(b, i) == entry);
+            assert(self.pt_mem.contains_table(table_base));
+            assert(self.pt_mem.table(table_base).level == level + 1);
+            arch.lemma_pte_index_bound(vaddr, level + 1);
+            assert(!G::from_u64(
+                self.pt_mem.read(table_base, arch.pte_index(vaddr, level + 1)),
+            ).valid());
+        } else {
+            pre.lemma_write_keeps_other_entry(b, i, entry, base, idx);
+            let pte = G::from_u64(pre.read(base, idx));
+            if self.pte_points_to_table(pte, level) {
+                // Both walks descend into the same table
+                assert(self.pt_mem.accessible(base, idx));
+                assert(pre.contains_table(pte.addr()));
+                self.lemma_link_empty_table_preserves_walk_from(
+                    pre,
+                    b,
+                    i,
+                    entry,
+                    vaddr,
+                    pte.addr(),
+                    level + 1,
+                );
+            }
+        }
+    }
+
+    /// Lemma. Allocating an intermediate table and linking it into an invalid entry keeps the
+    /// mappings.
+    ///
+    /// This is the state `insert` leaves when a deeper allocation fails.
+    pub proof fn lemma_link_new_table_keeps_interpret(
+        self,
+        base: PAddr,
+        level: nat,
+        idx: nat,
+        pt_mem: PageTableMem,
+        table: Table,
+    )
+        requires
+            self.invariants(),
+            self.pt_mem.contains_table(base),
+            level == self.pt_mem.table(base).level,
+            level + 1 < self.constants.arch.level_count(),
+            self.pt_mem.accessible(base, idx),
+            !G::from_u64(self.pt_mem.read(base, idx)).valid(),
+            self.pt_mem.allocates(level + 1, pt_mem, table),
+        ensures
+            ({
+                let entry = G::new(table.base, MemAttr::spec_default(), false).to_u64();
+                pt_mem.write(base, idx, entry).interpret::<G>() == self.pt_mem.interpret::<G>()
+            }),
+    {
+        broadcast use crate::common::pte::group_pte_lemmas;
+
+        table.size.lemma_aligned_implies_4k_aligned(table.base.0);
+        let entry = G::new(table.base, MemAttr::spec_default(), false).to_u64();
+        let s2 = Self::new(pt_mem.write(base, idx, entry), self.constants);
+        self.lemma_alloc_intermediate_table_preserves_invariants(base, level, idx, pt_mem, table);
+        self.lemma_alloc_keeps_interpret(level + 1, pt_mem, table);
+        assert(pt_mem.accessible(base, idx));
+        assert(pt_mem.read(base, idx) == self.pt_mem.read(base, idx));
+
+        // The new table is linked, and has no valid entry
+        assert(s2.pte_points_to_table(G::from_u64(entry), pt_mem.table(base).level));
+        assert forall|i: nat| i < self.constants.arch.entry_count(level + 1) implies !G::from_u64(
+            s2.pt_mem.read(table.base, i),
+        ).valid() by {
+            assert(pt_mem.accessible(table.base, i));
+            pt_mem.lemma_write_keeps_other_entry(base, idx, entry, table.base, i);
+            assert(pt_mem.read(table.base, i) == 0);
+        }
+        assert(s2.is_table_empty(table.base));
+
+        // Filling the entry only adds mappings, and every walk of the result that reaches a
+        // valid entry avoids the new table
+        s2.lemma_fill_grows_interpret(pt_mem, base, idx, entry);
+        let root = pt_mem.root();
+        assert(s2.pt_mem.root() == root);
+        assert forall|vaddr: VAddr| #[trigger]
+            s2.pt_mem.walk_from::<G>(vaddr, root, 0).0.valid() implies s2.pt_mem.walk_from::<G>(
+            vaddr,
+            root,
+            0,
+        ) == pt_mem.walk_from::<G>(vaddr, root, 0) by {
+            s2.lemma_link_empty_table_preserves_walk_from(pt_mem, base, idx, entry, vaddr, root, 0);
+        }
+        lemma_walks_imply_submap::<G>(s2.pt_mem, pt_mem);
+        lemma_submap_antisym(s2.pt_mem.interpret::<G>(), pt_mem.interpret::<G>());
+    }
+
+    /// Lemma. Deallocating a table that is no longer linked keeps the mappings.
+    ///
+    /// `self` is the page table after deallocating the table at `table_base` from `pre`.
+    pub proof fn lemma_dealloc_keeps_interpret(self, pre: PageTableMem, table_base: PAddr)
+        requires
+            self.invariants(),
+            pre.invariants(),
+            pre.contains_table(table_base),
+            table_base != pre.root(),
+            self.pt_mem == pre.dealloc_table(table_base),
+        ensures
+            self.pt_mem.interpret::<G>() == pre.interpret::<G>(),
+    {
+        let root = self.pt_mem.root();
+        assert(pre.root() == root);
+        // Tables of `self` are the same in `pre`
+        assert forall|base2: PAddr| #[trigger]
+            self.pt_mem.contains_table(base2) implies pre.table_view(base2)
+            == self.pt_mem.table_view(base2) by {
+            let table = choose|table: Table| #[trigger]
+                self.pt_mem.tables.contains(table) && table.base == base2;
+            assert(pre.tables.contains(table));
+        }
+        assert forall|vaddr: VAddr| #[trigger]
+            pre.walk_from::<G>(vaddr, root, 0) == self.pt_mem.walk_from::<G>(vaddr, root, 0) by {
+            self.lemma_same_tables_same_walk_from(pre, vaddr, root, 0);
+        }
+        lemma_walks_imply_submap::<G>(self.pt_mem, pre);
+        lemma_walks_imply_submap::<G>(pre, self.pt_mem);
+        lemma_submap_antisym(self.pt_mem.interpret::<G>(), pre.interpret::<G>());
+    }
+
+    /// Lemma. `remove` only invalidates an entry, so it only removes mappings.
+    pub proof fn lemma_remove_shrinks_interpret(self, vbase: VAddr, base: PAddr, level: nat)
+        requires
+            self.invariants(),
+            self.pt_mem.contains_table(base),
+            level == self.pt_mem.table(base).level,
+            level < self.constants.arch.level_count(),
+        ensures
+            self.remove(vbase, base, level).0.pt_mem.interpret::<G>().submap_of(
+                self.pt_mem.interpret::<G>(),
+            ),
+        decreases self.constants.arch.level_count() - level,
+    {
+        broadcast use crate::common::pte::group_pte_lemmas;
+
+        let idx = self.constants.arch.pte_index(vbase, level);
+        let pte = G::from_u64(self.pt_mem.read(base, idx));
+        assert(self.pt_mem.accessible(base, idx));
+
+        if pte.valid() {
+            if self.pte_points_to_table(pte, level) {
+                // Remove from the next table
+                self.lemma_remove_shrinks_interpret(vbase, pte.addr(), level + 1);
+            } else {
+                // Invalidate the entry
+                G::lemma_eq_by_u64(G::from_u64(G::empty().to_u64()), G::empty());
+                self.lemma_invalidate_shrinks_interpret(base, idx, G::empty().to_u64());
+            }
+        }
+    }
+
+    /// Theorem. Every state `insert` passes through has at least the mappings before `insert`,
+    /// and at most the mappings after it.
+    pub proof fn theorem_insert_observable_states(
+        self,
+        vbase: VAddr,
+        base: PAddr,
+        level: nat,
+        target_level: nat,
+        new_pte: G,
+        allocs: Seq<(PageTableMem, Table)>,
+        m: PageTableMem,
+    )
+        requires
+            self.invariants(),
+            self.pt_mem.contains_table(base),
+            level == self.pt_mem.table(base).level,
+            level <= target_level < self.constants.arch.level_count(),
+            self.pte_valid_frame(new_pte, target_level),
+            self.insert_passes_through(vbase, base, level, target_level, new_pte, allocs, m),
+        ensures
+            self.pt_mem.interpret::<G>().submap_of(m.interpret::<G>()),
+            m.interpret::<G>().submap_of(
+                self.insert(
+                    vbase,
+                    base,
+                    level,
+                    target_level,
+                    new_pte,
+                    allocs,
+                ).0.pt_mem.interpret::<G>(),
+            ),
+        decreases target_level - level,
+    {
+        let idx = self.constants.arch.pte_index(vbase, level);
+        let pte = G::from_u64(self.pt_mem.read(base, idx));
+        assert(self.pt_mem.accessible(base, idx));
+        let post = self.insert(vbase, base, level, target_level, new_pte, allocs).0;
+        self.lemma_insert_preserves_invariants(vbase, base, level, target_level, new_pte, allocs);
+
+        if level >= target_level {
+            if !pte.valid() {
+                // Write the leaf entry into an invalid entry
+                post.lemma_fill_grows_interpret(self.pt_mem, base, idx, new_pte.to_u64());
+            }
+        } else if pte.valid() {
+            if !pte.huge() {
+                // `insert` recurses into the next table with the same page table
+                self.theorem_insert_observable_states(
+                    vbase,
+                    pte.addr(),
+                    level + 1,
+                    target_level,
+                    new_pte,
+                    allocs,
+                    self.pt_mem,
+                );
+                if m != self.pt_mem {
+                    self.theorem_insert_observable_states(
+                        vbase,
+                        pte.addr(),
+                        level + 1,
+                        target_level,
+                        new_pte,
+                        allocs,
+                        m,
+                    );
+                }
+            }
+        } else if allocs.len() > 0 && self.pt_mem.allocates(level + 1, allocs[0].0, allocs[0].1) {
+            // Allocate intermediate table
+            let (pt_mem, table) = allocs[0];
+            let rest_allocs = allocs.drop_first();
+            let entry = G::new(table.base, MemAttr::spec_default(), false).to_u64();
+            // `s2` is the state after writing the descriptor of the new table
+            let s2 = Self::new(pt_mem.write(base, idx, entry), self.constants);
+            self.lemma_alloc_intermediate_table_preserves_invariants(
+                base,
+                level,
+                idx,
+                pt_mem,
+                table,
+            );
+            assert(s2.invariants());
+
+            // Allocating keeps the mappings, and the descriptor fills an invalid entry
+            self.lemma_alloc_keeps_interpret(level + 1, pt_mem, table);
+            assert(pt_mem.accessible(base, idx));
+            assert(pt_mem.read(base, idx) == self.pt_mem.read(base, idx));
+            s2.lemma_fill_grows_interpret(pt_mem, base, idx, entry);
+
+            // The rest of `insert` proceeds from `s2`
+            s2.theorem_insert_observable_states(
+                vbase,
+                table.base,
+                level + 1,
+                target_level,
+                new_pte,
+                rest_allocs,
+                s2.pt_mem,
+            );
+            lemma_submap_trans(
+                pt_mem.interpret::<G>(),
+                s2.pt_mem.interpret::<G>(),
+                post.pt_mem.interpret::<G>(),
+            );
+            if m != self.pt_mem && m != pt_mem {
+                s2.theorem_insert_observable_states(
+                    vbase,
+                    table.base,
+                    level + 1,
+                    target_level,
+                    new_pte,
+                    rest_allocs,
+                    m,
+                );
+                lemma_submap_trans(
+                    pt_mem.interpret::<G>(),
+                    s2.pt_mem.interpret::<G>(),
+                    m.interpret::<G>(),
+                );
+            }
+        }
+    }
+
+    /// Theorem. A write `insert` performs between the states `m1` and `m2` is break-before-make
+    /// on the TLB side, if the TLB only caches mappings before `insert` and the mappings
+    /// `insert` adds overlap none of them.
+    pub proof fn theorem_insert_tlb_clear(
+        self,
+        vbase: VAddr,
+        base: PAddr,
+        level: nat,
+        target_level: nat,
+        new_pte: G,
+        allocs: Seq<(PageTableMem, Table)>,
+        m1: PageTableMem,
+        m2: PageTableMem,
+        tlb: TLB,
+        tag: TLBTag,
+    )
+        requires
+            self.invariants(),
+            self.pt_mem.contains_table(base),
+            level == self.pt_mem.table(base).level,
+            level <= target_level < self.constants.arch.level_count(),
+            self.pte_valid_frame(new_pte, target_level),
+            self.insert_passes_through(vbase, base, level, target_level, new_pte, allocs, m1),
+            self.insert_passes_through(vbase, base, level, target_level, new_pte, allocs, m2),
+            forall|vbase2: VAddr, frame2: Frame| #[trigger]
+                tlb.contains_mapping(tag, vbase2, frame2) ==> self.pt_mem.interpret::<
+                    G,
+                >().contains_pair(vbase2, frame2),
+            forall|vbase1: VAddr, frame1: Frame, vbase2: VAddr, frame2: Frame|
+                #[trigger] self.insert(
+                    vbase,
+                    base,
+                    level,
+                    target_level,
+                    new_pte,
+                    allocs,
+                ).0.pt_mem.interpret::<G>().contains_pair(vbase1, frame1)
+                    && !self.pt_mem.interpret::<G>().contains_pair(vbase1, frame1)
+                    && #[trigger] self.pt_mem.interpret::<G>().contains_pair(vbase2, frame2)
+                    ==> !VAddr::overlap(
+                    vbase1,
+                    frame1.size.as_nat(),
+                    vbase2,
+                    frame2.size.as_nat(),
+                ),
+        ensures
+            tlb.clear_for::<G>(tag, m1, m2),
+    {
+        let post = self.insert(vbase, base, level, target_level, new_pte, allocs).0;
+        self.theorem_insert_observable_states(
+            vbase,
+            base,
+            level,
+            target_level,
+            new_pte,
+            allocs,
+            m1,
+        );
+        self.theorem_insert_observable_states(
+            vbase,
+            base,
+            level,
+            target_level,
+            new_pte,
+            allocs,
+            m2,
+        );
+        assert forall|vbase1: VAddr, frame1: Frame, vbase2: VAddr, frame2: Frame|
+            #[trigger] m2.interpret::<G>().contains_pair(vbase1, frame1)
+                && !m1.interpret::<G>().contains_pair(vbase1, frame1)
+                && #[trigger] tlb.contains_mapping(tag, vbase2, frame2) implies !VAddr::overlap(
+            vbase1,
+            frame1.size.as_nat(),
+            vbase2,
+            frame2.size.as_nat(),
+        ) by {
+            // Added by `insert`: in `post` since `m2` is a submap of it, and not before
+            // `insert` since the mappings before `insert` are a submap of `m1`
+            assert(post.pt_mem.interpret::<G>().contains_pair(vbase1, frame1));
+            assert(!self.pt_mem.interpret::<G>().contains_pair(vbase1, frame1));
+            assert(self.pt_mem.interpret::<G>().contains_pair(vbase2, frame2));
+        }
+    }
+
+    /// Theorem. Every state `prune` passes through has at most the mappings before `prune`,
+    /// and at least the mappings after it.
+    pub proof fn theorem_prune_observable_states(
+        self,
+        vaddr: VAddr,
+        base: PAddr,
+        level: nat,
+        m: PageTableMem,
+    )
+        requires
+            self.invariants(),
+            self.pt_mem.contains_table(base),
+            level == self.pt_mem.table(base).level,
+            level < self.constants.arch.level_count(),
+            self.prune_passes_through(vaddr, base, level, m),
+        ensures
+            m.interpret::<G>().submap_of(self.pt_mem.interpret::<G>()),
+            self.prune(vaddr, base, level).pt_mem.interpret::<G>().submap_of(
+                m.interpret::<G>(),
+            ),
+        decreases self.constants.arch.level_count() - level,
+    {
+        broadcast use crate::common::pte::group_pte_lemmas;
+
+        let idx = self.constants.arch.pte_index(vaddr, level);
+        let pte = G::from_u64(self.pt_mem.read(base, idx));
+        assert(self.pt_mem.accessible(base, idx));
+        let post = self.prune(vaddr, base, level);
+
+        if self.pte_points_to_table(pte, level) {
+            let child = pte.addr();
+            // `s2` is the state after pruning the next table
+            let s2 = self.prune(vaddr, child, level + 1);
+            self.lemma_prune_preserves_invariants(vaddr, child, level + 1);
+            self.lemma_prune_preserves_lower_tables(vaddr, child, level + 1, base);
+            self.lemma_prune_preserves_lower_tables(vaddr, child, level + 1, child);
+            self.theorem_prune_observable_states(vaddr, child, level + 1, self.pt_mem);
+            if self.prune_passes_through(vaddr, child, level + 1, m) {
+                self.theorem_prune_observable_states(vaddr, child, level + 1, m);
+            }
+
+            if s2.is_table_empty(child) {
+                // Unlink the empty table, then deallocate it
+                let empty = G::empty().to_u64();
+                let pt_mem = s2.pt_mem.write(base, idx, empty);
+                G::lemma_eq_by_u64(G::from_u64(empty), G::empty());
+                s2.lemma_invalidate_shrinks_interpret(base, idx, empty);
+                s2.lemma_dealloc_intermediate_table_preserves_invariants(base, level, idx);
+                s2.pt_mem.lemma_dealloc_write_commute(child, base, idx, empty);
+                assert(post.pt_mem == pt_mem.dealloc_table(child));
+                post.lemma_dealloc_keeps_interpret(pt_mem, child);
+
+                lemma_submap_trans(
+                    pt_mem.interpret::<G>(),
+                    s2.pt_mem.interpret::<G>(),
+                    self.pt_mem.interpret::<G>(),
+                );
+                if self.prune_passes_through(vaddr, child, level + 1, m) {
+                    lemma_submap_trans(
+                        post.pt_mem.interpret::<G>(),
+                        s2.pt_mem.interpret::<G>(),
+                        m.interpret::<G>(),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Theorem. Every state unmapping `vbase` passes through has at most the mappings before
+    /// unmapping, and at least the mappings after it.
+    pub proof fn theorem_unmap_observable_states(self, vbase: VAddr, m: PageTableMem)
+        requires
+            self.invariants(),
+            self.unmap_passes_through(vbase, m),
+        ensures
+            m.interpret::<G>().submap_of(self.pt_mem.interpret::<G>()),
+            ({
+                let root = self.pt_mem.root();
+                let (s2, res) = self.remove(vbase, root, 0);
+                let post = if res is Ok {
+                    s2.prune(vbase, root, 0)
+                } else {
+                    s2
+                };
+                post.pt_mem.interpret::<G>().submap_of(m.interpret::<G>())
+            }),
+    {
+        let root = self.pt_mem.root();
+        let (s2, res) = self.remove(vbase, root, 0);
+        self.lemma_remove_preserves_invariants(vbase, root, 0);
+        self.lemma_remove_preserves_root(vbase, root, 0);
+        self.lemma_remove_shrinks_interpret(vbase, root, 0);
+
+        if res is Ok {
+            // `prune` proceeds from `s2`
+            s2.theorem_prune_observable_states(vbase, root, 0, s2.pt_mem);
+            lemma_submap_trans(
+                s2.prune(vbase, root, 0).pt_mem.interpret::<G>(),
+                s2.pt_mem.interpret::<G>(),
+                self.pt_mem.interpret::<G>(),
+            );
+            if m != self.pt_mem {
+                s2.theorem_prune_observable_states(vbase, root, 0, m);
+                lemma_submap_trans(
+                    m.interpret::<G>(),
+                    s2.pt_mem.interpret::<G>(),
+                    self.pt_mem.interpret::<G>(),
+                );
+            }
+        }
+    }
+
+    /// Theorem. The write `protect` performs on the entry that maps `vbase` only changes the
+    /// attributes, so the TLB keeps the old translation until `vbase` is invalidated.
+    ///
+    /// `protect` rejects a change of the memory type or shareability, which would need a break.
+    pub proof fn theorem_protect_changes_attr(self, vbase: VAddr, entry: u64)
+        requires
+            self.invariants(),
+            ({
+                let (pte, level) = self.pt_mem.walk_from::<G>(vbase, self.pt_mem.root(), 0);
+                let new_pte = G::from_u64(entry);
+                &&& pte.valid()
+                &&& same_target(pte, new_pte)
+                &&& pte.attr().spec_same_memory_type(new_pte.attr())
+            }),
+        ensures
+            ({
+                let (b, i) = self.pt_mem.walk_entry_from::<G>(vbase, self.pt_mem.root(), 0);
+                self.pt_mem.write_changes_attr::<G>(b, i, entry)
+            }),
+    {
+        self.lemma_invariants_implies_walkable();
+        self.pt_mem.lemma_contains_root();
+        self.pt_mem.lemma_walk_entry_from::<G>(vbase, self.pt_mem.root(), 0);
+    }
+}
+
+/// Lemma. If every walk of `pt_mem1` that reaches a valid entry is also a walk of `pt_mem2`,
+/// the mappings of `pt_mem1` are a submap of those of `pt_mem2`.
+proof fn lemma_walks_imply_submap<G: GhostPTE>(pt_mem1: PageTableMem, pt_mem2: PageTableMem)
+    requires
+        pt_mem1.arch == pt_mem2.arch,
+        pt_mem1.root() == pt_mem2.root(),
+        forall|vaddr: VAddr| #[trigger]
+            pt_mem1.walk_from::<G>(vaddr, pt_mem1.root(), 0).0.valid() ==> pt_mem2.walk_from::<G>(
+                vaddr,
+                pt_mem2.root(),
+                0,
+            ) == pt_mem1.walk_from::<G>(vaddr, pt_mem1.root(), 0),
+    ensures
+        pt_mem1.interpret::<G>().submap_of(pt_mem2.interpret::<G>()),
+{
+    let m1 = pt_mem1.interpret::<G>();
+    let m2 = pt_mem2.interpret::<G>();
+    assert forall|vbase: VAddr| #[trigger] m1.contains_key(vbase) implies m2.contains_key(vbase)
+        && m2[vbase] == m1[vbase] by {
+        let frame = m1[vbase];
+        assert(pt_mem1.walk::<G>(vbase, frame));
+        assert(pt_mem1.walk_from::<G>(vbase, pt_mem1.root(), 0).0.valid());
+        assert(pt_mem2.walk::<G>(vbase, frame));
+        assert(m2.contains_key(vbase));
+        assert(pt_mem2.walk::<G>(vbase, m2[vbase]));
+    }
+}
+
+} // verus!
@@ -0,0 +1,1070 @@
+//! Software dirty page logging.
+//!
+//! Tracks the pages written in a zone of virtual memory without relying on hardware Dirty
+//! flags, e.g. for live migration:
+//!
+//! - `harvest` write-protects every writable mapping that overlaps the zone, and returns and
+//!   resets the pages logged since the previous harvest.
+//! - The first write to a protected mapping faults. `handle_write_fault` logs the pages of the
+//!   mapping and restores write permission, so later writes proceed without faults.
+//!
+//! The log keeps one bit per leaf page of the zone. Every page written since the last harvest
+//! is logged (`DirtyLog::lemma_write_is_logged`).
+use alloc::vec::Vec;
+use vstd::prelude::*;
+
+use super::pt_exec::PageTableExec;
+use crate::{
+    common::{
+        addr::{AccessSize, PAddr, VAddr, VAddrExec, WORD_SIZE},
+        frame::{Frame, FrameExec, MemAttr},
+        pte::{ExecPTE, GhostPTE},
+        MemoryResult, MmioAccess, PagingResult, Privilege,
+    },
+    spec::{
+        hardware::HardwareState,
+        low_level::LowLevelState,
+        memory::{PageTableMemExec, TLBExec, TLBOp, TLBTag},
+        page_table::{PTConstants, PageTableState},
+    },
+};
+
+verus! {
+
+/// `frame` with write permission set to `writable`.
+pub open spec fn with_writable(frame: Frame, writable: bool) -> Frame {
+    Frame { attr: MemAttr { writable, ..frame.attr }, ..frame }
+}
+
+/// If every mapping could be mapped again after removing it.
+///
+/// Mappings are then aligned and do not overlap each other, as if they were all created by
+/// `map`. Changing attributes in place keeps this (`lemma_same_extent_keeps_remappable`).
+pub open spec fn remappable(mappings: Map<VAddr, Frame>, constants: PTConstants) -> bool {
+    forall|vbase: VAddr, frame: Frame| #[trigger]
+        mappings.contains_pair(vbase, frame) ==> {
+            let s = PageTableState::new(mappings.remove(vbase), constants);
+            &&& s.map_pre(vbase, frame)
+            &&& !s.overlaps_vmem(vbase, frame)
+        }
+}
+
+/// If bit `bit` of `word` is set.
+pub open spec fn bit_set(word: u64, bit: u64) -> bool {
+    (word >> bit) & 1u64 == 1u64
+}
+
+/// Dirty log of a zone of virtual memory.
+pub struct DirtyLog<M: PageTableMemExec, G: GhostPTE, E: ExecPTE<G>> {
+    /// Page table.
+    pub pt: PageTableExec<M, G, E>,
+    /// Base address of the zone, aligned to the leaf frame size.
+    pub start: VAddrExec,
+    /// Number of leaf pages in the zone.
+    pub page_count: usize,
+    /// Dirty bitmap. Bit `i % 64` of word `i / 64` logs page `i` of the zone.
+    pub bitmap: Vec<u64>,
+    /// Mappings write-protected by the log. Bit `i % 64` of word `i / 64` marks the mapping
+    /// whose first zone page is page `i`. Only mappings that cover their first zone page are
+    /// marked, so no two mappings share a bit.
+    pub protected: Vec<u64>,
+}
+
+impl<M, G, E> DirtyLog<M, G, E> where M: PageTableMemExec, G: GhostPTE, E: ExecPTE<G> {
+    /// Mappings of the page table.
+    pub open spec fn mappings(self) -> Map<VAddr, Frame> {
+        self.pt.pt_mem@.interpret::<G>()
+    }
+
+    /// Abstract page table state.
+    pub open spec fn pt_state(self) -> PageTableState {
+        PageTableState::new(self.mappings(), self.pt.constants@)
+    }
+
+    /// Leaf frame size.
+    pub open spec fn leaf_size(self) -> nat {
+        self.pt.constants@.arch.leaf_frame_size().as_nat()
+    }
+
+    /// Base address of page `page` of the zone.
+    pub open spec fn page_vaddr(self, page: nat) -> VAddr {
+        VAddr(self.start@.0 + page * self.leaf_size())
+    }
+
+    /// If page `page` of the zone is logged.
+    pub open spec fn logged(self, page: nat) -> bool {
+        bit_set(self.bitmap@[(page / 64) as int], (page % 64) as u64)
+    }
+
+    /// First zone page not below `vbase`.
+    pub open spec fn first_page(self, vbase: VAddr) -> nat {
+        if vbase.0 > self.start@.0 {
+            (vbase.0 - self.start@.0) as nat / self.leaf_size()
+        } else {
+            0
+        }
+    }
+
+    /// If the mapping `(vbase, size)` covers its first zone page, which keys its protection.
+    pub open spec fn covers_first_page(self, vbase: VAddr, size: nat) -> bool {
+        let page = self.first_page(vbase);
+        page < self.page_count && self.page_vaddr(page).within(vbase, size)
+    }
+
+    /// If the mapping `(vbase, size)` is write-protected by the log.
+    pub open spec fn protected(self, vbase: VAddr, size: nat) -> bool {
+        let page = self.first_page(vbase);
+        &&& self.covers_first_page(vbase, size)
+        &&& bit_set(self.protected@[(page / 64) as int], (page % 64) as u64)
+    }
+
+    /// If every zone page covered by a writable mapping is logged.
+    ///
+    /// Pages written since the last harvest were writable when written, so they are logged.
+    pub open spec fn writable_logged(self) -> bool {
+        forall|vbase: VAddr, frame: Frame, page: nat|
+            #[trigger] self.mappings().contains_pair(vbase, frame) && frame.attr.writable && page
+                < self.page_count && #[trigger] self.page_vaddr(page).within(
+                vbase,
+                frame.size.as_nat(),
+            ) ==> self.logged(page)
+    }
+
+    /// If no writable mapping covers a zone page below `vaddr`.
+    pub open spec fn read_only_below(self, vaddr: nat) -> bool {
+        forall|vbase: VAddr, frame: Frame, page: nat|
+            #[trigger] self.mappings().contains_pair(vbase, frame) && frame.attr.writable && page
+                < self.page_count && self.page_vaddr(page).0 < vaddr
+                ==> !#[trigger] self.page_vaddr(page).within(vbase, frame.size.as_nat())
+    }
+
+    /// Invariants.
+    pub open spec fn inv(self) -> bool {
+        &&& self.pt@.invariants()
+        &&& self.pt.constants@.arch.valid()
+        &&& self.start@.aligned(self.leaf_size())
+        &&& self.start.0 + self.page_count * self.leaf_size() <= usize::MAX
+        &&& self.page_count <= self.bitmap.len() * 64
+        &&& self.protected.len() == self.bitmap.len()
+        &&& remappable(self.mappings(), self.pt.constants@)
+        &&& self.writable_logged()
+    }
+
+    /// If `self` and `other` log the same zone of the same page table.
+    pub open spec fn same_zone(self, other: Self) -> bool {
+        &&& self.pt.constants == other.pt.constants
+        &&& self.start == other.start
+        &&& self.page_count == other.page_count
+        &&& self.bitmap.len() == other.bitmap.len()
+    }
+
+    /// Start logging `page_count` leaf pages from `start`.
+    ///
+    /// Every page is logged before the first harvest, which then returns the whole zone.
+    pub fn new(pt: PageTableExec<M, G, E>, start: VAddrExec, page_count: usize) -> (res: Self)
+        requires
+            pt@.invariants(),
+            pt.constants@.arch.valid(),
+            start@.aligned(pt.constants@.arch.leaf_frame_size().as_nat()),
+            start.0 + page_count * pt.constants@.arch.leaf_frame_size().as_nat() <= usize::MAX,
+            remappable(pt.pt_mem@.interpret::<G>(), pt.constants@),
+        ensures
+            res.inv(),
+            res.pt == pt,
+            res.start == start,
+            res.page_count == page_count,
+            forall|page: nat| page < page_count ==> res.logged(page),
+    {
+        let word_count = page_count / 64 + 1;
+        let mut bitmap = Vec::new();
+        let mut protected = Vec::new();
+        while bitmap.len() < word_count
+            invariant
+                bitmap.len() <= word_count,
+                protected.len() == bitmap.len(),
+                forall|i| 0 <= i < bitmap.len() ==> bitmap[i] == u64::MAX,
+            decreases word_count - bitmap.len(),
+        {
+            bitmap.push(u64::MAX);
+            protected.push(0);
+        }
+        let res = Self { pt, start, page_count, bitmap, protected };
+        assert(page_count <= word_count * 64) by (nonlinear_arith)
+            requires
+                word_count == page_count / 64 + 1,
+        ;
+        assert forall|page: nat| page < page_count implies res.logged(page) by {
+            lemma_bit_set_ones((page % 64) as u64);
+        }
+        res
+    }
+
+    /// Handle a write fault at `vaddr`.
+    ///
+    /// If `vaddr` is mapped by a mapping write-protected by the log, logs the pages of the
+    /// mapping, restores write permission and returns `Ok`. Otherwise the fault is not caused by
+    /// the log, and it is left to the caller. A mapping that covers no zone page is never
+    /// write-protected by the log, so it is never made writable here.
+    pub fn handle_write_fault<T: TLBExec>(&mut self, vaddr: VAddrExec, tlb: &mut T) -> (res:
+        PagingResult)
+        requires
+            old(self).inv(),
+            vaddr@.aligned(WORD_SIZE),
+        ensures
+            self.inv(),
+            self.same_zone(*old(self)),
+            forall|page: nat|
+                page < self.page_count && old(self).logged(page) ==> self.logged(page),
+            res is Ok ==> exists|vbase: VAddr, frame: Frame|
+                {
+                    &&& #[trigger] old(self).mappings().contains_pair(vbase, frame)
+                    &&& vaddr@.within(vbase, frame.size.as_nat())
+                    &&& !frame.attr.writable
+                    &&& old(self).protected(vbase, frame.size.as_nat())
+                    &&& self.mappings() == old(self).mappings().insert(
+                        vbase,
+                        with_writable(frame, true),
+                    )
+                },
+            res is Err ==> self.mappings() == old(self).mappings(),
+            res is Err ==> tlb.issued() == old(tlb).issued(),
+    {
+        match self.query(vaddr) {
+            Ok((vbase, frame)) => {
+                proof {
+                    self.lemma_mapping_leaf_aligned(vbase@, frame@);
+                }
+                if !frame.attr.writable && self.take_protected(vbase, frame.size.as_usize()) {
+                    self.restore(vbase, frame, tlb);
+                    Ok(())
+                } else {
+                    Err(())
+                }
+            },
+            Err(_) => Err(()),
+        }
+    }
+
+    /// Write-protect every writable mapping that overlaps the zone, then return and reset the
+    /// logged pages.
+    ///
+    /// Returns the base addresses of the logged pages. Every page written since the previous
+    /// harvest is returned (`DirtyLog::lemma_write_is_logged`).
+    pub fn harvest<T: TLBExec>(&mut self, tlb: &mut T) -> (res: Vec<VAddrExec>)
+        requires
+            old(self).inv(),
+        ensures
+            self.inv(),
+            self.same_zone(*old(self)),
+            self.mappings().dom() == old(self).mappings().dom(),
+            forall|page: nat|
+                page < old(self).page_count && old(self).logged(page) ==> exists|i: int|
+                    0 <= i < res.len() && #[trigger] res[i]@ == old(self).page_vaddr(page),
+            forall|page: nat| page < self.page_count ==> !self.logged(page),
+            old(tlb).issued().is_prefix_of(tlb.issued()),
+    {
+        let leaf = self.leaf_size_exec();
+        let end = self.start.0 + self.page_count * leaf;
+        let ghost old_log = *self;
+        proof {
+            self.lemma_zone_end_aligned();
+        }
+
+        // 1. Write-protect the zone, so every write from now on faults
+        let mut vaddr = self.start;
+        while vaddr.0 < end
+            invariant
+                self.inv(),
+                self.same_zone(old_log),
+                self.bitmap == old_log.bitmap,
+                self.mappings().dom() == old_log.mappings().dom(),
+                leaf == self.leaf_size(),
+                end == self.start.0 + self.page_count * self.leaf_size(),
+                (end as nat) % self.leaf_size() == 0,
+                vaddr@.aligned(self.leaf_size()),
+                vaddr.0 <= end,
+                self.read_only_below(vaddr.0 as nat),
+                old(tlb).issued().is_prefix_of(tlb.issued()),
+            decreases end - vaddr.0,
+        {
+            proof {
+                self.lemma_leaf_aligned_word_aligned(vaddr@);
+            }
+            match self.query(vaddr) {
+                Ok((vbase, frame)) => {
+                    let ghost covered = self.mappings();
+                    proof {
+                        self.lemma_mapping_leaf_aligned(vbase@, frame@);
+                    }
+                    if frame.attr.writable {
+                        self.remap(vbase, frame, false, tlb);
+                        self.mark_protected(vbase, frame.size.as_usize());
+                    }
+                    let ghost read_only = with_writable(frame@, false);
+                    assert(self.mappings().contains_pair(vbase@, read_only));
+                    // Continue after the mapping
+                    let size = frame.size.as_usize();
+                    let next = if size >= end - vbase.0 {
+                        end
+                    } else {
+                        vbase.0 + size
+                    };
+                    proof {
+                        // Zone pages up to `next` are covered by the read-only `(vbase, frame)`,
+                        // and mappings do not overlap
+                        assert forall|vbase2: VAddr, frame2: Frame, page: nat|
+                            #[trigger] self.mappings().contains_pair(vbase2, frame2)
+                                && frame2.attr.writable && page < self.page_count
+                                && self.page_vaddr(page).0 < next
+                            implies !#[trigger] self.page_vaddr(page).within(
+                            vbase2,
+                            frame2.size.as_nat(),
+                        ) by {
+                            if vbase2 != vbase@ {
+                                assert(covered.contains_pair(vbase2, frame2));
+                            }
+                            if self.page_vaddr(page).0 >= vaddr.0 && self.page_vaddr(page).within(
+                                vbase2,
+                                frame2.size.as_nat(),
+                            ) {
+                                self.lemma_mappings_disjoint(
+                                    vbase@,
+                                    read_only,
+                                    vbase2,
+                                    frame2,
+                                    self.page_vaddr(page),
+                                );
+                            }
+                        }
+                    }
+                    vaddr = VAddrExec(next);
+                },
+                Err(_) => {
+                    proof {
+                        // The page at `vaddr` is not mapped, and it is the only zone page
+                        // before the next one
+                        assert forall|vbase2: VAddr, frame2: Frame, page: nat|
+                            #[trigger] self.mappings().contains_pair(vbase2, frame2)
+                                && frame2.attr.writable && page < self.page_count
+                                && self.page_vaddr(page).0 < vaddr.0 + leaf
+                            implies !#[trigger] self.page_vaddr(page).within(
+                            vbase2,
+                            frame2.size.as_nat(),
+                        ) by {
+                            if self.page_vaddr(page).0 >= vaddr.0 {
+                                self.lemma_page_vaddr_aligned(page);
+                                assert(self.page_vaddr(page) == vaddr@) by (nonlinear_arith)
+                                    requires
+                                        leaf > 0,
+                                        self.page_vaddr(page).0 % leaf as nat == 0,
+                                        vaddr.0 % leaf == 0,
+                                        vaddr.0 <= self.page_vaddr(page).0 < vaddr.0 + leaf,
+                                ;
+                            }
+                        }
+                        assert(vaddr.0 + leaf <= end) by (nonlinear_arith)
+                            requires
+                                leaf > 0,
+                                vaddr.0 % leaf == 0,
+                                end % leaf == 0,
+                                vaddr.0 < end,
+                        ;
+                    }
+                    // Continue from the next page
+                    vaddr = VAddrExec(vaddr.0 + leaf);
+                },
+            }
+        }
+        let ghost pt = self.pt;
+
+        // 2. Collect the logged pages
+        let mut dirty = Vec::new();
+        let mut page = 0;
+        while page < self.page_count
+            invariant
+                self.inv(),
+                self.same_zone(old_log),
+                self.pt == pt,
+                self.bitmap == old_log.bitmap,
+                self.mappings().dom() == old_log.mappings().dom(),
+                self.read_only_below(end as nat),
+                leaf == self.leaf_size(),
+                end == self.start.0 + self.page_count * self.leaf_size(),
+                old(tlb).issued().is_prefix_of(tlb.issued()),
+                forall|p: nat|
+                    p < page && old_log.logged(p) ==> exists|i: int|
+                        0 <= i < dirty.len() && #[trigger] dirty[i]@ == old_log.page_vaddr(p),
+            decreases self.page_count - page,
+        {
+            if (self.bitmap[page / 64] >> (page % 64) as u64) & 1u64 == 1u64 {
+                proof {
+                    assert(page * leaf <= self.page_count * leaf) by (nonlinear_arith)
+                        requires
+                            page < self.page_count,
+                    ;
+                }
+                dirty.push(VAddrExec(self.start.0 + page * leaf));
+                assert(dirty[dirty.len() - 1]@ == old_log.page_vaddr(page as nat));
+            }
+            page = page + 1;
+        }
+
+        // 3. Reset the log. No writable mapping covers the zone, so no page needs a bit.
+        proof {
+            assert(self.read_only_below(end as nat));
+            assert forall|p: nat| p < self.page_count implies #[trigger] self.page_vaddr(p).0 < end
+                by {
+                assert(p * leaf < self.page_count * leaf) by (nonlinear_arith)
+                    requires
+                        p < self.page_count,
+                        leaf > 0,
+                ;
+            }
+        }
+        let mut i = 0;
+        while i < self.bitmap.len()
+            invariant
+                self.pt == pt,
+                self.start == old_log.start,
+                self.page_count == old_log.page_count,
+                self.bitmap.len() == old_log.bitmap.len(),
+                forall|p: nat| p < self.page_count ==> #[trigger] self.page_vaddr(p).0 < end,
+                self.read_only_below(end as nat),
+                forall|j| 0 <= j < i ==> self.bitmap[j] == 0,
+            decreases self.bitmap.len() - i,
+        {
+            self.bitmap.set(i, 0);
+            i = i + 1;
+        }
+        assert forall|p: nat| p < self.page_count implies !self.logged(p) by {
+            lemma_bit_set_zero((p % 64) as u64);
+        }
+        assert(self.writable_logged());
+        dirty
+    }
+
+    /// Leaf frame size.
+    fn leaf_size_exec(&self) -> (res: usize)
+        requires
+            self.pt.constants@.arch.valid(),
+        ensures
+            res == self.leaf_size(),
+            res > 0,
+    {
+        let arch = &self.pt.constants.arch;
+        arch.frame_size(arch.level_count() - 1).as_usize()
+    }
+
+    /// Query the mapping for `vaddr`.
+    fn query(&self, vaddr: VAddrExec) -> (res: PagingResult<(VAddrExec, FrameExec)>)
+        requires
+            self.inv(),
+            vaddr@.aligned(WORD_SIZE),
+        ensures
+            PageTableState::query(
+                self.pt_state(),
+                self.pt_state(),
+                vaddr@,
+                match res {
+                    Ok((vaddr, frame)) => Ok((vaddr@, frame@)),
+                    Err(()) => Err(()),
+                },
+            ),
+    {
+        proof {
+            self.pt@.model_consistent_with_hardware();
+            self.pt@.lemma_view_implies_invariants();
+            self.pt@@.query_refinement(vaddr@);
+        }
+        self.pt.query(vaddr)
+    }
+
+    /// First zone page not below `vbase`.
+    fn first_page_exec(&self, vbase: VAddrExec) -> (res: usize)
+        requires
+            self.pt.constants@.arch.valid(),
+        ensures
+            res == self.first_page(vbase@),
+    {
+        if vbase.0 > self.start.0 {
+            (vbase.0 - self.start.0) / self.leaf_size_exec()
+        } else {
+            0
+        }
+    }
+
+    /// First zone page of the mapping `(vbase, size)`, if the mapping covers it.
+    fn covered_first_page(&self, vbase: VAddrExec, size: usize) -> (res: Option<usize>)
+        requires
+            self.inv(),
+            vbase@.aligned(self.leaf_size()),
+        ensures
+            res is Some <==> self.covers_first_page(vbase@, size as nat),
+            res is Some ==> res->Some_0 == self.first_page(vbase@),
+    {
+        let leaf = self.leaf_size_exec();
+        let page = self.first_page_exec(vbase);
+        proof {
+            self.lemma_first_page_at_or_after(vbase@, page as nat);
+        }
+        if page >= self.page_count {
+            return None;
+        }
+        proof {
+            assert(page * leaf <= self.page_count * leaf) by (nonlinear_arith)
+                requires
+                    page < self.page_count,
+            ;
+        }
+        if self.start.0 + page * leaf - vbase.0 < size {
+            Some(page)
+        } else {
+            None
+        }
+    }
+
+    /// Mark the mapping `(vbase, size)` as write-protected by the log.
+    ///
+    /// The mapping is not marked if it covers no zone page.
+    fn mark_protected(&mut self, vbase: VAddrExec, size: usize)
+        requires
+            old(self).inv(),
+            vbase@.aligned(old(self).leaf_size()),
+        ensures
+            self.inv(),
+            self.same_zone(*old(self)),
+            self.pt == old(self).pt,
+            self.bitmap == old(self).bitmap,
+            old(self).covers_first_page(vbase@, size as nat) ==> self.protected(
+                vbase@,
+                size as nat,
+            ),
+    {
+        if let Some(page) = self.covered_first_page(vbase, size) {
+            let w = page / 64;
+            assert(w < self.protected.len()) by (nonlinear_arith)
+                requires
+                    w == page / 64,
+                    page < self.page_count <= self.protected.len() * 64,
+            ;
+            let b = (page % 64) as u64;
+            let word = self.protected[w];
+            self.protected.set(w, word | (1u64 << b));
+            proof {
+                lemma_bit_set_or(word, b, b);
+            }
+        }
+    }
+
+    /// Unmark the mapping `(vbase, size)`, and return whether it was write-protected by the log.
+    fn take_protected(&mut self, vbase: VAddrExec, size: usize) -> (res: bool)
+        requires
+            old(self).inv(),
+            vbase@.aligned(old(self).leaf_size()),
+        ensures
+            self.inv(),
+            self.same_zone(*old(self)),
+            self.pt == old(self).pt,
+            self.bitmap == old(self).bitmap,
+            res == old(self).protected(vbase@, size as nat),
+            !self.protected(vbase@, size as nat),
+    {
+        let page = match self.covered_first_page(vbase, size) {
+            Some(page) => page,
+            None => return false,
+        };
+        let w = page / 64;
+        assert(w < self.protected.len()) by (nonlinear_arith)
+            requires
+                w == page / 64,
+                page < self.page_count <= self.protected.len() * 64,
+        ;
+        let b = (page % 64) as u64;
+        let word = self.protected[w];
+        self.protected.set(w, word & !(1u64 << b));
+        proof {
+            lemma_bit_set_clear(word, b);
+        }
+        (word >> b) & 1u64 == 1u64
+    }
+
+    /// Log the zone pages of mapping `(vbase, frame)` and restore its write permission.
+    ///
+    /// Pages are logged before the mapping becomes writable, so a harvest never misses a write.
+    fn restore<T: TLBExec>(&mut self, vbase: VAddrExec, frame: FrameExec, tlb: &mut T)
+        requires
+            old(self).inv(),
+            old(self).mappings().contains_pair(vbase@, frame@),
+        ensures
+            self.inv(),
+            self.same_zone(*old(self)),
+            self.mappings() == old(self).mappings().insert(vbase@, with_writable(frame@, true)),
+            forall|page: nat|
+                page < self.page_count && old(self).logged(page) ==> self.logged(page),
+            tlb.issued() == old(tlb).issued().push(
+                TLBOp::VA(TLBTag(old(self).pt.constants.tag as nat), vbase@),
+            ),
+    {
+        proof {
+            self.lemma_mapping_leaf_aligned(vbase@, frame@);
+        }
+        self.log_mapping(vbase, frame.size.as_usize());
+        self.remap(vbase, frame, true, tlb);
+    }
+
+    /// Log every zone page within `(vbase, size)`.
+    fn log_mapping(&mut self, vbase: VAddrExec, size: usize)
+        requires
+            old(self).inv(),
+            vbase@.aligned(old(self).leaf_size()),
+        ensures
+            self.inv(),
+            self.same_zone(*old(self)),
+            self.pt == old(self).pt,
+            self.protected == old(self).protected,
+            forall|page: nat|
+                page < self.page_count && old(self).logged(page) ==> self.logged(page),
+            forall|page: nat|
+                page < self.page_count && #[trigger] self.page_vaddr(page).within(
+                    vbase@,
+                    size as nat,
+                ) ==> self.logged(page),
+    {
+        let leaf = self.leaf_size_exec();
+        let ghost old_log = *self;
+        // Start from the first zone page not below `vbase`
+        let mut page = self.first_page_exec(vbase);
+        proof {
+            self.lemma_first_page_at_or_after(vbase@, page as nat);
+        }
+        let mut done = false;
+        while !done && page < self.page_count
+            invariant
+                self.inv(),
+                self.same_zone(old_log),
+                self.pt == old_log.pt,
+                self.protected == old_log.protected,
+                leaf == self.leaf_size(),
+                page < self.page_count ==> vbase.0 <= self.page_vaddr(page as nat).0,
+                forall|p: nat| p < self.page_count && old_log.logged(p) ==> self.logged(p),
+                forall|p: nat|
+                    p < page && #[trigger] self.page_vaddr(p).within(vbase@, size as nat)
+                        ==> self.logged(p),
+                done ==> forall|p: nat|
+                    page <= p ==> !#[trigger] self.page_vaddr(p).within(vbase@, size as nat),
+            decreases self.page_count - page + if done {
+                0int
+            } else {
+                1int
+            },
+        {
+            proof {
+                assert(page * leaf <= self.page_count * leaf) by (nonlinear_arith)
+                    requires
+                        page < self.page_count,
+                ;
+            }
+            let pvaddr = self.start.0 + page * leaf;
+            if pvaddr - vbase.0 >= size {
+                // This and later pages are past the end of the mapping
+                proof {
+                    assert forall|p: nat| page <= p implies !#[trigger] self.page_vaddr(p).within(
+                        vbase@,
+                        size as nat,
+                    ) by {
+                        assert(page * leaf <= p * leaf) by (nonlinear_arith)
+                            requires
+                                page <= p,
+                        ;
+                    }
+                }
+                done = true;
+            } else {
+                let w = page / 64;
+                let b = (page % 64) as u64;
+                let word = self.bitmap[w];
+                self.bitmap.set(w, word | (1u64 << b));
+                proof {
+                    assert forall|p: nat|
+                        p < self.page_count && old_log.logged(p) implies self.logged(p) by {
+                        if p / 64 == w {
+                            lemma_bit_set_or(word, b, (p % 64) as u64);
+                        }
+                    }
+                    lemma_bit_set_or(word, b, b);
+                    assert((page + 1) * leaf == page * leaf + leaf) by (nonlinear_arith);
+                }
+                page = page + 1;
+            }
+        }
+    }
+
+    /// Change the write permission of mapping `(vbase, frame)` in place.
+    ///
+    /// The mapping stays valid, and the old permission is invalidated in the TLB.
+    fn remap<T: TLBExec>(&mut self, vbase: VAddrExec, frame: FrameExec, writable: bool, tlb: &mut T)
+        requires
+            old(self).inv(),
+            old(self).mappings().contains_pair(vbase@, frame@),
+            writable ==> forall|page: nat|
+                page < old(self).page_count && #[trigger] old(self).page_vaddr(page).within(
+                    vbase@,
+                    frame.size.as_nat(),
+                ) ==> old(self).logged(page),
+        ensures
+            self.inv(),
+            self.same_zone(*old(self)),
+            self.bitmap == old(self).bitmap,
+            self.protected == old(self).protected,
+            self.mappings() == old(self).mappings().insert(
+                vbase@,
+                with_writable(frame@, writable),
+            ),
+            tlb.issued() == old(tlb).issued().push(
+                TLBOp::VA(TLBTag(old(self).pt.constants.tag as nat), vbase@),
+            ),
+    {
+        let ghost mappings = self.mappings();
+        proof {
+            lemma_same_extent_keeps_remappable(
+                mappings,
+                self.pt.constants@,
+                vbase@,
+                with_writable(mappings[vbase@], writable),
+            );
+        }
+        let attr = MemAttr::new(
+            frame.attr.readable,
+            writable,
+            frame.attr.executable,
+            frame.attr.user_accessible,
+            frame.attr.mem_type,
+            frame.attr.shareability,
+            frame.attr.global,
+            frame.attr.sw_bits,
+        );
+        assert(Frame { attr, ..frame@ } == with_writable(frame@, writable));
+        self.pt.protect(vbase, attr, tlb);
+        proof {
+            assert(self.mappings() =~= mappings.insert(vbase@, with_writable(frame@, writable)));
+            // Only `(vbase, frame)` changes, and it is writable only if its pages are logged
+            assert(self.writable_logged());
+        }
+    }
+
+    /// Lemma. Every mapping and its size are aligned to the leaf frame size.
+    proof fn lemma_mapping_leaf_aligned(self, vbase: VAddr, frame: Frame)
+        requires
+            self.pt.constants@.arch.valid(),
+            remappable(self.mappings(), self.pt.constants@),
+            self.mappings().contains_pair(vbase, frame),
+        ensures
+            vbase.aligned(self.leaf_size()),
+            frame.size.as_nat() % self.leaf_size() == 0,
+    {
+        let arch = self.pt.constants@.arch;
+        let s = PageTableState::new(self.mappings().remove(vbase), self.pt.constants@);
+        assert(s.map_pre(vbase, frame));
+        let level = arch.level_of_frame_size(frame.size);
+        arch.lemma_frame_size_aligned((arch.level_count() - 1) as nat, level);
+        let size = frame.size.as_nat();
+        let leaf = self.leaf_size();
+        // `size` is a multiple of `leaf`, so `vbase` aligned to `size` is aligned to `leaf`
+        vstd::arithmetic::div_mod::lemma_fundamental_div_mod(size as int, leaf as int);
+        vstd::arithmetic::div_mod::lemma_mod_mod(
+            vbase.0 as int,
+            leaf as int,
+            size as int / leaf as int,
+        );
+    }
+
+    /// Lemma. Mappings that translate the same address are the same mapping.
+    proof fn lemma_mappings_disjoint(
+        self,
+        vbase: VAddr,
+        frame: Frame,
+        vbase2: VAddr,
+        frame2: Frame,
+        vaddr: VAddr,
+    )
+        requires
+            remappable(self.mappings(), self.pt.constants@),
+            self.mappings().contains_pair(vbase, frame),
+            self.mappings().contains_pair(vbase2, frame2),
+            vaddr.within(vbase, frame.size.as_nat()),
+            vaddr.within(vbase2, frame2.size.as_nat()),
+        ensures
+            vbase == vbase2,
+            frame == frame2,
+    {
+        if vbase != vbase2 {
+            let s = PageTableState::new(self.mappings().remove(vbase2), self.pt.constants@);
+            assert(s.mappings.contains_key(vbase));
+            assert(VAddr::overlap(vbase, frame.size.as_nat(), vbase2, frame2.size.as_nat()));
+        }
+    }
+
+    /// Lemma. Zone pages are aligned to the leaf frame size.
+    proof fn lemma_page_vaddr_aligned(self, page: nat)
+        requires
+            self.pt.constants@.arch.valid(),
+            self.start@.aligned(self.leaf_size()),
+        ensures
+            self.page_vaddr(page).aligned(self.leaf_size()),
+    {
+        let leaf = self.leaf_size();
+        vstd::arithmetic::div_mod::lemma_mod_multiples_basic(page as int, leaf as int);
+        vstd::arithmetic::div_mod::lemma_add_mod_noop(
+            self.start@.0 as int,
+            (page * leaf) as int,
+            leaf as int,
+        );
+    }
+
+    /// Lemma. The end of the zone is aligned to the leaf frame size.
+    proof fn lemma_zone_end_aligned(self)
+        requires
+            self.pt.constants@.arch.valid(),
+            self.start@.aligned(self.leaf_size()),
+        ensures
+            (self.start.0 + self.page_count * self.leaf_size()) % self.leaf_size() == 0,
+    {
+        self.lemma_page_vaddr_aligned(self.page_count as nat);
+    }
+
+    /// Lemma. `page`, computed from `vbase`, is the first zone page not below `vbase`.
+    proof fn lemma_first_page_at_or_after(self, vbase: VAddr, page: nat)
+        requires
+            self.pt.constants@.arch.valid(),
+            self.start@.aligned(self.leaf_size()),
+            vbase.aligned(self.leaf_size()),
+            page == self.first_page(vbase),
+        ensures
+            vbase.0 <= self.page_vaddr(page).0,
+            forall|p: nat| p < page ==> #[trigger] self.page_vaddr(p).0 < vbase.0,
+    {
+        let leaf = self.leaf_size();
+        if vbase.0 > self.start@.0 {
+            let diff = (vbase.0 - self.start@.0) as nat;
+            vstd::arithmetic::div_mod::lemma_sub_mod_noop(
+                vbase.0 as int,
+                self.start@.0 as int,
+                leaf as int,
+            );
+            vstd::arithmetic::div_mod::lemma_fundamental_div_mod(diff as int, leaf as int);
+            assert(page * leaf == diff) by (nonlinear_arith)
+                requires
+                    diff % leaf == 0,
+                    diff == leaf * (diff / leaf) + diff % leaf,
+                    page == diff / leaf,
+            ;
+            assert forall|p: nat| p < page implies #[trigger] self.page_vaddr(p).0 < vbase.0 by {
+                assert(p * leaf < page * leaf) by (nonlinear_arith)
+                    requires
+                        p < page,
+                        leaf > 0,
+                ;
+            }
+        }
+    }
+
+    /// Lemma. A leaf-aligned address is word-aligned.
+    proof fn lemma_leaf_aligned_word_aligned(self, vaddr: VAddr)
+        requires
+            self.pt.constants@.arch.valid(),
+            vaddr.aligned(self.leaf_size()),
+        ensures
+            vaddr.aligned(WORD_SIZE),
+    {
+        let leaf = self.leaf_size();
+        // Every frame size is a multiple of the word size
+        assert(leaf % WORD_SIZE == 0);
+        vstd::arithmetic::div_mod::lemma_mod_mod(vaddr.0 as int, WORD_SIZE as int, leaf as int / 8);
+    }
+
+    /// Lemma. A mapping that translates an address of a zone page covers the base of the page.
+    proof fn lemma_page_within_mapping(self, vbase: VAddr, frame: Frame, page: nat, vaddr: VAddr)
+        requires
+            self.inv(),
+            self.mappings().contains_pair(vbase, frame),
+            vaddr.within(vbase, frame.size.as_nat()),
+            vaddr.within(self.page_vaddr(page), self.leaf_size()),
+        ensures
+            self.page_vaddr(page).within(vbase, frame.size.as_nat()),
+    {
+        self.lemma_mapping_leaf_aligned(vbase, frame);
+        self.lemma_page_vaddr_aligned(page);
+        let leaf = self.leaf_size();
+        let pbase = self.page_vaddr(page).0;
+        // Both bases are multiples of `leaf`, and `vbase < pbase + leaf`
+        assert(vbase.0 <= pbase) by (nonlinear_arith)
+            requires
+                leaf > 0,
+                vbase.0 % leaf == 0,
+                pbase % leaf == 0,
+                vbase.0 < pbase + leaf,
+        ;
+    }
+
+    /// Lemma. A successful hardware write to a zone page means the page is logged.
+    ///
+    /// The write goes through a writable mapping, either cached in the TLB or read from the page
+    /// table. The TLB only caches mappings of the page table (`tlb_is_submap_of_pt`), which the
+    /// log keeps by invalidating every permission it changes.
+    pub proof fn lemma_write_is_logged(
+        self,
+        s1: LowLevelState<G>,
+        s2: LowLevelState<G>,
+        vaddr: VAddr,
+        size: AccessSize,
+        value: u64,
+        privilege: Privilege,
+        mmio: Option<MmioAccess>,
+        page: nat,
+    )
+        requires
+            self.inv(),
+            s1.invariants(),
+            s1.pt == self.pt.pt_mem@,
+            LowLevelState::write(s1, s2, vaddr, size, value, privilege, MemoryResult::Ok(()), mmio),
+            page < self.page_count,
+            vaddr.within(self.page_vaddr(page), self.leaf_size()),
+        ensures
+            self.logged(page),
+    {
+        // Setting the Access and Dirty flags keeps the mappings
+        self.pt@.lemma_invariants_implies_walkable();
+        s1.pt.lemma_set_flags_keeps_interpret::<G>(vaddr, true);
+        let (h1, h2) = (s1.hw_state(), s2.hw_state());
+        let res = MemoryResult::Ok(());
+        let mid = if HardwareState::write_access(h1, h2, vaddr, size, value, privilege, res, mmio) {
+            h1
+        } else {
+            h1.set_flags(vaddr, true)
+        };
+        assert(HardwareState::write_access(mid, h2, vaddr, size, value, privilege, res, mmio));
+        // The write goes through a writable mapping of the page table
+        let (vbase, frame) = if mid.tlb_has_mapping_for(vaddr) {
+            mid.tlb_mapping_for(vaddr)
+        } else {
+            mid.pt_mapping_for(vaddr)
+        };
+        assert(self.mappings().contains_pair(vbase, frame));
+        assert(frame.attr.writable);
+        self.lemma_page_within_mapping(vbase, frame, page, vaddr);
+    }
+}
+
+/// Lemma. Replacing a mapping with a frame of the same base and size, e.g. changing its
+/// attributes in place, keeps every mapping remappable.
+pub proof fn lemma_same_extent_keeps_remappable(
+    mappings: Map<VAddr, Frame>,
+    constants: PTConstants,
+    vbase: VAddr,
+    new_frame: Frame,
+)
+    requires
+        remappable(mappings, constants),
+        mappings.contains_key(vbase),
+        new_frame.base == mappings[vbase].base,
+        new_frame.size == mappings[vbase].size,
+    ensures
+        remappable(mappings.insert(vbase, new_frame), constants),
+{
+    let frame = mappings[vbase];
+    let mappings2 = mappings.insert(vbase, new_frame);
+    assert forall|vbase2: VAddr, frame2: Frame| #[trigger]
+        mappings2.contains_pair(vbase2, frame2) implies {
+        let s = PageTableState::new(mappings2.remove(vbase2), constants);
+        &&& s.map_pre(vbase2, frame2)
+        &&& !s.overlaps_vmem(vbase2, frame2)
+    } by {
+        let s = PageTableState::new(mappings2.remove(vbase2), constants);
+        if vbase2 == vbase {
+            // Overlaps only depend on the base and size of the frame
+            assert(mappings2.remove(vbase2) =~= mappings.remove(vbase2));
+            assert(mappings.contains_pair(vbase, frame));
+        } else {
+            let s0 = PageTableState::new(mappings.remove(vbase2), constants);
+            assert(mappings.contains_pair(vbase2, frame2));
+            assert(mappings2.remove(vbase2) =~= mappings.remove(vbase2).insert(vbase, new_frame));
+            // `new_frame` has the same base and size as `frame`, so overlaps are unchanged
+            if s.overlaps_pmem(frame2) {
+                let frame3 = choose|frame3: Frame|
+                    {
+                        &&& #[trigger] s.mappings.contains_value(frame3)
+                        &&& PAddr::overlap(
+                            frame3.base,
+                            frame3.size.as_nat(),
+                            frame2.base,
+                            frame2.size.as_nat(),
+                        )
+                    };
+                let vbase3 = choose|vbase3: VAddr|
+                    s.mappings.contains_key(vbase3) && s.mappings[vbase3] == frame3;
+                if vbase3 == vbase {
+                    assert(s0.mappings.contains_value(frame));
+                } else {
+                    assert(s0.mappings.contains_key(vbase3));
+                    assert(s0.mappings.contains_value(frame3));
+                }
+            }
+            if s.overlaps_vmem(vbase2, frame2) {
+                let vbase3 = choose|vbase3: VAddr|
+                    {
+                        &&& #[trigger] s.mappings.contains_key(vbase3)
+                        &&& VAddr::overlap(
+                            vbase3,
+                            s.mappings[vbase3].size.as_nat(),
+                            vbase2,
+                            frame2.size.as_nat(),
+                        )
+                    };
+                assert(s0.mappings.contains_key(vbase3));
+            }
+        }
+    }
+}
+
+/// Lemma. Every bit of `u64::MAX` is set.
+proof fn lemma_bit_set_ones(bit: u64)
+    by (bit_vector)
+    requires
+        bit < 64,
+    ensures
+        bit_set(u64::MAX, bit),
+{
+}
+
+/// Lemma. No bit of `0` is set.
+proof fn lemma_bit_set_zero(bit: u64)
+    by (bit_vector)
+    requires
+        bit < 64,
+    ensures
+        !bit_set(0u64, bit),
+{
+}
+
+/// Lemma. Clearing bit `bit` clears it.
+proof fn lemma_bit_set_clear(word: u64, bit: u64)
+    by (bit_vector)
+    requires
+        bit < 64,
+    ensures
+        !bit_set(word & !(1u64 << bit), bit),
+{
+}
+
+/// Lemma. Setting bit `bit` sets it and keeps the other bits.
+proof fn lemma_bit_set_or(word: u64, bit: u64, bit2: u64)
+    by (bit_vector)
+    requires
+        bit < 64,
+        bit2 < 64,
+    ensures
+        bit_set(word | (1u64 << bit), bit2) == (bit == bit2 || bit_set(word, bit2)),
+{
+}
+
+} // verus!
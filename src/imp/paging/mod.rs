@@ -1,4 +1,7 @@
 //! Concrete page table implementation and refinement proof.
+mod bbm;
+pub mod dirty_log;
+pub mod ownership;
 mod pt;
 pub mod pt_exec;
 mod refinement;
@@ -0,0 +1,1142 @@
+//! Page ownership between two zones (pKVM-style).
+//!
+//! Every page mapped by a zone records its ownership state in the software bits of its entry
+//! (`SwBits::state`). Pages move between the page tables of the host and a guest only through
+//! the following operations, whose state changes are legal transitions (`legal_transition`):
+//!
+//! - `share` lends an exclusively owned page to the other zone. The owner records `SharedOwned`,
+//!   and the borrower maps the page as `SharedBorrowed`.
+//! - `unshare` ends the loan. The borrower unmaps the page, then the owner records `Owned`.
+//! - `donate` gives an exclusively owned page to the other zone. The donor unmaps the page, then
+//!   the recipient maps it as `Donated`. If the recipient cannot map it, the donor maps it
+//!   again, unless page table memory runs out.
+//!
+//! A physical page mapped by both zones is recorded as shared on both sides
+//! (`Ownership::shared_overlaps`), so it is never writable from both zones unless both sides
+//! record it as shared (`Ownership::lemma_writable_by_both_implies_shared`).
+use vstd::prelude::*;
+
+use super::{
+    dirty_log::{lemma_same_extent_keeps_remappable, remappable},
+    pt_exec::PageTableExec,
+};
+use crate::{
+    common::{
+        addr::{PAddr, VAddr, VAddrExec, WORD_SIZE},
+        frame::{Frame, FrameExec, MemAttr, PageState, SwBits},
+        pte::{ExecPTE, GhostPTE},
+        PagingResult,
+    },
+    spec::{
+        memory::{PageTableMemExec, TLBExec},
+        page_table::{PTConstants, PageTableState},
+    },
+};
+
+verus! {
+
+/// One of the two zones that exchange pages.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// The host (root zone).
+    Host,
+    /// The guest.
+    Guest,
+}
+
+impl Side {
+    /// The other zone.
+    pub open spec fn spec_other(self) -> Self {
+        match self {
+            Side::Host => Side::Guest,
+            Side::Guest => Side::Host,
+        }
+    }
+
+    /// (EXEC-MODE) The other zone.
+    pub fn other(self) -> (res: Self)
+        ensures
+            res == self.spec_other(),
+    {
+        match self {
+            Side::Host => Side::Guest,
+            Side::Guest => Side::Host,
+        }
+    }
+}
+
+/// If `state` means the page is owned by this zone alone.
+pub open spec fn exclusive(state: PageState) -> bool {
+    state is Owned || state is Donated
+}
+
+/// If two zones record a page they both map as shared, one as owner and one as borrower.
+pub open spec fn shared_pair(state1: PageState, state2: PageState) -> bool {
+    ||| state1 is SharedOwned && state2 is SharedBorrowed
+    ||| state1 is SharedBorrowed && state2 is SharedOwned
+}
+
+/// If a zone may change the state of the page at some address from `state1` to `state2`, where
+/// `None` means the address is not mapped.
+pub open spec fn legal_transition(state1: Option<PageState>, state2: Option<PageState>) -> bool {
+    match (state1, state2) {
+        // The owner shares a page, or ends a loan
+        (Some(s1), Some(s2)) => {
+            ||| exclusive(s1) && s2 is SharedOwned
+            ||| s1 is SharedOwned && s2 is Owned
+        },
+        // The borrower maps a shared page, or the recipient maps a donated page
+        (None, Some(s2)) => s2 is SharedBorrowed || s2 is Donated,
+        // The borrower unmaps a shared page, or the donor unmaps a donated page
+        (Some(s1), None) => s1 is SharedBorrowed || exclusive(s1),
+        (None, None) => false,
+    }
+}
+
+/// If mappings of `frame1` and `frame2` in different zones may coexist.
+///
+/// Frames that overlap in physical memory must be recorded as shared on both sides.
+pub open spec fn compatible(frame1: Frame, frame2: Frame) -> bool {
+    PAddr::overlap(frame1.base, frame1.size.as_nat(), frame2.base, frame2.size.as_nat())
+        ==> shared_pair(frame1.attr.sw_bits.state, frame2.attr.sw_bits.state)
+}
+
+/// `frame` with ownership state `state`.
+pub open spec fn with_state(frame: Frame, state: PageState) -> Frame {
+    Frame {
+        attr: MemAttr { sw_bits: SwBits { state, ..frame.attr.sw_bits }, ..frame.attr },
+        ..frame
+    }
+}
+
+/// Page tables of two zones, with the ownership of the pages they map.
+pub struct Ownership<M: PageTableMemExec, G: GhostPTE, E: ExecPTE<G>> {
+    /// Page table of the host.
+    pub host: PageTableExec<M, G, E>,
+    /// Page table of the guest.
+    pub guest: PageTableExec<M, G, E>,
+}
+
+impl<M, G, E> Ownership<M, G, E> where M: PageTableMemExec, G: GhostPTE, E: ExecPTE<G> {
+    /// Page table of `side`.
+    pub open spec fn pt(self, side: Side) -> PageTableExec<M, G, E> {
+        match side {
+            Side::Host => self.host,
+            Side::Guest => self.guest,
+        }
+    }
+
+    /// Mappings of `side`.
+    pub open spec fn mappings(self, side: Side) -> Map<VAddr, Frame> {
+        self.pt(side).pt_mem@.interpret::<G>()
+    }
+
+    /// Page table constants, shared by both zones.
+    pub open spec fn constants(self) -> PTConstants {
+        self.host.constants@
+    }
+
+    /// Abstract page table state of `side`.
+    pub open spec fn pt_state(self, side: Side) -> PageTableState {
+        PageTableState::new(self.mappings(side), self.pt(side).constants@)
+    }
+
+    /// Ownership state of the page mapped at `vbase` by `side`, or `None` if `vbase` is not
+    /// mapped.
+    pub open spec fn state(self, side: Side, vbase: VAddr) -> Option<PageState> {
+        if self.mappings(side).contains_key(vbase) {
+            Some(self.mappings(side)[vbase].attr.sw_bits.state)
+        } else {
+            None
+        }
+    }
+
+    /// If every physical page mapped by both zones is recorded as shared on both sides.
+    pub open spec fn shared_overlaps(self) -> bool {
+        forall|vbase1: VAddr, frame1: Frame, vbase2: VAddr, frame2: Frame|
+            #[trigger] self.mappings(Side::Host).contains_pair(vbase1, frame1)
+                && #[trigger] self.mappings(Side::Guest).contains_pair(vbase2, frame2)
+                ==> compatible(frame1, frame2)
+    }
+
+    /// If `frame` may be mapped by `side` next to the mappings of the other zone.
+    pub open spec fn compatible_with_other(self, side: Side, frame: Frame) -> bool {
+        forall|vbase2: VAddr, frame2: Frame| #[trigger]
+            self.mappings(side.spec_other()).contains_pair(vbase2, frame2) ==> compatible(
+                frame,
+                frame2,
+            )
+    }
+
+    /// Invariants.
+    pub open spec fn inv(self) -> bool {
+        &&& self.host@.invariants()
+        &&& self.guest@.invariants()
+        &&& self.constants().arch.valid()
+        &&& self.guest.constants@ == self.constants()
+        &&& remappable(self.mappings(Side::Host), self.constants())
+        &&& remappable(self.mappings(Side::Guest), self.constants())
+        &&& self.shared_overlaps()
+    }
+
+    /// If every state change from `old` to `self` is a legal transition.
+    pub open spec fn legal_from(self, old: Self) -> bool {
+        forall|side: Side, vbase: VAddr| #[trigger]
+            self.state(side, vbase) != old.state(side, vbase) ==> legal_transition(
+                old.state(side, vbase),
+                self.state(side, vbase),
+            )
+    }
+
+    /// If `self` and `other` manage the same zones.
+    pub open spec fn same_zones(self, other: Self) -> bool {
+        &&& self.host.constants == other.host.constants
+        &&& self.guest.constants == other.guest.constants
+    }
+
+    /// Start tracking the ownership of the pages mapped by `host` and `guest`.
+    ///
+    /// Pages already mapped must be recorded consistently.
+    pub fn new(host: PageTableExec<M, G, E>, guest: PageTableExec<M, G, E>) -> (res: Self)
+        requires
+            Self { host, guest }.inv(),
+        ensures
+            res.inv(),
+            res.host == host,
+            res.guest == guest,
+    {
+        Self { host, guest }
+    }
+
+    /// Share the page mapped at `vbase` by `owner` with the other zone, which maps it at
+    /// `borrower_vbase`.
+    ///
+    /// Fails if `vbase` is not the base of a mapping of a page exclusively owned by `owner`, or if
+    /// the borrower cannot map the page at `borrower_vbase`. Nothing changes on failure.
+    pub fn share<T: TLBExec>(
+        &mut self,
+        owner: Side,
+        vbase: VAddrExec,
+        borrower_vbase: VAddrExec,
+        tlb: &mut T,
+    ) -> (res: PagingResult)
+        requires
+            old(self).inv(),
+            vbase@.aligned(WORD_SIZE),
+        ensures
+            self.inv(),
+            self.same_zones(*old(self)),
+            self.legal_from(*old(self)),
+            res is Ok ==> {
+                let frame = old(self).mappings(owner)[vbase@];
+                &&& old(self).mappings(owner).contains_key(vbase@)
+                &&& self.mappings(owner) == old(self).mappings(owner).insert(
+                    vbase@,
+                    with_state(frame, PageState::SharedOwned),
+                )
+                &&& self.mappings(owner.spec_other()) == old(self).mappings(
+                    owner.spec_other(),
+                ).insert(borrower_vbase@, with_state(frame, PageState::SharedBorrowed))
+            },
+            res is Err ==> self.mappings(owner) == old(self).mappings(owner),
+            res is Err ==> self.mappings(owner.spec_other()) == old(self).mappings(
+                owner.spec_other(),
+            ),
+            old(tlb).issued().is_prefix_of(tlb.issued()),
+    {
+        let ghost old_self = *self;
+        let borrower = owner.other();
+        let (vb, frame) = match self.query(owner, vbase) {
+            Ok(mapping) => mapping,
+            Err(_) => return Err(()),
+        };
+        let state = frame.attr.sw_bits.state;
+        let exclusive = match state {
+            PageState::Owned | PageState::Donated => true,
+            _ => false,
+        };
+        if vb.0 != vbase.0 || !exclusive || !borrower_vbase.aligned(frame.size.as_usize()) {
+            return Err(());
+        }
+        assert(vb@ == vbase@);
+        proof {
+            // No page of the borrower overlaps the exclusively owned frame
+            self.lemma_exclusive_not_mapped_by_other(owner, vbase@, frame@);
+            self.lemma_remappable_map_pre(owner, vbase@, frame@);
+            assert(borrower.spec_other() == owner);
+            self.lemma_exclusive_map_pre(
+                borrower,
+                borrower_vbase@,
+                with_state(frame@, PageState::SharedBorrowed),
+                vbase@,
+                frame@,
+            );
+        }
+
+        // 1. Record the page as shared before the borrower may access it
+        self.set_state(owner, vbase, frame, PageState::SharedOwned, tlb);
+        let ghost shared = *self;
+
+        // 2. Map the page for the borrower
+        let borrowed = with_state_exec(&frame, PageState::SharedBorrowed);
+        proof {
+            // Only the shared frame of the owner overlaps the borrowed frame
+            assert forall|vbase2: VAddr, frame2: Frame| #[trigger]
+                self.mappings(owner).contains_pair(vbase2, frame2) implies compatible(
+                borrowed@,
+                frame2,
+            ) by {
+                if vbase2 != vbase@ {
+                    assert(old_self.mappings(owner).contains_pair(vbase2, frame2));
+                    lemma_remappable_pmem_disjoint(
+                        old_self.mappings(owner),
+                        old_self.constants(),
+                        vbase@,
+                        frame@,
+                        vbase2,
+                        frame2,
+                    );
+                }
+            }
+            assert(borrower.spec_other() == owner);
+            assert(self.mappings(borrower) == old_self.mappings(borrower));
+        }
+        if self.map(borrower, borrower_vbase, borrowed).is_err() {
+            // 3. Roll back to the previous state
+            let shared_frame = with_state_exec(&frame, PageState::SharedOwned);
+            proof {
+                assert(self.mappings(owner).contains_pair(vbase@, shared_frame@));
+                assert(with_state(shared_frame@, state) == frame@);
+                assert(old_self.mappings(owner).contains_key(vbase@));
+                self.lemma_exclusive_compatible(owner, frame@, old_self);
+            }
+            self.set_state(owner, vbase, shared_frame, state, tlb);
+            proof {
+                // The failed map left the borrower untouched, and the owner is restored
+                assert(self.mappings(borrower) == old_self.mappings(borrower));
+                assert(self.mappings(owner) =~= old_self.mappings(owner));
+                assert(self.legal_from(old_self)) by {
+                    self.lemma_same_mappings_legal(old_self);
+                }
+            }
+            return Err(());
+        }
+        proof {
+            assert(self.legal_from(old_self)) by {
+                assert forall|side: Side, vaddr: VAddr| #[trigger]
+                    self.state(side, vaddr) != old_self.state(side, vaddr) implies legal_transition(
+                    old_self.state(side, vaddr),
+                    self.state(side, vaddr),
+                ) by {
+                    if side == borrower && vaddr == borrower_vbase@ {
+                        // The borrower did not map `borrower_vbase` before
+                        assert(!shared.mappings(borrower).contains_key(borrower_vbase@));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// End the loan of the page mapped at `vbase` by `owner` and at `borrower_vbase` by the other
+    /// zone.
+    ///
+    /// Fails if the two mappings are not the two sides of the same shared page, or if the
+    /// borrower pinned its mapping. Nothing changes on failure.
+    pub fn unshare<T: TLBExec>(
+        &mut self,
+        owner: Side,
+        vbase: VAddrExec,
+        borrower_vbase: VAddrExec,
+        tlb: &mut T,
+    ) -> (res: PagingResult)
+        requires
+            old(self).inv(),
+            vbase@.aligned(WORD_SIZE),
+            borrower_vbase@.aligned(WORD_SIZE),
+        ensures
+            self.inv(),
+            self.same_zones(*old(self)),
+            self.legal_from(*old(self)),
+            res is Ok ==> {
+                let frame = old(self).mappings(owner)[vbase@];
+                &&& old(self).mappings(owner).contains_key(vbase@)
+                &&& self.mappings(owner) == old(self).mappings(owner).insert(
+                    vbase@,
+                    with_state(frame, PageState::Owned),
+                )
+                &&& self.mappings(owner.spec_other()) == old(self).mappings(
+                    owner.spec_other(),
+                ).remove(borrower_vbase@)
+            },
+            res is Err ==> self.mappings(owner) == old(self).mappings(owner),
+            res is Err ==> self.mappings(owner.spec_other()) == old(self).mappings(
+                owner.spec_other(),
+            ),
+            old(tlb).issued().is_prefix_of(tlb.issued()),
+    {
+        let ghost old_self = *self;
+        let borrower = owner.other();
+        let (vb, frame) = match self.query(owner, vbase) {
+            Ok(mapping) => mapping,
+            Err(_) => return Err(()),
+        };
+        let (vb2, frame2) = match self.query(borrower, borrower_vbase) {
+            Ok(mapping) => mapping,
+            Err(_) => return Err(()),
+        };
+        let shared_owned = match frame.attr.sw_bits.state {
+            PageState::SharedOwned => true,
+            _ => false,
+        };
+        let shared_borrowed = match frame2.attr.sw_bits.state {
+            PageState::SharedBorrowed => true,
+            _ => false,
+        };
+        if vb.0 != vbase.0 || vb2.0 != borrower_vbase.0 || !shared_owned || !shared_borrowed
+            || frame2.attr.sw_bits.pinned || frame2.base.0 != frame.base.0
+            || frame2.size.as_usize() != frame.size.as_usize() {
+            return Err(());
+        }
+        assert(vb@ == vbase@ && vb2@ == borrower_vbase@);
+        assert(frame2.size == frame.size);
+
+        // 1. Revoke the access of the borrower
+        self.unmap(borrower, borrower_vbase, tlb);
+        proof {
+            // The borrower mapped the page only once, so no page of the borrower overlaps it now
+            assert forall|vbase2: VAddr, frame3: Frame| #[trigger]
+                self.mappings(borrower).contains_pair(vbase2, frame3) implies compatible(
+                with_state(frame@, PageState::Owned),
+                frame3,
+            ) by {
+                assert(old_self.mappings(borrower).contains_pair(vbase2, frame3));
+                lemma_remappable_pmem_disjoint(
+                    old_self.mappings(borrower),
+                    old_self.constants(),
+                    borrower_vbase@,
+                    frame2@,
+                    vbase2,
+                    frame3,
+                );
+            }
+            assert(borrower.spec_other() == owner);
+        }
+
+        // 2. The owner owns the page alone again
+        self.set_state(owner, vbase, frame, PageState::Owned, tlb);
+        proof {
+            assert(self.legal_from(old_self));
+        }
+        Ok(())
+    }
+
+    /// Donate the page mapped at `vbase` by `donor` to the other zone, which maps it at
+    /// `recipient_vbase`.
+    ///
+    /// Fails if `vbase` is not the base of a mapping of a page exclusively owned by `donor`, if the
+    /// donor pinned the mapping, or if the recipient cannot map the page at `recipient_vbase`.
+    /// Nothing changes on failure, except that the donor may lose the page if page table memory
+    /// runs out while it maps the page again.
+    pub fn donate<T: TLBExec>(
+        &mut self,
+        donor: Side,
+        vbase: VAddrExec,
+        recipient_vbase: VAddrExec,
+        tlb: &mut T,
+    ) -> (res: PagingResult)
+        requires
+            old(self).inv(),
+            vbase@.aligned(WORD_SIZE),
+        ensures
+            self.inv(),
+            self.same_zones(*old(self)),
+            self.legal_from(*old(self)),
+            res is Ok ==> {
+                let frame = old(self).mappings(donor)[vbase@];
+                &&& old(self).mappings(donor).contains_key(vbase@)
+                &&& self.mappings(donor) == old(self).mappings(donor).remove(vbase@)
+                &&& self.mappings(donor.spec_other()) == old(self).mappings(
+                    donor.spec_other(),
+                ).insert(recipient_vbase@, with_state(frame, PageState::Donated))
+            },
+            // Running out of page table memory while rolling back leaves the page unmapped
+            res is Err ==> self.mappings(donor) == old(self).mappings(donor) || self.mappings(
+                donor,
+            ) == old(self).mappings(donor).remove(vbase@),
+            res is Err ==> self.mappings(donor.spec_other()) == old(self).mappings(
+                donor.spec_other(),
+            ),
+            old(tlb).issued().is_prefix_of(tlb.issued()),
+    {
+        let ghost old_self = *self;
+        let recipient = donor.other();
+        let (vb, frame) = match self.query(donor, vbase) {
+            Ok(mapping) => mapping,
+            Err(_) => return Err(()),
+        };
+        let exclusive = match frame.attr.sw_bits.state {
+            PageState::Owned | PageState::Donated => true,
+            _ => false,
+        };
+        if vb.0 != vbase.0 || !exclusive || frame.attr.sw_bits.pinned
+            || !recipient_vbase.aligned(frame.size.as_usize()) {
+            return Err(());
+        }
+        assert(vb@ == vbase@);
+        proof {
+            self.lemma_exclusive_not_mapped_by_other(donor, vbase@, frame@);
+            self.lemma_remappable_map_pre(donor, vbase@, frame@);
+            assert(recipient.spec_other() == donor);
+            self.lemma_exclusive_map_pre(
+                recipient,
+                recipient_vbase@,
+                with_state(frame@, PageState::Donated),
+                vbase@,
+                frame@,
+            );
+        }
+
+        // 1. Revoke the access of the donor
+        self.unmap(donor, vbase, tlb);
+
+        // 2. Map the page for the recipient
+        let donated = with_state_exec(&frame, PageState::Donated);
+        proof {
+            // The donor mapped the page only once, so no page of the donor overlaps it now
+            assert forall|vbase2: VAddr, frame2: Frame| #[trigger]
+                self.mappings(donor).contains_pair(vbase2, frame2) implies compatible(
+                donated@,
+                frame2,
+            ) by {
+                assert(old_self.mappings(donor).contains_pair(vbase2, frame2));
+                lemma_remappable_pmem_disjoint(
+                    old_self.mappings(donor),
+                    old_self.constants(),
+                    vbase@,
+                    frame@,
+                    vbase2,
+                    frame2,
+                );
+            }
+            assert(recipient.spec_other() == donor);
+        }
+        if self.map(recipient, recipient_vbase, donated).is_err() {
+            // 3. Roll back, mapping the page for the donor again
+            proof {
+                assert forall|vbase2: VAddr, frame2: Frame| #[trigger]
+                    self.mappings(recipient).contains_pair(vbase2, frame2) implies compatible(
+                    frame@,
+                    frame2,
+                ) by {
+                    assert(old_self.mappings(recipient).contains_pair(vbase2, frame2));
+                }
+                assert(self.mappings(donor) == old_self.mappings(donor).remove(vbase@));
+            }
+            let res = self.map(donor, vbase, frame);
+            proof {
+                // The failed map left the recipient untouched
+                assert(self.mappings(recipient) == old_self.mappings(recipient));
+                if res is Ok {
+                    assert(old_self.mappings(donor).remove(vbase@).insert(vbase@, frame@)
+                        =~= old_self.mappings(donor));
+                    assert(self.legal_from(old_self)) by {
+                        self.lemma_same_mappings_legal(old_self);
+                    }
+                } else {
+                    // Out of page table memory, the donor only lost the donated page
+                    assert(self.legal_from(old_self)) by {
+                        assert forall|side: Side, vaddr: VAddr| #[trigger]
+                            self.state(side, vaddr) != old_self.state(
+                                side,
+                                vaddr,
+                            ) implies legal_transition(
+                            old_self.state(side, vaddr),
+                            self.state(side, vaddr),
+                        ) by {
+                            assert(side == donor && vaddr == vbase@);
+                        }
+                    }
+                }
+            }
+            return Err(());
+        }
+        proof {
+            assert(self.legal_from(old_self)) by {
+                assert forall|side: Side, vaddr: VAddr| #[trigger]
+                    self.state(side, vaddr) != old_self.state(side, vaddr) implies legal_transition(
+                    old_self.state(side, vaddr),
+                    self.state(side, vaddr),
+                ) by {
+                    if side == recipient && vaddr == recipient_vbase@ {
+                        // The recipient did not map `recipient_vbase` before
+                        assert(!old_self.mappings(recipient).contains_key(recipient_vbase@));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Query the mapping of `side` for `vaddr`.
+    fn query(&self, side: Side, vaddr: VAddrExec) -> (res: PagingResult<(VAddrExec, FrameExec)>)
+        requires
+            self.inv(),
+            vaddr@.aligned(WORD_SIZE),
+        ensures
+            PageTableState::query(
+                self.pt_state(side),
+                self.pt_state(side),
+                vaddr@,
+                match res {
+                    Ok((vaddr, frame)) => Ok((vaddr@, frame@)),
+                    Err(()) => Err(()),
+                },
+            ),
+    {
+        proof {
+            self.pt(side)@.model_consistent_with_hardware();
+            self.pt(side)@.lemma_view_implies_invariants();
+            self.pt(side)@@.query_refinement(vaddr@);
+        }
+        match side {
+            Side::Host => self.host.query(vaddr),
+            Side::Guest => self.guest.query(vaddr),
+        }
+    }
+
+    /// Map `frame` at `vbase` for `side`.
+    ///
+    /// Fails, changing nothing, if the mapping overlaps an existing one in virtual memory, or
+    /// if page table memory runs out.
+    fn map(&mut self, side: Side, vbase: VAddrExec, frame: FrameExec) -> (res: PagingResult)
+        requires
+            old(self).inv(),
+            old(self).pt_state(side).map_pre(vbase@, frame@),
+            old(self).compatible_with_other(side, frame@),
+        ensures
+            self.inv(),
+            self.same_zones(*old(self)),
+            self.mappings(side.spec_other()) == old(self).mappings(side.spec_other()),
+            old(self).pt_state(side).overlaps_vmem(vbase@, frame@) ==> res is Err,
+            res is Ok ==> !old(self).mappings(side).contains_key(vbase@),
+            res is Ok ==> self.mappings(side) == old(self).mappings(side).insert(vbase@, frame@),
+            res is Err ==> self.mappings(side) == old(self).mappings(side),
+    {
+        let ghost mappings = self.mappings(side);
+        let ghost constants = self.constants();
+        proof {
+            self.pt(side)@.model_consistent_with_hardware();
+            self.pt(side)@.lemma_view_implies_invariants();
+            self.pt(side)@@.map_refinement(vbase@, frame@);
+        }
+        let res = match side {
+            Side::Host => self.host.map(vbase, frame),
+            Side::Guest => self.guest.map(vbase, frame),
+        };
+        proof {
+            self.pt(side)@.model_consistent_with_hardware();
+            if res is Ok {
+                let s = PageTableState::new(mappings, constants);
+                if mappings.contains_key(vbase@) {
+                    // A mapping at `vbase` overlaps the new one
+                    assert(s.overlaps_vmem(vbase@, frame@));
+                }
+                lemma_insert_keeps_remappable(mappings, constants, vbase@, frame@);
+                self.lemma_compatible_keeps_shared_overlaps(side, vbase@, frame@, *old(self));
+            }
+        }
+        res
+    }
+
+    /// Unmap the mapping at `vbase` of `side`, and invalidate it in the TLB.
+    fn unmap<T: TLBExec>(&mut self, side: Side, vbase: VAddrExec, tlb: &mut T)
+        requires
+            old(self).inv(),
+            old(self).mappings(side).contains_key(vbase@),
+        ensures
+            self.inv(),
+            self.same_zones(*old(self)),
+            self.mappings(side) == old(self).mappings(side).remove(vbase@),
+            self.mappings(side.spec_other()) == old(self).mappings(side.spec_other()),
+            old(tlb).issued().is_prefix_of(tlb.issued()),
+    {
+        let ghost mappings = self.mappings(side);
+        let ghost constants = self.constants();
+        proof {
+            self.lemma_mapping_unmappable(side, vbase@);
+            self.pt(side)@.model_consistent_with_hardware();
+            self.pt(side)@.lemma_view_implies_invariants();
+            self.pt(side)@@.unmap_refinement(vbase@);
+        }
+        match side {
+            Side::Host => {
+                self.host.unmap(vbase, tlb);
+            },
+            Side::Guest => {
+                self.guest.unmap(vbase, tlb);
+            },
+        }
+        proof {
+            self.pt(side)@.model_consistent_with_hardware();
+            lemma_remove_keeps_remappable(mappings, constants, vbase@);
+            assert(self.shared_overlaps()) by {
+                assert forall|vbase1: VAddr, frame1: Frame, vbase2: VAddr, frame2: Frame|
+                    #[trigger] self.mappings(Side::Host).contains_pair(vbase1, frame1)
+                        && #[trigger] self.mappings(Side::Guest).contains_pair(
+                        vbase2,
+                        frame2,
+                    ) implies compatible(frame1, frame2) by {
+                    assert(old(self).mappings(Side::Host).contains_pair(vbase1, frame1));
+                    assert(old(self).mappings(Side::Guest).contains_pair(vbase2, frame2));
+                }
+            }
+            assert(old(tlb).issued().is_prefix_of(tlb.issued()));
+        }
+    }
+
+    /// Change the ownership state of mapping `(vbase, frame)` of `side`.
+    ///
+    /// The entry is rewritten in place with the same output address and size, so no table is
+    /// allocated and the change cannot fail. The old entry is then invalidated in the TLB.
+    fn set_state<T: TLBExec>(
+        &mut self,
+        side: Side,
+        vbase: VAddrExec,
+        frame: FrameExec,
+        state: PageState,
+        tlb: &mut T,
+    )
+        requires
+            old(self).inv(),
+            old(self).mappings(side).contains_pair(vbase@, frame@),
+            old(self).compatible_with_other(side, with_state(frame@, state)),
+        ensures
+            self.inv(),
+            self.same_zones(*old(self)),
+            self.mappings(side) == old(self).mappings(side).insert(
+                vbase@,
+                with_state(frame@, state),
+            ),
+            self.mappings(side.spec_other()) == old(self).mappings(side.spec_other()),
+            old(tlb).issued().is_prefix_of(tlb.issued()),
+    {
+        let ghost mappings = self.mappings(side);
+        let new_frame = with_state_exec(&frame, state);
+        assert(Frame { attr: new_frame.attr, ..frame@ } == new_frame@);
+        let res = match side {
+            Side::Host => self.host.protect(vbase, new_frame.attr, tlb),
+            Side::Guest => self.guest.protect(vbase, new_frame.attr, tlb),
+        };
+        proof {
+            assert(res is Ok);
+            assert(self.mappings(side) == mappings.insert(vbase@, new_frame@));
+            lemma_same_extent_keeps_remappable(mappings, self.constants(), vbase@, new_frame@);
+            self.lemma_compatible_keeps_shared_overlaps(side, vbase@, new_frame@, *old(self));
+        }
+    }
+
+    /// Lemma. A page exclusively owned by `side` is not mapped by the other zone.
+    proof fn lemma_exclusive_not_mapped_by_other(self, side: Side, vbase: VAddr, frame: Frame)
+        requires
+            self.inv(),
+            self.mappings(side).contains_pair(vbase, frame),
+            exclusive(frame.attr.sw_bits.state),
+        ensures
+            forall|vbase2: VAddr, frame2: Frame| #[trigger]
+                self.mappings(side.spec_other()).contains_pair(vbase2, frame2) ==> !PAddr::overlap(
+                    frame.base,
+                    frame.size.as_nat(),
+                    frame2.base,
+                    frame2.size.as_nat(),
+                ),
+            forall|state: PageState|
+                self.compatible_with_other(side, #[trigger] with_state(frame, state)),
+    {
+        assert forall|vbase2: VAddr, frame2: Frame| #[trigger]
+            self.mappings(side.spec_other()).contains_pair(vbase2, frame2) implies !PAddr::overlap(
+            frame.base,
+            frame.size.as_nat(),
+            frame2.base,
+            frame2.size.as_nat(),
+        ) by {
+            match side {
+                Side::Host => assert(compatible(frame, frame2)),
+                Side::Guest => assert(compatible(frame2, frame)),
+            }
+        }
+    }
+
+    /// Lemma. Going back to the exclusive state of `frame` in `old` keeps the other zone
+    /// compatible, since it did not change.
+    proof fn lemma_exclusive_compatible(self, side: Side, frame: Frame, old: Self)
+        requires
+            old.inv(),
+            old.mappings(side).contains_value(frame),
+            exclusive(frame.attr.sw_bits.state),
+            self.mappings(side.spec_other()) == old.mappings(side.spec_other()),
+        ensures
+            self.compatible_with_other(side, frame),
+    {
+        let vbase = choose|vbase: VAddr| #[trigger]
+            old.mappings(side).contains_pair(vbase, frame);
+        old.lemma_exclusive_not_mapped_by_other(side, vbase, frame);
+    }
+
+    /// Lemma. `frame2`, a copy of the frame of mapping `(vbase1, frame)` of the other zone,
+    /// satisfies the map precondition of `side` at `vbase`.
+    ///
+    /// `side` maps nothing that overlaps `frame` in physical memory.
+    proof fn lemma_exclusive_map_pre(
+        self,
+        side: Side,
+        vbase: VAddr,
+        frame2: Frame,
+        vbase1: VAddr,
+        frame: Frame,
+    )
+        requires
+            self.inv(),
+            vbase.aligned(frame.size.as_nat()),
+            frame2.base == frame.base,
+            frame2.size == frame.size,
+            PageTableState::new(
+                self.mappings(side.spec_other()).remove(vbase1),
+                self.constants(),
+            ).map_pre(vbase1, frame),
+            forall|vbase3: VAddr, frame3: Frame| #[trigger]
+                self.mappings(side).contains_pair(vbase3, frame3) ==> !PAddr::overlap(
+                    frame.base,
+                    frame.size.as_nat(),
+                    frame3.base,
+                    frame3.size.as_nat(),
+                ),
+        ensures
+            self.pt_state(side).map_pre(vbase, frame2),
+    {
+        let s = self.pt_state(side);
+        if s.overlaps_pmem(frame2) {
+            let frame3 = choose|frame3: Frame|
+                {
+                    &&& #[trigger] s.mappings.contains_value(frame3)
+                    &&& PAddr::overlap(
+                        frame3.base,
+                        frame3.size.as_nat(),
+                        frame2.base,
+                        frame2.size.as_nat(),
+                    )
+                };
+            let vbase3 = choose|vbase3: VAddr|
+                s.mappings.contains_key(vbase3) && s.mappings[vbase3] == frame3;
+            assert(self.mappings(side).contains_pair(vbase3, frame3));
+        }
+    }
+
+    /// Lemma. A mapping of `side` satisfies the map precondition once it is removed, and the
+    /// same holds for the frame in any ownership state.
+    proof fn lemma_remappable_map_pre(self, side: Side, vbase: VAddr, frame: Frame)
+        requires
+            self.inv(),
+            self.mappings(side).contains_pair(vbase, frame),
+        ensures
+            forall|state: PageState|
+                {
+                    let s = PageTableState::new(
+                        self.mappings(side).remove(vbase),
+                        self.constants(),
+                    );
+                    &&& s.map_pre(vbase, #[trigger] with_state(frame, state))
+                    &&& !s.overlaps_vmem(vbase, with_state(frame, state))
+                },
+            ({
+                let s = PageTableState::new(self.mappings(side).remove(vbase), self.constants());
+                &&& s.map_pre(vbase, frame)
+                &&& !s.overlaps_vmem(vbase, frame)
+            }),
+    {
+        // The precondition only depends on the base and size of the frame
+        assert(remappable(self.mappings(side), self.constants()));
+    }
+
+    /// Lemma. Every mapping can be unmapped.
+    proof fn lemma_mapping_unmappable(self, side: Side, vbase: VAddr)
+        requires
+            self.inv(),
+            self.mappings(side).contains_key(vbase),
+        ensures
+            self.pt_state(side).unmap_pre(vbase),
+    {
+        let frame = self.mappings(side)[vbase];
+        let s = PageTableState::new(self.mappings(side).remove(vbase), self.constants());
+        assert(s.map_pre(vbase, frame));
+        let arch = self.constants().arch;
+        let level = arch.level_of_frame_size(frame.size);
+        arch.lemma_frame_size_aligned((arch.level_count() - 1) as nat, level);
+        let size = frame.size.as_nat();
+        let leaf = arch.leaf_frame_size().as_nat();
+        // `size` is a multiple of `leaf`, so `vbase` aligned to `size` is aligned to `leaf`
+        vstd::arithmetic::div_mod::lemma_fundamental_div_mod(size as int, leaf as int);
+        vstd::arithmetic::div_mod::lemma_mod_mod(
+            vbase.0 as int,
+            leaf as int,
+            size as int / leaf as int,
+        );
+    }
+
+    /// Lemma. Adding a mapping of `side` compatible with the other zone keeps every physical page
+    /// mapped by both zones shared.
+    proof fn lemma_compatible_keeps_shared_overlaps(
+        self,
+        side: Side,
+        vbase: VAddr,
+        frame: Frame,
+        old: Self,
+    )
+        requires
+            old.shared_overlaps(),
+            old.compatible_with_other(side, frame),
+            self.mappings(side) == old.mappings(side).insert(vbase, frame),
+            self.mappings(side.spec_other()) == old.mappings(side.spec_other()),
+        ensures
+            self.shared_overlaps(),
+    {
+        assert forall|vbase1: VAddr, frame1: Frame, vbase2: VAddr, frame2: Frame|
+            #[trigger] self.mappings(Side::Host).contains_pair(vbase1, frame1)
+                && #[trigger] self.mappings(Side::Guest).contains_pair(
+                vbase2,
+                frame2,
+            ) implies compatible(frame1, frame2) by {
+            match side {
+                Side::Host => if vbase1 == vbase {
+                    assert(old.mappings(Side::Guest).contains_pair(vbase2, frame2));
+                } else {
+                    assert(old.mappings(Side::Host).contains_pair(vbase1, frame1));
+                    assert(old.mappings(Side::Guest).contains_pair(vbase2, frame2));
+                },
+                Side::Guest => if vbase2 == vbase {
+                    // Overlap and shared states are symmetric
+                    assert(old.mappings(Side::Host).contains_pair(vbase1, frame1));
+                    assert(compatible(frame2, frame1));
+                } else {
+                    assert(old.mappings(Side::Host).contains_pair(vbase1, frame1));
+                    assert(old.mappings(Side::Guest).contains_pair(vbase2, frame2));
+                },
+            }
+        }
+    }
+
+    /// Lemma. Zones with the same mappings as `old` made only legal transitions.
+    proof fn lemma_same_mappings_legal(self, old: Self)
+        requires
+            self.mappings(Side::Host) == old.mappings(Side::Host),
+            self.mappings(Side::Guest) == old.mappings(Side::Guest),
+        ensures
+            self.legal_from(old),
+    {
+        assert forall|side: Side, vbase: VAddr| #[trigger]
+            self.state(side, vbase) == old.state(side, vbase) by {
+            match side {
+                Side::Host => {},
+                Side::Guest => {},
+            }
+        }
+    }
+
+    /// Theorem. A physical page writable from both zones is recorded as shared on both sides.
+    pub proof fn lemma_writable_by_both_implies_shared(
+        self,
+        vbase1: VAddr,
+        frame1: Frame,
+        vbase2: VAddr,
+        frame2: Frame,
+    )
+        requires
+            self.inv(),
+            self.mappings(Side::Host).contains_pair(vbase1, frame1),
+            self.mappings(Side::Guest).contains_pair(vbase2, frame2),
+            frame1.attr.writable,
+            frame2.attr.writable,
+            PAddr::overlap(frame1.base, frame1.size.as_nat(), frame2.base, frame2.size.as_nat()),
+        ensures
+            frame1.attr.sw_bits.state is SharedOwned || frame1.attr.sw_bits.state is SharedBorrowed,
+            frame2.attr.sw_bits.state is SharedOwned || frame2.attr.sw_bits.state is SharedBorrowed,
+    {
+        assert(compatible(frame1, frame2));
+    }
+}
+
+/// `frame` with ownership state `state`.
+fn with_state_exec(frame: &FrameExec, state: PageState) -> (res: FrameExec)
+    ensures
+        res@ == with_state(frame@, state),
+{
+    let sw_bits = SwBits {
+        state,
+        pinned: frame.attr.sw_bits.pinned,
+        mmio_emulated: frame.attr.sw_bits.mmio_emulated,
+    };
+    let attr = MemAttr::new(
+        frame.attr.readable,
+        frame.attr.writable,
+        frame.attr.executable,
+        frame.attr.user_accessible,
+        frame.attr.mem_type,
+        frame.attr.shareability,
+        frame.attr.global,
+        sw_bits,
+    );
+    FrameExec { base: frame.base, size: frame.size, attr }
+}
+
+/// Lemma. Two different mappings of a remappable zone do not overlap in physical memory.
+proof fn lemma_remappable_pmem_disjoint(
+    mappings: Map<VAddr, Frame>,
+    constants: PTConstants,
+    vbase1: VAddr,
+    frame1: Frame,
+    vbase2: VAddr,
+    frame2: Frame,
+)
+    requires
+        remappable(mappings, constants),
+        mappings.contains_pair(vbase1, frame1),
+        mappings.contains_pair(vbase2, frame2),
+        vbase1 != vbase2,
+    ensures
+        !PAddr::overlap(frame1.base, frame1.size.as_nat(), frame2.base, frame2.size.as_nat()),
+{
+    let s = PageTableState::new(mappings.remove(vbase1), constants);
+    assert(s.map_pre(vbase1, frame1));
+    assert(s.mappings.contains_key(vbase2));
+    assert(s.mappings.contains_value(frame2));
+}
+
+/// Lemma. Removing a mapping keeps every mapping remappable.
+proof fn lemma_remove_keeps_remappable(
+    mappings: Map<VAddr, Frame>,
+    constants: PTConstants,
+    vbase: VAddr,
+)
+    requires
+        remappable(mappings, constants),
+    ensures
+        remappable(mappings.remove(vbase), constants),
+{
+    let mappings2 = mappings.remove(vbase);
+    assert forall|vbase2: VAddr, frame2: Frame| #[trigger]
+        mappings2.contains_pair(vbase2, frame2) implies {
+        let s = PageTableState::new(mappings2.remove(vbase2), constants);
+        &&& s.map_pre(vbase2, frame2)
+        &&& !s.overlaps_vmem(vbase2, frame2)
+    } by {
+        let s = PageTableState::new(mappings2.remove(vbase2), constants);
+        let s0 = PageTableState::new(mappings.remove(vbase2), constants);
+        assert(mappings.contains_pair(vbase2, frame2));
+        // Fewer mappings overlap less
+        if s.overlaps_pmem(frame2) {
+            let frame3 = choose|frame3: Frame|
+                {
+                    &&& #[trigger] s.mappings.contains_value(frame3)
+                    &&& PAddr::overlap(
+                        frame3.base,
+                        frame3.size.as_nat(),
+                        frame2.base,
+                        frame2.size.as_nat(),
+                    )
+                };
+            let vbase3 = choose|vbase3: VAddr|
+                s.mappings.contains_key(vbase3) && s.mappings[vbase3] == frame3;
+            assert(s0.mappings.contains_key(vbase3));
+            assert(s0.mappings.contains_value(frame3));
+        }
+        if s.overlaps_vmem(vbase2, frame2) {
+            let vbase3 = choose|vbase3: VAddr|
+                {
+                    &&& #[trigger] s.mappings.contains_key(vbase3)
+                    &&& VAddr::overlap(
+                        vbase3,
+                        s.mappings[vbase3].size.as_nat(),
+                        vbase2,
+                        frame2.size.as_nat(),
+                    )
+                };
+            assert(s0.mappings.contains_key(vbase3));
+        }
+    }
+}
+
+/// Lemma. Adding a mapping that satisfies the map precondition and does not overlap existing
+/// mappings in virtual memory keeps every mapping remappable.
+proof fn lemma_insert_keeps_remappable(
+    mappings: Map<VAddr, Frame>,
+    constants: PTConstants,
+    vbase: VAddr,
+    frame: Frame,
+)
+    requires
+        remappable(mappings, constants),
+        !mappings.contains_key(vbase),
+        PageTableState::new(mappings, constants).map_pre(vbase, frame),
+        !PageTableState::new(mappings, constants).overlaps_vmem(vbase, frame),
+    ensures
+        remappable(mappings.insert(vbase, frame), constants),
+{
+    let s1 = PageTableState::new(mappings, constants);
+    let mappings2 = mappings.insert(vbase, frame);
+    assert forall|vbase2: VAddr, frame2: Frame| #[trigger]
+        mappings2.contains_pair(vbase2, frame2) implies {
+        let s = PageTableState::new(mappings2.remove(vbase2), constants);
+        &&& s.map_pre(vbase2, frame2)
+        &&& !s.overlaps_vmem(vbase2, frame2)
+    } by {
+        let s = PageTableState::new(mappings2.remove(vbase2), constants);
+        if vbase2 == vbase {
+            assert(mappings2.remove(vbase2) =~= mappings);
+        } else {
+            let s0 = PageTableState::new(mappings.remove(vbase2), constants);
+            assert(mappings.contains_pair(vbase2, frame2));
+            assert(mappings2.remove(vbase2) =~= mappings.remove(vbase2).insert(vbase, frame));
+            if s.overlaps_pmem(frame2) {
+                let frame3 = choose|frame3: Frame|
+                    {
+                        &&& #[trigger] s.mappings.contains_value(frame3)
+                        &&& PAddr::overlap(
+                            frame3.base,
+                            frame3.size.as_nat(),
+                            frame2.base,
+                            frame2.size.as_nat(),
+                        )
+                    };
+                let vbase3 = choose|vbase3: VAddr|
+                    s.mappings.contains_key(vbase3) && s.mappings[vbase3] == frame3;
+                if vbase3 == vbase {
+                    // `frame` does not overlap `frame2`, which is already mapped
+                    assert(s1.mappings.contains_value(frame2));
+                } else {
+                    assert(s0.mappings.contains_key(vbase3));
+                    assert(s0.mappings.contains_value(frame3));
+                }
+            }
+            if s.overlaps_vmem(vbase2, frame2) {
+                let vbase3 = choose|vbase3: VAddr|
+                    {
+                        &&& #[trigger] s.mappings.contains_key(vbase3)
+                        &&& VAddr::overlap(
+                            vbase3,
+                            s.mappings[vbase3].size.as_nat(),
+                            vbase2,
+                            frame2.size.as_nat(),
+                        )
+                    };
+                if vbase3 == vbase {
+                    // `(vbase, frame)` does not overlap `(vbase2, frame2)`, which is already mapped
+                    assert(s1.mappings.contains_key(vbase2));
+                } else {
+                    assert(s0.mappings.contains_key(vbase3));
+                }
+            }
+        }
+    }
+}
+
+} // verus!
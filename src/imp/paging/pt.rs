@@ -1,22 +1,22 @@
 //! Spec-mode page table implementation.
-use std::marker::PhantomData;
+use core::marker::PhantomData;
 use vstd::prelude::*;
 
 use crate::{
     common::{
         addr::{PAddr, VAddr},
-        frame::{Frame, MemAttr},
-        pte::GhostPTE,
+        frame::{Frame, FrameSize, MemAttr},
+        pte::{same_target, same_translation, GhostPTE},
         PagingResult,
     },
-    imp::lemmas::lemma_not_in_seq_implies_not_in_subseq,
+    imp::lemmas::{lemma_map_eq_pair, lemma_not_in_seq_implies_not_in_subseq},
     imp::tree::{
         model::PTTreeModel,
         node::{NodeEntry, PTTreeNode},
         path::PTTreePath,
     },
     spec::{
-        memory::PageTableMem,
+        memory::{PageTableMem, Table},
         page_table::{PTConstants, PageTableState},
     },
 };
@@ -70,6 +70,28 @@ impl<G> PageTable<G> where G: GhostPTE {
         Frame { base: pte.addr(), attr: pte.attr(), size: self.constants.arch.frame_size(level) }
     }
 
+    /// The frame interpreted from an entry encoded with `attr` carries the memory type and
+    /// shareability of `attr`.
+    pub proof fn lemma_pte_to_frame_keeps_mem_type(
+        self,
+        addr: PAddr,
+        attr: MemAttr,
+        huge: bool,
+        level: nat,
+    )
+        requires
+            addr.aligned(FrameSize::Size4K.as_nat()),
+        ensures
+            ({
+                let pte = G::from_u64(G::new(addr, attr, huge).to_u64());
+                let frame = self.pte_to_frame(pte, level);
+                &&& frame.attr.mem_type == attr.mem_type
+                &&& frame.attr.shareability == attr.shareability
+            }),
+    {
+        G::lemma_encoding_keeps_attr(addr, attr, huge);
+    }
+
     /// If all pte in a table are invalid.
     pub open spec fn is_table_empty(self, base: PAddr) -> bool
         recommends
@@ -208,6 +230,10 @@ impl<G> PageTable<G> where G: GhostPTE {
     }
 
     /// Perform a recursive specification-level page table insertion starting from a given base.
+    ///
+    /// Intermediate tables are taken from `allocs`, the `(pt_mem, table)` results of the table
+    /// allocations in order. The insertion fails as out of memory if `allocs` runs out, or if
+    /// the next pair is not an allocation `PageTableMem::allocates` admits.
     pub open spec fn insert(
         self,
         vbase: VAddr,
@@ -215,6 +241,7 @@ impl<G> PageTable<G> where G: GhostPTE {
         level: nat,
         target_level: nat,
         new_pte: G,
+        allocs: Seq<(PageTableMem, Table)>,
     ) -> (Self, PagingResult)
         recommends
             self.invariants(),
@@ -239,12 +266,16 @@ impl<G> PageTable<G> where G: GhostPTE {
                     (self, Err(()))
                 } else {
                     // Insert at next level
-                    self.insert(vbase, pte.addr(), level + 1, target_level, new_pte)
+                    self.insert(vbase, pte.addr(), level + 1, target_level, new_pte, allocs)
                 }
-            } else {
+            } else if allocs.len() > 0 && self.pt_mem.allocates(
+                level + 1,
+                allocs[0].0,
+                allocs[0].1,
+            ) {
                 // Insert intermediate table
-                // Allocate a new table
-                let (pt_mem, table) = self.pt_mem.alloc_table(level + 1);
+                // Take the allocated table
+                let (pt_mem, table) = allocs[0];
                 // Write entry
                 let pt_mem = pt_mem.write(
                     base,
@@ -257,7 +288,67 @@ impl<G> PageTable<G> where G: GhostPTE {
                     level + 1,
                     target_level,
                     new_pte,
+                    allocs.drop_first(),
                 )
+            } else {
+                // Out of page table memory
+                (self, Err(()))
+            }
+        }
+    }
+
+    /// Entries written by `insert`, as `(base, index, value)` in the order they are written.
+    pub open spec fn insert_writes(
+        self,
+        vbase: VAddr,
+        base: PAddr,
+        level: nat,
+        target_level: nat,
+        new_pte: G,
+        allocs: Seq<(PageTableMem, Table)>,
+    ) -> Seq<(PAddr, nat, u64)>
+        recommends
+            self.invariants(),
+            self.pt_mem.contains_table(base),
+            self.pt_mem.table(base).level == level,
+            level <= target_level < self.constants.arch.level_count(),
+            self.pte_valid_frame(new_pte, target_level),
+        decreases target_level - level,
+    {
+        let idx = self.constants.arch.pte_index(vbase, level);
+        let pte = G::from_u64(self.pt_mem.read(base, idx));
+        if level >= target_level {
+            if pte.valid() {
+                seq![]
+            } else {
+                seq![(base, idx, new_pte.to_u64())]
+            }
+        } else {
+            if pte.valid() {
+                if pte.huge() {
+                    seq![]
+                } else {
+                    self.insert_writes(vbase, pte.addr(), level + 1, target_level, new_pte, allocs)
+                }
+            } else if allocs.len() > 0 && self.pt_mem.allocates(
+                level + 1,
+                allocs[0].0,
+                allocs[0].1,
+            ) {
+                let (pt_mem, table) = allocs[0];
+                let entry = G::new(table.base, MemAttr::spec_default(), false).to_u64();
+                seq![(base, idx, entry)].add(
+                    Self::new(pt_mem.write(base, idx, entry), self.constants).insert_writes(
+                        vbase,
+                        table.base,
+                        level + 1,
+                        target_level,
+                        new_pte,
+                        allocs.drop_first(),
+                    ),
+                )
+            } else {
+                seq![]
             }
         }
     }
@@ -419,6 +510,366 @@ impl<G> PageTable<G> where G: GhostPTE {
         assume(false);
     }
 
+    /// Lemma. Every table descriptor points to a table on the next level, so hardware walks
+    /// only read existing tables.
+    pub proof fn lemma_invariants_implies_walkable(self)
+        requires
+            self.invariants(),
+        ensures
+            self.pt_mem.walkable::<G>(),
+    {
+        assert forall|base: PAddr, index: nat| #[trigger]
+            self.pt_mem.accessible(base, index) implies {
+            let pte = G::from_u64(self.pt_mem.read(base, index));
+            let level = self.pt_mem.table(base).level;
+            level + 1 < self.pt_mem.arch.level_count() && pte.valid() && !pte.huge() ==> {
+                &&& self.pt_mem.contains_table(pte.addr())
+                &&& self.pt_mem.table(pte.addr()).level == level + 1
+            }
+        } by {
+            let pte = G::from_u64(self.pt_mem.read(base, index));
+            assert(self.pte_points_to_table(pte, self.pt_mem.table(base).level) ==> {
+                &&& self.pt_mem.contains_table(pte.addr())
+                &&& self.pt_mem.table(pte.addr()).level == self.pt_mem.table(base).level + 1
+            });
+        }
+    }
+
+    /// Lemma. Writing an entry with the same translation, e.g. with a different Dirty flag,
+    /// keeps the invariants and the interpretation.
+    pub proof fn lemma_write_same_translation_preserves_invariants(
+        self,
+        b: PAddr,
+        i: nat,
+        entry: u64,
+    )
+        requires
+            self.invariants(),
+            self.pt_mem.accessible(b, i),
+            same_translation(G::from_u64(self.pt_mem.read(b, i)), G::from_u64(entry)),
+        ensures
+            Self::new(self.pt_mem.write(b, i, entry), self.constants).invariants(),
+            self.pt_mem.write(b, i, entry).root() == self.pt_mem.root(),
+            self.pt_mem.write(b, i, entry).interpret::<G>() == self.pt_mem.interpret::<G>(),
+    {
+        let pt2 = Self::new(self.pt_mem.write(b, i, entry), self.constants);
+        self.lemma_invariants_implies_walkable();
+        self.pt_mem.lemma_write_same_translation_keeps_interpret::<G>(b, i, entry);
+        // Every entry decodes to the same translation, so the invariants still hold
+        assert forall|base: PAddr, idx: nat| #[trigger]
+            pt2.pt_mem.accessible(base, idx) implies same_translation(
+            G::from_u64(self.pt_mem.read(base, idx)),
+            G::from_u64(pt2.pt_mem.read(base, idx)),
+        ) by {
+            if base != b || idx != i {
+                self.pt_mem.lemma_write_keeps_other_entry(b, i, entry, base, idx);
+            }
+        }
+        assert(pt2.invariants());
+    }
+
+    /// Lemma. Walks from tables on the same level that terminate at the same entry go through
+    /// the same tables, so they agree on the index at every level.
+    ///
+    /// Relies on the invariant that no two table descriptors point to the same table.
+    pub proof fn lemma_same_walk_entry_same_indices(
+        self,
+        v1: VAddr,
+        v2: VAddr,
+        base1: PAddr,
+        base2: PAddr,
+        level: nat,
+    )
+        requires
+            self.invariants(),
+            self.pt_mem.contains_table(base1),
+            self.pt_mem.contains_table(base2),
+            self.pt_mem.table(base1).level == level,
+            self.pt_mem.table(base2).level == level,
+            level < self.constants.arch.level_count(),
+            self.pt_mem.walk_entry_from::<G>(v1, base1, level) == self.pt_mem.walk_entry_from::<G>(
+                v2,
+                base2,
+                level,
+            ),
+        ensures
+            base1 == base2,
+            forall|l: nat|
+                level <= l <= self.pt_mem.walk_from::<G>(v1, base1, level).1
+                    ==> #[trigger] self.constants.arch.pte_index(v1, l)
+                    == self.constants.arch.pte_index(v2, l),
+        decreases self.constants.arch.level_count() - level,
+    {
+        let pt_mem = self.pt_mem;
+        let arch = self.constants.arch;
+        self.lemma_invariants_implies_walkable();
+        pt_mem.lemma_walk_entry_from::<G>(v1, base1, level);
+        pt_mem.lemma_walk_entry_from::<G>(v2, base2, level);
+        let idx1 = arch.pte_index(v1, level);
+        let idx2 = arch.pte_index(v2, level);
+        arch.lemma_pte_index_bound(v1, level);
+        arch.lemma_pte_index_bound(v2, level);
+        assert(pt_mem.accessible(base1, idx1));
+        assert(pt_mem.accessible(base2, idx2));
+        let pte1 = G::from_u64(pt_mem.read(base1, idx1));
+        let pte2 = G::from_u64(pt_mem.read(base2, idx2));
+        let down1 = level + 1 < arch.level_count() && pte1.valid() && !pte1.huge();
+        let down2 = level + 1 < arch.level_count() && pte2.valid() && !pte2.huge();
+        if down1 && down2 {
+            self.lemma_same_walk_entry_same_indices(v1, v2, pte1.addr(), pte2.addr(), level + 1);
+            // Both descriptors point to the same table, so they are the same entry
+            assert(self.pte_points_to_table(pte1, level));
+            assert(self.pte_points_to_table(pte2, level));
+        } else if down1 {
+            // The walk for `v1` terminates on a higher level than the entry it shares with `v2`
+            pt_mem.lemma_walk_from_level_lower_bound::<G>(v1, pte1.addr(), level + 1);
+        } else if down2 {
+            pt_mem.lemma_walk_from_level_lower_bound::<G>(v2, pte2.addr(), level + 1);
+        }
+    }
+
+    /// Lemma. The walks for two different base addresses never terminate at the same entry.
+    pub proof fn lemma_walk_entry_unique_vbase(self, v1: VAddr, v2: VAddr)
+        requires
+            self.invariants(),
+            self.pt_mem.walk_entry_from::<G>(v1, self.pt_mem.root(), 0)
+                == self.pt_mem.walk_entry_from::<G>(v2, self.pt_mem.root(), 0),
+            v1 == self.constants.arch.vbase(
+                v1,
+                self.pt_mem.walk_from::<G>(v1, self.pt_mem.root(), 0).1,
+            ),
+            v2 == self.constants.arch.vbase(
+                v2,
+                self.pt_mem.walk_from::<G>(v2, self.pt_mem.root(), 0).1,
+            ),
+        ensures
+            v1 == v2,
+    {
+        let pt_mem = self.pt_mem;
+        let arch = self.constants.arch;
+        let root = pt_mem.root();
+        self.lemma_invariants_implies_walkable();
+        pt_mem.lemma_contains_root();
+        pt_mem.lemma_walk_entry_from::<G>(v1, root, 0);
+        pt_mem.lemma_walk_entry_from::<G>(v2, root, 0);
+        pt_mem.lemma_walk_level_bound::<G>(v1);
+        // Both walks terminate on the level of the shared entry
+        let level = pt_mem.walk_from::<G>(v1, root, 0).1;
+        assert(pt_mem.walk_from::<G>(v2, root, 0).1 == level);
+        self.lemma_same_walk_entry_same_indices(v1, v2, root, root, 0);
+        // So both addresses have the same path down to that level
+        let path1 = PTTreePath::from_vaddr_root(v1, arch, level);
+        let path2 = PTTreePath::from_vaddr_root(v2, arch, level);
+        assert(path1.0 =~= path2.0);
+        arch.lemma_vbase_range_and_alignment(v1, level);
+        arch.lemma_vbase_range_and_alignment(v2, level);
+        PTTreePath::lemma_from_vaddr_root_yields_valid_path(v1, arch, level);
+        PTTreePath::lemma_from_vaddr_root_yields_valid_path(v2, arch, level);
+        PTTreePath::lemma_to_vaddr_inverts_from_vaddr(arch, v1, path1);
+        PTTreePath::lemma_to_vaddr_inverts_from_vaddr(arch, v2, path2);
+    }
+
+    /// Lemma. Rewriting the entry that maps `vbase` with one that links the same frame keeps
+    /// the invariants, and only changes the attributes of the mapping at `vbase`.
+    pub proof fn lemma_protect_write(self, vbase: VAddr, entry: u64)
+        requires
+            self.invariants(),
+            ({
+                let (pte, level) = self.pt_mem.walk_from::<G>(vbase, self.pt_mem.root(), 0);
+                &&& pte.valid()
+                &&& vbase == self.constants.arch.vbase(vbase, level)
+                &&& same_target(pte, G::from_u64(entry))
+            }),
+        ensures
+            ({
+                let (b, i) = self.pt_mem.walk_entry_from::<G>(vbase, self.pt_mem.root(), 0);
+                let pt_mem2 = self.pt_mem.write(b, i, entry);
+                let mappings = self.pt_mem.interpret::<G>();
+                &&& Self::new(pt_mem2, self.constants).invariants()
+                &&& pt_mem2.root() == self.pt_mem.root()
+                &&& mappings.contains_key(vbase)
+                &&& pt_mem2.interpret::<G>() == mappings.insert(
+                    vbase,
+                    Frame { attr: G::from_u64(entry).attr(), ..mappings[vbase] },
+                )
+            }),
+    {
+        let pt_mem = self.pt_mem;
+        let root = pt_mem.root();
+        let (b, i) = pt_mem.walk_entry_from::<G>(vbase, root, 0);
+        let (pte, level) = pt_mem.walk_from::<G>(vbase, root, 0);
+        let new_pte = G::from_u64(entry);
+        let pt2 = Self::new(pt_mem.write(b, i, entry), self.constants);
+        let post = pt2.pt_mem;
+        self.lemma_invariants_implies_walkable();
+        pt_mem.lemma_contains_root();
+        pt_mem.lemma_walk_entry_from::<G>(vbase, root, 0);
+        pt_mem.lemma_write_preserves_invariants(b, i, entry);
+        assert(post.read(b, i) == entry);
+        // Every entry links the same table or frame, so the invariants still hold
+        assert forall|base: PAddr, idx: nat| #[trigger]
+            post.accessible(base, idx) implies same_target(
+            G::from_u64(pt_mem.read(base, idx)),
+            G::from_u64(post.read(base, idx)),
+        ) by {
+            if base != b || idx != i {
+                pt_mem.lemma_write_keeps_other_entry(b, i, entry, base, idx);
+            }
+        }
+        assert(pt2.invariants());
+        pt2.lemma_invariants_implies_walkable();
+        post.lemma_contains_root();
+        // The frame mapped at `vbase` before and after the write
+        let frame = Frame {
+            base: pte.addr(),
+            size: self.constants.arch.frame_size(level),
+            attr: pte.attr(),
+        };
+        let frame2 = Frame { attr: new_pte.attr(), ..frame };
+        assert(pt_mem.walk::<G>(vbase, frame));
+        assert forall|vaddr: VAddr, f: Frame| #[trigger]
+            post.walk::<G>(vaddr, f) == if vaddr == vbase {
+                f == frame2
+            } else {
+                pt_mem.walk::<G>(vaddr, f)
+            } by {
+            pt_mem.lemma_write_same_target_walk_from::<G>(b, i, entry, vaddr, root, 0);
+            pt_mem.lemma_walk_entry_from::<G>(vaddr, root, 0);
+            post.lemma_walk_entry_from::<G>(vaddr, root, 0);
+            let (b2, i2) = pt_mem.walk_entry_from::<G>(vaddr, root, 0);
+            let level2 = pt_mem.walk_from::<G>(vaddr, root, 0).1;
+            if b2 == b && i2 == i {
+                if vaddr != vbase && vaddr == self.constants.arch.vbase(vaddr, level2) {
+                    // Only `vbase` is mapped through the rewritten entry
+                    self.lemma_walk_entry_unique_vbase(vaddr, vbase);
+                }
+            } else {
+                pt_mem.lemma_write_keeps_other_entry(b, i, entry, b2, i2);
+            }
+        }
+        assert(post.walk::<G>(vbase, frame2));
+        assert(post.interpret::<G>() =~= pt_mem.interpret::<G>().insert(vbase, frame2));
+    }
+
+    /// Lemma. A mapping that shares an address with the page or block where the walk for
+    /// `vaddr` terminates is mapped by that entry.
+    pub proof fn lemma_mapping_in_walk_block(self, vaddr: VAddr, vbase: VAddr, a: VAddr)
+        requires
+            self.invariants(),
+            self.pt_mem.interpret::<G>().contains_key(vbase),
+            a.within(vbase, self.pt_mem.interpret::<G>()[vbase].size.as_nat()),
+            ({
+                let level = self.pt_mem.walk_from::<G>(vaddr, self.pt_mem.root(), 0).1;
+                a.within(
+                    self.constants.arch.vbase(vaddr, level),
+                    self.constants.arch.frame_size(level).as_nat(),
+                )
+            }),
+        ensures
+            ({
+                let root = self.pt_mem.root();
+                let level = self.pt_mem.walk_from::<G>(vaddr, root, 0).1;
+                &&& vbase == self.constants.arch.vbase(vaddr, level)
+                &&& self.pt_mem.walk_from::<G>(vbase, root, 0) == self.pt_mem.walk_from::<G>(
+                    vaddr,
+                    root,
+                    0,
+                )
+                &&& self.pt_mem.walk_entry_from::<G>(vbase, root, 0)
+                    == self.pt_mem.walk_entry_from::<G>(vaddr, root, 0)
+            }),
+    {
+        let pt_mem = self.pt_mem;
+        let arch = self.constants.arch;
+        let root = pt_mem.root();
+        let frame = pt_mem.interpret::<G>()[vbase];
+        assert(pt_mem.walk::<G>(vbase, frame));
+        self.lemma_invariants_implies_walkable();
+        pt_mem.lemma_walk_level_bound::<G>(vbase);
+        pt_mem.lemma_walk_level_bound::<G>(vaddr);
+        let level1 = pt_mem.walk_from::<G>(vbase, root, 0).1;
+        let level2 = pt_mem.walk_from::<G>(vaddr, root, 0).1;
+        // `a` is walked like `vbase`, and like `vaddr`
+        arch.lemma_vbase_range_and_alignment(vbase, level1);
+        arch.lemma_vbase_within(vbase, a, level1);
+        pt_mem.lemma_walk_from_same_block::<G>(vbase, a, root, 0);
+        arch.lemma_vbase_range_and_alignment(vaddr, level2);
+        arch.lemma_vbase_within(arch.vbase(vaddr, level2), a, level2);
+        pt_mem.lemma_walk_from_same_block::<G>(vaddr, a, root, 0);
+    }
+
+    /// Lemma. A mapping whose walk terminates at the same entry as the walk for `vaddr` is
+    /// the page or block of `vaddr` on that level.
+    pub proof fn lemma_walk_entry_vbase(self, vaddr: VAddr, vbase: VAddr)
+        requires
+            self.invariants(),
+            self.pt_mem.interpret::<G>().contains_key(vbase),
+            self.pt_mem.walk_entry_from::<G>(vbase, self.pt_mem.root(), 0)
+                == self.pt_mem.walk_entry_from::<G>(vaddr, self.pt_mem.root(), 0),
+        ensures
+            vbase == self.constants.arch.vbase(
+                vaddr,
+                self.pt_mem.walk_from::<G>(vaddr, self.pt_mem.root(), 0).1,
+            ),
+    {
+        let pt_mem = self.pt_mem;
+        let arch = self.constants.arch;
+        let root = pt_mem.root();
+        assert(pt_mem.walk::<G>(vbase, pt_mem.interpret::<G>()[vbase]));
+        self.lemma_invariants_implies_walkable();
+        pt_mem.lemma_walk_level_bound::<G>(vaddr);
+        let level = pt_mem.walk_from::<G>(vaddr, root, 0).1;
+        let vbase2 = arch.vbase(vaddr, level);
+        // `vbase2` is walked like `vaddr`, and is the base of its own page or block
+        arch.lemma_vbase_range_and_alignment(vaddr, level);
+        arch.lemma_vbase_within(vbase2, vbase2, level);
+        pt_mem.lemma_walk_from_same_block::<G>(vaddr, vbase2, root, 0);
+        self.lemma_walk_entry_unique_vbase(vbase, vbase2);
+    }
+
+    /// Lemma. Clearing the Dirty flag of entry `(b, i)` keeps the invariants and the
+    /// interpretation, and only clears the flag of the walks that terminate there.
+    pub proof fn lemma_clear_dirty_write(self, b: PAddr, i: nat, entry: u64)
+        requires
+            self.invariants(),
+            self.pt_mem.accessible(b, i),
+            G::from_u64(entry) == G::from_u64(self.pt_mem.read(b, i)).clear_dirty(),
+        ensures
+            Self::new(self.pt_mem.write(b, i, entry), self.constants).invariants(),
+            self.pt_mem.write(b, i, entry).root() == self.pt_mem.root(),
+            self.pt_mem.write(b, i, entry).interpret::<G>() == self.pt_mem.interpret::<G>(),
+            forall|vaddr: VAddr| #[trigger]
+                self.pt_mem.write(b, i, entry).dirty::<G>(vaddr) == (self.pt_mem.dirty::<G>(vaddr)
+                    && self.pt_mem.walk_entry_from::<G>(vaddr, self.pt_mem.root(), 0) != (b, i)),
+    {
+        broadcast use crate::common::pte::group_pte_lemmas;
+
+        let pt_mem = self.pt_mem;
+        let post = pt_mem.write(b, i, entry);
+        let root = pt_mem.root();
+        self.lemma_write_same_translation_preserves_invariants(b, i, entry);
+        self.lemma_invariants_implies_walkable();
+        Self::new(post, self.constants).lemma_invariants_implies_walkable();
+        pt_mem.lemma_contains_root();
+        post.lemma_contains_root();
+        assert(post.read(b, i) == entry);
+        assert forall|vaddr: VAddr| #[trigger]
+            post.dirty::<G>(vaddr) == (pt_mem.dirty::<G>(vaddr) && pt_mem.walk_entry_from::<G>(
+                vaddr,
+                root,
+                0,
+            ) != (b, i)) by {
+            // The walk terminates at the same entry, which only changes at `(b, i)`
+            pt_mem.lemma_write_same_target_walk_from::<G>(b, i, entry, vaddr, root, 0);
+            pt_mem.lemma_walk_entry_from::<G>(vaddr, root, 0);
+            post.lemma_walk_entry_from::<G>(vaddr, root, 0);
+            let (b2, i2) = pt_mem.walk_entry_from::<G>(vaddr, root, 0);
+            if b2 != b || i2 != i {
+                pt_mem.lemma_write_keeps_other_entry(b, i, entry, b2, i2);
+            }
+        }
+    }
+
     /// Lemma. Empty table --construct_node--> empty node.
     pub proof fn lemma_empty_table_constructs_empty_node(self, base: PAddr)
         requires
@@ -632,12 +1083,15 @@ impl<G> PageTable<G> where G: GhostPTE {
         }
     }
 
-    /// Lemma. Allocating an intermediate table preserves invariants.
+    /// Lemma. Allocating an intermediate table `table`, yielding `allocated`, and linking it
+    /// at `(base, idx)` preserves invariants.
     pub proof fn lemma_alloc_intermediate_table_preserves_invariants(
         self,
         base: PAddr,
         level: nat,
         idx: nat,
+        allocated: PageTableMem,
+        table: Table,
     )
         requires
             self.invariants(),
@@ -646,10 +1100,10 @@ impl<G> PageTable<G> where G: GhostPTE {
             level + 1 < self.constants.arch.level_count(),
             self.pt_mem.accessible(base, idx),
             !G::from_u64(self.pt_mem.read(base, idx)).valid(),
+            self.pt_mem.allocates(level + 1, allocated, table),
         ensures
             ({
-                let (pt_mem, table) = self.pt_mem.alloc_table(level + 1);
-                let pt_mem = pt_mem.write(
+                let pt_mem = allocated.write(
                     base,
                     idx,
                     G::new(table.base, MemAttr::spec_default(), false).to_u64(),
@@ -659,8 +1113,7 @@ impl<G> PageTable<G> where G: GhostPTE {
     {
         broadcast use crate::common::pte::group_pte_lemmas;
 
-        let (pt_mem, table) = self.pt_mem.alloc_table(level + 1);
-        let pt_mem = pt_mem.write(
+        let pt_mem = allocated.write(
             base,
             idx,
             G::new(table.base, MemAttr::spec_default(), false).to_u64(),
@@ -743,6 +1196,7 @@ impl<G> PageTable<G> where G: GhostPTE {
         level: nat,
         target_level: nat,
         new_pte: G,
+        allocs: Seq<(PageTableMem, Table)>,
         base2: PAddr,
     )
         requires
@@ -753,8 +1207,10 @@ impl<G> PageTable<G> where G: GhostPTE {
             self.pte_valid_frame(new_pte, target_level),
             self.pt_mem.contains_table(base2),
         ensures
-            self.insert(vbase, base, level, target_level, new_pte).0.pt_mem.contains_table(base2),
-            self.insert(vbase, base, level, target_level, new_pte).0.pt_mem.table(base2)
+            self.insert(vbase, base, level, target_level, new_pte, allocs).0.pt_mem.contains_table(
+                base2,
+            ),
+            self.insert(vbase, base, level, target_level, new_pte, allocs).0.pt_mem.table(base2)
                 == self.pt_mem.table(base2),
         decreases target_level - level,
     {
@@ -771,6 +1227,7 @@ impl<G> PageTable<G> where G: GhostPTE {
                         level + 1,
                         target_level,
                         new_pte,
+                        allocs,
                     );
                     self.lemma_insert_preserves_old_tables(
                         vbase,
@@ -778,24 +1235,36 @@ impl<G> PageTable<G> where G: GhostPTE {
                         level + 1,
                         target_level,
                         new_pte,
+                        allocs,
                         base2,
                     )
                 }
-            } else {
-                let (pt_mem, table) = self.pt_mem.alloc_table(level + 1);
-                let pt_mem = pt_mem.write(
+            } else if allocs.len() > 0 && self.pt_mem.allocates(
+                level + 1,
+                allocs[0].0,
+                allocs[0].1,
+            ) {
+                let (allocated, table) = allocs[0];
+                let pt_mem = allocated.write(
                     base,
                     idx,
                     G::new(table.base, MemAttr::spec_default(), false).to_u64(),
                 );
-                self.lemma_alloc_intermediate_table_preserves_invariants(base, level, idx);
-                // Ensures `pt_mem` after `alloc_table` satisfies the invariants
+                // Ensures `pt_mem` after allocating the table satisfies the invariants
+                self.lemma_alloc_intermediate_table_preserves_invariants(
+                    base,
+                    level,
+                    idx,
+                    allocated,
+                    table,
+                );
                 Self::new(pt_mem, self.constants).lemma_insert_preserves_old_tables(
                     vbase,
                     table.base,
                     level + 1,
                     target_level,
                     new_pte,
+                    allocs.drop_first(),
                     base2,
                 );
             }
@@ -810,6 +1279,7 @@ impl<G> PageTable<G> where G: GhostPTE {
         level: nat,
         target_level: nat,
         new_pte: G,
+        allocs: Seq<(PageTableMem, Table)>,
     )
         requires
             self.invariants(),
@@ -818,7 +1288,7 @@ impl<G> PageTable<G> where G: GhostPTE {
             level <= target_level < self.constants.arch.level_count(),
             self.pte_valid_frame(new_pte, target_level),
         ensures
-            self.insert(vbase, base, level, target_level, new_pte).0.pt_mem.root()
+            self.insert(vbase, base, level, target_level, new_pte, allocs).0.pt_mem.root()
                 == self.pt_mem.root(),
         decreases target_level - level,
     {
@@ -836,12 +1306,17 @@ impl<G> PageTable<G> where G: GhostPTE {
                         level + 1,
                         target_level,
                         new_pte,
+                        allocs,
                     )
                 }
-            } else {
+            } else if allocs.len() > 0 && self.pt_mem.allocates(
+                level + 1,
+                allocs[0].0,
+                allocs[0].1,
+            ) {
                 // Allocate intermediate table
-                let (pt_mem, table) = self.pt_mem.alloc_table(level + 1);
-                let pt_mem = pt_mem.write(
+                let (allocated, table) = allocs[0];
+                let pt_mem = allocated.write(
                     base,
                     idx,
                     G::new(table.base, MemAttr::spec_default(), false).to_u64(),
@@ -849,9 +1324,22 @@ impl<G> PageTable<G> where G: GhostPTE {
                 // `s2` is the state after allocating an intermediate table
                 let s2 = Self::new(pt_mem, self.constants);
 
-                self.lemma_alloc_intermediate_table_preserves_invariants(base, level, idx);
+                self.lemma_alloc_intermediate_table_preserves_invariants(
+                    base,
+                    level,
+                    idx,
+                    allocated,
+                    table,
+                );
                 assert(s2.invariants());
-                s2.lemma_insert_preserves_root(vbase, table.base, level + 1, target_level, new_pte)
+                s2.lemma_insert_preserves_root(
+                    vbase,
+                    table.base,
+                    level + 1,
+                    target_level,
+                    new_pte,
+                    allocs.drop_first(),
+                )
             }
         }
     }
@@ -864,6 +1352,7 @@ impl<G> PageTable<G> where G: GhostPTE {
         level: nat,
         target_level: nat,
         new_pte: G,
+        allocs: Seq<(PageTableMem, Table)>,
     )
         requires
             self.invariants(),
@@ -872,8 +1361,9 @@ impl<G> PageTable<G> where G: GhostPTE {
             level <= target_level < self.constants.arch.level_count(),
             self.pte_valid_frame(new_pte, target_level),
         ensures
-            self.insert(vbase, base, level, target_level, new_pte).0.constants == self.constants,
-            self.insert(vbase, base, level, target_level, new_pte).0.invariants(),
+            self.insert(vbase, base, level, target_level, new_pte, allocs).0.constants
+                == self.constants,
+            self.insert(vbase, base, level, target_level, new_pte, allocs).0.invariants(),
         decreases target_level - level,
     {
         let idx = self.constants.arch.pte_index(vbase, level);
@@ -890,12 +1380,17 @@ impl<G> PageTable<G> where G: GhostPTE {
                         level + 1,
                         target_level,
                         new_pte,
+                        allocs,
                     )
                 }
-            } else {
+            } else if allocs.len() > 0 && self.pt_mem.allocates(
+                level + 1,
+                allocs[0].0,
+                allocs[0].1,
+            ) {
                 // Allocate intermediate table
-                let (pt_mem, table) = self.pt_mem.alloc_table(level + 1);
-                let pt_mem = pt_mem.write(
+                let (allocated, table) = allocs[0];
+                let pt_mem = allocated.write(
                     base,
                     idx,
                     G::new(table.base, MemAttr::spec_default(), false).to_u64(),
@@ -903,7 +1398,13 @@ impl<G> PageTable<G> where G: GhostPTE {
                 // `s2` is the state after allocating an intermediate table
                 let s2 = Self::new(pt_mem, self.constants);
 
-                self.lemma_alloc_intermediate_table_preserves_invariants(base, level, idx);
+                self.lemma_alloc_intermediate_table_preserves_invariants(
+                    base,
+                    level,
+                    idx,
+                    allocated,
+                    table,
+                );
                 assert(s2.invariants());
                 s2.lemma_insert_preserves_invariants(
                     vbase,
@@ -911,19 +1412,24 @@ impl<G> PageTable<G> where G: GhostPTE {
                     level + 1,
                     target_level,
                     new_pte,
+                    allocs.drop_first(),
                 );
             }
         }
     }
 
-    /// Lemma. When `insert` allocates an intermediate table, the result must succeed (`Ok`).
-    pub proof fn lemma_insert_intermediate_node_results_ok(
+    /// Lemma. A successful `insert` writes the leaf entry last.
+    ///
+    /// Every earlier write stores a descriptor of a table that did not exist before `insert`,
+    /// so a concurrent walker cannot reach the new mapping until the leaf entry is written.
+    pub proof fn lemma_insert_writes_leaf_last(
         self,
         vbase: VAddr,
         base: PAddr,
         level: nat,
         target_level: nat,
         new_pte: G,
+        allocs: Seq<(PageTableMem, Table)>,
     )
         requires
             self.invariants(),
@@ -931,50 +1437,94 @@ impl<G> PageTable<G> where G: GhostPTE {
             level == self.pt_mem.table(base).level,
             level <= target_level < self.constants.arch.level_count(),
             self.pte_valid_frame(new_pte, target_level),
+            self.insert(vbase, base, level, target_level, new_pte, allocs).1 is Ok,
         ensures
             ({
-                let idx = self.constants.arch.pte_index(vbase, level);
-                let pte = G::from_u64(self.pt_mem.read(base, idx));
-                level < target_level && !pte.valid() ==> self.insert(
-                    vbase,
-                    base,
-                    level,
-                    target_level,
-                    new_pte,
-                ).1 is Ok
+                let writes = self.insert_writes(vbase, base, level, target_level, new_pte, allocs);
+                &&& writes.len() > 0
+                &&& writes.last().2 == new_pte.to_u64()
+                &&& forall|i: int|
+                    0 <= i < writes.len() - 1 ==> exists|table_base: PAddr|
+                        !self.pt_mem.contains_table(table_base) && #[trigger] writes[i].2
+                            == G::new(table_base, MemAttr::spec_default(), false).to_u64()
             }),
         decreases target_level - level,
     {
         let idx = self.constants.arch.pte_index(vbase, level);
         let pte = G::from_u64(self.pt_mem.read(base, idx));
-        if level < target_level && !pte.valid() {
-            // Allocate intermediate table
-            let (pt_mem, table) = self.pt_mem.alloc_table(level + 1);
-            let pt_mem = pt_mem.write(
-                base,
-                idx,
-                G::new(table.base, MemAttr::spec_default(), false).to_u64(),
-            );
-            // `s2` is the state after allocating an intermediate table
-            let s2 = Self::new(pt_mem, self.constants);
-            self.lemma_alloc_intermediate_table_preserves_invariants(base, level, idx);
-            assert(s2.invariants());
-
-            let (_, insert_res) = s2.insert(vbase, table.base, level + 1, target_level, new_pte);
-            let idx = s2.constants.arch.pte_index(vbase, level + 1);
-            let pte = G::from_u64(s2.pt_mem.read(table.base, idx));
-            // New table is empty
-            assert(s2.pt_mem.read(table.base, idx) == 0);
-            assert(!pte.valid());
-
-            // Recursive proof for the next level
-            s2.lemma_insert_intermediate_node_results_ok(
-                vbase,
-                table.base,
-                level + 1,
-                target_level,
-                new_pte,
-            );
+        assert(self.pt_mem.accessible(base, idx));
+
+        if level < target_level {
+            if pte.valid() {
+                if !pte.huge() {
+                    // Recursively insert into the next table
+                    self.lemma_insert_writes_leaf_last(
+                        vbase,
+                        pte.addr(),
+                        level + 1,
+                        target_level,
+                        new_pte,
+                        allocs,
+                    );
+                }
+            } else {
+                // Allocate intermediate table, which succeeded as `insert` succeeded
+                let (allocated, table) = allocs[0];
+                assert(allocs.len() > 0 && self.pt_mem.allocates(level + 1, allocated, table));
+                let entry = G::new(table.base, MemAttr::spec_default(), false).to_u64();
+                // `s2` is the state after allocating an intermediate table
+                let s2 = Self::new(allocated.write(base, idx, entry), self.constants);
+                let rest_allocs = allocs.drop_first();
+
+                self.lemma_alloc_intermediate_table_preserves_invariants(
+                    base,
+                    level,
+                    idx,
+                    allocated,
+                    table,
+                );
+                assert(s2.invariants());
+                s2.lemma_insert_writes_leaf_last(
+                    vbase,
+                    table.base,
+                    level + 1,
+                    target_level,
+                    new_pte,
+                    rest_allocs,
+                );
+
+                let writes = self.insert_writes(vbase, base, level, target_level, new_pte, allocs);
+                let rest = s2.insert_writes(
+                    vbase,
+                    table.base,
+                    level + 1,
+                    target_level,
+                    new_pte,
+                    rest_allocs,
+                );
+                assert(writes == seq![(base, idx, entry)].add(rest));
+                assert forall|i: int| 0 <= i < writes.len() - 1 implies exists|table_base: PAddr|
+                    !self.pt_mem.contains_table(table_base) && #[trigger] writes[i].2 == G::new(
+                        table_base,
+                        MemAttr::spec_default(),
+                        false,
+                    ).to_u64() by {
+                    if i == 0 {
+                        // The descriptor of the newly allocated table
+                        assert(!self.pt_mem.contains_table(table.base));
+                    } else {
+                        // Descriptors written by the recursive `insert` point to tables that
+                        // `s2` does not have, which `self` does not have either
+                        assert(writes[i] == rest[i - 1]);
+                        let table_base = choose|table_base: PAddr|
+                            !s2.pt_mem.contains_table(table_base) && #[trigger] rest[i - 1].2
+                                == G::new(table_base, MemAttr::spec_default(), false).to_u64();
+                        if self.pt_mem.contains_table(table_base) {
+                            assert(s2.pt_mem.contains_table(table_base));
+                        }
+                    }
+                }
+            }
         }
     }
 
@@ -987,6 +1537,7 @@ impl<G> PageTable<G> where G: GhostPTE {
         level: nat,
         target_level: nat,
         new_pte: G,
+        allocs: Seq<(PageTableMem, Table)>,
         base2: PAddr,
     )
         requires
@@ -998,11 +1549,12 @@ impl<G> PageTable<G> where G: GhostPTE {
             self.pt_mem.contains_table(base2),
             !self.collect_table_chain(vbase, base, level).contains(base2),
         ensures
-            self.insert(vbase, base, level, target_level, new_pte).0.pt_mem.contains_table(base2),
-            self.insert(vbase, base, level, target_level, new_pte).0.pt_mem.table(base2)
-                == self.pt_mem.table(base2),
-            self.insert(vbase, base, level, target_level, new_pte).0.pt_mem.table_view(base2)
-                == self.pt_mem.table_view(base2),
+            ({
+                let s2 = self.insert(vbase, base, level, target_level, new_pte, allocs).0;
+                &&& s2.pt_mem.contains_table(base2)
+                &&& s2.pt_mem.table(base2) == self.pt_mem.table(base2)
+                &&& s2.pt_mem.table_view(base2) == self.pt_mem.table_view(base2)
+            }),
         decreases target_level - level,
     {
         broadcast use crate::common::pte::group_pte_lemmas;
@@ -1029,20 +1581,31 @@ impl<G> PageTable<G> where G: GhostPTE {
                         level + 1,
                         target_level,
                         new_pte,
+                        allocs,
                         base2,
                     )
                 }
-            } else {
+            } else if allocs.len() > 0 && self.pt_mem.allocates(
+                level + 1,
+                allocs[0].0,
+                allocs[0].1,
+            ) {
                 // Allocate intermediate table — create a new table and link it from `base` via the PTE
-                let (pt_mem, table) = self.pt_mem.alloc_table(level + 1);
-                let pt_mem = pt_mem.write(
+                let (allocated, table) = allocs[0];
+                let pt_mem = allocated.write(
                     base,
                     idx,
                     G::new(table.base, MemAttr::spec_default(), false).to_u64(),
                 );
                 // `s2` is the state after allocating an intermediate table
                 let s2 = Self::new(pt_mem, self.constants);
-                self.lemma_alloc_intermediate_table_preserves_invariants(base, level, idx);
+                self.lemma_alloc_intermediate_table_preserves_invariants(
+                    base,
+                    level,
+                    idx,
+                    allocated,
+                    table,
+                );
                 assert(s2.invariants());
 
                 let pte = G::new(table.base, MemAttr::spec_default(), false);
@@ -1061,6 +1624,7 @@ impl<G> PageTable<G> where G: GhostPTE {
                     level + 1,
                     target_level,
                     new_pte,
+                    allocs.drop_first(),
                     base2,
                 );
             }
@@ -1077,6 +1641,7 @@ impl<G> PageTable<G> where G: GhostPTE {
         level: nat,
         target_level: nat,
         new_pte: G,
+        allocs: Seq<(PageTableMem, Table)>,
         base2: PAddr,
         level2: nat,
     )
@@ -1097,17 +1662,19 @@ impl<G> PageTable<G> where G: GhostPTE {
                 level,
                 target_level,
                 new_pte,
+                allocs,
             ).0.construct_node(base2, level2),
         decreases self.constants.arch.level_count() - level2,
     {
-        let s2 = self.insert(vbase, base, level, target_level, new_pte).0;
-        self.lemma_insert_preserves_invariants(vbase, base, level, target_level, new_pte);
+        let s2 = self.insert(vbase, base, level, target_level, new_pte, allocs).0;
+        self.lemma_insert_preserves_invariants(vbase, base, level, target_level, new_pte, allocs);
         self.lemma_insert_preserves_tables_outside_chain(
             vbase,
             base,
             level,
             target_level,
             new_pte,
+            allocs,
             base2,
         );
         assert(self.pt_mem.table_view(base2) == s2.pt_mem.table_view(base2));
@@ -1145,6 +1712,7 @@ impl<G> PageTable<G> where G: GhostPTE {
                         level,
                         target_level,
                         new_pte,
+                        allocs,
                         pte.addr(),
                         level2 + 1,
                     );
@@ -1161,6 +1729,8 @@ impl<G> PageTable<G> where G: GhostPTE {
     }
 
     /// Lemma. The implementation-level insertion is consistent with the tree model.
+    ///
+    /// A failed insertion may also be out of page table memory, which the model does not have.
     pub proof fn lemma_insert_consistent_with_model(
         self,
         vbase: VAddr,
@@ -1168,6 +1738,7 @@ impl<G> PageTable<G> where G: GhostPTE {
         level: nat,
         target_level: nat,
         new_pte: G,
+        allocs: Seq<(PageTableMem, Table)>,
     )
         requires
             self.invariants(),
@@ -1177,20 +1748,34 @@ impl<G> PageTable<G> where G: GhostPTE {
             self.pte_valid_frame(new_pte, target_level),
         ensures
             ({
-                let (s2, res) = self.insert(vbase, base, level, target_level, new_pte);
+                let (s2, res) = self.insert(vbase, base, level, target_level, new_pte, allocs);
                 let node = self.construct_node(base, level);
                 let node2 = s2.construct_node(base, level);
                 let path = PTTreePath::from_vaddr(vbase, self.constants.arch, level, target_level);
-                (node2, res) == node.insert(path, self.pte_to_frame(new_pte, target_level))
+                res is Ok ==> (node2, res) == node.insert(
+                    path,
+                    self.pte_to_frame(new_pte, target_level),
+                )
             }),
         decreases target_level - level,
     {
         broadcast use crate::common::pte::group_pte_lemmas;
 
         let new_frame = self.pte_to_frame(new_pte, target_level);
-        let (s2, res) = self.insert(vbase, base, level, target_level, new_pte);
-        self.lemma_insert_preserves_invariants(vbase, base, level, target_level, new_pte);
-        self.lemma_insert_preserves_old_tables(vbase, base, level, target_level, new_pte, base);
+        let (s2, res) = self.insert(vbase, base, level, target_level, new_pte, allocs);
+        if res is Err {
+            return;
+        }
+        self.lemma_insert_preserves_invariants(vbase, base, level, target_level, new_pte, allocs);
+        self.lemma_insert_preserves_old_tables(
+            vbase,
+            base,
+            level,
+            target_level,
+            new_pte,
+            allocs,
+            base,
+        );
 
         let node = self.construct_node(base, level);
         let node2 = s2.construct_node(base, level);
@@ -1230,6 +1815,7 @@ impl<G> PageTable<G> where G: GhostPTE {
                         level + 1,
                         target_level,
                         new_pte,
+                        allocs,
                     ).0);
                     // Recursive call shows subnode is updated according to model
                     self.lemma_insert_consistent_with_model(
@@ -1238,6 +1824,7 @@ impl<G> PageTable<G> where G: GhostPTE {
                         level + 1,
                         target_level,
                         new_pte,
+                        allocs,
                     );
                     PTTreePath::lemma_from_vaddr_step(vbase, arch, level, target_level);
                     assert(s2.construct_node(subtable_base, level + 1) == new_subnode);
@@ -1251,6 +1838,7 @@ impl<G> PageTable<G> where G: GhostPTE {
                         level + 1,
                         target_level,
                         new_pte,
+                        allocs,
                         base,
                     );
                     assert(s2.pt_mem.table_view(base) == self.pt_mem.table_view(base));
@@ -1279,6 +1867,7 @@ impl<G> PageTable<G> where G: GhostPTE {
                                     level,
                                     target_level,
                                     new_pte,
+                                    allocs,
                                     pte_i.addr(),
                                     level + 1,
                                 );
@@ -1289,14 +1878,22 @@ impl<G> PageTable<G> where G: GhostPTE {
                     assert(node2.entries == right.entries);
                 }
             } else {
-                let (allocated, table) = self.pt_mem.alloc_table(level + 1);
+                // The table was allocated, as `insert` succeeded
+                let (allocated, table) = allocs[0];
+                assert(allocs.len() > 0 && self.pt_mem.allocates(level + 1, allocated, table));
                 let written = allocated.write(
                     base,
                     idx,
                     G::new(table.base, MemAttr::spec_default(), false).to_u64(),
                 );
                 let subtable_base = table.base;
-                self.lemma_alloc_intermediate_table_preserves_invariants(base, level, idx);
+                self.lemma_alloc_intermediate_table_preserves_invariants(
+                    base,
+                    level,
+                    idx,
+                    allocated,
+                    table,
+                );
 
                 // s3 is the state after allocating a new intermediate table
                 let s3 = Self::new(written, self.constants);
@@ -1310,7 +1907,15 @@ impl<G> PageTable<G> where G: GhostPTE {
                 ));
 
                 let new_subnode = subnode.insert(remain, new_frame).0;
-                assert(s2 == s3.insert(vbase, table.base, level + 1, target_level, new_pte).0);
+                let rest_allocs = allocs.drop_first();
+                assert(s2 == s3.insert(
+                    vbase,
+                    table.base,
+                    level + 1,
+                    target_level,
+                    new_pte,
+                    rest_allocs,
+                ).0);
                 // Recursive call shows subnode is updated according to model
                 s3.lemma_insert_consistent_with_model(
                     vbase,
@@ -1318,6 +1923,7 @@ impl<G> PageTable<G> where G: GhostPTE {
                     level + 1,
                     target_level,
                     new_pte,
+                    rest_allocs,
                 );
                 PTTreePath::lemma_from_vaddr_step(vbase, arch, level, target_level);
                 assert(s2.construct_node(subtable_base, level + 1) == new_subnode);
@@ -1937,15 +2543,94 @@ impl<G> PageTable<G> where G: GhostPTE {
         }
     }
 
-    /// Axiom. The interpreted view of the page table memory is consistent with the view derived
+    /// Lemma. The hardware page table walk over `pt_mem` visits the same entries as the
+    /// specification-level walk, and a valid terminal entry always points to a frame.
+    pub proof fn lemma_hw_walk_consistent_with_walk(self, vaddr: VAddr, base: PAddr, level: nat)
+        requires
+            self.invariants(),
+            self.pt_mem.contains_table(base),
+            level == self.pt_mem.table(base).level,
+            level < self.constants.arch.level_count(),
+        ensures
+            self.pt_mem.walk_from::<G>(vaddr, base, level) == self.walk(vaddr, base, level),
+            ({
+                let (pte, level2) = self.walk(vaddr, base, level);
+                &&& level <= level2 < self.constants.arch.level_count()
+                &&& pte.valid() ==> self.pte_points_to_frame(pte, level2)
+            }),
+        decreases self.constants.arch.level_count() - level,
+    {
+        let arch = self.constants.arch;
+        let idx = arch.pte_index(vaddr, level);
+        arch.lemma_pte_index_bound(vaddr, level);
+        assert(self.pt_mem.accessible(base, idx));
+        let pte = G::from_u64(self.pt_mem.read(base, idx));
+        if self.pte_points_to_table(pte, level) {
+            // Invariants ensure the subtable exists and is one level higher
+            assert(self.pt_mem.contains_table(pte.addr()));
+            assert(self.pt_mem.table(pte.addr()).level == level + 1);
+            self.lemma_hw_walk_consistent_with_walk(vaddr, pte.addr(), level + 1);
+        }
+    }
+
+    /// Theorem. The interpreted view of the page table memory is consistent with the view derived
     /// from the model tree, ensuring semantic agreement between hardware and software views.
-    #[verifier::external_body]
     pub proof fn model_consistent_with_hardware(self)
         requires
             self.invariants(),
         ensures
-            self@@ == PageTableState::new(self.pt_mem.interpret(), self.constants),
+            self@@ == PageTableState::new(self.pt_mem.interpret::<G>(), self.constants),
     {
+        let arch = self.constants.arch;
+        let root = self.pt_mem.root();
+        let model = self@;
+        let hw = self.pt_mem.interpret::<G>();
+        self.pt_mem.lemma_contains_root();
+        self.construct_node_facts(root, 0);
+        self.lemma_view_implies_invariants();
+        assert(model.arch() == arch);
+
+        // 1. Any mapping reached by the hardware walk is also in the model.
+        assert forall|vbase: VAddr, frame: Frame| #[trigger]
+            hw.contains_pair(vbase, frame) implies model.mappings().contains_pair(vbase, frame) by {
+            assert(self.pt_mem.walk::<G>(vbase, frame));
+            let (pte, level) = self.walk(vbase, root, 0);
+            self.lemma_hw_walk_consistent_with_walk(vbase, root, 0);
+            // `walk` reaches the same entry as `visit` on the model tree
+            self.lemma_walk_consistent_with_model(vbase, root, 0);
+            assert(self.pte_to_frame(pte, level) == frame);
+            assert(model.query(vbase) == PagingResult::Ok((vbase, frame)));
+            // `query` succeeds with `(vbase, frame)`, so the model has the mapping
+            model.lemma_query_ok_implies_mapping_exist(vbase);
+            model.lemma_mapping_exist_implies_query_ok(vbase);
+            assert(model.mapping_for(vbase) == (vbase, frame));
+        }
+
+        // 2. Any mapping in the model is also reached by the hardware walk.
+        assert forall|vbase: VAddr, frame: Frame| #[trigger]
+            model.mappings().contains_pair(vbase, frame) implies hw.contains_pair(vbase, frame) by {
+            assert(vbase.within(vbase, frame.size.as_nat()));
+            assert(model.has_mapping_for(vbase));
+            model.lemma_mapping_exist_implies_query_ok(vbase);
+            // The mapping found by `query` is exactly `(vbase, frame)` (no overlap in vmem)
+            let (vbase2, frame2) = model.mapping_for(vbase);
+            model.lemma_mappings_nonoverlap_in_vmem();
+            assert(VAddr::overlap(vbase2, frame2.size.as_nat(), vbase, frame.size.as_nat()));
+            assert(vbase2 == vbase && frame2 == frame);
+
+            let (pte, level) = self.walk(vbase, root, 0);
+            self.lemma_hw_walk_consistent_with_walk(vbase, root, 0);
+            self.lemma_walk_consistent_with_model(vbase, root, 0);
+            // `visit` ends with a frame entry, so `walk` ends with a valid frame entry
+            assert(pte.valid());
+            assert(self.pte_to_frame(pte, level) == frame);
+            assert(arch.vbase(vbase, level) == vbase);
+            assert(self.pt_mem.walk::<G>(vbase, frame));
+            // The frame reached by the walk is unique
+            assert(hw[vbase] == frame);
+        }
+
+        lemma_map_eq_pair(model.mappings(), hw);
     }
 }
 
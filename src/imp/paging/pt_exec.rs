@@ -1,18 +1,19 @@
 //! Executable page table implementation.
-use std::marker::PhantomData;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
 use vstd::prelude::*;
 
 use super::pt::PageTable;
 use crate::{
     common::{
-        addr::{PAddrExec, VAddr, VAddrExec},
+        addr::{PAddr, PAddrExec, VAddr, VAddrExec},
         arch::PTArch,
-        frame::{Frame, FrameExec, FrameSize, MemAttr},
+        frame::{Frame, FrameExec, MemAttr},
         pte::{ExecPTE, GhostPTE},
         PagingResult,
     },
     imp::{interface::PTConstantsExec, tree::path::PTTreePath},
-    spec::memory::PageTableMemExec,
+    spec::memory::{PageTableMem, PageTableMemExec, TLBExec, TLBOp, TLBTag, Table},
 };
 
 verus! {
@@ -105,10 +106,50 @@ impl<M, G, E> PageTableExec<M, G, E> where M: PageTableMemExec, G: GhostPTE, E:
         }
     }
 
+    /// Locate the entry where the walk for `vaddr` from `base` terminates, and return it with
+    /// the level of its table.
+    pub fn walk_entry(&self, vaddr: VAddrExec, base: PAddrExec, level: usize) -> (res: (
+        PAddrExec,
+        usize,
+        usize,
+    ))
+        requires
+            self@.invariants(),
+            self.pt_mem@.contains_table(base@),
+            self.pt_mem@.table(base@).level == level,
+        ensures
+            (res.0@, res.1 as nat) == self.pt_mem@.walk_entry_from::<G>(
+                vaddr@,
+                base@,
+                level as nat,
+            ),
+            self.pt_mem@.accessible(res.0@, res.1 as nat),
+            self.pt_mem@.table(res.0@).level == res.2,
+            res.2 < self.arch().level_count(),
+        decreases self.arch().level_count() - level,
+    {
+        proof {
+            self@.lemma_invariants_implies_walkable();
+            self.pt_mem@.lemma_walk_entry_from::<G>(vaddr@, base@, level as nat);
+        }
+        let idx = self.constants.arch.pte_index(vaddr, level);
+        assert(self.pt_mem@.accessible(base@, idx as nat));
+        let pte = E::from_u64(self.pt_mem.read(base, idx));
+        if level < self.constants.arch.level_count() - 1 && pte.valid() && !pte.huge() {
+            self.walk_entry(vaddr, pte.addr(), level + 1)
+        } else {
+            (base, idx, level)
+        }
+    }
+
     /// Insert a page table entry into the page table, creates intermediate tables if necessary.
     ///
     /// `target_level` is the level at which the entry should be inserted.
     /// `new_pte` is the entry to be inserted.
+    ///
+    /// Also returns the entries written, in order, and the tables allocated, as the
+    /// `(pt_mem, table)` pairs `PageTable::insert` takes. The leaf entry is written last
+    /// (`PageTable::lemma_insert_writes_leaf_last`).
     pub fn insert(
         &mut self,
         vbase: VAddrExec,
@@ -116,7 +157,11 @@ impl<M, G, E> PageTableExec<M, G, E> where M: PageTableMemExec, G: GhostPTE, E:
         level: usize,
         target_level: usize,
         new_pte: E,
-    ) -> (res: PagingResult)
+    ) -> (res: (
+        PagingResult,
+        Ghost<Seq<(PAddr, nat, u64)>>,
+        Ghost<Seq<(PageTableMem, Table)>>,
+    ))
         requires
             old(self)@.invariants(),
             level <= target_level < old(self).arch().level_count(),
@@ -124,14 +169,30 @@ impl<M, G, E> PageTableExec<M, G, E> where M: PageTableMemExec, G: GhostPTE, E:
             old(self).pt_mem@.table(base@).level == level,
             old(self)@.pte_valid_frame(new_pte@, target_level as nat),
         ensures
-            (self@, res) == old(self)@.insert(
+            res.0 is Ok ==> (self@, res.0) == old(self)@.insert(
                 vbase@,
                 base@,
                 level as nat,
                 target_level as nat,
                 new_pte@,
+                res.2@,
             ),
-            res is Err ==> old(self) == self,
+            res.0 is Ok ==> res.1@ == old(self)@.insert_writes(
+                vbase@,
+                base@,
+                level as nat,
+                target_level as nat,
+                new_pte@,
+                res.2@,
+            ),
+            self.constants == old(self).constants,
+            // Conflicting entry, or out of page table memory: intermediate tables may be left
+            // linked, but empty
+            res.0 is Err ==> {
+                &&& self@.invariants()
+                &&& self@.pt_mem.root() == old(self)@.pt_mem.root()
+                &&& self@.pt_mem.interpret::<G>() == old(self)@.pt_mem.interpret::<G>()
+            },
     {
         let idx = self.constants.arch.pte_index(vbase, level);
         assert(self.pt_mem@.accessible(base@, idx as nat));
@@ -139,46 +200,70 @@ impl<M, G, E> PageTableExec<M, G, E> where M: PageTableMemExec, G: GhostPTE, E:
         if level >= target_level {
             // Insert at current level
             if pte.valid() {
-                PagingResult::Err(())
+                (PagingResult::Err(()), Ghost(seq![]), Ghost(seq![]))
             } else {
-                self.pt_mem.write(base, idx, new_pte.to_u64());
-                PagingResult::Ok(())
+                let entry = new_pte.to_u64();
+                self.pt_mem.write(base, idx, entry);
+                (PagingResult::Ok(()), Ghost(seq![(base@, idx as nat, entry)]), Ghost(seq![]))
             }
         } else {
             if pte.valid() {
                 if pte.huge() {
-                    PagingResult::Err(())
+                    (PagingResult::Err(()), Ghost(seq![]), Ghost(seq![]))
                 } else {
                     // Insert at next level
                     self.insert(vbase, pte.addr(), level + 1, target_level, new_pte)
                 }
             } else {
+                // Allocate intermediate table
+                let ghost pt = self@;
+                let table = self.pt_mem.alloc_table(level + 1);
+                if table.is_none() {
+                    // Out of page table memory, nothing is written
+                    return (PagingResult::Err(()), Ghost(seq![]), Ghost(seq![]));
+                }
+                let table = table.unwrap();
+                let ghost allocated = self.pt_mem@;
                 proof {
-                    self@.lemma_alloc_intermediate_table_preserves_invariants(
+                    pt.lemma_alloc_intermediate_table_preserves_invariants(
                         base@,
                         level as nat,
                         idx as nat,
+                        allocated,
+                        table@,
                     );
-                    // Lemma ensures this branch returns Ok
-                    self@.lemma_insert_intermediate_node_results_ok(
-                        vbase@,
-                        base@,
-                        level as nat,
-                        target_level as nat,
-                        new_pte@,
-                    );
-                }
-                // Allocate intermediate table
-                let table = self.pt_mem.alloc_table(level + 1);
-                proof {
-                    assume(table.base@.aligned(FrameSize::Size4K.as_nat()));
+                    // The table is aligned to its size, a multiple of 4K
+                    table.size.lemma_aligned_implies_4k_aligned(table.base@.0);
                 }
                 // Write entry
                 let pte = E::new(table.base, MemAttr::default(), false);
-                self.pt_mem.write(base, idx, pte.to_u64());
+                let entry = pte.to_u64();
+                self.pt_mem.write(base, idx, entry);
 
                 // Insert at next level
-                self.insert(vbase, table.base, level + 1, target_level, new_pte)
+                let (res, writes, allocs) = self.insert(
+                    vbase,
+                    table.base,
+                    level + 1,
+                    target_level,
+                    new_pte,
+                );
+                let ghost all_allocs = seq![(allocated, table@)].add(allocs@);
+                proof {
+                    assert(all_allocs[0] == (allocated, table@));
+                    assert(all_allocs.drop_first() == allocs@);
+                    if res is Err {
+                        // A deeper allocation failed, the new table stays empty
+                        pt.lemma_link_new_table_keeps_interpret(
+                            base@,
+                            level as nat,
+                            idx as nat,
+                            allocated,
+                            table@,
+                        );
+                    }
+                }
+                (res, Ghost(seq![(base@, idx as nat, entry)].add(writes@)), Ghost(all_allocs))
             }
         }
     }
@@ -192,6 +277,7 @@ impl<M, G, E> PageTableExec<M, G, E> where M: PageTableMemExec, G: GhostPTE, E:
             old(self).pt_mem@.table(base@).level == level,
         ensures
             (self@, res) == old(self)@.remove(vbase@, base@, level as nat),
+            self.constants == old(self).constants,
             res is Err ==> old(self) == self,
     {
         let idx = self.constants.arch.pte_index(vbase, level);
@@ -233,6 +319,7 @@ impl<M, G, E> PageTableExec<M, G, E> where M: PageTableMemExec, G: GhostPTE, E:
             old(self).pt_mem@.table(base@).level == level,
         ensures
             self@ == old(self)@.prune(vaddr@, base@, level as nat),
+            self.constants == old(self).constants,
     {
         let idx = self.constants.arch.pte_index(vaddr, level);
         assert(self.pt_mem@.accessible(base@, idx as nat));
@@ -261,10 +348,19 @@ impl<M, G, E> PageTableExec<M, G, E> where M: PageTableMemExec, G: GhostPTE, E:
             assert(self.pt_mem@.contains_table(pte@.addr()));
 
             if self.is_table_empty(pte.addr(), level + 1) {
-                // If subtable is empty, deallocate the table, and mark the entry as invalid
-                self.pt_mem.dealloc_table(pte.addr());
-                assert(self.pt_mem@.accessible(base@, idx as nat));
+                // If subtable is empty, mark the entry as invalid, and deallocate the table.
+                // The entry is invalidated first so the walker never reaches a deallocated table.
+                proof {
+                    self.pt_mem@.lemma_dealloc_write_commute(
+                        pte@.addr(),
+                        base@,
+                        idx as nat,
+                        G::empty().to_u64(),
+                    );
+                }
                 self.pt_mem.write(base, idx, E::empty().to_u64());
+                assert(self.pt_mem@.contains_table(pte@.addr()));
+                self.pt_mem.dealloc_table(pte.addr());
             }
         }
     }
@@ -340,56 +436,82 @@ impl<M, G, E> PageTableExec<M, G, E> where M: PageTableMemExec, G: GhostPTE, E:
             frame.base.0 + frame.size.as_nat() <= old(self).constants.pmem_ub.0,
         ensures
             self@.invariants(),
+            self.constants == old(self).constants,
             ({
                 let (s2, r) = old(self)@@.map(vbase@, frame@);
-                r is Ok == res is Ok && s2 == self@@
+                &&& res is Ok ==> r is Ok && s2 == self@@
+                &&& r is Err ==> res is Err
             }),
+            // Fails without changing the mappings, also when out of page table memory
+            res is Err ==> self@.pt_mem.interpret::<G>() == old(self)@.pt_mem.interpret::<G>(),
     {
         broadcast use crate::common::pte::group_pte_lemmas;
 
         let target_level = self.constants.arch.level_of_frame_size(frame.size);
         let huge = target_level < self.constants.arch.level_count() - 1;
         proof {
-            assume(frame.base@.aligned(FrameSize::Size4K.as_nat()));
+            frame.size.lemma_aligned_implies_4k_aligned(frame.base@.0);
         }
         let new_pte = E::new(frame.base, frame.attr, huge);
 
+        let ghost pt = self@;
+        let ghost root = self.pt_mem@.root();
         proof {
-            let root = self.pt_mem@.root();
-            self.view().construct_node_facts(root, 0);
-            // Ensures #1
-            self.view().lemma_insert_preserves_invariants(
-                vbase@,
-                root,
-                0,
-                target_level as nat,
-                new_pte@,
-            );
-            // Ensures #2
-            self.view().lemma_insert_consistent_with_model(
-                vbase@,
-                root,
-                0,
-                target_level as nat,
-                new_pte@,
-            );
-            self.view().lemma_insert_preserves_root(vbase@, root, 0, target_level as nat, new_pte@);
+            pt.construct_node_facts(root, 0);
         }
 
-        self.insert(vbase, self.pt_mem.root(), 0, target_level, new_pte)
+        let (res, _, allocs) = self.insert(vbase, self.pt_mem.root(), 0, target_level, new_pte);
+        proof {
+            if res is Ok {
+                // Ensures #1
+                pt.lemma_insert_preserves_invariants(
+                    vbase@,
+                    root,
+                    0,
+                    target_level as nat,
+                    new_pte@,
+                    allocs@,
+                );
+                // Ensures #2
+                pt.lemma_insert_consistent_with_model(
+                    vbase@,
+                    root,
+                    0,
+                    target_level as nat,
+                    new_pte@,
+                    allocs@,
+                );
+                pt.lemma_insert_preserves_root(
+                    vbase@,
+                    root,
+                    0,
+                    target_level as nat,
+                    new_pte@,
+                    allocs@,
+                );
+            }
+        }
+        res
     }
 
-    /// Remove the mapping for a given virtual base address.
-    pub fn unmap(&mut self, vbase: VAddrExec) -> (res: PagingResult)
+    /// Remove the mapping for a given virtual base address, and invalidate it in the TLB
+    /// through `tlb` once it is removed from the page table.
+    pub fn unmap<T: TLBExec>(&mut self, vbase: VAddrExec, tlb: &mut T) -> (res: PagingResult)
         requires
             old(self)@.invariants(),
         ensures
             self@.invariants(),
+            self.constants == old(self).constants,
             ({
                 let (s2, r) = old(self)@@.unmap(vbase@);
                 r is Ok == res is Ok && s2 == self@@
             }),
+            res is Ok ==> tlb.issued() == old(tlb).issued().push(
+                TLBOp::VA(TLBTag(old(self).constants.tag as nat), vbase@),
+            ),
+            res is Err ==> tlb.issued() == old(tlb).issued(),
     {
+        let tag = self.constants.tag;
         let ghost root = self.pt_mem@.root();
         proof {
             self@.construct_node_facts(root, 0);
@@ -410,9 +532,319 @@ impl<M, G, E> PageTableExec<M, G, E> where M: PageTableMemExec, G: GhostPTE, E:
         }
         if res.is_ok() {
             self.prune(vbase, self.pt_mem.root(), 0);
+            tlb.invalidate_va(tag, vbase);
         }
         res
     }
+
+    /// Change the attributes of the page or block mapped at `vbase` to `attr`.
+    ///
+    /// The descriptor is rewritten in place with the same output address and block size, so
+    /// the translation never goes through an invalid entry and no break-before-make is needed.
+    /// `vbase` is then invalidated through `tlb`, so that no cached translation keeps the old
+    /// attributes.
+    ///
+    /// Changing the memory type or shareability would need break-before-make, so `attr` must
+    /// keep those of the mapping. Otherwise nothing is changed and `Err` is returned.
+    pub fn protect<T: TLBExec>(&mut self, vbase: VAddrExec, attr: MemAttr, tlb: &mut T) -> (res:
+        PagingResult)
+        requires
+            old(self)@.invariants(),
+        ensures
+            self@.invariants(),
+            self.constants == old(self).constants,
+            ({
+                let mappings = old(self).pt_mem@.interpret::<G>();
+                &&& res is Ok == (mappings.contains_key(vbase@)
+                    && mappings[vbase@].attr.spec_same_memory_type(attr))
+                &&& res is Ok ==> self.pt_mem@.interpret::<G>() == mappings.insert(
+                    vbase@,
+                    Frame { attr, ..mappings[vbase@] },
+                )
+                &&& res is Err ==> self.pt_mem@.interpret::<G>() == mappings
+            }),
+            res is Ok ==> tlb.issued() == old(tlb).issued().push(
+                TLBOp::VA(TLBTag(old(self).constants.tag as nat), vbase@),
+            ),
+            res is Err ==> tlb.issued() == old(tlb).issued(),
+    {
+        broadcast use crate::common::pte::group_pte_lemmas;
+
+        let tag = self.constants.tag;
+        proof {
+            self.pt_mem@.lemma_contains_root();
+            self@.lemma_invariants_implies_walkable();
+            self.pt_mem@.lemma_walk_entry_from::<G>(vbase@, self.pt_mem@.root(), 0);
+        }
+        let (base, idx, level) = self.walk_entry(vbase, self.pt_mem.root(), 0);
+        let pte = E::from_u64(self.pt_mem.read(base, idx));
+        if !pte.valid() || vbase.0 != self.constants.arch.vbase(vbase, level).0 {
+            proof {
+                // `vbase` is not the base address of a mapped page or block
+                let mappings = self.pt_mem@.interpret::<G>();
+                if mappings.contains_key(vbase@) {
+                    assert(self.pt_mem@.walk::<G>(vbase@, mappings[vbase@]));
+                }
+            }
+            return PagingResult::Err(());
+        }
+        if !pte.attr().same_memory_type(&attr) {
+            proof {
+                // The mapping at `vbase` carries the attributes of `pte`
+                let mappings = self.pt_mem@.interpret::<G>();
+                if mappings.contains_key(vbase@) {
+                    assert(self.pt_mem@.walk::<G>(vbase@, mappings[vbase@]));
+                }
+            }
+            return PagingResult::Err(());
+        }
+        proof {
+            // The output address is aligned to the page or block size, thus to 4K
+            assert(self@.pte_points_to_frame(pte@, level as nat));
+            self.arch().frame_size(level as nat).lemma_aligned_implies_4k_aligned(pte@.addr().0);
+        }
+        let entry = E::new(pte.addr(), attr, pte.huge()).to_u64();
+        proof {
+            assert(G::from_u64(entry) == G::new(pte@.addr(), attr, pte@.huge()));
+            self@.lemma_protect_write(vbase@, entry);
+            self@.theorem_protect_changes_attr(vbase@, entry);
+            // The mapping keeps its memory type and shareability
+            let mappings = self.pt_mem@.interpret::<G>();
+            assert(self.pt_mem@.walk::<G>(vbase@, mappings[vbase@]));
+        }
+        self.pt_mem.write(base, idx, entry);
+        tlb.invalidate_va(tag, vbase);
+        PagingResult::Ok(())
+    }
+
+    /// Clear the Dirty flag of entry `(base, idx)`, and return whether it was a valid dirty
+    /// descriptor.
+    ///
+    /// The Dirty flag does not take part in the translation, so the entry is written in place
+    /// without break-before-make.
+    fn clear_dirty_entry(&mut self, base: PAddrExec, idx: usize) -> (res: bool)
+        requires
+            old(self)@.invariants(),
+            old(self).pt_mem@.accessible(base@, idx as nat),
+        ensures
+            self@.invariants(),
+            self.constants == old(self).constants,
+            self.pt_mem@.root() == old(self).pt_mem@.root(),
+            self.pt_mem@.interpret::<G>() == old(self).pt_mem@.interpret::<G>(),
+            res == {
+                let pte = G::from_u64(old(self).pt_mem@.read(base@, idx as nat));
+                pte.valid() && pte.dirty()
+            },
+            res ==> forall|vaddr: VAddr| #[trigger]
+                self.pt_mem@.dirty::<G>(vaddr) == (old(self).pt_mem@.dirty::<G>(vaddr)
+                    && old(self).pt_mem@.walk_entry_from::<G>(vaddr, old(self).pt_mem@.root(), 0)
+                    != (base@, idx as nat)),
+            !res ==> self.pt_mem@ == old(self).pt_mem@,
+    {
+        broadcast use crate::common::pte::group_pte_lemmas;
+
+        let pte = E::from_u64(self.pt_mem.read(base, idx));
+        if pte.valid() && pte.dirty() {
+            let entry = pte.clear_dirty().to_u64();
+            proof {
+                assert(G::from_u64(entry) == pte@.clear_dirty());
+                self@.lemma_clear_dirty_write(base@, idx as nat, entry);
+            }
+            self.pt_mem.write(base, idx, entry);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Test and clear the Dirty flag of the page or block that translates `vaddr`.
+    ///
+    /// Returns whether it was written since the flag was last cleared. The mappings are
+    /// unchanged, but the caller must invalidate `vaddr` in the TLB before relying on the MMU
+    /// to set the flag again.
+    pub fn test_and_clear_dirty(&mut self, vaddr: VAddrExec) -> (res: bool)
+        requires
+            old(self)@.invariants(),
+        ensures
+            self@.invariants(),
+            self.constants == old(self).constants,
+            self.pt_mem@.interpret::<G>() == old(self).pt_mem@.interpret::<G>(),
+            res == {
+                let pt_mem = old(self).pt_mem@;
+                let (base, idx) = pt_mem.walk_entry_from::<G>(vaddr@, pt_mem.root(), 0);
+                let pte = G::from_u64(pt_mem.read(base, idx));
+                pte.valid() && pte.dirty()
+            },
+    {
+        proof {
+            self.pt_mem@.lemma_contains_root();
+        }
+        let (base, idx, _) = self.walk_entry(vaddr, self.pt_mem.root(), 0);
+        self.clear_dirty_entry(base, idx)
+    }
+
+    /// Test and clear the Dirty flags of all pages and blocks that overlap `(start, size)`,
+    /// e.g. to harvest the pages written during a round of live migration.
+    ///
+    /// Returns the base addresses of the dirty pages and blocks, which is every one that was
+    /// dirty. Each is invalidated through `tlb` right after its flag is cleared, so that the
+    /// next write walks the page table and sets the flag again. A write between the two can
+    /// only hit a page or block returned by this call, so the caller sees it when it copies the
+    /// returned pages afterwards.
+    pub fn collect_dirty<T: TLBExec>(
+        &mut self,
+        start: VAddrExec,
+        size: usize,
+        tlb: &mut T,
+    ) -> (res: Vec<VAddrExec>)
+        requires
+            old(self)@.invariants(),
+            start.0 + size <= usize::MAX,
+        ensures
+            self@.invariants(),
+            self.constants == old(self).constants,
+            self.pt_mem@.interpret::<G>() == old(self).pt_mem@.interpret::<G>(),
+            forall|i| 0 <= i < res.len() ==> #[trigger] res[i].0 < start.0 + size,
+            // Every mapping that overlaps the range and was dirty is returned, and now is clean
+            forall|vbase: VAddr|
+                {
+                    let mappings = old(self).pt_mem@.interpret::<G>();
+                    &&& #[trigger] mappings.contains_key(vbase)
+                    &&& size > 0
+                    &&& vbase.0 < start.0 + size
+                    &&& start.0 < vbase.0 + mappings[vbase].size.as_nat()
+                    &&& old(self).pt_mem@.dirty::<G>(vbase)
+                } ==> {
+                    &&& exists|i| 0 <= i < res.len() && #[trigger] res[i]@ == vbase
+                    &&& !self.pt_mem@.dirty::<G>(vbase)
+                },
+            tlb.issued() == old(tlb).issued() + res@.map_values(
+                |v: VAddrExec| TLBOp::VA(TLBTag(old(self).constants.tag as nat), v@),
+            ),
+    {
+        let ghost old_mem = self.pt_mem@;
+        let ghost mappings = old_mem.interpret::<G>();
+        let ghost tag = TLBTag(self.constants.tag as nat);
+        let end = start.0 + size;
+        let mut dirty = Vec::new();
+        let mut vaddr = start;
+        while vaddr.0 < end
+            invariant
+                self@.invariants(),
+                self.constants == old(self).constants,
+                self.pt_mem@.interpret::<G>() == mappings,
+                mappings == old(self).pt_mem@.interpret::<G>(),
+                old_mem == old(self).pt_mem@,
+                tag == TLBTag(old(self).constants.tag as nat),
+                end == start.0 + size,
+                start.0 <= vaddr.0 <= end,
+                forall|i| 0 <= i < dirty.len() ==> #[trigger] dirty[i].0 < end,
+                tlb.issued() == old(tlb).issued() + dirty@.map_values(
+                    |v: VAddrExec| TLBOp::VA(tag, v@),
+                ),
+                // Mappings that overlap `[start, vaddr)` are done
+                forall|vbase: VAddr|
+                    #[trigger] mappings.contains_key(vbase) && start.0 < vaddr.0 && vbase.0
+                        < vaddr.0 && start.0 < vbase.0 + mappings[vbase].size.as_nat()
+                        && old_mem.dirty::<G>(vbase) ==> {
+                        &&& exists|i| 0 <= i < dirty.len() && #[trigger] dirty[i]@ == vbase
+                        &&& !self.pt_mem@.dirty::<G>(vbase)
+                    },
+                // The others are untouched
+                forall|vbase: VAddr|
+                    #[trigger] mappings.contains_key(vbase) && !(start.0 < vaddr.0 && vbase.0
+                        < vaddr.0 && start.0 < vbase.0 + mappings[vbase].size.as_nat())
+                        ==> self.pt_mem@.dirty::<G>(vbase) == old_mem.dirty::<G>(vbase),
+            decreases end - vaddr.0,
+        {
+            let ghost pre = self.pt_mem@;
+            let ghost pre_dirty = dirty@;
+            proof {
+                pre.lemma_contains_root();
+                self@.lemma_invariants_implies_walkable();
+            }
+            let (base, idx, level) = self.walk_entry(vaddr, self.pt_mem.root(), 0);
+            let vbase = self.constants.arch.vbase(vaddr, level);
+            let fsize = self.constants.arch.frame_size(level).as_usize();
+            proof {
+                pre.lemma_walk_entry_from::<G>(vaddr@, pre.root(), 0);
+                self.arch().lemma_vbase_range_and_alignment(vaddr@, level as nat);
+            }
+            let cleared = self.clear_dirty_entry(base, idx);
+            if cleared {
+                tlb.invalidate_va(self.constants.tag, vbase);
+                dirty.push(vbase);
+                proof {
+                    let ops = pre_dirty.map_values(|v: VAddrExec| TLBOp::VA(tag, v@));
+                    assert(dirty@ == pre_dirty.push(vbase));
+                    assert(dirty@.map_values(|v: VAddrExec| TLBOp::VA(tag, v@)) =~= ops.push(
+                        TLBOp::VA(tag, vbase@),
+                    ));
+                    assert(old(tlb).issued() + ops.push(TLBOp::VA(tag, vbase@)) =~= (old(
+                        tlb,
+                    ).issued() + ops).push(TLBOp::VA(tag, vbase@)));
+                }
+            }
+            // Continue from the next page or block
+            let next = if fsize >= end - vbase.0 {
+                end
+            } else {
+                vbase.0 + fsize
+            };
+            proof {
+                let pt = PageTable::<G>::new(pre, self.constants@);
+                let root = pre.root();
+                assert forall|vb: VAddr| #[trigger]
+                    mappings.contains_key(vb) implies {
+                    let done = start.0 < next && vb.0 < next && start.0 < vb.0
+                        + mappings[vb].size.as_nat();
+                    let done_before = start.0 < vaddr.0 && vb.0 < vaddr.0 && start.0 < vb.0
+                        + mappings[vb].size.as_nat();
+                    &&& done && old_mem.dirty::<G>(vb) ==> {
+                        &&& exists|i| 0 <= i < dirty.len() && #[trigger] dirty[i]@ == vb
+                        &&& !self.pt_mem@.dirty::<G>(vb)
+                    }
+                    &&& !done ==> self.pt_mem@.dirty::<G>(vb) == old_mem.dirty::<G>(vb)
+                } by {
+                    let size_vb = mappings[vb].size.as_nat();
+                    let done = start.0 < next && vb.0 < next && start.0 < vb.0 + size_vb;
+                    let done_before = start.0 < vaddr.0 && vb.0 < vaddr.0 && start.0 < vb.0
+                        + size_vb;
+                    // The mapping at `vb` is the one cleared here iff its walk terminates at
+                    // `(base, idx)`, which is iff it is the page or block of `vaddr`
+                    let at_entry = pre.walk_entry_from::<G>(vb, root, 0) == (base@, idx as nat);
+                    if at_entry {
+                        pt.lemma_walk_entry_vbase(vaddr@, vb);
+                        // Both walks terminate on the level of the entry
+                        pre.lemma_walk_entry_from::<G>(vb, root, 0);
+                        assert(pre.walk::<G>(vb, mappings[vb]));
+                    }
+                    if done && !done_before {
+                        // `vb` shares an address with the page or block of `vaddr`
+                        let a = if vb.0 >= vaddr.0 {
+                            vb
+                        } else {
+                            vaddr@
+                        };
+                        pt.lemma_mapping_in_walk_block(vaddr@, vb, a);
+                    }
+                    if done_before && old_mem.dirty::<G>(vb) {
+                        // Already returned and clean, and clearing keeps it clean
+                        let i = choose|i| 0 <= i < pre_dirty.len() && #[trigger] pre_dirty[i]@
+                            == vb;
+                        assert(dirty[i]@ == vb);
+                    } else if done && old_mem.dirty::<G>(vb) {
+                        // The entry is valid and dirty, so it is cleared and `vb` is returned
+                        assert(pre.walk::<G>(vb, mappings[vb]));
+                        assert(cleared);
+                        assert(dirty[dirty.len() - 1]@ == vb);
+                    }
+                }
+            }
+            vaddr = VAddrExec(next);
+        }
+        dirty
+    }
 }
 
 } // verus!
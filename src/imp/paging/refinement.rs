@@ -1,17 +1,20 @@
 //! Implementation refinement proof.
-use std::marker::PhantomData;
+use core::marker::PhantomData;
 use vstd::prelude::*;
 
 use super::pt_exec::PageTableExec;
 use crate::{
     common::{
-        addr::{PAddr, VAddrExec},
-        frame::FrameExec,
+        addr::{PAddr, VAddr, VAddrExec},
+        frame::{Frame, FrameExec},
         pte::{ExecPTE, GhostPTE},
         PagingResult,
     },
-    imp::interface::{PTConstantsExec, PageTableInterface},
-    spec::memory::PageTableMemExec,
+    imp::{
+        interface::{PTConstantsExec, PageTableInterface},
+        tree::path::PTTreePath,
+    },
+    spec::memory::{PageTableMemExec, TLBExec},
 };
 
 verus! {
@@ -23,7 +26,7 @@ verus! {
 /// remaining system, we can complete the proof of the paging system.
 pub struct PageTableImpl<M: PageTableMemExec, G: GhostPTE, E: ExecPTE<G>>(PhantomData<(M, G, E)>);
 
-impl<M, G, E> PageTableInterface<M> for PageTableImpl<M, G, E> where
+impl<M, G, E> PageTableInterface<M, G> for PageTableImpl<M, G, E> where
     M: PageTableMemExec,
     G: GhostPTE,
     E: ExecPTE<G>,
@@ -49,6 +52,35 @@ impl<M, G, E> PageTableInterface<M> for PageTableImpl<M, G, E> where
         }
     }
 
+    proof fn lemma_walk_answers_query(pt_mem: M, constants: PTConstantsExec, vaddr: VAddr) {
+        let pt = PageTableExec::<M, G, E> { pt_mem, constants, _phantom: PhantomData }@;
+        let arch = constants@.arch;
+        let root = pt_mem@.root();
+        pt_mem@.lemma_contains_root();
+        pt.lemma_invariants_implies_walkable();
+        pt.model_consistent_with_hardware();
+        pt.lemma_view_implies_invariants();
+        pt@.query_refinement(vaddr);
+
+        // The walk from the root ends at the entry the model answers the query from
+        pt.construct_node_facts(root, 0);
+        pt.lemma_construct_node_implies_invariants(root, 0);
+        pt.lemma_hw_walk_consistent_with_walk(vaddr, root, 0);
+        pt.lemma_walk_consistent_with_model(vaddr, root, 0);
+        let node = pt.construct_node(root, 0);
+        node.lemma_visit_length_bounds(
+            PTTreePath::from_vaddr_root(vaddr, arch, (arch.level_count() - 1) as nat),
+        );
+        let (pte, level) = pt.walk(vaddr, root, 0);
+        if pte.valid() {
+            assert(pt@.query(vaddr) == PagingResult::Ok(
+                (arch.vbase(vaddr, level), pt.pte_to_frame(pte, level)),
+            ));
+        } else {
+            assert(pt@.query(vaddr) == PagingResult::<(VAddr, Frame)>::Err(()));
+        }
+    }
+
     fn map(pt_mem: M, constants: PTConstantsExec, vbase: VAddrExec, frame: FrameExec) -> (res: (
         PagingResult,
         M,
@@ -68,7 +100,12 @@ impl<M, G, E> PageTableInterface<M> for PageTableImpl<M, G, E> where
         (res, pt.pt_mem)
     }
 
-    fn unmap(pt_mem: M, constants: PTConstantsExec, vbase: VAddrExec) -> (res: (PagingResult, M)) {
+    fn unmap<T: TLBExec>(
+        pt_mem: M,
+        constants: PTConstantsExec,
+        vbase: VAddrExec,
+        tlb: &mut T,
+    ) -> (res: (PagingResult, M)) {
         let mut pt = PageTableExec::<M, G, E>::new(pt_mem, constants);
         proof {
             assert(pt@.invariants());
@@ -76,7 +113,7 @@ impl<M, G, E> PageTableInterface<M> for PageTableImpl<M, G, E> where
             pt@.lemma_view_implies_invariants();
             pt@@.unmap_refinement(vbase@);
         }
-        let res = pt.unmap(vbase);
+        let res = pt.unmap(vbase, tlb);
         proof {
             assert(pt@.invariants());
             pt@.model_consistent_with_hardware();
@@ -121,6 +121,7 @@ impl PTTreeModel {
                 arch: self.arch(),
                 pmem_lb: self.pmem_lb(),
                 pmem_ub: self.pmem_ub(),
+                reserved: self.root.constants.reserved,
             },
         }
     }
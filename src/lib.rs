@@ -1,12 +1,40 @@
-#![feature(lazy_cell)]
+//! Verified page table for the hvisor hypervisor.
+//!
+//! - `arch` provides `PageTableApi` and its architecture implementations.
+//! - `common` and `memory` provide the architecture description (`PTArchExec`), frames, PTEs
+//!   and page table memory backends.
+//! - `spec` and `imp` hold the specification and the verified implementation, for downstream
+//!   proofs.
+//!
+//! Features:
+//!
+//! - `std`: build against the standard library. Without it the crate is `no_std` and needs
+//!   only `alloc`.
+//! - `arch-easy`: the toy architecture `arch::easy`.
+//! - `concurrent`: the thread-safe page table `imp::concurrent` and its state machine
+//!   `spec::concurrent`.
+//!
+//! Ghost code is erased outside Verus, so the crate builds with plain cargo. Items only Verus
+//! needs are compiled under `cfg(verus_keep_ghost)`. Verus does not read the manifest, so pass
+//! the features to verify, e.g. `--cfg 'feature="arch-easy"'`.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
 use vstd::prelude::*;
 
-mod arch;
-mod common;
-mod imp;
-mod memory;
-mod spec;
+pub mod arch;
+pub mod common;
+pub mod imp;
+pub mod memory;
+pub mod spec;
+
+pub use arch::PageTableApi;
+#[cfg(feature = "arch-easy")]
+pub use arch::easy::{EasyPageTable, EasyTLB};
+pub use common::arch::{PTArchExec, PTArchLevelExec};
+pub use memory::{DirectMapHost, MemLog, NoLog};
+pub use spec::memory::{PageTableMemExec, TLBExec};
 
 verus! {
     
@@ -14,30 +42,6 @@ global layout usize is size == 8;
 
 } // verus!
 /// Although the project is a library, Verus requires a main function to run the verification.
+#[cfg(verus_keep_ghost)]
 fn main() {
-    println!("Running Test...");
-    test();
-}
-
-fn test() {
-    use arch::easy::EasyPageTable;
-    use arch::PageTableApi;
-    use common::frame::MemAttr;
-    use memory::PooledPageTableMem;
-    
-    let mut pt = EasyPageTable::<PooledPageTableMem>::new();
-    println!("PageTable Inited");
-    let r1 = pt.map(0x1000, 0x2000, 4096, MemAttr::default());
-    assert!(r1.is_ok());
-    println!("map ok");
-    let r2 = pt.query(0x1020);
-    assert!(r2.is_ok());
-    assert!(r2.unwrap() == (0x1000, 0x2000, 4096, MemAttr::default()));
-    println!("query_after_map ok");
-    let r3 = pt.unmap(0x1000);
-    assert!(r3.is_ok());
-    println!("unmap ok");
-    let r4 = pt.query(0x1010);
-    assert!(r4.is_err());
-    println!("query_after_unmap ok ");
 }
@@ -1,33 +1,137 @@
-//! An easy executable page table memory implementation.
+//! Easy executable page table memory implementations.
 //!
 //! Page Table Memory is a collection of page tables, and provides read/write, alloc/dealloc functionality.
 //! The implementation should refine the specification defined in `spec::memory::PageTableMem`.
+//!
+//! `PooledPageTableMem` stores tables in host memory and uses host addresses as physical
+//! addresses. `SimPageTableMem` stores tables in a simulated physical memory placed at a
+//! configurable physical base. `DirectMapPageTableMem` stores tables in real physical frames
+//! and accesses them through the host's direct map. `DirectMapWalker` reads them without the
+//! lock for the lock-free queries of `imp::concurrent`.
+//!
+//! Only the allocator of `SimPageTableMem` is verified. The others are trusted to meet
+//! `PageTableMem::allocates`. The buddy allocator of `PooledPageTableMem` is specified, but
+//! not verified (`BuddyAllocator`).
+//!
+//! Backends report table allocation and deallocation to a `MemLog` hook, so a bare-metal host
+//! can route the messages to its own console.
+use alloc::{boxed::Box, collections::BTreeSet, vec, vec::Vec};
+use core::marker::PhantomData;
 use vstd::prelude::*;
 
-use crate::common::{addr::PAddrExec, arch::PTArchExec, frame::FrameSize};
-use crate::spec::memory::{PageTableMem, PageTableMemExec, TableExec};
+use crate::common::{addr::{PAddr, PAddrExec, PIdx}, arch::PTArchExec, frame::FrameSize};
+#[cfg(feature = "concurrent")]
+use crate::imp::concurrent::PageTableWalkerExec;
+#[cfg(feature = "concurrent")]
+use crate::spec::concurrent::PageTableSM;
+use crate::spec::memory::{
+    lemma_entry_idx, PageTableMem, PageTableMemExec, PhysMem, Table, TableExec,
+};
 
 verus! {
 
+broadcast use crate::spec::memory::group_pt_mem_lemmas;
+
+// Let verified code hold the unverified region. Only Verus needs it.
+#[cfg(verus_keep_ghost)]
 #[verifier::external_type_specification]
-pub struct ExFramePool(FramePool);
+pub struct ExDefaultRegion(DefaultRegion);
+
+/// Error returned when no free block is large enough.
+pub struct AllocError;
 
-/// Concrete implementation of page table memory using a easy frame pool.
-pub struct PooledPageTableMem {
+/// Hook that observes table allocation and deallocation, e.g. to log them.
+pub trait MemLog {
+    /// A table has been allocated.
+    fn table_allocated(table: TableExec);
+
+    /// The table at `base` has been deallocated.
+    fn table_deallocated(base: PAddrExec);
+}
+
+/// Hook that ignores every event.
+pub struct NoLog;
+
+impl MemLog for NoLog {
+    fn table_allocated(_table: TableExec) {
+    }
+
+    fn table_deallocated(_base: PAddrExec) {
+    }
+}
+
+/// Concrete implementation of page table memory using a buddy allocator over host memory.
+pub struct PooledPageTableMem<L: MemLog> {
     /// All tables in the hierarchical page table, the first table is the root.
     pub tables: Vec<TableExec>,
     /// Page table architecture.
     pub arch: PTArchExec,
-    /// Frame pool for allocating/deallocating frames.
-    pub pool: Box<FramePool>,
+    /// Allocator of the frames that store the tables.
+    pub pool: BuddyAllocator,
+    /// Region the allocator manages, as `(base, size)`.
+    pub region: (PAddrExec, usize),
+    /// Region owned by the page table memory, if the caller did not provide one.
+    pub backing: Option<Box<DefaultRegion>>,
+    /// Log hook.
+    pub _log: PhantomData<L>,
+}
+
+impl<L: MemLog> PooledPageTableMem<L> {
+    /// Physical memory that stores the tables.
+    ///
+    /// Tables are accessed through raw pointers, so the contents are opaque to the verifier.
+    pub open spec fn phys_mem(self) -> PhysMem;
+
+    /// Construct a page table memory that allocates tables from `region`, and initialize the
+    /// root table.
+    ///
+    /// Returns `Err` if `region` cannot hold the root table.
+    #[verifier::external_body]
+    pub fn with_region(
+        arch: PTArchExec,
+        region: &'static mut [u8],
+    ) -> (res: Result<Self, AllocError>)
+        requires
+            arch@.valid(),
+        ensures
+            res is Ok ==> res->Ok_0@.init(),
+            res is Ok ==> res->Ok_0@.arch == arch@,
+    {
+        let region = (PAddrExec(region.as_mut_ptr() as usize), region.len());
+        Self::with_pool(arch, region, None)
+    }
+
+    /// Construct a page table memory that allocates tables from `region`, and initialize the
+    /// root table.
+    #[verifier::external_body]
+    fn with_pool(
+        arch: PTArchExec,
+        region: (PAddrExec, usize),
+        backing: Option<Box<DefaultRegion>>,
+    ) -> (res: Result<Self, AllocError>) {
+        let mut pool = BuddyAllocator::new(region.0.0, region.1)?;
+        let size = FrameSize::from_usize(arch.table_size(0)).ok_or(AllocError)?;
+        let base = pool.alloc_zeroed(size.as_usize())?;
+        let table = TableExec { base, size, level: 0 };
+        Ok(PooledPageTableMem {
+            tables: vec![table],
+            arch,
+            pool,
+            region,
+            backing,
+            _log: PhantomData,
+        })
+    }
 }
 
-impl PageTableMemExec for PooledPageTableMem {
+impl<L: MemLog> PageTableMemExec for PooledPageTableMem<L> {
     /// View the concrete page table memory as an abstract page table memory.
     open spec fn view(self) -> PageTableMem {
         PageTableMem {
             tables: Seq::new(self.tables.len() as nat, |i| self.tables[i]@),
             arch: self.arch@,
+            mem: self.phys_mem(),
+            pool: seq![(self.region.0@, self.region.1 as nat)],
         }
     }
 
@@ -36,6 +140,23 @@ impl PageTableMemExec for PooledPageTableMem {
         self.tables[0].base
     }
 
+    /// Host memory, which serves as physical memory.
+    ///
+    /// Assumption: Host addresses are used as physical addresses, so `phys_mem` spans the
+    /// whole address space.
+    #[verifier::external_body]
+    fn pmem(&self) -> (res: Option<(PAddrExec, usize)>) {
+        Some((PAddrExec(0), usize::MAX & !7))
+    }
+
+    /// The region the allocator manages.
+    fn pool(&self) -> (res: Vec<(PAddrExec, usize)>) {
+        let mut res = Vec::new();
+        res.push(self.region);
+        assert(res@[0] == self.region);
+        res
+    }
+
     /// If a table is empty.
     #[verifier::external_body]
     fn is_table_empty(&self, base: PAddrExec) -> (res: bool) {
@@ -53,39 +174,43 @@ impl PageTableMemExec for PooledPageTableMem {
     }
 
     /// Construct a new page table memory and initialize the root table.
+    ///
+    /// Returns `None` if the default region cannot hold the root table.
     #[verifier::external_body]
-    fn new_init(arch: PTArchExec) -> (res: PooledPageTableMem) {
-        // TODO: only support 4k frame size for now
-        let mut pool = Box::new(FramePool::new());
-        let base = pool.alloc();
-        let table = TableExec { base, size: FrameSize::Size4K, level: 0 };
-        PooledPageTableMem { tables: vec![table], arch, pool }
+    fn new_init(arch: PTArchExec) -> (res: Option<PooledPageTableMem<L>>) {
+        let backing = Box::new(DefaultRegion([0; DEFAULT_REGION_SIZE]));
+        let region = (PAddrExec(backing.0.as_ptr() as usize), DEFAULT_REGION_SIZE);
+        Self::with_pool(arch, region, Some(backing)).ok()
     }
 
     /// Allocate a new table and returns the table descriptor.
     ///
-    /// Assumption: To satisfy the post-condition we need to assume the correctness of
-    /// the memory allocator, which may be verified in the future work.
+    /// Returns `None` if the pool has no free block of the table size.
+    ///
+    /// Assumption: The buddy allocator is not verified. Its specification gives a block
+    /// aligned to its size, in the region and disjoint from every allocated block
+    /// (`BuddyAllocator::alloc`). Together with the zeroing through raw pointers, which
+    /// `phys_mem` cannot track, it is trusted to meet `PageTableMem::allocates`.
     #[verifier::external_body]
-    fn alloc_table(&mut self, level: usize) -> (res: TableExec) {
-        let _size = self.arch.frame_size(level).as_usize();
-        // TODO: only support 4k frame size for now
-        let base = self.pool.alloc();
-        let table = TableExec { base, size: FrameSize::Size4K, level };
+    fn alloc_table(&mut self, level: usize) -> (res: Option<TableExec>) {
+        let size = FrameSize::from_usize(self.arch.table_size(level))?;
+        let base = self.pool.alloc_zeroed(size.as_usize()).ok()?;
+        let table = TableExec { base, size, level };
         self.tables.push(table);
-        println!("Allocate table at {:#x}", base.0);
-        table
+        L::table_allocated(table);
+        Some(table)
     }
 
     /// Deallocate a table.
     ///
-    /// Assumption: To satisfy the post-condition we need to assume the correctness of
-    /// the memory allocator, which may be verified in the future work.
+    /// Assumption: The buddy allocator is not verified, see `alloc_table`.
     #[verifier::external_body]
     fn dealloc_table(&mut self, base: PAddrExec) {
+        let size = self.tables.iter().find(|t| t.base == base).unwrap().size;
         self.tables.retain(|t| t.base != base);
-        self.pool.dealloc(base);
-        println!("Deallocate table at {:#x}", base.0);
+        // The block was allocated with the same size, so the size is valid
+        let _ = self.pool.dealloc(base.0, size.as_usize());
+        L::table_deallocated(base);
     }
 
     /// Get the value at the given index in the given table.
@@ -105,36 +230,1240 @@ impl PageTableMemExec for PooledPageTableMem {
     }
 }
 
+
+/// Physical address of the default simulated physical memory.
+pub const SIM_PMEM_BASE: usize = 0x4000_0000;
+
+/// Size of the default simulated physical memory.
+pub const SIM_PMEM_SIZE: usize = 0x10_0000;
+
+/// Size of the part of the default simulated physical memory that tables are allocated from.
+///
+/// The rest holds the frames page tables map.
+pub const SIM_POOL_SIZE: usize = 0x4_0000;
+
+/// Size of a frame of the simulated physical memory, the unit its allocator tracks.
+pub const SIM_FRAME_SIZE: usize = 0x1000;
+
+/// Concrete implementation of page table memory using a simulated physical memory.
+///
+/// Physical memory is an array of words placed at physical address `base`, so tables live at
+/// hardware-like physical addresses. Tables are allocated from the first `pool_size` bytes, and
+/// the rest holds mapped frames. Every access is a bounds-checked array access, and the table
+/// allocator is verified.
+pub struct SimPageTableMem {
+    /// All tables in the hierarchical page table, the first table is the root.
+    pub tables: Vec<TableExec>,
+    /// Page table architecture.
+    pub arch: PTArchExec,
+    /// Simulated physical memory, `words[0]` is stored at physical address `base`.
+    pub words: Vec<u64>,
+    /// Physical address of the simulated physical memory.
+    pub base: PAddrExec,
+    /// Size of the table pool at `base`.
+    pub pool_size: usize,
+}
+
+impl SimPageTableMem {
+    /// If the simulated physical memory starts at a frame boundary and ends inside the
+    /// address space, so frame addresses are computed without overflow.
+    pub open spec fn well_placed(self) -> bool {
+        &&& self.base@.aligned(SIM_FRAME_SIZE as nat)
+        &&& self.base.0 + self.words.len() * 8 <= usize::MAX
+        &&& self.pool_size <= self.words.len() * 8
+    }
+
+    /// Number of frames in the table pool.
+    pub open spec fn frame_count(self) -> nat {
+        self.pool_size as nat / SIM_FRAME_SIZE as nat
+    }
+
+    /// Physical address of frame `f`.
+    pub open spec fn frame_addr(self, f: nat) -> PAddr {
+        PAddr(self.base.0 as nat + f * SIM_FRAME_SIZE as nat)
+    }
+
+    /// If frame `f` overlaps no table.
+    pub open spec fn frame_free(self, f: nat) -> bool {
+        forall|i|
+            #![auto]
+            0 <= i < self@.tables.len() ==> !PAddr::overlap(
+                self@.tables[i].base,
+                self@.tables[i].size.as_nat(),
+                self.frame_addr(f),
+                SIM_FRAME_SIZE as nat,
+            )
+    }
+
+    /// Construct a page table memory over `size` bytes of simulated physical memory at `base`,
+    /// with the first `pool_size` bytes as the table pool, and initialize the root table at
+    /// `base`.
+    ///
+    /// Returns `Err` if the root table size is not a frame size, or exceeds `pool_size`, or if
+    /// `pool_size` exceeds the whole words of memory.
+    pub fn with_region(arch: PTArchExec, base: PAddrExec, size: usize, pool_size: usize) -> (res:
+        Result<Self, AllocError>)
+        requires
+            arch@.valid(),
+            base@.aligned(SIM_FRAME_SIZE as nat),
+            base.0 + size <= usize::MAX,
+        ensures
+            res is Ok ==> {
+                let pt_mem = res->Ok_0;
+                &&& pt_mem@.init()
+                &&& pt_mem@.arch == arch@
+                &&& pt_mem@.root() == base@
+                &&& pt_mem@.mem.lb() == base@.idx()
+                &&& pt_mem@.mem.len() == size as nat / 8
+                &&& pt_mem@.pool == seq![(base@, pool_size as nat)]
+                &&& pt_mem.well_placed()
+            },
+    {
+        let entry_count = arch.entry_count(0);
+        if entry_count > usize::MAX / 8 {
+            return Err(AllocError);
+        }
+        let root_size = match FrameSize::from_usize(entry_count * 8) {
+            Some(root_size) => root_size,
+            None => return Err(AllocError),
+        };
+        if root_size.as_usize() > pool_size || pool_size > size / 8 * 8 {
+            return Err(AllocError);
+        }
+        let word_count = size / 8;
+        let mut words = Vec::new();
+        while words.len() < word_count
+            invariant
+                words.len() <= word_count,
+                forall|i| 0 <= i < words.len() ==> words[i] == 0u64,
+            decreases word_count - words.len(),
+        {
+            words.push(0);
+        }
+        let root = TableExec { base, size: root_size, level: 0 };
+        let mut tables = Vec::new();
+        tables.push(root);
+        let res = Self { tables, arch, words, base, pool_size };
+        proof {
+            let pt_mem = res@;
+            let table_size = root_size.as_nat();
+            assert(pt_mem.tables.len() == 1);
+            assert(pt_mem.tables[0] == root@);
+            assert(base.0 % 8 == 0 && (base.0 + table_size) / 8 <= base.0 / 8 + word_count
+                && table_size <= word_count * 8 && base.0 + word_count * 8 <= usize::MAX)
+                by (nonlinear_arith)
+                requires
+                    base.0 % 0x1000 == 0,
+                    table_size == entry_count * 8,
+                    table_size <= pool_size,
+                    pool_size <= size / 8 * 8,
+                    word_count == size / 8,
+                    base.0 + size <= usize::MAX,
+            ;
+            assert(pt_mem.table_in_mem(root@));
+            assert(pt_mem.pool[0] == (base@, pool_size as nat));
+            assert(pt_mem.table_in_pool(root@));
+            assert(pt_mem.invariants());
+            assert(pt_mem.tables.contains(root@));
+            assert(pt_mem.table(base@) == root@);
+            assert forall|i: int| 0 <= i < arch@.entry_count(0) implies #[trigger]
+                pt_mem.table_view(base@)[i] == 0u64 by {
+                pt_mem.lemma_entry_in_mem(base@, i as nat);
+            }
+            assert(pt_mem.table_view(base@) =~= seq![0u64; arch@.entry_count(0)]);
+        }
+        Ok(res)
+    }
+
+    /// Index into `words` of the entry at the given index in the given table.
+    fn word_index(&self, base: PAddrExec, index: usize) -> (res: usize)
+        requires
+            self@.invariants(),
+            self@.accessible(base@, index as nat),
+        ensures
+            res < self.words.len(),
+            res == self@.entry_addr(base@, index as nat).idx().0 - self.base@.idx().0,
+    {
+        proof {
+            self@.lemma_entry_in_mem(base@, index as nat);
+            let i = choose|i| 0 <= i < self@.tables.len() && self@.tables[i] == self@.table(base@);
+            assert(self@.table_in_mem(self@.tables[i]));
+        }
+        base.0 / 8 - self.base.0 / 8 + index
+    }
+
+    /// Allocation bitmap of the frames, marking every frame that overlaps a table.
+    ///
+    /// The bitmap is rebuilt from `tables`, so it never disagrees with them.
+    fn used_frames(&self) -> (res: Vec<bool>)
+        requires
+            self@.invariants(),
+            self.well_placed(),
+        ensures
+            res.len() == self.frame_count(),
+            forall|f: int| 0 <= f < res.len() && !#[trigger] res[f] ==> self.frame_free(f as nat),
+    {
+        let frame_count = self.pool_size / SIM_FRAME_SIZE;
+        let mut used = Vec::new();
+        while used.len() < frame_count
+            invariant
+                used.len() <= frame_count,
+                forall|f: int| 0 <= f < used.len() ==> !used[f],
+            decreases frame_count - used.len(),
+        {
+            used.push(false);
+        }
+        // Mark the frames each table overlaps
+        let mut i = 0;
+        while i < self.tables.len()
+            invariant
+                self@.invariants(),
+                self.well_placed(),
+                i <= self.tables.len(),
+                used.len() == frame_count,
+                frame_count == self.frame_count(),
+                forall|f: int, j: int|
+                    0 <= f < used.len() && 0 <= j < i && !#[trigger] used[f] ==> !PAddr::overlap(
+                        #[trigger] self@.tables[j].base,
+                        self@.tables[j].size.as_nat(),
+                        self.frame_addr(f as nat),
+                        SIM_FRAME_SIZE as nat,
+                    ),
+            decreases self.tables.len() - i,
+        {
+            proof {
+                self.lemma_table_bounds(i as int);
+            }
+            let table = self.tables[i];
+            // The table overlaps frames `[first, end)`
+            let offset = table.base.0 - self.base.0;
+            let first = offset / SIM_FRAME_SIZE;
+            let end = (offset + table.size.as_usize() - 1) / SIM_FRAME_SIZE + 1;
+            let mut f = first;
+            while f < end && f < frame_count
+                invariant
+                    first <= f,
+                    used.len() == frame_count,
+                    forall|g: int| first <= g < f && g < used.len() ==> used[g],
+                    forall|g: int, j: int|
+                        0 <= g < used.len() && 0 <= j < i && !#[trigger] used[g]
+                            ==> !PAddr::overlap(
+                            #[trigger] self@.tables[j].base,
+                            self@.tables[j].size.as_nat(),
+                            self.frame_addr(g as nat),
+                            SIM_FRAME_SIZE as nat,
+                        ),
+                decreases frame_count - f,
+            {
+                used.set(f, true);
+                f += 1;
+            }
+            proof {
+                assert forall|g: int| 0 <= g < used.len() && !#[trigger] used[g] implies
+                    !PAddr::overlap(
+                    self@.tables[i as int].base,
+                    self@.tables[i as int].size.as_nat(),
+                    self.frame_addr(g as nat),
+                    SIM_FRAME_SIZE as nat,
+                ) by {
+                    lemma_frame_outside(
+                        self.base.0 as nat,
+                        table.base.0 as nat,
+                        table.size.as_nat(),
+                        g as nat,
+                    );
+                }
+            }
+            i += 1;
+        }
+        used
+    }
+
+    /// Find `count` frames free in `used` from a frame whose address is aligned to `align`,
+    /// and return the first of them.
+    fn find_free_frames(&self, used: &Vec<bool>, count: usize, align: usize) -> (res: Option<
+        usize,
+    >)
+        requires
+            self.well_placed(),
+            used.len() == self.frame_count(),
+            align > 0,
+        ensures
+            res matches Some(f) ==> {
+                &&& f + count <= used.len()
+                &&& self.frame_addr(f as nat).aligned(align as nat)
+                &&& forall|g: int| f <= g < f + count ==> !#[trigger] used[g]
+            },
+    {
+        if count > used.len() {
+            return None;
+        }
+        let mut f = 0;
+        while f <= used.len() - count
+            invariant
+                self.well_placed(),
+                used.len() == self.frame_count(),
+                count <= used.len(),
+                f <= used.len() - count + 1,
+                align > 0,
+            decreases used.len() + 1 - f,
+        {
+            proof {
+                assert(self.base.0 + f * 0x1000 <= usize::MAX) by (nonlinear_arith)
+                    requires
+                        f <= self.pool_size / 0x1000,
+                        self.pool_size <= self.words.len() * 8,
+                        self.base.0 + self.words.len() * 8 <= usize::MAX,
+                ;
+            }
+            if (self.base.0 + f * SIM_FRAME_SIZE) % align == 0 {
+                let mut k = 0;
+                while k < count && !used[f + k]
+                    invariant
+                        f + count <= used.len(),
+                        k <= count,
+                        forall|g: int| f <= g < f + k ==> !used[g],
+                    decreases count - k,
+                {
+                    k += 1;
+                }
+                if k == count {
+                    return Some(f);
+                }
+            }
+            f += 1;
+        }
+        None
+    }
+
+    /// Zero `count` words from `words[start]`.
+    fn zero_words(&mut self, start: usize, count: usize)
+        requires
+            start + count <= old(self).words.len(),
+        ensures
+            self.tables@ == old(self).tables@,
+            self.arch == old(self).arch,
+            self.base == old(self).base,
+            self.pool_size == old(self).pool_size,
+            self.words.len() == old(self).words.len(),
+            forall|k: int|
+                0 <= k < self.words.len() ==> #[trigger] self.words[k] == if start <= k < start
+                    + count {
+                    0u64
+                } else {
+                    old(self).words[k]
+                },
+    {
+        let mut k = start;
+        while k < start + count
+            invariant
+                start <= k <= start + count,
+                start + count <= self.words.len(),
+                self.tables@ == old(self).tables@,
+                self.arch == old(self).arch,
+                self.base == old(self).base,
+                self.pool_size == old(self).pool_size,
+                self.words.len() == old(self).words.len(),
+                forall|j: int|
+                    0 <= j < self.words.len() ==> #[trigger] self.words[j] == if start <= j < k {
+                        0u64
+                    } else {
+                        old(self).words[j]
+                    },
+            decreases start + count - k,
+        {
+            self.words.set(k, 0);
+            k += 1;
+        }
+    }
+
+    /// Lemma. Every table lies in the simulated physical memory.
+    proof fn lemma_table_bounds(self, i: int)
+        requires
+            self@.invariants(),
+            self.well_placed(),
+            0 <= i < self.tables.len(),
+        ensures
+            self.base.0 <= self.tables[i].base.0,
+            self.tables[i].base.0 + self.tables[i].size.as_nat() <= self.base.0
+                + self.words.len() * 8,
+            self.tables[i].size.as_nat() > 0,
+    {
+        let table = self@.tables[i];
+        assert(self@.table_in_mem(table));
+        let entry_count = self@.arch.entry_count(table.level);
+        assert(self.base.0 <= table.base.0 && table.base.0 + table.size.as_nat() <= self.base.0
+            + self.words.len() * 8 && table.size.as_nat() > 0) by (nonlinear_arith)
+            requires
+                self.base.0 % 0x1000 == 0,
+                table.base.0 % 8 == 0,
+                table.size.as_nat() == entry_count * 8,
+                entry_count > 1,
+                self.base.0 / 8 <= table.base.0 / 8,
+                (table.base.0 + table.size.as_nat()) / 8 <= self.base.0 / 8 + self.words.len(),
+        ;
+    }
+
+    /// Lemma. The `n` frames from frame `f` overlap no table if none of them does.
+    proof fn lemma_free_frames_no_overlap(self, f: nat, n: nat)
+        requires
+            n > 0,
+            forall|g: nat| f <= g < f + n ==> #[trigger] self.frame_free(g),
+        ensures
+            forall|i|
+                #![auto]
+                0 <= i < self@.tables.len() ==> !PAddr::overlap(
+                    self@.tables[i].base,
+                    self@.tables[i].size.as_nat(),
+                    self.frame_addr(f),
+                    n * SIM_FRAME_SIZE as nat,
+                ),
+    {
+        assert forall|i| 0 <= i < self@.tables.len() implies !PAddr::overlap(
+            #[trigger] self@.tables[i].base,
+            self@.tables[i].size.as_nat(),
+            self.frame_addr(f),
+            n * SIM_FRAME_SIZE as nat,
+        ) by {
+            let table = self@.tables[i];
+            let start = self.frame_addr(f).0;
+            if PAddr::overlap(table.base, table.size.as_nat(), self.frame_addr(f), n * 0x1000) {
+                // The first address in both regions lies in one of the frames
+                let p = if table.base.0 <= start {
+                    start
+                } else {
+                    table.base.0
+                };
+                lemma_addr_in_frame(self.base.0 as nat, f, n, p);
+                let g = ((p - self.base.0) / 0x1000) as nat;
+                assert(PAddr::overlap(
+                    table.base,
+                    table.size.as_nat(),
+                    self.frame_addr(g),
+                    SIM_FRAME_SIZE as nat,
+                ));
+                assert(self.frame_free(g));
+            }
+        }
+    }
+}
+
+impl PageTableMemExec for SimPageTableMem {
+    /// View the concrete page table memory as an abstract page table memory.
+    open spec fn view(self) -> PageTableMem {
+        PageTableMem {
+            tables: Seq::new(self.tables.len() as nat, |i| self.tables[i]@),
+            arch: self.arch@,
+            mem: PhysMem { mem: self.words@, base: self.base@.idx() },
+            pool: seq![(self.base@, self.pool_size as nat)],
+        }
+    }
+
+    /// Physical address of the root page table.
+    fn root(&self) -> (res: PAddrExec) {
+        self.tables[0].base
+    }
+
+    /// If a table is empty.
+    fn is_table_empty(&self, base: PAddrExec) -> (res: bool) {
+        proof {
+            assert(self@.tables.contains(self@.table(base@)));
+        }
+        // Find the table descriptor
+        let mut i = 0;
+        while self.tables[i].base.0 != base.0
+            invariant
+                self@.invariants(),
+                self@.contains_table(base@),
+                self@.tables.contains(self@.table(base@)),
+                0 <= i < self.tables.len(),
+                forall|j| 0 <= j < i ==> self.tables[j].base.0 != base.0,
+            decreases self.tables.len() - i,
+        {
+            proof {
+                let k = choose|k|
+                    0 <= k < self@.tables.len() && self@.tables[k] == self@.table(base@);
+                assert(k > i);
+            }
+            i += 1;
+        }
+        proof {
+            let k = choose|k|
+                0 <= k < self@.tables.len() && self@.tables[k] == self@.table(base@);
+            assert(self@.tables[i as int].base == base@);
+            assert(k == i);
+            self@.table_view_facts(base@);
+        }
+        let count = self.arch.entry_count(self.tables[i].level);
+        let mut index = 0;
+        while index < count
+            invariant
+                self@.invariants(),
+                self@.contains_table(base@),
+                count == self@.arch.entry_count(self@.table(base@).level),
+                count == self@.table_view(base@).len(),
+                index <= count,
+                forall|j| 0 <= j < index ==> self@.table_view(base@)[j] == 0u64,
+            decreases count - index,
+        {
+            if self.read(base, index) != 0 {
+                assert(self@.table_view(base@)[index as int] != seq![0u64; count as nat][
+                    index as int]);
+                return false;
+            }
+            index += 1;
+        }
+        assert(self@.table_view(base@) =~= seq![0u64; self@.table_view(base@).len()]);
+        true
+    }
+
+    /// Simulated physical memory.
+    ///
+    /// Returns `None` if it is not word-aligned or ends past the address space.
+    fn pmem(&self) -> (res: Option<(PAddrExec, usize)>) {
+        if self.base.0 % 8 != 0 || self.words.len() > (usize::MAX - self.base.0) / 8 {
+            return None;
+        }
+        proof {
+            assert(self.base.0 + self.words.len() * 8 <= usize::MAX && (self.base.0 / 8) * 8
+                == self.base.0 && (self.base.0 / 8 + self.words.len()) * 8 == self.base.0
+                + self.words.len() * 8) by (nonlinear_arith)
+                requires
+                    self.base.0 % 8 == 0,
+                    self.words.len() <= (usize::MAX - self.base.0) / 8,
+            ;
+        }
+        Some((self.base, self.words.len() * 8))
+    }
+
+    /// The table pool at the start of the simulated physical memory.
+    fn pool(&self) -> (res: Vec<(PAddrExec, usize)>) {
+        let mut res = Vec::new();
+        res.push((self.base, self.pool_size));
+        assert(res@[0] == (self.base, self.pool_size));
+        res
+    }
+
+    /// Construct a new page table memory over the default simulated physical memory and
+    /// initialize the root table.
+    fn new_init(arch: PTArchExec) -> (res: Option<SimPageTableMem>) {
+        match Self::with_region(arch, PAddrExec(SIM_PMEM_BASE), SIM_PMEM_SIZE, SIM_POOL_SIZE) {
+            Ok(pt_mem) => Some(pt_mem),
+            Err(_) => None,
+        }
+    }
+
+    /// Allocate a new table and returns the table descriptor.
+    ///
+    /// Takes the first run of free frames that holds the table and is aligned to its size.
+    /// Returns `None` if there is no such run, or if the table size is not a frame size.
+    fn alloc_table(&mut self, level: usize) -> (res: Option<TableExec>) {
+        let entry_count = self.arch.entry_count(level);
+        if entry_count > usize::MAX / 8 {
+            return None;
+        }
+        let size = match FrameSize::from_usize(entry_count * 8) {
+            Some(size) => size,
+            None => return None,
+        };
+        if self.base.0 % SIM_FRAME_SIZE != 0 || self.words.len() > (usize::MAX - self.base.0) / 8 {
+            return None;
+        }
+        proof {
+            assert(self.base.0 + self.words.len() * 8 <= usize::MAX) by (nonlinear_arith)
+                requires
+                    self.words.len() <= (usize::MAX - self.base.0) / 8,
+            ;
+        }
+        if self.pool_size > self.words.len() * 8 {
+            return None;
+        }
+        proof {
+            assert(size.as_nat() % 0x1000 == 0 && size.as_nat() >= 0x1000);
+        }
+        let used = self.used_frames();
+        let frame_count = size.as_usize() / SIM_FRAME_SIZE;
+        let frame = match self.find_free_frames(&used, frame_count, size.as_usize()) {
+            Some(frame) => frame,
+            None => return None,
+        };
+        let ghost pt_mem = self@;
+        let ghost start = frame * (SIM_FRAME_SIZE / 8);
+        let ghost count = size.as_nat() / 8;
+        proof {
+            assert({
+                &&& self.base.0 + frame * 0x1000 <= usize::MAX
+                &&& frame_count > 0
+                &&& frame_count * 0x1000 == size.as_nat()
+                &&& count * 8 == size.as_nat()
+                &&& count == entry_count
+                &&& start + count <= self.words.len()
+                &&& self.base.0 % 8 == 0
+                &&& (self.base.0 + frame * 0x1000) / 8 == self.base.0 / 8 + start
+                &&& frame * 0x1000 + size.as_nat() <= self.pool_size
+            }) by (nonlinear_arith)
+                requires
+                    self.base.0 % 0x1000 == 0,
+                    self.base.0 + self.words.len() * 8 <= usize::MAX,
+                    self.pool_size <= self.words.len() * 8,
+                    frame + frame_count <= self.pool_size / 0x1000,
+                    size.as_nat() % 0x1000 == 0,
+                    size.as_nat() >= 0x1000,
+                    size.as_nat() == entry_count * 8,
+                    frame_count == size.as_nat() / 0x1000,
+                    start == frame * 0x200,
+                    count == size.as_nat() / 8,
+            ;
+            assert forall|g: nat| frame <= g < frame + frame_count implies #[trigger]
+                self.frame_free(g) by {
+                assert(!used[g as int]);
+            }
+            self.lemma_free_frames_no_overlap(frame as nat, frame_count as nat);
+        }
+        let base = PAddrExec(self.base.0 + frame * SIM_FRAME_SIZE);
+        assert(base@ == self.frame_addr(frame as nat));
+        self.zero_words(frame * (SIM_FRAME_SIZE / 8), size.as_usize() / 8);
+        let table = TableExec { base, size, level };
+        self.tables.push(table);
+        proof {
+            let s1 = pt_mem;
+            let s2 = self@;
+            let new_table = table@;
+            assert(s2.tables =~= s1.tables.push(new_table));
+            // `base` is not a table yet
+            assert(!s1.contains_table(base@)) by {
+                if s1.contains_table(base@) {
+                    let t = choose|t: Table| #[trigger] s1.tables.contains(t) && t.base == base@;
+                    let i = choose|i| 0 <= i < s1.tables.len() && s1.tables[i] == t;
+                    assert(PAddr::overlap(t.base, t.size.as_nat(), base@, size.as_nat()));
+                }
+            }
+            assert(s2.tables[s1.tables.len() as int] == new_table);
+            assert(s2.tables.contains(new_table));
+            assert(s2.table(base@) == new_table) by {
+                let t = s2.table(base@);
+                let i = choose|i| 0 <= i < s2.tables.len() && s2.tables[i] == t;
+                if i < s1.tables.len() {
+                    assert(s1.tables[i] == t);
+                    assert(s1.tables.contains(t));
+                }
+            }
+            assert(s2.table_in_mem(new_table));
+            assert(s1.pool[0] == (self.base@, self.pool_size as nat));
+            assert(s1.table_in_pool(new_table));
+            // Words outside the new table are unchanged
+            assert forall|pidx: PIdx|
+                s1.mem.contains(pidx) && !pidx.addr().within(base@, size.as_nat()) implies
+                #[trigger] s2.mem.read(pidx) == s1.mem.read(pidx) by {
+                let k = pidx.0 - self.base.0 / 8;
+                assert(k < start || k >= start + count) by (nonlinear_arith)
+                    requires
+                        self.base.0 % 8 == 0,
+                        base.0 / 8 == self.base.0 / 8 + start,
+                        base.0 % 8 == 0,
+                        count * 8 == size.as_nat(),
+                        !(base.0 <= pidx.0 * 8 < base.0 + size.as_nat()),
+                        k == pidx.0 - self.base.0 / 8,
+                ;
+            }
+            // The new table is zeroed
+            assert forall|i: int| 0 <= i < count implies #[trigger] s2.table_view(base@)[i]
+                == 0u64 by {
+                lemma_entry_idx(base@, i as nat);
+            }
+            assert(s2.table_view(base@) =~= seq![0u64; s1.arch.entry_count(level as nat)]);
+            // Old tables are unchanged
+            assert forall|b: PAddr| #[trigger] s1.contains_table(b) implies s2.table_view(b)
+                == s1.table_view(b) by {
+                let t = s1.table(b);
+                let i = choose|i| 0 <= i < s1.tables.len() && s1.tables[i] == t;
+                assert(s2.tables[i] == t);
+                assert(s2.contains_table(b));
+                assert(s2.table(b) == t) by {
+                    let t2 = s2.table(b);
+                    let j = choose|j| 0 <= j < s2.tables.len() && s2.tables[j] == t2;
+                    s1.lemma_table_base_unique();
+                    if j < s1.tables.len() {
+                        assert(s1.tables[j] == t2);
+                    }
+                }
+                assert forall|e: int| 0 <= e < s1.table_view(b).len() implies #[trigger]
+                    s2.table_view(b)[e] == s1.table_view(b)[e] by {
+                    s1.lemma_entry_in_mem(b, e as nat);
+                    // The entry lies in table `i`, which doesn't overlap the new table
+                    assert(!PAddr::overlap(t.base, t.size.as_nat(), base@, size.as_nat()));
+                }
+                assert(s2.table_view(b) =~= s1.table_view(b));
+            }
+            assert(s1.allocates(level as nat, s2, new_table));
+        }
+        Some(table)
+    }
+
+    /// Deallocate a table.
+    fn dealloc_table(&mut self, base: PAddrExec) {
+        // Find the table descriptor
+        let mut i = 0;
+        while self.tables[i].base.0 != base.0
+            invariant
+                self@.contains_table(base@),
+                0 <= i < self.tables.len(),
+                forall|j| 0 <= j < i ==> self.tables[j].base.0 != base.0,
+            decreases self.tables.len() - i,
+        {
+            proof {
+                let table = choose|table: Table| #[trigger]
+                    self@.tables.contains(table) && table.base == base@;
+                let k = choose|k| 0 <= k < self@.tables.len() && self@.tables[k] == table;
+                assert(k > i);
+            }
+            i += 1;
+        }
+        let ghost pt_mem = self@;
+        self.tables.remove(i);
+        proof {
+            assert(self@.tables =~= pt_mem.tables.remove(i as int));
+            assert(self@ == PageTableMem { tables: pt_mem.tables.remove(i as int), ..pt_mem });
+            assert(pt_mem.tables[i as int].base == base@);
+            pt_mem.axiom_backend_dealloc_table(base@, self@);
+        }
+    }
+
+    /// Get the value at the given index in the given table.
+    fn read(&self, base: PAddrExec, index: usize) -> (res: u64) {
+        let word = self.word_index(base, index);
+        proof {
+            self@.lemma_entry_in_mem(base@, index as nat);
+        }
+        self.words[word]
+    }
+
+    /// Write the value to the given index in the given table.
+    fn write(&mut self, base: PAddrExec, index: usize, value: u64) {
+        let word = self.word_index(base, index);
+        self.words.set(word, value);
+        assert(self@.tables =~= old(self)@.tables);
+    }
+}
+
+/// Lemma. A frame of the memory at `base` outside the frames `[(tb - base) / 4K,
+/// (tb - base + ts - 1) / 4K + 1)` does not overlap region `(tb, ts)`.
+proof fn lemma_frame_outside(base: nat, tb: nat, ts: nat, g: nat)
+    requires
+        base <= tb,
+        ts > 0,
+        g < (tb - base) / 0x1000 || g >= (tb - base + ts - 1) / 0x1000 + 1,
+    ensures
+        !PAddr::overlap(PAddr(tb), ts, PAddr(base + g * 0x1000), 0x1000),
+{
+    if g < (tb - base) / 0x1000 {
+        assert(base + g * 0x1000 + 0x1000 <= tb) by (nonlinear_arith)
+            requires
+                base <= tb,
+                g < (tb - base) / 0x1000,
+        ;
+    } else {
+        assert(base + g * 0x1000 >= tb + ts) by (nonlinear_arith)
+            requires
+                base <= tb,
+                ts > 0,
+                g >= (tb - base + ts - 1) / 0x1000 + 1,
+        ;
+    }
+}
+
+/// Lemma. Address `p` in frames `[f, f + n)` of the memory at `base` lies in frame
+/// `(p - base) / 4K`.
+proof fn lemma_addr_in_frame(base: nat, f: nat, n: nat, p: nat)
+    requires
+        base + f * 0x1000 <= p < base + (f + n) * 0x1000,
+    ensures
+        ({
+            let g = (p - base) / 0x1000;
+            &&& f <= g < f + n
+            &&& base + g * 0x1000 <= p < base + g * 0x1000 + 0x1000
+        }),
+{
+    let g = (p - base) / 0x1000;
+    assert(f <= g < f + n && base + g * 0x1000 <= p < base + g * 0x1000 + 0x1000)
+        by (nonlinear_arith)
+        requires
+            base + f * 0x1000 <= p < base + (f + n) * 0x1000,
+            g == (p - base) / 0x1000,
+    ;
+}
+
+/// Services a host (e.g. a hypervisor) provides to `DirectMapPageTableMem`.
+pub trait DirectMapHost {
+    /// Virtual address through which physical address `paddr` is accessed.
+    ///
+    /// Usually `paddr` plus a fixed offset of the host's linear mapping.
+    fn phys_to_virt(paddr: PAddrExec) -> usize;
+
+    /// Allocate `size` bytes of physical memory, aligned to `size`.
+    ///
+    /// Returns `None` if the host is out of memory. The memory must not be in use, which the
+    /// contract cannot state since the host keeps its allocator state to itself.
+    fn alloc_frames(size: FrameSize) -> (res: Option<PAddrExec>)
+        ensures
+            res matches Some(paddr) ==> {
+                &&& paddr@.aligned(size.as_nat())
+                &&& PAddr::inside_any(paddr@, size.as_nat(), Self::pool())
+            },
+    ;
+
+    /// Deallocate `size` bytes at `paddr` returned by `alloc_frames` with the same `size`.
+    fn dealloc_frames(paddr: PAddrExec, size: FrameSize);
+
+    /// If the entry at `paddr` is inside the pool.
+    fn in_pool(paddr: PAddrExec) -> (res: bool)
+        ensures
+            res == PAddr::inside_any(paddr@, 8, Self::pool()),
+    ;
+
+    /// Physical regions `alloc_frames` hands out memory from, as `(base, size)` pairs.
+    spec fn pool() -> Seq<(PAddr, nat)>;
+
+    /// Physical memory of the machine, as `(base, size)`, word-aligned.
+    fn pmem() -> (PAddrExec, usize);
+
+    /// Regions of `pool`.
+    fn pool_regions() -> (res: Vec<(PAddrExec, usize)>)
+        ensures
+            res@.len() == Self::pool().len(),
+            forall|i|
+                0 <= i < res@.len() ==> (#[trigger] res@[i]).0@ == Self::pool()[i].0 && res@[i].1
+                    == Self::pool()[i].1,
+    ;
+}
+
+/// Concrete implementation of page table memory for a host with a direct map.
+///
+/// Tables are allocated from the host's frame allocator and identified by physical addresses,
+/// so table descriptors store real physical addresses. The contents are accessed through
+/// `H::phys_to_virt`.
+pub struct DirectMapPageTableMem<H: DirectMapHost, L: MemLog> {
+    /// All tables in the hierarchical page table, the first table is the root.
+    pub tables: Vec<TableExec>,
+    /// Page table architecture.
+    pub arch: PTArchExec,
+    /// Host services.
+    pub _host: PhantomData<H>,
+    /// Log hook.
+    pub _log: PhantomData<L>,
+}
+
+impl<H: DirectMapHost, L: MemLog> DirectMapPageTableMem<H, L> {
+    /// Physical memory that stores the tables.
+    ///
+    /// Tables are accessed through the direct map, so the contents are opaque to the verifier.
+    pub open spec fn phys_mem(self) -> PhysMem;
+
+    /// Allocate a table of the given level from the host and zero it.
+    ///
+    /// Returns `None` if the table size is not a frame size, or if the host is out of memory.
+    ///
+    /// Assumption: `H::phys_to_virt` maps the whole table to writable memory.
+    #[verifier::external_body]
+    fn alloc_zeroed_table(arch: &PTArchExec, level: usize) -> (res: Option<TableExec>)
+        requires
+            arch@.valid(),
+            level < arch@.level_count(),
+        ensures
+            res matches Some(table) ==> {
+                &&& table.level == level
+                &&& table.size.as_nat() == arch@.table_size(level as nat)
+                &&& table.base@.aligned(table.size.as_nat())
+                &&& PAddr::inside_any(table.base@, table.size.as_nat(), H::pool())
+            },
+    {
+        let size = FrameSize::from_usize(arch.entry_count(level).checked_mul(8)?)?;
+        let base = H::alloc_frames(size)?;
+        let vaddr = H::phys_to_virt(base);
+        unsafe { core::ptr::write_bytes(vaddr as *mut u8, 0, size.as_usize()) };
+        Some(TableExec { base, size, level })
+    }
+}
+
+impl<H: DirectMapHost, L: MemLog> PageTableMemExec for DirectMapPageTableMem<H, L> {
+    /// View the concrete page table memory as an abstract page table memory.
+    open spec fn view(self) -> PageTableMem {
+        PageTableMem {
+            tables: Seq::new(self.tables.len() as nat, |i| self.tables[i]@),
+            arch: self.arch@,
+            mem: self.phys_mem(),
+            pool: H::pool(),
+        }
+    }
+
+    /// Physical address of the root page table.
+    fn root(&self) -> (res: PAddrExec) {
+        self.tables[0].base
+    }
+
+    /// Physical memory of the host.
+    ///
+    /// Assumption: `H::pmem` is the memory `phys_mem` stands for.
+    #[verifier::external_body]
+    fn pmem(&self) -> (res: Option<(PAddrExec, usize)>) {
+        Some(H::pmem())
+    }
+
+    /// Regions the host allocates tables from.
+    fn pool(&self) -> (res: Vec<(PAddrExec, usize)>) {
+        H::pool_regions()
+    }
+
+    /// If a table is empty.
+    ///
+    /// Assumption: `H::phys_to_virt` maps the table to readable memory.
+    #[verifier::external_body]
+    fn is_table_empty(&self, base: PAddrExec) -> (res: bool) {
+        let table = self.tables.iter().find(|t| t.base == base).unwrap();
+        let entry_count = self.arch.entry_count(table.level);
+        let vaddr = H::phys_to_virt(base);
+        let entries = unsafe { core::slice::from_raw_parts(vaddr as *const u64, entry_count) };
+        for &entry in entries {
+            if entry != 0 {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Construct a new page table memory and initialize the root table.
+    ///
+    /// Returns `None` if the root table cannot be allocated.
+    ///
+    /// Assumption: The root table is stored in `phys_mem`, which is opaque to the verifier.
+    #[verifier::external_body]
+    fn new_init(arch: PTArchExec) -> (res: Option<DirectMapPageTableMem<H, L>>) {
+        let table = Self::alloc_zeroed_table(&arch, 0)?;
+        Some(
+            DirectMapPageTableMem {
+                tables: vec![table],
+                arch,
+                _host: PhantomData,
+                _log: PhantomData,
+            },
+        )
+    }
+
+    /// Allocate a new table and returns the table descriptor.
+    ///
+    /// Returns `None`, changing nothing, if the table cannot be allocated.
+    ///
+    /// Assumption: The host's allocator is not verified. It is trusted to hand out memory not
+    /// used by any table, i.e. to meet the trusted boundary of table allocation
+    /// (`PageTableMem::allocates`).
+    #[verifier::external_body]
+    fn alloc_table(&mut self, level: usize) -> (res: Option<TableExec>) {
+        let table = Self::alloc_zeroed_table(&self.arch, level)?;
+        self.tables.push(table);
+        L::table_allocated(table);
+        Some(table)
+    }
+
+    /// Deallocate a table.
+    ///
+    /// Assumption: The host's allocator is not verified, see `alloc_table`.
+    #[verifier::external_body]
+    fn dealloc_table(&mut self, base: PAddrExec) {
+        let size = self.tables.iter().find(|t| t.base == base).unwrap().size;
+        self.tables.retain(|t| t.base != base);
+        H::dealloc_frames(base, size);
+        L::table_deallocated(base);
+    }
+
+    /// Get the value at the given index in the given table.
+    ///
+    /// Assumption: `H::phys_to_virt` maps the entry to readable memory.
+    #[verifier::external_body]
+    fn read(&self, base: PAddrExec, index: usize) -> (res: u64) {
+        let vaddr = H::phys_to_virt(base);
+        unsafe { (vaddr as *const u64).add(index).read_volatile() }
+    }
+
+    /// Write the value to the given index in the given table.
+    ///
+    /// The entry is stored atomically with release ordering, for `DirectMapWalker`.
+    ///
+    /// Assumption: `H::phys_to_virt` maps the entry to writable memory.
+    #[verifier::external_body]
+    fn write(&mut self, base: PAddrExec, index: usize, value: u64) {
+        let vaddr = H::phys_to_virt(base);
+        let ptr = unsafe { (vaddr as *mut u64).add(index) };
+        let entry = unsafe { core::sync::atomic::AtomicU64::from_ptr(ptr) };
+        entry.store(value, core::sync::atomic::Ordering::Release)
+    }
+}
+
+/// Lock-free walker of the tables of a `DirectMapPageTableMem`.
+#[cfg(feature = "concurrent")]
+pub struct DirectMapWalker<H: DirectMapHost> {
+    /// Host services.
+    pub _host: PhantomData<H>,
+}
+
+#[cfg(feature = "concurrent")]
+impl<H: DirectMapHost> DirectMapWalker<H> {
+    /// Load the entry at `paddr` with acquire ordering.
+    ///
+    /// Assumption: `H::phys_to_virt` maps the pool to readable memory.
+    #[verifier::external_body]
+    fn load_entry(paddr: PAddrExec) -> (res: u64)
+        requires
+            PAddr::inside_any(paddr@, 8, H::pool()),
+    {
+        let vaddr = H::phys_to_virt(paddr);
+        let entry = unsafe { core::sync::atomic::AtomicU64::from_ptr(vaddr as *mut u64) };
+        entry.load(core::sync::atomic::Ordering::Acquire)
+    }
+
+    /// Axiom. `value`, loaded from the entry at `index` of the table at `base`, observed the
+    /// region of `published` at a count no smaller than the published one, and read the
+    /// published memory if the count is the published one.
+    ///
+    /// This is the trusted boundary of lock-free walks. The token cannot be obtained from the
+    /// state machine, since a walker holds no count of the region, and the count at the time
+    /// of the load is only known to the hardware. It is sound for a `value` of a single atomic
+    /// load, as writers only write the tables of a region while its count is odd and store the
+    /// next even count with release ordering. Entries outside the pool are not in the tables
+    /// of `published`, which are `DirectMapPageTableMem`s of `H`, and count as loading 0.
+    #[verifier::external_body]
+    proof fn axiom_load_observes(
+        tracked published: &PageTableSM::published,
+        base: PAddr,
+        index: nat,
+        value: u64,
+    ) -> (tracked seen: PageTableSM::seen)
+        ensures
+            seen.instance_id() == published.instance_id(),
+            seen.element().0 == published.key().0,
+            seen.element().1 >= published.key().1,
+            seen.element().1 == published.key().1 && published.value().1.accessible(base, index)
+                ==> value == published.value().1.read(base, index),
+    {
+        unimplemented!()
+    }
+}
+
+#[cfg(feature = "concurrent")]
+impl<H: DirectMapHost> PageTableWalkerExec for DirectMapWalker<H> {
+    /// Load the entry at `index` of the table at `base` with acquire ordering.
+    ///
+    /// A stale descriptor may point anywhere, so entries outside the pool load as 0. The count
+    /// the load observes is trusted (`axiom_load_observes`).
+    fn load(
+        &self,
+        published: Tracked<&PageTableSM::published>,
+        base: PAddrExec,
+        index: usize,
+    ) -> (res: (u64, Tracked<PageTableSM::seen>)) {
+        let value = if index > (usize::MAX - base.0) / 8 {
+            // The entry wraps around the address space
+            0
+        } else {
+            assert(index * 8 <= usize::MAX - base.0) by (nonlinear_arith)
+                requires
+                    index <= (usize::MAX - base.0) / 8,
+            ;
+            let paddr = PAddrExec(base.0 + index * 8);
+            if H::in_pool(paddr) {
+                Self::load_entry(paddr)
+            } else {
+                0
+            }
+        };
+        let tracked seen = Self::axiom_load_observes(
+            published.borrow(),
+            base@,
+            index as nat,
+            value,
+        );
+        (value, Tracked(seen))
+    }
+}
+
+/// Buddy allocator of 4k frames over a memory region.
+///
+/// A block of order `k` is `4k << k` bytes and aligned to its size, so power-of-two tables are
+/// aligned as hardware requires. The region may have any size, it is split into the largest
+/// aligned blocks that fit.
+///
+/// The free lists are not verified. The allocator is specified by the blocks it has handed
+/// out (`allocated`), which `alloc` never overlaps.
+#[verifier::external_body]
+pub struct BuddyAllocator {
+    /// Free blocks of each order.
+    free: Vec<BTreeSet<usize>>,
+}
+
+impl BuddyAllocator {
+    /// Region the allocator manages, as `(base, size)`.
+    pub open spec fn region(self) -> (nat, nat);
+
+    /// Blocks allocated and not deallocated yet, as `(base, size)` pairs.
+    pub open spec fn allocated(self) -> Set<(nat, nat)>;
+
+    /// If a block of `size` bytes at `addr` may be allocated: it is aligned to its size, lies
+    /// in the region and overlaps no allocated block.
+    pub open spec fn available(self, addr: nat, size: nat) -> bool {
+        let (base, len) = self.region();
+        &&& size > 0
+        &&& addr % size == 0
+        &&& base <= addr
+        &&& addr + size <= base + len
+        &&& forall|block: (nat, nat)| #[trigger]
+            self.allocated().contains(block) ==> !PAddr::overlap(
+                PAddr(block.0),
+                block.1,
+                PAddr(addr),
+                size,
+            )
+    }
+
+    /// Create an allocator of the frames within `[start, start + size)`.
+    ///
+    /// Returns `Err` if the region wraps around the address space.
+    #[verifier::external_body]
+    pub fn new(start: usize, size: usize) -> (res: Result<Self, AllocError>)
+        ensures
+            res is Ok == (start + size <= usize::MAX),
+            res is Ok ==> res->Ok_0.region() == (start as nat, size as nat),
+            res is Ok ==> res->Ok_0.allocated() === Set::empty(),
+    {
+        let mut free = vec![BTreeSet::new(); Self::MAX_ORDER + 1];
+        let end = start.checked_add(size).ok_or(AllocError)? & !(Self::FRAME_SIZE - 1);
+        // No frame starts in the region if rounding `start` up overflows
+        let mut addr = start.checked_next_multiple_of(Self::FRAME_SIZE).unwrap_or(end);
+        while addr < end {
+            let mut order = 0;
+            while order < Self::MAX_ORDER {
+                let next = Self::FRAME_SIZE << (order + 1);
+                if addr % next != 0 || end - addr < next {
+                    break;
+                }
+                order += 1;
+            }
+            free[order].insert(addr);
+            addr += Self::FRAME_SIZE << order;
+        }
+        Ok(Self { free })
+    }
+
+    /// Allocate a block of `size` bytes, aligned to `size`.
+    ///
+    /// Returns `Err` if `size` is not a power-of-two multiple of 4k, or no free block is large
+    /// enough.
+    #[verifier::external_body]
+    pub fn alloc(&mut self, size: usize) -> (res: Result<usize, AllocError>)
+        ensures
+            self.region() == old(self).region(),
+            res is Ok ==> old(self).available(res->Ok_0 as nat, size as nat),
+            res is Ok ==> self.allocated() == old(self).allocated().insert(
+                (res->Ok_0 as nat, size as nat),
+            ),
+            res is Err ==> self.allocated() == old(self).allocated(),
+    {
+        let order = Self::order(size)?;
+        let (mut k, addr) = (order..=Self::MAX_ORDER)
+            .find_map(|k| self.free[k].pop_first().map(|addr| (k, addr)))
+            .ok_or(AllocError)?;
+        // Return the upper halves of the split block
+        while k > order {
+            k -= 1;
+            self.free[k].insert(addr + (Self::FRAME_SIZE << k));
+        }
+        Ok(addr)
+    }
+
+    /// Allocate a zeroed block of `size` bytes, aligned to `size`.
+    #[verifier::external_body]
+    fn alloc_zeroed(&mut self, size: usize) -> (res: Result<PAddrExec, AllocError>)
+        ensures
+            self.region() == old(self).region(),
+            res is Ok ==> old(self).available(res->Ok_0.0 as nat, size as nat),
+            res is Ok ==> self.allocated() == old(self).allocated().insert(
+                (res->Ok_0.0 as nat, size as nat),
+            ),
+            res is Err ==> self.allocated() == old(self).allocated(),
+    {
+        let addr = self.alloc(size)?;
+        unsafe { core::ptr::write_bytes(addr as *mut u8, 0, size) };
+        Ok(PAddrExec(addr))
+    }
+
+    /// Deallocate the block of `size` bytes at `addr`, and merge it with its free buddies.
+    #[verifier::external_body]
+    pub fn dealloc(&mut self, addr: usize, size: usize) -> (res: Result<(), AllocError>)
+        requires
+            old(self).allocated().contains((addr as nat, size as nat)),
+        ensures
+            res is Ok,
+            self.region() == old(self).region(),
+            self.allocated() == old(self).allocated().remove((addr as nat, size as nat)),
+    {
+        let mut order = Self::order(size)?;
+        let mut addr = addr;
+        while order < Self::MAX_ORDER {
+            let buddy = addr ^ (Self::FRAME_SIZE << order);
+            if !self.free[order].remove(&buddy) {
+                break;
+            }
+            addr = addr.min(buddy);
+            order += 1;
+        }
+        self.free[order].insert(addr);
+        Ok(())
+    }
+}
+
 } // verus!
 
-/// An easy memory pool that uses a bitmap to track 4k physical frames.
+/// Size of the region a page table memory allocates when the caller provides none.
+pub const DEFAULT_REGION_SIZE: usize = 0x100000;
+
+/// Region a page table memory allocates when the caller provides none.
 #[repr(C, align(4096))]
-pub struct FramePool {
-    /// Memory region.
-    pub mem: [u8; 0x100000],
-    /// Bitmap of the physical frames.
-    pub bitmap: [bool; 0x100],
-}
+pub struct DefaultRegion(pub [u8; DEFAULT_REGION_SIZE]);
+
+impl BuddyAllocator {
+    /// Log2 of the size of an order 0 block.
+    const FRAME_SHIFT: usize = 12;
+
+    /// Size of an order 0 block.
+    const FRAME_SIZE: usize = 1 << Self::FRAME_SHIFT;
+
+    /// Largest order, the largest block whose size still fits in `usize`.
+    const MAX_ORDER: usize = usize::BITS as usize - 1 - Self::FRAME_SHIFT;
 
-impl FramePool {
-    /// Create a new frame pool.
-    pub fn new() -> Self {
-        Self {
-            bitmap: [false; 0x100],
-            mem: [0; 0x100000],
+    /// Order of blocks of `size` bytes.
+    ///
+    /// Returns `Err` unless `size` is a power-of-two multiple of 4k, up to the largest order.
+    fn order(size: usize) -> Result<usize, AllocError> {
+        if !size.is_power_of_two() || size < Self::FRAME_SIZE {
+            return Err(AllocError);
+        }
+        let order = (size / Self::FRAME_SIZE).trailing_zeros() as usize;
+        if order > Self::MAX_ORDER {
+            return Err(AllocError);
         }
+        Ok(order)
     }
+}
+
+/// Hook that prints every event to standard output.
+#[cfg(feature = "std")]
+pub struct StdLog;
 
-    /// Allocate a 4k physical frame.
-    fn alloc(&mut self) -> PAddrExec {
-        let idx = self.bitmap.iter().position(|b| !b).unwrap();
-        self.bitmap[idx] = true;
-        PAddrExec(self.mem.as_ptr() as usize + idx as usize * 4096)
+#[cfg(feature = "std")]
+impl MemLog for StdLog {
+    fn table_allocated(table: TableExec) {
+        std::println!("Allocate table at {:#x}", table.base.0);
     }
 
-    /// Deallocate a 4k physical frame.
-    fn dealloc(&mut self, addr: PAddrExec) {
-        let idx = (addr.0 - self.mem.as_ptr() as usize) / 4096;
-        self.bitmap[idx] = false;
+    fn table_deallocated(base: PAddrExec) {
+        std::println!("Deallocate table at {:#x}", base.0);
     }
 }
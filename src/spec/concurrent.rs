@@ -0,0 +1,690 @@
+//! Linearizability specification of concurrent page table operations.
+//!
+//! Several CPUs operate on one page table. The virtual address space is split into regions, one
+//! per root table entry, and each region is owned by whoever holds its `regions` token, so
+//! operations on different regions do not wait for each other. Each operation takes effect
+//! atomically at a single step of `PageTableSM`, which is a `PageTableState` transition of its
+//! region. A mapping never crosses a region, so every region step is also a transition of the
+//! whole page table (`lemma_map_linearizable`, `lemma_unmap_linearizable`,
+//! `lemma_query_in_region`), and concurrent executions are linearizable against the page table
+//! specification.
+//!
+//! Mapping a frame requires the frame not to overlap any existing mapping, which a single CPU
+//! cannot check while others are mapping. Callers therefore own the frames they map: a `frames`
+//! token proves the frame is not mapped. Mapping consumes the token, and unmapping returns it.
+//!
+//! Queries may also run without the lock, overlapping an in-flight update. Each region has a
+//! sequence count, which is odd while a writer updates the tables of the region. Bringing the
+//! count back to an even value publishes the page table memory of the region, and a query
+//! that sees the same even count before and after walking the tables has read the published
+//! memory (`seen_bound`). Such a query returns the result of the state either before or after
+//! the update (`query_linearizable`).
+use vstd::prelude::*;
+use verus_state_machines_macros::tokenized_state_machine;
+
+use super::{
+    memory::PageTableMem,
+    page_table::{PTConstants, PageTableState},
+};
+use crate::common::{
+    addr::{PAddr, VAddr},
+    frame::{Frame, FrameSize},
+    PagingResult,
+};
+
+verus! {
+
+/// Region of `vaddr`, which is the index of its root table entry.
+pub open spec fn region_of(constants: PTConstants, vaddr: VAddr) -> nat {
+    constants.arch.pte_index(vaddr, 0)
+}
+
+/// Mappings of the whole page table, made of the mappings of each region.
+pub open spec fn union_mappings(
+    constants: PTConstants,
+    regions: Map<nat, Map<VAddr, Frame>>,
+) -> Map<VAddr, Frame> {
+    Map::new(
+        |vbase: VAddr|
+            regions.contains_key(region_of(constants, vbase)) && regions[region_of(
+                constants,
+                vbase,
+            )].contains_key(vbase),
+        |vbase: VAddr| regions[region_of(constants, vbase)][vbase],
+    )
+}
+
+/// Lemma. Addresses within an aligned mapping are in the region of its base address.
+pub proof fn lemma_within_mapping_same_region(
+    constants: PTConstants,
+    vbase: VAddr,
+    size: FrameSize,
+    vaddr: VAddr,
+)
+    requires
+        constants.arch.valid(),
+        constants.arch.is_valid_frame_size(size),
+        vbase.aligned(size.as_nat()),
+        vaddr.within(vbase, size.as_nat()),
+    ensures
+        region_of(constants, vaddr) == region_of(constants, vbase),
+{
+    let arch = constants.arch;
+    let level = arch.level_of_frame_size(size);
+    arch.lemma_vbase_within(vbase, vaddr, level);
+    arch.lemma_vbase_within(vbase, vbase, level);
+    arch.lemma_same_vbase_same_pte_index(vaddr, vbase, level, 0);
+}
+
+tokenized_state_machine!{
+    PageTableSM {
+        fields {
+            // Page table constants.
+            #[sharding(constant)]
+            pub constants: PTConstants,
+
+            // Mappings of each region, owned by whoever holds the lock of the region.
+            #[sharding(map)]
+            pub regions: Map<nat, Map<VAddr, Frame>>,
+
+            // Sequence count of each region, owned by whoever holds the lock of the region.
+            #[sharding(map)]
+            pub writer: Map<nat, nat>,
+
+            // Sequence count of each region, readable by walkers at any time.
+            #[sharding(map)]
+            pub counts: Map<nat, nat>,
+
+            // Mappings and page table memory of a region, published at an even count.
+            #[sharding(persistent_map)]
+            pub published: Map<(nat, nat), (Map<VAddr, Frame>, PageTableMem)>,
+
+            // Counts of regions observed by walkers.
+            #[sharding(persistent_set)]
+            pub seen: Set<(nat, nat)>,
+
+            // Frames owned by callers, which are not mapped.
+            #[sharding(set)]
+            pub frames: Set<Frame>,
+        }
+
+        // The architecture is valid.
+        #[invariant]
+        pub fn arch_valid(&self) -> bool {
+            self.constants.arch.valid()
+        }
+
+        // Mappings lie in the region of their base address, aligned to a supported frame size.
+        #[invariant]
+        pub fn regions_wf(&self) -> bool {
+            forall|i: nat, vbase: VAddr|
+                self.regions.contains_key(i) && #[trigger] self.regions[i].contains_key(vbase)
+                    ==> {
+                    &&& region_of(self.constants, vbase) == i
+                    &&& self.constants.arch.is_valid_frame_size(self.regions[i][vbase].size)
+                    &&& vbase.aligned(self.regions[i][vbase].size.as_nat())
+                }
+        }
+
+        // Writers and walkers agree on the count of every region.
+        #[invariant]
+        pub fn counts_agree(&self) -> bool {
+            forall|i: nat|
+                #[trigger] self.regions.contains_key(i) <==> {
+                    &&& self.writer.contains_key(i)
+                    &&& self.counts.contains_key(i)
+                    &&& self.writer[i] == self.counts[i]
+                }
+        }
+
+        // At an even count, the published mappings are the mappings of the region.
+        #[invariant]
+        pub fn published_current(&self) -> bool {
+            forall|i: nat|
+                #[trigger] self.counts.contains_key(i) && self.counts[i] % 2 == 0 ==> {
+                    &&& self.published.contains_key((i, self.counts[i]))
+                    &&& self.published[(i, self.counts[i])].0 == self.regions[i]
+                }
+        }
+
+        // Published and seen counts do not exceed the current count of their region.
+        #[invariant]
+        pub fn counts_bound(&self) -> bool {
+            &&& forall|k: (nat, nat)| #[trigger]
+                self.published.contains_key(k) ==> self.counts.contains_key(k.0) && k.1
+                    <= self.counts[k.0]
+            &&& forall|k: (nat, nat)| #[trigger]
+                self.seen.contains(k) ==> self.counts.contains_key(k.0) && k.1 <= self.counts[k.0]
+        }
+
+        // Owned frames do not overlap any mapping.
+        #[invariant]
+        pub fn frames_unmapped(&self) -> bool {
+            forall|i: nat, frame: Frame|
+                #[trigger] self.regions.contains_key(i) && #[trigger] self.frames.contains(frame)
+                    ==> !PageTableState::new(self.regions[i], self.constants).overlaps_pmem(frame)
+        }
+
+        // Owned frames do not overlap each other.
+        #[invariant]
+        pub fn frames_disjoint(&self) -> bool {
+            forall|frame1: Frame, frame2: Frame|
+                #[trigger] self.frames.contains(frame1) && #[trigger] self.frames.contains(frame2)
+                    && frame1 != frame2 ==> !PAddr::overlap(
+                        frame1.base,
+                        frame1.size.as_nat(),
+                        frame2.base,
+                        frame2.size.as_nat(),
+                    )
+        }
+
+        // Mappings do not overlap in physical memory, even across regions.
+        #[invariant]
+        pub fn mappings_nonoverlap_in_pmem(&self) -> bool {
+            forall|i: nat, j: nat, vbase1: VAddr, vbase2: VAddr|
+                self.regions.contains_key(i) && self.regions.contains_key(j)
+                    && #[trigger] self.regions[i].contains_key(vbase1)
+                    && #[trigger] self.regions[j].contains_key(vbase2) && vbase1 != vbase2
+                    ==> !PAddr::overlap(
+                        self.regions[i][vbase1].base,
+                        self.regions[i][vbase1].size.as_nat(),
+                        self.regions[j][vbase2].base,
+                        self.regions[j][vbase2].size.as_nat(),
+                    )
+        }
+
+        // Mappings of a region do not overlap in virtual memory.
+        #[invariant]
+        pub fn mappings_nonoverlap_in_vmem(&self) -> bool {
+            forall|i: nat, vbase1: VAddr, vbase2: VAddr|
+                self.regions.contains_key(i) && #[trigger] self.regions[i].contains_key(vbase1)
+                    && #[trigger] self.regions[i].contains_key(vbase2) && vbase1 != vbase2
+                    ==> !VAddr::overlap(
+                        vbase1,
+                        self.regions[i][vbase1].size.as_nat(),
+                        vbase2,
+                        self.regions[i][vbase2].size.as_nat(),
+                    )
+        }
+
+        init!{
+            initialize(constants: PTConstants, frames: Set<Frame>) {
+                require(constants.arch.valid());
+                require(forall|frame1: Frame, frame2: Frame|
+                    #[trigger] frames.contains(frame1) && #[trigger] frames.contains(frame2)
+                        && frame1 != frame2 ==> !PAddr::overlap(
+                            frame1.base,
+                            frame1.size.as_nat(),
+                            frame2.base,
+                            frame2.size.as_nat(),
+                        ));
+                init constants = constants;
+                init regions = Map::new(
+                    |i: nat| i < constants.arch.entry_count(0),
+                    |i: nat| Map::empty(),
+                );
+                // Regions are unpublished until their tables are set up
+                init writer = Map::new(|i: nat| i < constants.arch.entry_count(0), |i: nat| 1);
+                init counts = Map::new(|i: nat| i < constants.arch.entry_count(0), |i: nat| 1);
+                init published = Map::empty();
+                init seen = Set::empty();
+                init frames = frames;
+            }
+        }
+
+        // Linearization point of a successful `map` in region `index`. The mapped frame is no
+        // longer owned.
+        transition!{
+            map(index: nat, vbase: VAddr, frame: Frame, mappings: Map<VAddr, Frame>) {
+                remove regions -= [index => let old];
+                have writer >= [index => let count];
+                require(count % 2 == 1);
+                remove frames -= set { frame };
+                require(index == region_of(pre.constants, vbase));
+                require(PageTableState::map(
+                    PageTableState::new(old, pre.constants),
+                    PageTableState::new(mappings, pre.constants),
+                    vbase,
+                    frame,
+                    Ok(()),
+                ));
+                add regions += [index => mappings];
+            }
+        }
+
+        // Linearization point of a successful `unmap` in region `index`. The unmapped frame is
+        // owned again.
+        transition!{
+            unmap(index: nat, vbase: VAddr, mappings: Map<VAddr, Frame>) {
+                remove regions -= [index => let old];
+                have writer >= [index => let count];
+                require(count % 2 == 1);
+                require(index == region_of(pre.constants, vbase));
+                require(PageTableState::unmap(
+                    PageTableState::new(old, pre.constants),
+                    PageTableState::new(mappings, pre.constants),
+                    vbase,
+                    Ok(()),
+                ));
+                add regions += [index => mappings];
+                add frames += set { old[vbase] } by {
+                    // The frame is mapped, so no owned frame is equal to it.
+                    assert(old.contains_value(old[vbase]));
+                    if pre.frames.contains(old[vbase]) {
+                        assert(pre.regions.contains_key(index));
+                        assert(PageTableState::new(old, pre.constants).overlaps_pmem(old[vbase]));
+                    }
+                };
+            }
+        }
+
+        // A writer starts updating the tables of region `index`.
+        transition!{
+            begin(index: nat) {
+                remove writer -= [index => let count];
+                remove counts -= [index => let _current];
+                require(count % 2 == 0);
+                add writer += [index => count + 1];
+                add counts += [index => count + 1];
+            }
+        }
+
+        // A writer finishes updating the tables of region `index`, and publishes them with the
+        // mappings of the region.
+        transition!{
+            publish(index: nat, mem: PageTableMem) {
+                have regions >= [index => let mappings];
+                remove writer -= [index => let count];
+                remove counts -= [index => let _current];
+                require(count % 2 == 1);
+                add writer += [index => count + 1];
+                add counts += [index => count + 1];
+                add published (union)= [(index, count + 1) => (mappings, mem)] by {
+                    // Published counts do not exceed the current one
+                    assert(pre.counts[index] == count);
+                };
+            }
+        }
+
+        // A walker observes the count of region `index`.
+        transition!{
+            observe(index: nat) {
+                have counts >= [index => let count];
+                add seen (union)= set { (index, count) };
+            }
+        }
+
+        // Counts only grow, so a count seen earlier does not exceed the current one.
+        property!{
+            seen_bound(index: nat, count: nat) {
+                have counts >= [index => let current];
+                have seen >= set { (index, count) };
+                assert(count <= current);
+            }
+        }
+
+        // An owned frame can be mapped in any region.
+        property!{
+            frame_unmapped(index: nat, frame: Frame) {
+                have regions >= [index => let mappings];
+                have frames >= set { frame };
+                assert(!PageTableState::new(mappings, pre.constants).overlaps_pmem(frame));
+            }
+        }
+
+        #[inductive(initialize)]
+        fn initialize_inductive(post: Self, constants: PTConstants, frames: Set<Frame>) {
+        }
+
+        #[inductive(begin)]
+        fn begin_inductive(pre: Self, post: Self, index: nat) {
+            assert(post.counts === pre.counts.insert(index, pre.counts[index] + 1));
+            assert(post.writer === pre.writer.insert(index, pre.writer[index] + 1));
+        }
+
+        #[inductive(publish)]
+        fn publish_inductive(pre: Self, post: Self, index: nat, mem: PageTableMem) {
+            let count = pre.counts[index] + 1;
+            assert(post.counts === pre.counts.insert(index, count));
+            assert(post.writer === pre.writer.insert(index, count));
+            assert(post.published === pre.published.insert(
+                (index, count),
+                (pre.regions[index], mem),
+            ));
+        }
+
+        #[inductive(observe)]
+        fn observe_inductive(pre: Self, post: Self, index: nat) {
+            assert(post.seen === pre.seen.insert((index, pre.counts[index])));
+        }
+
+        #[inductive(map)]
+        fn map_inductive(
+            pre: Self,
+            post: Self,
+            index: nat,
+            vbase: VAddr,
+            frame: Frame,
+            mappings: Map<VAddr, Frame>,
+        ) {
+            let old = pre.regions[index];
+            assert(post.regions === pre.regions.insert(index, mappings));
+            assert(post.frames === pre.frames.remove(frame));
+            // The count is odd, so no published mappings are the ones of the region
+            assert(pre.counts[index] % 2 == 1);
+            assert(mappings === old.insert(vbase, frame));
+            assert forall|i: nat, f: Frame|
+                #[trigger] post.regions.contains_key(i) && #[trigger] post.frames.contains(
+                    f,
+                ) implies !PageTableState::new(post.regions[i], post.constants).overlaps_pmem(
+                f,
+            ) by {
+                assert(pre.frames.contains(f) && f != frame);
+                assert(pre.regions.contains_key(i));
+                if i == index && PageTableState::new(mappings, post.constants).overlaps_pmem(f) {
+                    // Proof by contradiction. `f` overlaps the new frame or an old mapping.
+                    let frame2 = choose|frame2: Frame|
+                        #[trigger] mappings.contains_value(frame2) && PAddr::overlap(
+                            frame2.base,
+                            frame2.size.as_nat(),
+                            f.base,
+                            f.size.as_nat(),
+                        );
+                    if frame2 != frame {
+                        let vbase2 = choose|vbase2: VAddr|
+                            #[trigger] mappings.contains_key(vbase2) && mappings[vbase2] == frame2;
+                        assert(old.contains_key(vbase2));
+                        assert(old.contains_value(frame2));
+                        assert(PageTableState::new(old, pre.constants).overlaps_pmem(f));
+                    }
+                }
+            }
+            assert forall|i: nat, j: nat, vbase1: VAddr, vbase2: VAddr|
+                post.regions.contains_key(i) && post.regions.contains_key(j)
+                    && #[trigger] post.regions[i].contains_key(vbase1)
+                    && #[trigger] post.regions[j].contains_key(vbase2) && vbase1 != vbase2
+                    implies !PAddr::overlap(
+                        post.regions[i][vbase1].base,
+                        post.regions[i][vbase1].size.as_nat(),
+                        post.regions[j][vbase2].base,
+                        post.regions[j][vbase2].size.as_nat(),
+                    ) by {
+                // The new frame does not overlap mappings of any region
+                if i == index && vbase1 == vbase {
+                    assert(pre.regions[j].contains_value(post.regions[j][vbase2]));
+                    assert(!PageTableState::new(pre.regions[j], pre.constants).overlaps_pmem(
+                        frame,
+                    ));
+                } else if j == index && vbase2 == vbase {
+                    assert(pre.regions[i].contains_value(post.regions[i][vbase1]));
+                    assert(!PageTableState::new(pre.regions[i], pre.constants).overlaps_pmem(
+                        frame,
+                    ));
+                }
+            }
+            assert forall|i: nat, vbase1: VAddr, vbase2: VAddr|
+                post.regions.contains_key(i) && #[trigger] post.regions[i].contains_key(vbase1)
+                    && #[trigger] post.regions[i].contains_key(vbase2) && vbase1 != vbase2
+                    implies !VAddr::overlap(
+                        vbase1,
+                        post.regions[i][vbase1].size.as_nat(),
+                        vbase2,
+                        post.regions[i][vbase2].size.as_nat(),
+                    ) by {
+                // The new mapping does not overlap old mappings of its region
+                if i == index && vbase1 == vbase {
+                    assert(old.contains_key(vbase2));
+                } else if i == index && vbase2 == vbase {
+                    assert(old.contains_key(vbase1));
+                }
+            }
+        }
+
+        #[inductive(unmap)]
+        fn unmap_inductive(
+            pre: Self,
+            post: Self,
+            index: nat,
+            vbase: VAddr,
+            mappings: Map<VAddr, Frame>,
+        ) {
+            let old = pre.regions[index];
+            let unmapped = old[vbase];
+            assert(post.regions === pre.regions.insert(index, mappings));
+            // The count is odd, so no published mappings are the ones of the region
+            assert(pre.counts[index] % 2 == 1);
+            assert(post.frames === pre.frames.insert(unmapped));
+            assert(mappings === old.remove(vbase));
+            assert forall|i: nat, f: Frame|
+                #[trigger] post.regions.contains_key(i) && #[trigger] post.frames.contains(
+                    f,
+                ) implies !PageTableState::new(post.regions[i], post.constants).overlaps_pmem(
+                f,
+            ) by {
+                let s2 = PageTableState::new(post.regions[i], post.constants);
+                assert(pre.regions.contains_key(i));
+                if s2.overlaps_pmem(f) {
+                    // Proof by contradiction. Remaining mappings overlap neither the unmapped
+                    // frame nor other owned frames.
+                    let frame2 = choose|frame2: Frame|
+                        #[trigger] post.regions[i].contains_value(frame2) && PAddr::overlap(
+                            frame2.base,
+                            frame2.size.as_nat(),
+                            f.base,
+                            f.size.as_nat(),
+                        );
+                    let vbase2 = choose|vbase2: VAddr|
+                        #[trigger] post.regions[i].contains_key(vbase2)
+                            && post.regions[i][vbase2] == frame2;
+                    assert(pre.regions[i].contains_key(vbase2));
+                    // The unmapped entry lies in region `index` only
+                    assert(vbase2 != vbase);
+                    if f != unmapped {
+                        assert(pre.regions[i].contains_value(frame2));
+                    } else {
+                        assert(pre.regions[index].contains_key(vbase));
+                    }
+                }
+            }
+            assert forall|frame1: Frame, frame2: Frame|
+                #[trigger] post.frames.contains(frame1) && #[trigger] post.frames.contains(frame2)
+                    && frame1 != frame2 implies !PAddr::overlap(
+                        frame1.base,
+                        frame1.size.as_nat(),
+                        frame2.base,
+                        frame2.size.as_nat(),
+                    ) by {
+                // Owned frames do not overlap the unmapped frame, which was mapped
+                assert(pre.regions.contains_key(index));
+                assert(old.contains_value(unmapped));
+            }
+        }
+    }
+}
+
+/// Lemma. An owned frame overlaps no mapping of the whole page table.
+proof fn lemma_frame_unmapped_in_union(s: PageTableSM::State, frame: Frame)
+    requires
+        s.invariant(),
+        s.frames.contains(frame),
+    ensures
+        !PageTableState::new(union_mappings(s.constants, s.regions), s.constants).overlaps_pmem(
+            frame,
+        ),
+{
+    let mappings = union_mappings(s.constants, s.regions);
+    if PageTableState::new(mappings, s.constants).overlaps_pmem(frame) {
+        // Proof by contradiction. The overlapping mapping belongs to a region.
+        let frame2 = choose|frame2: Frame|
+            #[trigger] mappings.contains_value(frame2) && PAddr::overlap(
+                frame2.base,
+                frame2.size.as_nat(),
+                frame.base,
+                frame.size.as_nat(),
+            );
+        let vbase2 = choose|vbase2: VAddr|
+            #[trigger] mappings.contains_key(vbase2) && mappings[vbase2] == frame2;
+        let i = region_of(s.constants, vbase2);
+        assert(s.regions[i].contains_value(frame2));
+        assert(PageTableState::new(s.regions[i], s.constants).overlaps_pmem(frame));
+    }
+}
+
+/// Lemma. A `map` step of a region is a successful `map` of the whole page table.
+pub proof fn lemma_map_linearizable(
+    pre: PageTableSM::State,
+    post: PageTableSM::State,
+    index: nat,
+    vbase: VAddr,
+    frame: Frame,
+    mappings: Map<VAddr, Frame>,
+)
+    requires
+        pre.invariant(),
+        PageTableSM::State::map(pre, post, index, vbase, frame, mappings),
+    ensures
+        PageTableState::map(
+            PageTableState::new(union_mappings(pre.constants, pre.regions), pre.constants),
+            PageTableState::new(union_mappings(post.constants, post.regions), post.constants),
+            vbase,
+            frame,
+            Ok(()),
+        ),
+{
+    let constants = pre.constants;
+    let old = pre.regions[index];
+    let s1 = PageTableState::new(union_mappings(constants, pre.regions), constants);
+    assert(post.regions === pre.regions.insert(index, mappings));
+    assert(mappings === old.insert(vbase, frame));
+    lemma_frame_unmapped_in_union(pre, frame);
+    if s1.overlaps_vmem(vbase, frame) {
+        // Proof by contradiction. An overlapping mapping would be in the same region.
+        let vbase2 = choose|vbase2: VAddr|
+            #[trigger] s1.mappings.contains_key(vbase2) && VAddr::overlap(
+                vbase2,
+                s1.mappings[vbase2].size.as_nat(),
+                vbase,
+                frame.size.as_nat(),
+            );
+        let i = region_of(constants, vbase2);
+        let size2 = s1.mappings[vbase2].size;
+        assert(pre.regions[i].contains_key(vbase2));
+        if vbase2.0 <= vbase.0 {
+            lemma_within_mapping_same_region(constants, vbase2, size2, vbase);
+        } else {
+            lemma_within_mapping_same_region(constants, vbase, frame.size, vbase2);
+        }
+        assert(i == index);
+        assert(PageTableState::new(old, constants).overlaps_vmem(vbase, frame));
+    }
+    assert(union_mappings(constants, post.regions) =~= s1.mappings.insert(vbase, frame));
+}
+
+/// Lemma. An `unmap` step of a region is a successful `unmap` of the whole page table.
+pub proof fn lemma_unmap_linearizable(
+    pre: PageTableSM::State,
+    post: PageTableSM::State,
+    index: nat,
+    vbase: VAddr,
+    mappings: Map<VAddr, Frame>,
+)
+    requires
+        pre.invariant(),
+        PageTableSM::State::unmap(pre, post, index, vbase, mappings),
+    ensures
+        PageTableState::unmap(
+            PageTableState::new(union_mappings(pre.constants, pre.regions), pre.constants),
+            PageTableState::new(union_mappings(post.constants, post.regions), post.constants),
+            vbase,
+            Ok(()),
+        ),
+{
+    let constants = pre.constants;
+    let old = pre.regions[index];
+    let s1 = PageTableState::new(union_mappings(constants, pre.regions), constants);
+    assert(post.regions === pre.regions.insert(index, mappings));
+    assert(mappings === old.remove(vbase));
+    assert(s1.mappings.contains_key(vbase));
+    assert(union_mappings(constants, post.regions) =~= s1.mappings.remove(vbase));
+}
+
+/// Lemma. A query answered from the region of `vaddr` is a query of the whole page table.
+pub proof fn lemma_query_in_region(
+    s: PageTableSM::State,
+    index: nat,
+    vaddr: VAddr,
+    res: PagingResult<(VAddr, Frame)>,
+)
+    requires
+        s.invariant(),
+        s.regions.contains_key(index),
+        index == region_of(s.constants, vaddr),
+        PageTableState::query(
+            PageTableState::new(s.regions[index], s.constants),
+            PageTableState::new(s.regions[index], s.constants),
+            vaddr,
+            res,
+        ),
+    ensures
+        PageTableState::query(
+            PageTableState::new(union_mappings(s.constants, s.regions), s.constants),
+            PageTableState::new(union_mappings(s.constants, s.regions), s.constants),
+            vaddr,
+            res,
+        ),
+{
+    let constants = s.constants;
+    let region = PageTableState::new(s.regions[index], constants);
+    let whole = PageTableState::new(union_mappings(constants, s.regions), constants);
+    // Mappings containing `vaddr` are the same in the region and in the whole page table
+    assert forall|vbase: VAddr, frame: Frame| vaddr.within(vbase, frame.size.as_nat()) implies (
+    #[trigger] whole.mappings.contains_pair(vbase, frame) <==> region.mappings.contains_pair(
+        vbase,
+        frame,
+    )) by {
+        if whole.mappings.contains_pair(vbase, frame) {
+            let i = region_of(constants, vbase);
+            assert(s.regions[i].contains_key(vbase));
+            lemma_within_mapping_same_region(constants, vbase, frame.size, vaddr);
+        }
+    }
+    if whole.has_mapping_for(vaddr) {
+        let (vbase1, frame1) = whole.mapping_for(vaddr);
+        let (vbase2, frame2) = region.mapping_for(vaddr);
+        assert(region.mappings.contains_pair(vbase1, frame1));
+        assert(region.has_mapping_for(vaddr));
+        // Mappings of a region do not overlap, so only one of them contains `vaddr`
+        if vbase1 != vbase2 {
+            assert(s.regions[index].contains_key(vbase1));
+            assert(s.regions[index].contains_key(vbase2));
+            assert(VAddr::overlap(vbase1, frame1.size.as_nat(), vbase2, frame2.size.as_nat()));
+        }
+    } else if region.has_mapping_for(vaddr) {
+        let (vbase, frame) = region.mapping_for(vaddr);
+        assert(whole.mappings.contains_pair(vbase, frame));
+    }
+}
+
+/// If `s2` follows `s1` by at most one successful `map` or `unmap`.
+pub open spec fn at_most_one_update(s1: PageTableState, s2: PageTableState) -> bool {
+    ||| s1 == s2
+    ||| exists|vbase: VAddr, frame: Frame| #[trigger]
+        PageTableState::map(s1, s2, vbase, frame, Ok(()))
+    ||| exists|vbase: VAddr| #[trigger] PageTableState::unmap(s1, s2, vbase, Ok(()))
+}
+
+/// Result of a query that overlaps an update from `s1` to `s2`.
+///
+/// The result is the one of the state before or after the update, never the one of a partially
+/// applied update.
+pub open spec fn query_linearizable(
+    s1: PageTableState,
+    s2: PageTableState,
+    vaddr: VAddr,
+    res: PagingResult<(VAddr, Frame)>,
+) -> bool {
+    &&& at_most_one_update(s1, s2)
+    &&& PageTableState::query(s1, s1, vaddr, res) || PageTableState::query(s2, s2, vaddr, res)
+}
+
+} // verus!
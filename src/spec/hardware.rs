@@ -4,128 +4,265 @@
 //！operations. The hardware state includes:
 //！
 //！- Physical memory.
-//！- Page table memory.
+//！- Page table memory, which is stored in physical memory.
 //！- Translation Lookaside Buffer (TLB).
 //！
+//！Devices are outside the hardware state. Accesses to device memory are forwarded to them.
+//！
 //！The module specifies hardware behavior during memory translations, TLB management, and page
-//！table operarations.
+//！table operarations. Page table operations are observed by the walker entry by entry, so
+//！software must follow break-before-make when replacing valid descriptors. The MMU itself
+//！writes page table memory only to set the Access and Dirty flags of descriptors.
 //！
 //！**Assumption:** The hardware behavior refines the hardware specification, ensuring correctness
 //！in memory translations. This specification underpins the entire verification process.
+//！Besides this module, the trusted base only includes the descriptor format (`GhostPTE`) that
+//！the hardware page table walk decodes.
+use core::marker::PhantomData;
 use vstd::prelude::*;
 
-use super::memory::{PageTableMem, PhysMem, TLB};
+use super::memory::{PageTableMem, PhysMem, TLBOp, TLBTag, Table, TLB};
 use crate::common::{
-    addr::{VAddr, WORD_SIZE},
+    addr::{AccessSize, PAddr, PIdx, VAddr},
     frame::Frame,
-    MemoryResult,
+    pte::GhostPTE,
+    FaultKind, MemoryResult, MmioAccess, Privilege,
 };
 
 verus! {
 
 /// Abstract state managed by hardware.
-pub struct HardwareState {
+///
+/// `G` is the page table descriptor format the MMU decodes during a page table walk.
+pub struct HardwareState<G: GhostPTE> {
     /// Physical memory.
     pub mem: PhysMem,
-    /// Page table.
+    /// Page table. Tables are stored in physical memory `mem`.
     pub pt: PageTableMem,
     /// Translation Lookaside Buffer.
     pub tlb: TLB,
+    /// Tag of the running address space (ASID for stage-1, VMID for stage-2).
+    ///
+    /// Lookups only match entries with this tag, and fills are tagged with it.
+    pub tag: TLBTag,
+    /// Phantom data.
+    pub _phantom: PhantomData<G>,
 }
 
 /// State transition specification.
-impl HardwareState {
+impl<G> HardwareState<G> where G: GhostPTE {
     /// Hardware init state.
     ///
     /// No mappings exist in the page table and TLB.
     pub open spec fn init(self) -> bool {
         &&& self.tlb.is_empty()
-        &&& self.pt.interpret() === Map::empty()
+        &&& self.pt.interpret::<G>() === Map::empty()
     }
 
-    /// Hardware state transition - memory read.
-    pub open spec fn read(s1: Self, s2: Self, vaddr: VAddr, res: MemoryResult<u64>) -> bool {
-        &&& vaddr.aligned(
-            WORD_SIZE,
+    /// Hardware state transition - memory read of `size` bytes at `privilege`.
+    ///
+    /// The MMU may set the Access flag of the descriptor that translates `vaddr` first. A read
+    /// of device memory is forwarded to the device as `mmio`.
+    pub open spec fn read(
+        s1: Self,
+        s2: Self,
+        vaddr: VAddr,
+        size: AccessSize,
+        privilege: Privilege,
+        res: MemoryResult<u64>,
+        mmio: Option<MmioAccess>,
+    ) -> bool {
+        ||| Self::read_access(s1, s2, vaddr, size, privilege, res, mmio)
+        ||| s1.pt_has_mapping_for(vaddr) && Self::read_access(
+            s1.set_flags(vaddr, false),
+            s2,
+            vaddr,
+            size,
+            privilege,
+            res,
+            mmio,
         )
+    }
+
+    /// Memory read, once the MMU has updated the descriptor flags.
+    pub open spec fn read_access(
+        s1: Self,
+        s2: Self,
+        vaddr: VAddr,
+        size: AccessSize,
+        privilege: Privilege,
+        res: MemoryResult<u64>,
+        mmio: Option<MmioAccess>,
+    ) -> bool {
+        &&& s1.tag == s2.tag
         // Memory and page table should not be updated
         &&& s1.mem === s2.mem
         &&& s1.pt === s2.pt
-        // Check mapping
-        &&& if s1.tlb_has_mapping_for(vaddr) {
+        // Check alignment, then mapping
+        &&& if !vaddr.aligned(size.as_nat()) {
+            // 0. Misaligned, so the access may span two pages. Nothing is translated.
+            &&& res is AlignmentFault
+            &&& mmio is None
+            &&& s2.tlb === s1.tlb
+        } else if s1.tlb_has_mapping_for(vaddr) {
             // 1. TLB hit
             let (base, frame) = s1.tlb_mapping_for(vaddr);
-            let pidx = vaddr.map(base, frame.base).idx();
-            // Check frame attributes
-            &&& if s1.mem.contains(pidx) && frame.attr.readable && frame.attr.user_accessible {
-                &&& res is Ok
-                &&& res->Ok_0 === s1.mem.read(pidx)
-            } else {
-                &&& res is PageFault
-            }
+            &&& s1.read_through(vaddr, size, privilege, base, frame, res, mmio)
             &&& s1.tlb === s2.tlb
         } else if s1.pt_has_mapping_for(vaddr) {
             // 2. TLB miss, page table hit
             let (base, frame) = s1.pt_mapping_for(vaddr);
-            let pidx = vaddr.map(base, frame.base).idx();
-            // Check frame attributes
-            &&& if s1.mem.contains(pidx) && frame.attr.readable && frame.attr.user_accessible {
-                &&& res is Ok
-                &&& res->Ok_0 === s1.mem.read(pidx)
-            } else {
-                &&& res is PageFault
-            }
+            &&& s1.read_through(vaddr, size, privilege, base, frame, res, mmio)
             // TLB should be updated
-            &&& s2.tlb === s1.tlb.update(base, frame)
+            &&& s2.tlb === s1.tlb.update(s1.tag, base, frame)
         } else {
             // 3. TLB miss, page table miss
-            &&& res is PageFault
+            &&& res.is_fault(FaultKind::Translation, s1.walk_level(vaddr))
+            &&& mmio is None
             &&& s2.tlb === s1.tlb
         }
     }
 
-    /// State transition - memory write.
+    /// Hardware state transition - fetch of a `size`-byte instruction at `privilege`.
+    ///
+    /// `size` is the instruction size of the architecture, e.g. 4 bytes on AArch64 and 2 bytes
+    /// for RISC-V compressed instructions. Mirrors `read`, but the mapping must be executable.
+    /// The MMU may set the Access flag of the descriptor that translates `vaddr` first.
+    pub open spec fn fetch(
+        s1: Self,
+        s2: Self,
+        vaddr: VAddr,
+        size: AccessSize,
+        privilege: Privilege,
+        res: MemoryResult<u64>,
+    ) -> bool {
+        ||| Self::fetch_access(s1, s2, vaddr, size, privilege, res)
+        ||| s1.pt_has_mapping_for(vaddr) && Self::fetch_access(
+            s1.set_flags(vaddr, false),
+            s2,
+            vaddr,
+            size,
+            privilege,
+            res,
+        )
+    }
+
+    /// Instruction fetch, once the MMU has updated the descriptor flags.
+    pub open spec fn fetch_access(
+        s1: Self,
+        s2: Self,
+        vaddr: VAddr,
+        size: AccessSize,
+        privilege: Privilege,
+        res: MemoryResult<u64>,
+    ) -> bool {
+        &&& s1.tag == s2.tag
+        // Memory and page table should not be updated
+        &&& s1.mem === s2.mem
+        &&& s1.pt === s2.pt
+        // Check alignment, then mapping
+        &&& if !vaddr.aligned(size.as_nat()) {
+            // 0. Misaligned. Nothing is translated.
+            &&& res is AlignmentFault
+            &&& s2.tlb === s1.tlb
+        } else if s1.tlb_has_mapping_for(vaddr) {
+            // 1. TLB hit
+            let (base, frame) = s1.tlb_mapping_for(vaddr);
+            &&& s1.fetch_through(vaddr, size, privilege, base, frame, res)
+            &&& s1.tlb === s2.tlb
+        } else if s1.pt_has_mapping_for(vaddr) {
+            // 2. TLB miss, page table hit
+            let (base, frame) = s1.pt_mapping_for(vaddr);
+            &&& s1.fetch_through(vaddr, size, privilege, base, frame, res)
+            // TLB should be updated
+            &&& s2.tlb === s1.tlb.update(s1.tag, base, frame)
+        } else {
+            // 3. TLB miss, page table miss
+            &&& res.is_fault(FaultKind::Translation, s1.walk_level(vaddr))
+            &&& s2.tlb === s1.tlb
+        }
+    }
+
+    /// State transition - memory write of the low `size` bytes of `value` at `privilege`.
+    ///
+    /// The MMU may set the Access and Dirty flags of the descriptor that translates `vaddr`
+    /// first, and it must set them when a successful write goes through a mapping of the page
+    /// table. A write of device memory is forwarded to the device as `mmio`.
+    ///
+    /// The TLB does not cache the Dirty flag here. Hardware that does only sets the flag again
+    /// once the entry is invalidated, so software that clears it must invalidate the entry.
     pub open spec fn write(
         s1: Self,
         s2: Self,
         vaddr: VAddr,
+        size: AccessSize,
         value: u64,
+        privilege: Privilege,
         res: MemoryResult<()>,
+        mmio: Option<MmioAccess>,
     ) -> bool {
-        &&& vaddr.aligned(WORD_SIZE)
-        // Page table should not be updated
-        &&& s1.pt === s2.pt
-        // Check mapping
-        &&& if s1.tlb_has_mapping_for(vaddr) {
+        if res is Ok && s1.translates_through_pt(vaddr) {
+            Self::write_access(
+                s1.set_flags(vaddr, true),
+                s2,
+                vaddr,
+                size,
+                value,
+                privilege,
+                res,
+                mmio,
+            )
+        } else {
+            ||| Self::write_access(s1, s2, vaddr, size, value, privilege, res, mmio)
+            ||| s1.pt_has_mapping_for(vaddr) && Self::write_access(
+                s1.set_flags(vaddr, true),
+                s2,
+                vaddr,
+                size,
+                value,
+                privilege,
+                res,
+                mmio,
+            )
+        }
+    }
+
+    /// Memory write, once the MMU has updated the descriptor flags.
+    pub open spec fn write_access(
+        s1: Self,
+        s2: Self,
+        vaddr: VAddr,
+        size: AccessSize,
+        value: u64,
+        privilege: Privilege,
+        res: MemoryResult<()>,
+        mmio: Option<MmioAccess>,
+    ) -> bool {
+        &&& s1.tag == s2.tag
+        // Page table is stored in physical memory, so the walker observes the updated memory
+        &&& s2.pt === s1.pt.with_mem(s2.mem)
+        // Check alignment, then mapping
+        &&& if !vaddr.aligned(size.as_nat()) {
+            // 0. Misaligned, so the access may span two pages. No byte is written.
+            &&& res is AlignmentFault
+            &&& mmio is None
+            &&& s2.mem === s1.mem
+            &&& s2.tlb === s1.tlb
+        } else if s1.tlb_has_mapping_for(vaddr) {
             // 1. TLB hit
             let (base, frame) = s1.tlb_mapping_for(vaddr);
-            let pidx = vaddr.map(base, frame.base).idx();
-            // Check frame attributes
-            &&& if s1.mem.contains(pidx) && frame.attr.writable && frame.attr.user_accessible {
-                &&& res is Ok
-                &&& s2.mem === s1.mem.write(pidx, value)
-            } else {
-                &&& res is PageFault
-                &&& s2.mem === s1.mem
-            }
+            &&& s1.write_through(s2, vaddr, size, value, privilege, base, frame, res, mmio)
             &&& s1.tlb === s2.tlb
         } else if s1.pt_has_mapping_for(vaddr) {
             // 2. TLB miss, page table hit
             let (base, frame) = s1.pt_mapping_for(vaddr);
-            let pidx = vaddr.map(base, frame.base).idx();
-            // Check frame attributes
-            &&& if s1.mem.contains(pidx) && frame.attr.writable && frame.attr.user_accessible {
-                &&& res is Ok
-                &&& s2.mem === s1.mem.write(pidx, value)
-            } else {
-                &&& res is PageFault
-                &&& s2.mem === s1.mem
-            }
+            &&& s1.write_through(s2, vaddr, size, value, privilege, base, frame, res, mmio)
             // TLB should be updated
-            &&& s2.tlb === s1.tlb.update(base, frame)
+            &&& s2.tlb === s1.tlb.update(s1.tag, base, frame)
         } else {
             // 3. TLB miss, page table miss
-            &&& res is PageFault
+            &&& res.is_fault(FaultKind::Translation, s1.walk_level(vaddr))
+            &&& mmio is None
             &&& s2.mem === s1.mem
             &&& s2.tlb === s1.tlb
         }
@@ -134,57 +271,298 @@ impl HardwareState {
     /// State transition - Page table operation. This operation is performed when
     /// page table is accessed or modified by hypervisor.
     ///
-    /// - Memory should not be updated.
+    /// - Only memory that stores page tables may be updated.
     /// - New entries should not be added to TLB when operating the page table. They
     /// can only be added when TLB miss occurs during memory access.
+    ///
+    /// `pt_op` summarizes a whole operation. Its individual steps are `pt_write`, `pt_alloc`
+    /// and `pt_dealloc`, and the walker may run between any two of them (`pt_grow_steps`,
+    /// `pt_shrink_steps`).
     pub open spec fn pt_op(s1: Self, s2: Self) -> bool {
-        &&& s1.mem === s2.mem
-        &&& forall|vbase: VAddr, frame: Frame|
-            s2.tlb.contains_mapping(vbase, frame) ==> s1.tlb.contains_mapping(vbase, frame)
+        &&& s1.tag == s2.tag
+        &&& s2.pt.mem === s2.mem
+        &&& s1.mem.lb() == s2.mem.lb()
+        &&& s1.mem.ub() == s2.mem.ub()
+        &&& forall|pidx: PIdx|
+            s1.mem.contains(pidx) && !s1.pt.in_table(pidx) && !s2.pt.in_table(pidx)
+                ==> #[trigger] s2.mem.read(pidx) == s1.mem.read(pidx)
+        &&& forall|tag: TLBTag, vbase: VAddr, frame: Frame|
+            s2.tlb.contains_mapping(tag, vbase, frame) ==> s1.tlb.contains_mapping(
+                tag,
+                vbase,
+                frame,
+            )
     }
 
-    /// State transition - explicit TLB eviction.
-    pub open spec fn tlb_evict(s1: Self, s2: Self, vbase: VAddr) -> bool {
-        &&& s1.tlb.contains_base(vbase)
-        &&& s2.tlb === s1.tlb.evict(vbase)
+    /// State transition - Page table entry write performed by software.
+    ///
+    /// The walker observes the written entry from the next step on. The TLB is unchanged by a
+    /// break-before-make write, and by a write that only changes the attributes of a valid
+    /// descriptor other than its memory type and shareability, whose old translation stays
+    /// cached until it is invalidated. Any other write
+    /// may leave conflicting translations in the TLB, so the TLB is unconstrained after it.
+    pub open spec fn pt_write(s1: Self, s2: Self, base: PAddr, index: nat, entry: u64) -> bool {
+        &&& s1.tag == s2.tag
+        &&& s1.pt.accessible(base, index)
+        &&& s2.pt === s1.pt.write(base, index, entry)
+        &&& s2.mem === s2.pt.mem
+        &&& s1.write_is_bbm(base, index, entry) || s1.pt.write_changes_attr::<G>(
+            base,
+            index,
+            entry,
+        ) ==> s2.tlb === s1.tlb
+    }
+
+    /// If writing `entry` at the given index in the given table follows break-before-make.
+    ///
+    /// The page table side holds (`PageTableMem::write_is_bbm`), and the TLB caches no
+    /// translation that overlaps a mapping the write adds. After an entry is broken, the TLB
+    /// maintenance that invalidates its old translation must complete before it is made valid.
+    pub open spec fn write_is_bbm(self, base: PAddr, index: nat, entry: u64) -> bool {
+        &&& self.pt.write_is_bbm::<G>(base, index, entry)
+        &&& self.tlb.clear_for::<G>(self.tag, self.pt, self.pt.write(base, index, entry))
+    }
+
+    /// State transition - Page table allocation performed by software.
+    ///
+    /// The backend may allocate any `table` that `PageTableMem::allocates` admits.
+    pub open spec fn pt_alloc(s1: Self, s2: Self, level: nat, table: Table) -> bool {
+        &&& s1.tag == s2.tag
+        &&& level < s1.pt.arch.level_count()
+        &&& s1.pt.allocates(level, s2.pt, table)
+        &&& s2.mem === s2.pt.mem
+        &&& s2.tlb === s1.tlb
+    }
+
+    /// State transition - Page table deallocation performed by software.
+    pub open spec fn pt_dealloc(s1: Self, s2: Self, base: PAddr) -> bool {
+        &&& s1.tag == s2.tag
+        &&& s1.pt.contains_table(base)
+        &&& base != s1.pt.root()
+        &&& s2.pt === s1.pt.dealloc_table(base)
+        &&& s2.mem === s2.pt.mem
+        &&& s2.tlb === s1.tlb
+    }
+
+    /// State transition - Entry write performed by software that follows break-before-make.
+    pub open spec fn pt_bbm_write(s1: Self, s2: Self) -> bool {
+        exists|base: PAddr, index: nat, entry: u64|
+            {
+                &&& s1.write_is_bbm(base, index, entry)
+                &&& #[trigger] Self::pt_write(s1, s2, base, index, entry)
+            }
+    }
+
+    /// If `path` lists the states from `s1` to `s2`, each one step after the previous one.
+    ///
+    /// The walker may observe every state of `path`.
+    pub open spec fn pt_path(s1: Self, s2: Self, path: Seq<Self>) -> bool {
+        &&& path.len() > 0
+        &&& path[0] === s1
+        &&& path.last() === s2
+    }
+
+    /// If `path` takes `s1` to `s2` by break-before-make writes and table allocations only.
+    ///
+    /// These are the steps of a map operation.
+    pub open spec fn pt_grow_steps(s1: Self, s2: Self, path: Seq<Self>) -> bool {
+        &&& Self::pt_path(s1, s2, path)
+        &&& forall|i: int|
+            0 <= i < path.len() - 1 ==> {
+                ||| Self::pt_bbm_write(#[trigger] path[i], path[i + 1])
+                ||| exists|level: nat, table: Table|
+                    Self::pt_alloc(path[i], path[i + 1], level, table)
+            }
+    }
+
+    /// If `path` takes `s1` to `s2` by break-before-make writes and table deallocations only.
+    ///
+    /// These are the steps of an unmap operation.
+    pub open spec fn pt_shrink_steps(s1: Self, s2: Self, path: Seq<Self>) -> bool {
+        &&& Self::pt_path(s1, s2, path)
+        &&& forall|i: int|
+            0 <= i < path.len() - 1 ==> {
+                ||| Self::pt_bbm_write(#[trigger] path[i], path[i + 1])
+                ||| exists|base: PAddr| Self::pt_dealloc(path[i], path[i + 1], base)
+            }
+    }
+
+    /// State transition - TLB maintenance operation (TLBI) issued by software.
+    pub open spec fn tlb_invalidate(s1: Self, s2: Self, op: TLBOp) -> bool {
+        &&& s2.tlb === s1.tlb.invalidate(op)
+        &&& s1.tag == s2.tag
         &&& s1.mem === s2.mem
         &&& s1.pt === s2.pt
     }
 }
 
 /// Helper functions.
-impl HardwareState {
-    /// If TLB has a mapping for `vaddr`.
+impl<G> HardwareState<G> where G: GhostPTE {
+    /// State after the MMU sets the Access flag, and the Dirty flag if `dirty`, of the
+    /// descriptor that translates `vaddr`.
+    ///
+    /// The flags are stored in page table memory, and never change the translation
+    /// (`PageTableMem::lemma_set_flags_keeps_interpret`).
+    pub open spec fn set_flags(self, vaddr: VAddr, dirty: bool) -> Self {
+        let pt = self.pt.set_flags::<G>(vaddr, dirty);
+        HardwareState { mem: pt.mem, pt, tlb: self.tlb, tag: self.tag, _phantom: PhantomData }
+    }
+
+    /// Read `size` bytes at `vaddr` at `privilege` through mapping `(base, frame)`.
+    ///
+    /// An output address outside physical memory is checked before the permissions. Faults are
+    /// raised on the level of the mapping. Device memory answers with a value of its choice,
+    /// normal memory with the bytes in their lanes of the word.
+    pub open spec fn read_through(
+        self,
+        vaddr: VAddr,
+        size: AccessSize,
+        privilege: Privilege,
+        base: VAddr,
+        frame: Frame,
+        res: MemoryResult<u64>,
+        mmio: Option<MmioAccess>,
+    ) -> bool {
+        let paddr = vaddr.map(base, frame.base);
+        let level = self.pt.arch.level_of_frame_size(frame.size);
+        if !self.mem.contains(paddr.idx()) {
+            &&& res.is_fault(FaultKind::AddressSize, level)
+            &&& mmio is None
+        } else if !frame.attr.permits_read(privilege) {
+            &&& res.is_fault(FaultKind::PermissionRead, level)
+            &&& mmio is None
+        } else if frame.attr.device() {
+            &&& res is Ok
+            &&& mmio == Some(MmioAccess::Read { paddr, size, value: res->Ok_0 })
+        } else {
+            &&& res is Ok
+            &&& res->Ok_0 === size.extract(self.mem.read(paddr.idx()), paddr.word_offset())
+            &&& mmio is None
+        }
+    }
+
+    /// Fetch a `size`-byte instruction at `vaddr` at `privilege` through mapping
+    /// `(base, frame)`.
+    ///
+    /// Checked in the same order as `read_through`, with the execute permission in place of
+    /// the read permission. The instruction is taken from its byte lanes of the word.
+    pub open spec fn fetch_through(
+        self,
+        vaddr: VAddr,
+        size: AccessSize,
+        privilege: Privilege,
+        base: VAddr,
+        frame: Frame,
+        res: MemoryResult<u64>,
+    ) -> bool {
+        let paddr = vaddr.map(base, frame.base);
+        let pidx = paddr.idx();
+        let level = self.pt.arch.level_of_frame_size(frame.size);
+        if !self.mem.contains(pidx) {
+            res.is_fault(FaultKind::AddressSize, level)
+        } else if !frame.attr.permits_exec(privilege) {
+            res.is_fault(FaultKind::PermissionExec, level)
+        } else {
+            &&& res is Ok
+            &&& res->Ok_0 === size.extract(self.mem.read(pidx), paddr.word_offset())
+        }
+    }
+
+    /// Write the low `size` bytes of `value` to `vaddr` at `privilege` through mapping
+    /// `(base, frame)`, resulting in memory `s2.mem`.
+    ///
+    /// Checked in the same order as `read_through`. Memory is unchanged on a fault and on a
+    /// device write. Otherwise the other byte lanes of the word are kept.
+    pub open spec fn write_through(
+        self,
+        s2: Self,
+        vaddr: VAddr,
+        size: AccessSize,
+        value: u64,
+        privilege: Privilege,
+        base: VAddr,
+        frame: Frame,
+        res: MemoryResult<()>,
+        mmio: Option<MmioAccess>,
+    ) -> bool {
+        let paddr = vaddr.map(base, frame.base);
+        let pidx = paddr.idx();
+        let level = self.pt.arch.level_of_frame_size(frame.size);
+        if !self.mem.contains(pidx) {
+            &&& res.is_fault(FaultKind::AddressSize, level)
+            &&& mmio is None
+            &&& s2.mem === self.mem
+        } else if !frame.attr.permits_write(privilege) {
+            &&& res.is_fault(FaultKind::PermissionWrite, level)
+            &&& mmio is None
+            &&& s2.mem === self.mem
+        } else if frame.attr.device() {
+            &&& res is Ok
+            &&& mmio == Some(MmioAccess::Write { paddr, size, value })
+            &&& s2.mem === self.mem
+        } else {
+            &&& res is Ok
+            &&& mmio is None
+            &&& s2.mem === self.mem.write(
+                pidx,
+                size.insert(self.mem.read(pidx), paddr.word_offset(), value),
+            )
+        }
+    }
+
+    /// Level of the descriptor that terminates the page table walk for `vaddr`.
+    ///
+    /// Reported by translation faults.
+    pub open spec fn walk_level(self, vaddr: VAddr) -> nat {
+        self.pt.walk_from::<G>(vaddr, self.pt.root(), 0).1
+    }
+
+    /// If TLB has a mapping for `vaddr` in the running address space.
     pub open spec fn tlb_has_mapping_for(self, vaddr: VAddr) -> bool {
         exists|vbase: VAddr, frame: Frame| #[trigger]
-            self.tlb.contains_mapping(vbase, frame) && vaddr.within(vbase, frame.size.as_nat())
+            self.tlb.contains_mapping(self.tag, vbase, frame) && vaddr.within(
+                vbase,
+                frame.size.as_nat(),
+            )
     }
 
-    /// Get the mapping for `vaddr` in TLB.
+    /// Get the mapping for `vaddr` in TLB in the running address space.
     pub open spec fn tlb_mapping_for(self, vaddr: VAddr) -> (VAddr, Frame)
         recommends
             self.tlb_has_mapping_for(vaddr),
     {
         choose|vbase: VAddr, frame: Frame| #[trigger]
-            self.tlb.contains_mapping(vbase, frame) && vaddr.within(vbase, frame.size.as_nat())
+            self.tlb.contains_mapping(self.tag, vbase, frame) && vaddr.within(
+                vbase,
+                frame.size.as_nat(),
+            )
     }
 
     /// If page table has a mapping for `vaddr`.
     pub open spec fn pt_has_mapping_for(self, vaddr: VAddr) -> bool {
         exists|vbase: VAddr, frame: Frame| #[trigger]
-            self.pt.interpret().contains_pair(vbase, frame) && vaddr.within(
+            self.pt.interpret::<G>().contains_pair(vbase, frame) && vaddr.within(
                 vbase,
                 frame.size.as_nat(),
             )
     }
 
+    /// If an access to `vaddr` is translated by the mapping of the page table, either on a TLB
+    /// miss or through a TLB entry that caches that mapping.
+    pub open spec fn translates_through_pt(self, vaddr: VAddr) -> bool {
+        &&& self.pt_has_mapping_for(vaddr)
+        &&& self.tlb_has_mapping_for(vaddr) ==> self.tlb_mapping_for(vaddr) == self.pt_mapping_for(
+            vaddr,
+        )
+    }
+
     /// Get the mapping for `vaddr` in page table.
     pub open spec fn pt_mapping_for(self, vaddr: VAddr) -> (VAddr, Frame)
         recommends
             self.pt_has_mapping_for(vaddr),
     {
         choose|vbase: VAddr, frame: Frame| #[trigger]
-            self.pt.interpret().contains_pair(vbase, frame) && vaddr.within(
+            self.pt.interpret::<G>().contains_pair(vbase, frame) && vaddr.within(
                 vbase,
                 frame.size.as_nat(),
             )
@@ -13,10 +13,10 @@
 use vstd::prelude::*;
 
 use crate::common::{
-    addr::{PAddr, PIdx, VAddr, VIdx, WORD_SIZE},
+    addr::{AccessSize, PAddr, PIdx, VAddr, VIdx},
     arch::PTArch,
     frame::Frame,
-    MemoryResult, PagingResult,
+    FaultKind, MemoryResult, MmioAccess, PagingResult, Privilege,
 };
 
 verus! {
@@ -27,6 +27,8 @@ pub struct HighLevelState {
     ///
     /// Use index rather than address. Addresses that are not aligned to 8-byte boundaries
     /// should not be used to access a value, while indexes don't face this issue.
+    /// Accesses smaller than a word touch byte lanes of the word containing them. Device pages
+    /// are not part of memory.
     pub mem: Map<VIdx, u64>,
     /// Mappings from virtual address to physical frames.
     ///
@@ -45,6 +47,8 @@ pub struct HighLevelConstants {
     pub pmem_lb: PIdx,
     /// Physical memory upper bound.
     pub pmem_ub: PIdx,
+    /// Physical regions reserved by the hypervisor, as `(base, size)` pairs.
+    pub reserved: Seq<(PAddr, nat)>,
 }
 
 /// State transition specifications.
@@ -56,62 +60,155 @@ impl HighLevelState {
         &&& self.constants.arch.valid()
     }
 
-    /// State transition - Read.
-    pub open spec fn read(s1: Self, s2: Self, vaddr: VAddr, res: MemoryResult<u64>) -> bool {
+    /// State transition - Read of `size` bytes at `privilege`.
+    ///
+    /// A read of device memory is forwarded to the device as `mmio`.
+    pub open spec fn read(
+        s1: Self,
+        s2: Self,
+        vaddr: VAddr,
+        size: AccessSize,
+        privilege: Privilege,
+        res: MemoryResult<u64>,
+        mmio: Option<MmioAccess>,
+    ) -> bool {
         &&& s1.constants === s2.constants
-        // vaddr should align to 8 bytes
-        &&& vaddr.aligned(
-            WORD_SIZE,
-        )
         // Memory and mappings should not be updated
         &&& s1.mappings === s2.mappings
         &&& s1.mem === s2.mem
-        // Check mapping
-        &&& if s1.has_mapping_for(vaddr) {
+        // Check alignment, then mapping
+        &&& if !vaddr.aligned(size.as_nat()) {
+            // A misaligned access faults as a whole, even if it spans two pages
+            &&& res is AlignmentFault
+            &&& mmio is None
+        } else if s1.has_mapping_for(vaddr) {
             let (base, frame) = s1.mapping_for(vaddr);
-            // Check frame attributes
-            if s1.within_pmem(vaddr.map(base, frame.base).idx()) && frame.attr.readable
-                && frame.attr.user_accessible {
+            let paddr = vaddr.map(base, frame.base);
+            let level = s1.constants.arch.level_of_frame_size(frame.size);
+            // Check physical address, then frame attributes
+            if !s1.within_pmem(paddr.idx()) {
+                &&& res.is_fault(FaultKind::AddressSize, level)
+                &&& mmio is None
+            } else if !frame.attr.permits_read(privilege) {
+                &&& res.is_fault(FaultKind::PermissionRead, level)
+                &&& mmio is None
+            } else if frame.attr.device() {
+                // The device answers with a value of its choice
+                &&& res is Ok
+                &&& mmio == Some(MmioAccess::Read { paddr, size, value: res->Ok_0 })
+            } else {
                 &&& res is Ok
-                // The value should be the value in the memory at `vidx`
-                &&& res->Ok_0 === s1.mem[vaddr.idx()]
+                // The value should be taken from the byte lanes of the word at `vidx`
+                &&& res->Ok_0 === size.extract(s1.mem[vaddr.idx()], vaddr.word_offset())
+                &&& mmio is None
+            }
+        } else {
+            // The level depends on the page table layout
+            &&& res.is_fault_kind(FaultKind::Translation)
+            &&& res.fault_level() < s1.constants.arch.level_count()
+            &&& mmio is None
+        }
+    }
+
+    /// State transition - Fetch of a `size`-byte instruction at `privilege`.
+    ///
+    /// `size` is the instruction size of the architecture, e.g. 4 bytes on AArch64 and 2 bytes
+    /// for RISC-V compressed instructions.
+    pub open spec fn fetch(
+        s1: Self,
+        s2: Self,
+        vaddr: VAddr,
+        size: AccessSize,
+        privilege: Privilege,
+        res: MemoryResult<u64>,
+    ) -> bool {
+        &&& s1.constants === s2.constants
+        // Memory and mappings should not be updated
+        &&& s1.mappings === s2.mappings
+        &&& s1.mem === s2.mem
+        // Check alignment, then mapping
+        &&& if !vaddr.aligned(size.as_nat()) {
+            // A misaligned fetch faults as a whole
+            res is AlignmentFault
+        } else if s1.has_mapping_for(vaddr) {
+            let (base, frame) = s1.mapping_for(vaddr);
+            let level = s1.constants.arch.level_of_frame_size(frame.size);
+            // Check physical address, then frame attributes
+            if !s1.within_pmem(vaddr.map(base, frame.base).idx()) {
+                res.is_fault(FaultKind::AddressSize, level)
+            } else if !frame.attr.permits_exec(privilege) {
+                res.is_fault(FaultKind::PermissionExec, level)
             } else {
-                res is PageFault
+                &&& res is Ok
+                // The instruction is taken from the byte lanes of the word at `vidx`
+                &&& res->Ok_0 === size.extract(s1.mem[vaddr.idx()], vaddr.word_offset())
             }
         } else {
-            res is PageFault
+            // The level depends on the page table layout
+            &&& res.is_fault_kind(FaultKind::Translation)
+            &&& res.fault_level() < s1.constants.arch.level_count()
         }
     }
 
-    /// State transition - write.
+    /// State transition - write of the low `size` bytes of `value` at `privilege`.
+    ///
+    /// A write of device memory is forwarded to the device as `mmio`.
     pub open spec fn write(
         s1: Self,
         s2: Self,
         vaddr: VAddr,
+        size: AccessSize,
         value: u64,
+        privilege: Privilege,
         res: MemoryResult<()>,
+        mmio: Option<MmioAccess>,
     ) -> bool {
         &&& s1.constants === s2.constants
-        // vaddr should align to 8 bytes
-        &&& vaddr.aligned(WORD_SIZE)
         // Mappings should not be updated
         &&& s1.mappings === s2.mappings
-        // Check mapping
-        &&& if s1.has_mapping_for(vaddr) {
+        // Check alignment, then mapping
+        &&& if !vaddr.aligned(size.as_nat()) {
+            // A misaligned access faults as a whole, even if it spans two pages
+            &&& res is AlignmentFault
+            &&& mmio is None
+            // Memory should not be updated
+            &&& s1.mem === s2.mem
+        } else if s1.has_mapping_for(vaddr) {
             let (base, frame) = s1.mapping_for(vaddr);
-            // Check frame attributes
-            if s1.within_pmem(vaddr.map(base, frame.base).idx()) && frame.attr.writable
-                && frame.attr.user_accessible {
-                &&& res is Ok
-                // Memory should be updated at `vidx` with `value`
-                &&& s2.mem === s1.mem.insert(vaddr.idx(), value)
-            } else {
-                &&& res is PageFault
+            let paddr = vaddr.map(base, frame.base);
+            let level = s1.constants.arch.level_of_frame_size(frame.size);
+            // Check physical address, then frame attributes
+            if !s1.within_pmem(paddr.idx()) {
+                &&& res.is_fault(FaultKind::AddressSize, level)
+                &&& mmio is None
+                // Memory should not be updated
+                &&& s1.mem === s2.mem
+            } else if !frame.attr.permits_write(privilege) {
+                &&& res.is_fault(FaultKind::PermissionWrite, level)
+                &&& mmio is None
                 // Memory should not be updated
                 &&& s1.mem === s2.mem
+            } else if frame.attr.device() {
+                // The value is not guaranteed to read back
+                &&& res is Ok
+                &&& mmio == Some(MmioAccess::Write { paddr, size, value })
+                // Device pages are not part of memory
+                &&& s1.mem === s2.mem
+            } else {
+                let vidx = vaddr.idx();
+                &&& res is Ok
+                &&& mmio is None
+                // Memory should be updated at the byte lanes of the word at `vidx`
+                &&& s2.mem === s1.mem.insert(
+                    vidx,
+                    size.insert(s1.mem[vidx], vaddr.word_offset(), value),
+                )
             }
         } else {
-            &&& res is PageFault
+            // The level depends on the page table layout
+            &&& res.is_fault_kind(FaultKind::Translation)
+            &&& res.fault_level() < s1.constants.arch.level_count()
+            &&& mmio is None
             // Memory should not be updated
             &&& s1.mem === s2.mem
         }
@@ -145,19 +242,24 @@ impl HighLevelState {
             <= s1.constants.pmem_ub.0
         // Frame should not overlap with existing pmem
         &&& !s1.overlaps_pmem(frame)
+        // Frame should not overlap with reserved memory
+        &&& !PAddr::overlap_any(frame.base, frame.size.as_nat(), s1.constants.reserved)
         // Check vmem overlapping
         &&& if s1.overlaps_vmem(vbase, frame) {
             &&& res is Err
             // Memory and mappings should not be updated
             &&& s1.mem === s2.mem
             &&& s1.mappings === s2.mappings
-        } else {
-            &&& res is Ok
+        } else if res is Ok {
             // Update mappings
             &&& s1.mappings.insert(vbase, frame)
                 === s2.mappings
             // Memory domain should be updated
             &&& s2.mem.dom() === s2.mem_domain_covered_by_mappings()
+        } else {
+            // Out of page table memory, memory and mappings should not be updated
+            &&& s1.mem === s2.mem
+            &&& s1.mappings === s2.mappings
         }
     }
 
@@ -216,12 +318,15 @@ impl HighLevelState {
 /// Helper functions.
 impl HighLevelState {
     /// Virtual memory domain covered by `self.mappings`.
+    ///
+    /// Device pages are excluded, since their contents belong to the device.
     pub open spec fn mem_domain_covered_by_mappings(self) -> Set<VIdx> {
         Set::new(
             |vidx: VIdx|
                 exists|vbase: VAddr, frame: Frame|
                     {
                         &&& #[trigger] self.mappings.contains_pair(vbase, frame)
+                        &&& !frame.attr.device()
                         &&& vidx.addr().within(vbase, frame.size.as_nat())
                     },
         )
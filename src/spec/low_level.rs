@@ -7,19 +7,21 @@
 //！the page table implementation refines the page table specification, we can conclude that the
 //！combined system (hardware + hypervisor) refines the low-level specification and, in turn, the
 //！high-level specification.
+use core::marker::PhantomData;
 use vstd::prelude::*;
 
 use super::{
     hardware::HardwareState,
     high_level::{HighLevelConstants, HighLevelState},
-    memory::{PageTableMem, PhysMem, TLB},
+    memory::{PageTableMem, PhysMem, TLBOp, TLBTag, TLB},
     page_table::{PTConstants, PageTableState},
 };
 use crate::common::{
-    addr::{PAddr, VAddr, VIdx},
+    addr::{AccessSize, PAddr, VAddr, VIdx},
     arch::PTArch,
     frame::Frame,
-    MemoryResult, PagingResult,
+    pte::GhostPTE,
+    MemoryResult, MmioAccess, PagingResult, Privilege,
 };
 
 verus! {
@@ -27,9 +29,11 @@ verus! {
 /// Low-level Memory State, which includes
 ///
 /// - Common memory: memory used by the OS and applications.
-/// - Page table memory: memory region that stores page tables.
-/// - TLB: translation Lookaside Buffer.
-pub struct LowLevelState {
+/// - Page table memory: tables stored in physical memory, never mapped to guests.
+/// - TLB: translation Lookaside Buffer, shared with other address spaces by tagging.
+///
+/// `G` is the page table descriptor format decoded by the hardware.
+pub struct LowLevelState<G: GhostPTE> {
     /// Physical memory.
     pub mem: PhysMem,
     /// Page table memory.
@@ -38,51 +42,98 @@ pub struct LowLevelState {
     pub tlb: TLB,
     /// Constants.
     pub constants: LowLevelConstants,
+    /// Phantom data.
+    pub _phantom: PhantomData<G>,
 }
 
 /// Constants for the low-level state.
 pub struct LowLevelConstants {
     /// Page table architecture
     pub arch: PTArch,
+    /// Physical regions reserved by the hypervisor, as `(base, size)` pairs.
+    pub reserved: Seq<(PAddr, nat)>,
+    /// TLB tag of this address space (ASID for stage-1, VMID for stage-2).
+    pub tag: TLBTag,
 }
 
 /// State transition specification.
-impl LowLevelState {
+impl<G> LowLevelState<G> where G: GhostPTE {
     /// Initial memory state.
     ///
     /// The initial state must satisfy the specification.
     pub open spec fn init(self) -> bool {
         &&& self.constants.arch.valid()
         &&& HardwareState::init(self.hw_state())
+        &&& self.pt_in_mem()
+        &&& self.pool_reserved()
+        &&& self.tables_reserved()
     }
 
-    /// State transition - Memory read.
-    pub open spec fn read(s1: Self, s2: Self, vaddr: VAddr, res: MemoryResult<u64>) -> bool {
+    /// State transition - Memory read of `size` bytes at `privilege`, forwarded to a device as
+    /// `mmio` on device memory.
+    pub open spec fn read(
+        s1: Self,
+        s2: Self,
+        vaddr: VAddr,
+        size: AccessSize,
+        privilege: Privilege,
+        res: MemoryResult<u64>,
+        mmio: Option<MmioAccess>,
+    ) -> bool {
         &&& s1.constants === s2.constants
-        &&& HardwareState::read(s1.hw_state(), s2.hw_state(), vaddr, res)
+        &&& HardwareState::read(s1.hw_state(), s2.hw_state(), vaddr, size, privilege, res, mmio)
     }
 
-    /// State transition - Memory write.
+    /// State transition - Fetch of a `size`-byte instruction at `privilege`.
+    pub open spec fn fetch(
+        s1: Self,
+        s2: Self,
+        vaddr: VAddr,
+        size: AccessSize,
+        privilege: Privilege,
+        res: MemoryResult<u64>,
+    ) -> bool {
+        &&& s1.constants === s2.constants
+        &&& HardwareState::fetch(s1.hw_state(), s2.hw_state(), vaddr, size, privilege, res)
+    }
+
+    /// State transition - Memory write of the low `size` bytes of `value` at `privilege`,
+    /// forwarded to a device as `mmio` on device memory.
     pub open spec fn write(
         s1: Self,
         s2: Self,
         vaddr: VAddr,
+        size: AccessSize,
         value: u64,
+        privilege: Privilege,
         res: MemoryResult<()>,
+        mmio: Option<MmioAccess>,
     ) -> bool {
         &&& s1.constants === s2.constants
-        &&& HardwareState::write(s1.hw_state(), s2.hw_state(), vaddr, value, res)
+        &&& HardwareState::write(
+            s1.hw_state(),
+            s2.hw_state(),
+            vaddr,
+            size,
+            value,
+            privilege,
+            res,
+            mmio,
+        )
     }
 
-    /// State transition - Explicit TLB eviction.
+    /// State transition - TLB maintenance.
     ///
-    /// Hypervisor uses specific instructions to evict TLB entries explicitly.
-    pub open spec fn tlb_evict(s1: Self, s2: Self, vbase: VAddr) -> bool {
+    /// Hypervisor uses TLB maintenance instructions to invalidate TLB entries explicitly. The
+    /// operation may target this address space or any other one.
+    pub open spec fn tlb_invalidate(s1: Self, s2: Self, op: TLBOp) -> bool {
         &&& s1.constants === s2.constants
-        &&& HardwareState::tlb_evict(s1.hw_state(), s2.hw_state(), vbase)
+        &&& HardwareState::tlb_invalidate(s1.hw_state(), s2.hw_state(), op)
     }
 
     /// State transition - Map a frame.
+    ///
+    /// The page table is updated one step at a time, and the walker may observe every step.
     pub open spec fn map(
         s1: Self,
         s2: Self,
@@ -100,10 +151,14 @@ impl LowLevelState {
             res,
         )
         // Hardware behaves as spec
-        &&& HardwareState::pt_op(s1.hw_state(), s2.hw_state())
+        &&& LowLevelState::map_steps(s1.hw_state(), s2.hw_state())
+        // Page table memory stays well-formed
+        &&& LowLevelState::pt_mem_op(s1, s2)
     }
 
     /// State transition - Unmap a frame.
+    ///
+    /// The page table is updated one step at a time, and the walker may observe every step.
     pub open spec fn unmap(s1: Self, s2: Self, vbase: VAddr, res: PagingResult) -> bool {
         &&& s1.constants === s2.constants
         // Page table spec satisfied
@@ -114,13 +169,22 @@ impl LowLevelState {
             res,
         )
         // Hardware behaves as spec
-        &&& HardwareState::pt_op(
-            s1.hw_state(),
-            s2.hw_state(),
-        )
-        // TLB doesn't contain the unmapped frame
-        // Normally, hypervisor ensures this using specific instructions.
-        &&& !s2.tlb.contains_base(vbase)
+        &&& if res is Ok {
+            // The page table is updated, then hypervisor invalidates the unmapped page
+            exists|mid: HardwareState<G>|
+                {
+                    &&& LowLevelState::unmap_steps(s1.hw_state(), mid)
+                    &&& HardwareState::tlb_invalidate(
+                        mid,
+                        s2.hw_state(),
+                        TLBOp::VA(s1.constants.tag, vbase),
+                    )
+                }
+        } else {
+            LowLevelState::unmap_steps(s1.hw_state(), s2.hw_state())
+        }
+        // Page table memory stays well-formed
+        &&& LowLevelState::pt_mem_op(s1, s2)
     }
 
     /// State transition - Query a vaddr.
@@ -140,15 +204,76 @@ impl LowLevelState {
         )
         // Hardware behaves as spec
         &&& HardwareState::pt_op(s1.hw_state(), s2.hw_state())
+        // Page table memory stays well-formed
+        &&& LowLevelState::pt_mem_op(s1, s2)
+    }
+
+    /// Requirements on page table memory after a page table operation.
+    ///
+    /// - Page table memory keeps its invariants and architecture.
+    /// - Table descriptors point to tables on the next level.
+    /// - The table pool is unchanged, so tables stay in reserved memory.
+    pub open spec fn pt_mem_op(s1: Self, s2: Self) -> bool {
+        &&& s2.pt.invariants()
+        &&& s2.pt.walkable::<G>()
+        &&& s2.pt.arch == s1.pt.arch
+        &&& s2.pt.pool == s1.pt.pool
+    }
+
+    /// If `s1` reaches `s2` by the steps of a map operation, and every state the walker may
+    /// observe on the way has the mappings of `s1`, and no mapping that `s2` has not.
+    ///
+    /// This holds of the states `insert` passes through, which only adds mappings
+    /// (`PageTable::theorem_insert_observable_states`) and only makes entries valid where the
+    /// TLB is clear (`PageTable::theorem_insert_tlb_clear`).
+    pub open spec fn map_steps(s1: HardwareState<G>, s2: HardwareState<G>) -> bool {
+        exists|path: Seq<HardwareState<G>>|
+            {
+                &&& HardwareState::pt_grow_steps(s1, s2, path)
+                &&& forall|i: int|
+                    0 <= i < path.len() ==> Self::mappings_between(
+                        s1.pt,
+                        #[trigger] path[i].pt,
+                        s2.pt,
+                    )
+            }
+    }
+
+    /// If `s1` reaches `s2` by the steps of an unmap operation, and every state the walker
+    /// may observe on the way has the mappings of `s2`, and no mapping that `s1` has not.
+    ///
+    /// This holds of the states unmapping passes through, which only removes mappings
+    /// (`PageTable::theorem_unmap_observable_states`).
+    pub open spec fn unmap_steps(s1: HardwareState<G>, s2: HardwareState<G>) -> bool {
+        exists|path: Seq<HardwareState<G>>|
+            {
+                &&& HardwareState::pt_shrink_steps(s1, s2, path)
+                &&& forall|i: int|
+                    0 <= i < path.len() ==> Self::mappings_between(
+                        s2.pt,
+                        #[trigger] path[i].pt,
+                        s1.pt,
+                    )
+            }
+    }
+
+    /// If `pt` has all mappings of `lo`, and no mapping that `hi` has not.
+    pub open spec fn mappings_between(
+        lo: PageTableMem,
+        pt: PageTableMem,
+        hi: PageTableMem,
+    ) -> bool {
+        &&& lo.interpret::<G>().submap_of(pt.interpret::<G>())
+        &&& pt.interpret::<G>().submap_of(hi.interpret::<G>())
     }
 }
 
 /// State Invariants.
-impl LowLevelState {
+impl<G> LowLevelState<G> where G: GhostPTE {
     /// All frames are within the physical memory bounds.
     pub open spec fn frames_within_pmem(self) -> bool {
         forall|vbase: VAddr, frame: Frame| #[trigger]
-            self.pt.interpret().contains_pair(vbase, frame) ==> self.mem.lb().0
+            self.pt.interpret::<G>().contains_pair(vbase, frame) ==> self.mem.lb().0
                 <= frame.base.idx().0 && frame.base.offset(frame.size.as_nat()).idx().0
                 <= self.mem.ub().0
     }
@@ -156,31 +281,25 @@ impl LowLevelState {
     /// All mappings (vbase, pbase) are 8-byte aligned.
     pub open spec fn mappings_aligned(self) -> bool {
         forall|vbase: VAddr, frame: Frame| #[trigger]
-            self.pt.interpret().contains_pair(vbase, frame) ==> vbase.aligned(frame.size.as_nat())
-                && frame.base.aligned(frame.size.as_nat())
+            self.pt.interpret::<G>().contains_pair(vbase, frame) ==> vbase.aligned(
+                frame.size.as_nat(),
+            ) && frame.base.aligned(frame.size.as_nat())
     }
 
     /// Page table mappings do not overlap in virtual memory.
     pub open spec fn mappings_nonoverlap_in_vmem(self) -> bool {
         forall|vbase1: VAddr, frame1: Frame, vbase2: VAddr, frame2: Frame|
-            self.pt.interpret().contains_pair(vbase1, frame1) && self.pt.interpret().contains_pair(
-                vbase2,
-                frame2,
-            ) ==> vbase1 == vbase2 || !VAddr::overlap(
-                vbase1,
-                frame1.size.as_nat(),
-                vbase2,
-                frame2.size.as_nat(),
-            )
+            self.pt.interpret::<G>().contains_pair(vbase1, frame1)
+                && self.pt.interpret::<G>().contains_pair(vbase2, frame2) ==> vbase1 == vbase2
+                || !VAddr::overlap(vbase1, frame1.size.as_nat(), vbase2, frame2.size.as_nat())
     }
 
     /// Page table mappings do not overlap in physical memory.
     pub open spec fn mappings_nonoverlap_in_pmem(self) -> bool {
         forall|vbase1: VAddr, frame1: Frame, vbase2: VAddr, frame2: Frame|
-            self.pt.interpret().contains_pair(vbase1, frame1) && self.pt.interpret().contains_pair(
-                vbase2,
-                frame2,
-            ) ==> vbase1 == vbase2 || !PAddr::overlap(
+            self.pt.interpret::<G>().contains_pair(vbase1, frame1)
+                && self.pt.interpret::<G>().contains_pair(vbase2, frame2) ==> vbase1 == vbase2
+                || !PAddr::overlap(
                 frame1.base,
                 frame1.size.as_nat(),
                 frame2.base,
@@ -188,48 +307,127 @@ impl LowLevelState {
             )
     }
 
-    /// TLB must be a submap of the page table.
+    /// TLB entries of this address space must be a submap of the page table.
+    ///
+    /// Entries with other tags are unconstrained, they belong to other address spaces.
     pub open spec fn tlb_is_submap_of_pt(self) -> bool {
         forall|vbase, frame|
-            self.tlb.contains_mapping(vbase, frame)
-                ==> #[trigger] self.pt.interpret().contains_pair(vbase, frame)
+            self.tlb.contains_mapping(self.constants.tag, vbase, frame)
+                ==> #[trigger] self.pt.interpret::<G>().contains_pair(vbase, frame)
     }
 
-    /// OS state invariants.
-    pub open spec fn invariants(self) -> bool {
+    /// TLB entries of this address space are aligned and never cover reserved memory.
+    ///
+    /// Follows from `tlb_is_submap_of_pt`, but stale entries must satisfy it as well.
+    pub open spec fn tlb_unreserved(self) -> bool {
+        forall|vbase: VAddr, frame: Frame| #[trigger]
+            self.tlb.contains_mapping(self.constants.tag, vbase, frame) ==> frame.base.aligned(
+                frame.size.as_nat(),
+            ) && !PAddr::overlap_any(
+                frame.base,
+                frame.size.as_nat(),
+                self.constants.reserved,
+            )
+    }
+
+    /// Page tables are stored in physical memory.
+    pub open spec fn pt_in_mem(self) -> bool {
+        &&& self.pt.mem === self.mem
+        &&& self.pt.arch == self.constants.arch
+        &&& self.pt.invariants()
+        &&& self.pt.walkable::<G>()
+    }
+
+    /// No mapping overlaps memory reserved by the hypervisor.
+    pub open spec fn mappings_unreserved(self) -> bool {
+        forall|vbase: VAddr, frame: Frame| #[trigger]
+            self.pt.interpret::<G>().contains_pair(vbase, frame) ==> !PAddr::overlap_any(
+                frame.base,
+                frame.size.as_nat(),
+                self.constants.reserved,
+            )
+    }
+
+    /// The table pool lies in memory reserved by the hypervisor.
+    pub open spec fn pool_reserved(self) -> bool {
+        forall|i|
+            0 <= i < self.pt.pool.len() ==> PAddr::inside_any(
+                #[trigger] self.pt.pool[i].0,
+                self.pt.pool[i].1,
+                self.constants.reserved,
+            )
+    }
+
+    /// All page tables lie in memory reserved by the hypervisor.
+    ///
+    /// Follows from `pool_reserved`, since tables are allocated from the pool.
+    pub open spec fn tables_reserved(self) -> bool {
+        forall|i|
+            0 <= i < self.pt.tables.len() ==> PAddr::inside_any(
+                #[trigger] self.pt.tables[i].base,
+                self.pt.tables[i].size.as_nat(),
+                self.constants.reserved,
+            )
+    }
+
+    /// No mapping covers page table memory, so page tables are unreachable from guests.
+    pub open spec fn tables_unmapped(self) -> bool {
+        forall|vbase: VAddr, frame: Frame|
+            #[trigger] self.pt.interpret::<G>().contains_pair(vbase, frame)
+                ==> !self.overlaps_tables(frame)
+    }
+
+    /// Invariants of memory and the page table, independent of the TLB.
+    pub open spec fn pt_invariants(self) -> bool {
         &&& self.constants.arch.valid()
+        &&& self.pt_in_mem()
+        &&& self.mappings_unreserved()
+        &&& self.pool_reserved()
+        &&& self.tables_reserved()
+        &&& self.tables_unmapped()
         &&& self.frames_within_pmem()
         &&& self.mappings_aligned()
         &&& self.mappings_nonoverlap_in_vmem()
         &&& self.mappings_nonoverlap_in_pmem()
+    }
+
+    /// OS state invariants.
+    pub open spec fn invariants(self) -> bool {
+        &&& self.pt_invariants()
         &&& self.tlb_is_submap_of_pt()
     }
 }
 
 /// View(abstraction) functions. `LowLevelState` --Abstratcion--> `HighLevelState`
-impl LowLevelState {
-    /// Collect all page mappings managed by OS memory state (pt_mem and TLB).
+impl<G> LowLevelState<G> where G: GhostPTE {
+    /// Collect all page mappings managed by OS memory state (pt_mem and TLB entries of this
+    /// address space).
     pub open spec fn all_mappings(self) -> Map<VAddr, Frame> {
         Map::new(
-            |vbase: VAddr| self.tlb.contains_base(vbase) || self.pt.interpret().contains_key(vbase),
+            |vbase: VAddr|
+                self.tlb.contains_base(self.constants.tag, vbase)
+                    || self.pt.interpret::<G>().contains_key(vbase),
             |vbase: VAddr|
                 {
-                    if self.tlb.contains_base(vbase) {
-                        self.tlb.index(vbase)
+                    if self.tlb.contains_base(self.constants.tag, vbase) {
+                        self.tlb.index(self.constants.tag, vbase)
                     } else {
-                        self.pt.interpret()[vbase]
+                        self.pt.interpret::<G>()[vbase]
                     }
                 },
         )
     }
 
     /// Interpret the common memory as a map (vidx -> word value).
+    ///
+    /// Only normal memory is interpreted. Device pages are backed by devices, not `mem`.
     pub open spec fn interpret_mem(self) -> Map<VIdx, u64> {
         Map::new(
             |vidx: VIdx|
                 exists|vbase: VAddr, frame: Frame|
                     {
                         &&& #[trigger] self.all_mappings().contains_pair(vbase, frame)
+                        &&& !frame.attr.device()
                         &&& vidx.addr().within(vbase, frame.size.as_nat())
                     },
             |vidx: VIdx|
@@ -237,6 +435,7 @@ impl LowLevelState {
                     let (vbase, frame) = choose|vbase: VAddr, frame: Frame|
                         {
                             &&& #[trigger] self.all_mappings().contains_pair(vbase, frame)
+                            &&& !frame.attr.device()
                             &&& vidx.addr().within(vbase, frame.size.as_nat())
                         };
                     self.mem.read(vidx.addr().map(vbase, frame.base).idx())
@@ -253,13 +452,14 @@ impl LowLevelState {
                 arch: self.constants.arch,
                 pmem_lb: self.mem.lb(),
                 pmem_ub: self.mem.ub(),
+                reserved: self.constants.reserved,
             },
         }
     }
 }
 
 /// Helper functions.
-impl LowLevelState {
+impl<G> LowLevelState<G> where G: GhostPTE {
     /// If exists a mapping that `vaddr` lies in.
     pub open spec fn has_mapping_for(self, vaddr: VAddr) -> bool {
         exists|vbase: VAddr, frame: Frame| #[trigger]
@@ -281,19 +481,50 @@ impl LowLevelState {
             )
     }
 
+    /// If `frame` overlaps any table in page table memory.
+    pub open spec fn overlaps_tables(self, frame: Frame) -> bool {
+        exists|i|
+            0 <= i < self.pt.tables.len() && PAddr::overlap(
+                frame.base,
+                frame.size.as_nat(),
+                #[trigger] self.pt.tables[i].base,
+                self.pt.tables[i].size.as_nat(),
+            )
+    }
+
+    /// State after the MMU sets the Access flag, and the Dirty flag if `dirty`, of the
+    /// descriptor that translates `vaddr`.
+    pub open spec fn set_flags(self, vaddr: VAddr, dirty: bool) -> Self {
+        let pt = self.pt.set_flags::<G>(vaddr, dirty);
+        LowLevelState {
+            mem: pt.mem,
+            pt,
+            tlb: self.tlb,
+            constants: self.constants,
+            _phantom: PhantomData,
+        }
+    }
+
     /// Extract the state that hardware should consider.
-    pub open spec fn hw_state(self) -> HardwareState {
-        HardwareState { mem: self.mem, pt: self.pt, tlb: self.tlb }
+    pub open spec fn hw_state(self) -> HardwareState<G> {
+        HardwareState {
+            mem: self.mem,
+            pt: self.pt,
+            tlb: self.tlb,
+            tag: self.constants.tag,
+            _phantom: PhantomData,
+        }
     }
 
     /// Extract the state that page table implementation should consider.
     pub open spec fn pt_state(self) -> PageTableState {
         PageTableState {
-            mappings: self.pt.interpret(),
+            mappings: self.pt.interpret::<G>(),
             constants: PTConstants {
                 arch: self.constants.arch,
                 pmem_ub: self.mem.ub().addr(),
                 pmem_lb: self.mem.lb().addr(),
+                reserved: self.constants.reserved,
             },
         }
     }
@@ -1,10 +1,13 @@
 /// Model of physical memory, page table memory, and TLB.
+use alloc::vec::Vec;
 use vstd::prelude::*;
 
 use crate::common::{
-    addr::{PAddr, PAddrExec, PIdx, VAddr},
-    arch::{PTArch, PTArchExec},
+    addr::{PAddr, PAddrExec, PIdx, VAddr, VAddrExec},
+    arch::{PTArch, PTArchExec, PTE_SIZE},
     frame::{Frame, FrameSize},
+    pte::{same_hw_translation, same_target, same_translation, GhostPTE},
+    PagingResult,
 };
 
 verus! {
@@ -55,8 +58,46 @@ impl PhysMem {
     }
 }
 
+/// TLB entry tag, identifying the address space an entry belongs to.
+///
+/// Stage-1 entries are tagged by ASID, stage-2 entries by VMID.
+pub struct TLBTag(pub nat);
+
+/// TLB maintenance operation (TLBI) issued by software.
+pub enum TLBOp {
+    /// Invalidate entries with the given tag that translate the given address. The address may
+    /// be anywhere inside the page.
+    VA(TLBTag, VAddr),
+    /// Invalidate entries with the given tag that overlap the virtual range `(start, size)`.
+    Range(TLBTag, VAddr, nat),
+    /// Invalidate all entries with the given tag.
+    Tag(TLBTag),
+    /// Invalidate all entries.
+    All,
+}
+
+impl TLBOp {
+    /// If the operation invalidates the entry `(tag, vbase) -> frame`.
+    pub open spec fn covers(self, tag: TLBTag, vbase: VAddr, frame: Frame) -> bool {
+        match self {
+            TLBOp::VA(t, vaddr) => t == tag && vaddr.within(vbase, frame.size.as_nat()),
+            TLBOp::Range(t, start, size) => t == tag && VAddr::overlap(
+                vbase,
+                frame.size.as_nat(),
+                start,
+                size,
+            ),
+            TLBOp::Tag(t) => t == tag,
+            TLBOp::All => true,
+        }
+    }
+}
+
 /// Translation lookaside buffer (TLB) model.
-pub struct TLB(pub Map<VAddr, Frame>);
+///
+/// Every entry is tagged, so entries of different address spaces coexist in the TLB and
+/// switching address spaces does not require flushing.
+pub struct TLB(pub Map<(TLBTag, VAddr), Frame>);
 
 /// TLB specification.
 impl TLB {
@@ -66,67 +107,180 @@ impl TLB {
     }
 
     /// Fill a TLB entry.
-    pub open spec fn fill(self, vbase: VAddr, frame: Frame) -> Self
+    pub open spec fn fill(self, tag: TLBTag, vbase: VAddr, frame: Frame) -> Self
         recommends
-            !self.0.contains_key(vbase),
+            !self.0.contains_key((tag, vbase)),
     {
-        TLB(self.0.insert(vbase, frame))
+        TLB(self.0.insert((tag, vbase), frame))
     }
 
     /// Evict a TLB entry.
-    pub open spec fn evict(self, vbase: VAddr) -> Self
+    pub open spec fn evict(self, tag: TLBTag, vbase: VAddr) -> Self
         recommends
-            self.0.contains_key(vbase),
+            self.0.contains_key((tag, vbase)),
     {
-        TLB(self.0.remove(vbase))
+        TLB(self.0.remove((tag, vbase)))
+    }
+
+    /// Invalidate entries with the given tag that translate `vaddr`.
+    pub open spec fn invalidate_va(self, tag: TLBTag, vaddr: VAddr) -> Self {
+        TLB(
+            Map::new(
+                |key: (TLBTag, VAddr)|
+                    self.0.contains_key(key) && !(key.0 == tag && vaddr.within(
+                        key.1,
+                        self.0[key].size.as_nat(),
+                    )),
+                |key: (TLBTag, VAddr)| self.0[key],
+            ),
+        )
+    }
+
+    /// Invalidate entries with the given tag that overlap the virtual range `(start, size)`.
+    pub open spec fn invalidate_range(self, tag: TLBTag, start: VAddr, size: nat) -> Self {
+        TLB(
+            Map::new(
+                |key: (TLBTag, VAddr)|
+                    self.0.contains_key(key) && !(key.0 == tag && VAddr::overlap(
+                        key.1,
+                        self.0[key].size.as_nat(),
+                        start,
+                        size,
+                    )),
+                |key: (TLBTag, VAddr)| self.0[key],
+            ),
+        )
+    }
+
+    /// Invalidate all entries with the given tag.
+    pub open spec fn invalidate_tag(self, tag: TLBTag) -> Self {
+        TLB(
+            Map::new(
+                |key: (TLBTag, VAddr)| self.0.contains_key(key) && key.0 != tag,
+                |key: (TLBTag, VAddr)| self.0[key],
+            ),
+        )
+    }
+
+    /// Perform a TLB maintenance operation.
+    pub open spec fn invalidate(self, op: TLBOp) -> Self {
+        match op {
+            TLBOp::VA(tag, vaddr) => self.invalidate_va(tag, vaddr),
+            TLBOp::Range(tag, start, size) => self.invalidate_range(tag, start, size),
+            TLBOp::Tag(tag) => self.invalidate_tag(tag),
+            TLBOp::All => TLB(Map::empty()),
+        }
     }
 
-    /// If TLB has a mapping with given base.
-    pub open spec fn contains_base(self, vbase: VAddr) -> bool {
-        self.0.contains_key(vbase)
+    /// Lemma. TLB maintenance operations only remove entries.
+    pub proof fn lemma_invalidate_removes_only(self, op: TLBOp)
+        ensures
+            forall|key: (TLBTag, VAddr), frame: Frame| #[trigger]
+                self.invalidate(op).0.contains_pair(key, frame) ==> self.0.contains_pair(
+                    key,
+                    frame,
+                ),
+    {
     }
 
-    /// If TLB has a given mapping `(base, frame)`
-    pub open spec fn contains_mapping(self, vbase: VAddr, frame: Frame) -> bool {
-        self.0.contains_pair(vbase, frame)
+    /// Lemma. TLB maintenance operations remove exactly the entries they cover.
+    pub proof fn lemma_invalidate_covers(self, op: TLBOp)
+        ensures
+            forall|key: (TLBTag, VAddr), frame: Frame| #[trigger]
+                self.invalidate(op).0.contains_pair(key, frame) == (self.0.contains_pair(
+                    key,
+                    frame,
+                ) && !op.covers(key.0, key.1, frame)),
+    {
+    }
+
+    /// Lemma. Invalidating by address removes the entry whose base is that address.
+    pub proof fn lemma_invalidate_va_removes_base(self, tag: TLBTag, vbase: VAddr)
+        ensures
+            !self.invalidate_va(tag, vbase).contains_base(tag, vbase),
+    {
+        if self.0.contains_key((tag, vbase)) {
+            // Every frame has a positive size, so `vbase` lies inside its own page.
+            assert(vbase.within(vbase, self.0[(tag, vbase)].size.as_nat()));
+        }
+    }
+
+    /// If TLB has a mapping with given tag and base.
+    pub open spec fn contains_base(self, tag: TLBTag, vbase: VAddr) -> bool {
+        self.0.contains_key((tag, vbase))
+    }
+
+    /// If TLB has a given mapping `(base, frame)` with given tag.
+    pub open spec fn contains_mapping(self, tag: TLBTag, vbase: VAddr, frame: Frame) -> bool {
+        self.0.contains_pair((tag, vbase), frame)
     }
 
     /// Index a TLB entry.
-    pub open spec fn index(self, vbase: VAddr) -> Frame
+    pub open spec fn index(self, tag: TLBTag, vbase: VAddr) -> Frame
         recommends
-            self.contains_base(vbase),
+            self.contains_base(tag, vbase),
     {
-        self.0[vbase]
+        self.0[(tag, vbase)]
+    }
+
+    /// If no entry with the given tag overlaps a mapping that `pt2` has and `pt1` has not.
+    ///
+    /// Making the mappings of `pt2` valid then cannot conflict with a cached translation.
+    pub open spec fn clear_for<G: GhostPTE>(
+        self,
+        tag: TLBTag,
+        pt1: PageTableMem,
+        pt2: PageTableMem,
+    ) -> bool {
+        forall|vbase: VAddr, frame: Frame, vbase2: VAddr, frame2: Frame|
+            #[trigger] pt2.interpret::<G>().contains_pair(vbase, frame)
+                && !pt1.interpret::<G>().contains_pair(vbase, frame)
+                && #[trigger] self.contains_mapping(tag, vbase2, frame2) ==> !VAddr::overlap(
+                vbase,
+                frame.size.as_nat(),
+                vbase2,
+                frame2.size.as_nat(),
+            )
+    }
+
+    /// Entries with the given tag, as `(vbase, frame)` mappings.
+    pub open spec fn tagged(self, tag: TLBTag) -> Map<VAddr, Frame> {
+        Map::new(
+            |vbase: VAddr| self.contains_base(tag, vbase),
+            |vbase: VAddr| self.index(tag, vbase),
+        )
     }
 
     /// Check if a new entry conflicts with an existing TLB entry, return the conflicting entry.
     ///
-    /// The concrete strategy varies depending on the TLB implementation.
-    /// This specification does not dictate the eviction strategy.
-    pub open spec fn conflict(self, vbase: VAddr, frame: Frame) -> Option<VAddr>;
+    /// The concrete strategy varies depending on the TLB implementation. The conflicting entry
+    /// may belong to any tag. This specification does not dictate the eviction strategy.
+    pub open spec fn conflict(self, tag: TLBTag, vbase: VAddr, frame: Frame) -> Option<
+        (TLBTag, VAddr),
+    >;
 
     /// The conflict entry returned by `conflict` must be in the TLB.
     ///
     /// This is an assumption made about the concrete TLB behavior.
     #[verifier::external_body]
-    pub broadcast proof fn lemma_conflict(self, vbase: VAddr, frame: Frame)
+    pub broadcast proof fn lemma_conflict(self, tag: TLBTag, vbase: VAddr, frame: Frame)
         ensures
-            match #[trigger] self.conflict(vbase, frame) {
+            match #[trigger] self.conflict(tag, vbase, frame) {
                 Some(conflict) => self.0.contains_key(conflict),
-                None => !self.0.contains_key(vbase),
+                None => !self.0.contains_key((tag, vbase)),
             },
     {
     }
 
     /// Update TLB with a new entry, if there is a conflict, evict the conflicting entry.
-    pub open spec fn update(self, vbase: VAddr, frame: Frame) -> Self
+    pub open spec fn update(self, tag: TLBTag, vbase: VAddr, frame: Frame) -> Self
         recommends
-            !self.0.contains_key(vbase),
+            !self.0.contains_key((tag, vbase)),
     {
-        if let Some(conflict) = self.conflict(vbase, frame) {
-            self.evict(conflict).fill(vbase, frame)
+        if let Some(conflict) = self.conflict(tag, vbase, frame) {
+            self.evict(conflict.0, conflict.1).fill(tag, vbase, frame)
         } else {
-            self.fill(vbase, frame)
+            self.fill(tag, vbase, frame)
         }
     }
 }
@@ -143,13 +297,17 @@ pub struct Table {
 
 /// Abstract model of page table memory, a memory region that stores page tables.
 ///
-/// Hardware reads page table memory to perform page table walk, but cannot write to it.
-/// Page table memory is modified by page table functions.
+/// Tables are regions of physical memory. Hardware reads page table memory to perform page
+/// table walk, and page table functions modify it by writing table entries.
 pub struct PageTableMem {
     /// All tables in the hierarchical page table, the first table is the root.
     pub tables: Seq<Table>,
     /// Page table architecture.
     pub arch: PTArch,
+    /// Physical memory that stores the tables.
+    pub mem: PhysMem,
+    /// Physical regions the table allocator hands out tables from, as `(base, size)` pairs.
+    pub pool: Seq<(PAddr, nat)>,
 }
 
 impl PageTableMem {
@@ -171,22 +329,61 @@ impl PageTableMem {
         choose|table: Table| #[trigger] self.tables.contains(table) && table.base == base
     }
 
+    /// Physical address of the entry at the given index in the given table.
+    pub open spec fn entry_addr(self, base: PAddr, index: nat) -> PAddr {
+        base.offset(index * PTE_SIZE)
+    }
+
     /// View a table as a sequence of entries.
-    pub open spec fn table_view(self, base: PAddr) -> Seq<u64>
-        recommends
-            self.contains_table(base),
-    ;
+    ///
+    /// Entries are read from physical memory. A non-existent table is viewed as empty.
+    pub open spec fn table_view(self, base: PAddr) -> Seq<u64> {
+        if self.contains_table(base) {
+            Seq::new(
+                self.arch.entry_count(self.table(base).level),
+                |i: int| self.mem.read(self.entry_addr(base, i as nat).idx()),
+            )
+        } else {
+            Seq::empty()
+        }
+    }
 
     /// Facts about table view.
-    #[verifier::external_body]
     pub broadcast proof fn table_view_facts(self, base: PAddr)
         requires
             self.invariants(),
+            self.contains_table(base),
         ensures
             #[trigger] self.table_view(base).len() == self.arch.entry_count(self.table(base).level),
     {
     }
 
+    /// If `table` is aligned to entry size and stored within physical memory.
+    pub open spec fn table_in_mem(self, table: Table) -> bool {
+        &&& table.base.aligned(PTE_SIZE)
+        &&& self.mem.lb().0 <= table.base.idx().0
+        &&& table.base.offset(table.size.as_nat()).idx().0 <= self.mem.ub().0
+    }
+
+    /// If `table` lies inside a region of the table pool.
+    pub open spec fn table_in_pool(self, table: Table) -> bool {
+        PAddr::inside_any(table.base, table.size.as_nat(), self.pool)
+    }
+
+    /// If physical word `pidx` lies in a table.
+    pub open spec fn in_table(self, pidx: PIdx) -> bool {
+        exists|i|
+            0 <= i < self.tables.len() && pidx.addr().within(
+                #[trigger] self.tables[i].base,
+                self.tables[i].size.as_nat(),
+            )
+    }
+
+    /// Replace the physical memory that stores the tables.
+    pub open spec fn with_mem(self, mem: PhysMem) -> Self {
+        PageTableMem { tables: self.tables, arch: self.arch, mem, pool: self.pool }
+    }
+
     /// If a table is empty.
     pub open spec fn is_table_empty(self, base: PAddr) -> bool
         recommends
@@ -210,19 +407,116 @@ impl PageTableMem {
 
     /// Interpret as `(vbase, frame)` mappings.
     ///
-    /// This function extracts all mappings that valid hardware page table walks can reach.
-    pub open spec fn interpret(self) -> Map<VAddr, Frame> {
+    /// This function extracts all mappings that valid hardware page table walks can reach,
+    /// decoding entries with the descriptor format `G`. Frames keep the global flag and the
+    /// software bits of their descriptors, which the MMU ignores; `Frame::hw` drops them.
+    pub open spec fn interpret<G: GhostPTE>(self) -> Map<VAddr, Frame> {
         Map::new(
-            |vaddr| exists|frame| #[trigger] self.walk(vaddr, frame),
-            |vaddr| choose|frame| #[trigger] self.walk(vaddr, frame),
+            |vbase| exists|frame| #[trigger] self.walk::<G>(vbase, frame),
+            |vbase| choose|frame| #[trigger] self.walk::<G>(vbase, frame),
         )
     }
 
-    /// Hardware page table walk behavior.
+    /// Hardware page table walk starting from the table at `base` on level `level`.
     ///
-    /// Returns true for those vaddr and frame that valid hardware page table walks can reach.
-    /// TODO: specify the behavior of hardware page table walk.
-    pub open spec fn walk(self, vaddr: VAddr, frame: Frame) -> bool;
+    /// Reads the entry indexed by `vaddr` level by level. The walk descends into the next-level
+    /// table while the entry is valid and not huge, and returns the terminal entry and its level.
+    pub open spec fn walk_from<G: GhostPTE>(self, vaddr: VAddr, base: PAddr, level: nat) -> (
+        G,
+        nat,
+    )
+        recommends
+            self.invariants(),
+            self.contains_table(base),
+            self.table(base).level == level,
+            level < self.arch.level_count(),
+        decreases self.arch.level_count() - level,
+    {
+        let pte = G::from_u64(self.read(base, self.arch.pte_index(vaddr, level)));
+        if level + 1 < self.arch.level_count() && pte.valid() && !pte.huge() {
+            self.walk_from::<G>(vaddr, pte.addr(), level + 1)
+        } else {
+            (pte, level)
+        }
+    }
+
+    /// Location `(base, index)` of the entry where `walk_from` terminates.
+    pub open spec fn walk_entry_from<G: GhostPTE>(
+        self,
+        vaddr: VAddr,
+        base: PAddr,
+        level: nat,
+    ) -> (PAddr, nat)
+        recommends
+            self.invariants(),
+            self.contains_table(base),
+            self.table(base).level == level,
+            level < self.arch.level_count(),
+        decreases self.arch.level_count() - level,
+    {
+        let index = self.arch.pte_index(vaddr, level);
+        let pte = G::from_u64(self.read(base, index));
+        if level + 1 < self.arch.level_count() && pte.valid() && !pte.huge() {
+            self.walk_entry_from::<G>(vaddr, pte.addr(), level + 1)
+        } else {
+            (base, index)
+        }
+    }
+
+    /// If `vbase` is mapped to `frame` by the walk from the root.
+    ///
+    /// Returns true if the walk for `vbase` from the root terminates at a valid block/page
+    /// descriptor, `vbase` is the base address of that block/page, and the descriptor maps
+    /// exactly `frame`.
+    pub open spec fn walk<G: GhostPTE>(self, vbase: VAddr, frame: Frame) -> bool {
+        let (pte, level) = self.walk_from::<G>(vbase, self.root(), 0);
+        &&& pte.valid()
+        // A descriptor on the last level must be a page descriptor
+        &&& level == self.arch.level_count() - 1 ==> !pte.huge()
+        &&& vbase == self.arch.vbase(vbase, level)
+        &&& frame == Frame {
+            base: pte.addr(),
+            size: self.arch.frame_size(level),
+            attr: pte.attr(),
+        }
+    }
+
+    /// Set the Access flag, and the Dirty flag if `dirty`, of the entry that terminates the
+    /// walk for `vaddr`.
+    ///
+    /// This is the page table update the MMU performs on a memory access.
+    pub open spec fn set_flags<G: GhostPTE>(self, vaddr: VAddr, dirty: bool) -> Self {
+        let (base, index) = self.walk_entry_from::<G>(vaddr, self.root(), 0);
+        let pte = G::from_u64(self.read(base, index)).set_accessed();
+        let pte = if dirty {
+            pte.set_dirty()
+        } else {
+            pte
+        };
+        self.write(base, index, pte.to_u64())
+    }
+
+    /// If the entry that terminates the walk for `vaddr` has the Dirty flag set.
+    pub open spec fn dirty<G: GhostPTE>(self, vaddr: VAddr) -> bool {
+        self.walk_from::<G>(vaddr, self.root(), 0).0.dirty()
+    }
+
+    /// Result of a query for `vaddr` answered by the walk from the root.
+    ///
+    /// A valid terminal entry maps the page or block containing `vaddr`.
+    pub open spec fn query_walk<G: GhostPTE>(self, vaddr: VAddr) -> PagingResult<(VAddr, Frame)> {
+        let (pte, level) = self.walk_from::<G>(vaddr, self.root(), 0);
+        if pte.valid() {
+            Ok(
+                (
+                    self.arch.vbase(vaddr, level),
+                    Frame { base: pte.addr(), size: self.arch.frame_size(level), attr: pte.attr() },
+                ),
+            )
+        } else {
+            Err(())
+        }
+    }
 
     /// Invariants.
     pub open spec fn invariants(self) -> bool {
@@ -249,6 +543,25 @@ impl PageTableMem {
                 self.tables[j].base,
                 self.tables[j].size.as_nat(),
             )
+        // All tables are stored in physical memory.
+        &&& forall|i| 0 <= i < self.tables.len() ==> self.table_in_mem(#[trigger] self.tables[i])
+        // All tables are allocated from the pool.
+        &&& forall|i| 0 <= i < self.tables.len() ==> self.table_in_pool(#[trigger] self.tables[i])
+    }
+
+    /// If every table descriptor, decoded with `G`, points to a table on the next level.
+    ///
+    /// Hardware walks then only read existing tables.
+    pub open spec fn walkable<G: GhostPTE>(self) -> bool {
+        forall|base: PAddr, index: nat| #[trigger]
+            self.accessible(base, index) ==> {
+                let pte = G::from_u64(self.read(base, index));
+                let level = self.table(base).level;
+                level + 1 < self.arch.level_count() && pte.valid() && !pte.huge() ==> {
+                    &&& self.contains_table(pte.addr())
+                    &&& self.table(pte.addr()).level == level + 1
+                }
+            }
     }
 
     /// Init State.
@@ -257,71 +570,100 @@ impl PageTableMem {
         &&& self.tables.len() == 1
         &&& self.tables[0].level == 0
         &&& self.tables[0].size.as_nat() == self.arch.table_size(0)
+        &&& self.table_in_mem(self.tables[0])
+        &&& self.table_in_pool(self.tables[0])
         &&& self.table_view(self.root()) == seq![0u64; self.arch.entry_count(0)]
     }
 
-    /// Allocate a new table.
-    pub open spec fn alloc_table(self, level: nat) -> (Self, Table)
+    /// If allocating a table of the given level from `self` may yield `s2` and `table`.
+    pub open spec fn allocates(self, level: nat, s2: Self, table: Table) -> bool {
+        &&& s2.arch == self.arch
+        // `self` doesn't have the table
+        &&& !self.contains_table(
+            table.base,
+        )
+        // new table has valid level
+        &&& table.level == level
+        // new table has valid size
+        &&& table.size.as_nat() == self.arch.table_size(
+            level,
+        )
+        // new table is aligned
+        &&& table.base.aligned(
+            table.size.as_nat(),
+        )
+        // new table is stored in physical memory
+        &&& s2.table_in_mem(
+            table,
+        )
+        // new table is allocated from the pool, which is unchanged
+        &&& self.table_in_pool(table)
+        &&& s2.pool == self.pool
+        // new table is empty
+        &&& s2.table_view(table.base)
+            == seq![0u64; self.arch.entry_count(level)]
+        // physical memory bounds are the same
+        &&& s2.mem.lb() == self.mem.lb()
+        &&& s2.mem.ub()
+            == self.mem.ub()
+        // physical memory outside the new table is the same
+        &&& forall|pidx: PIdx|
+            self.mem.contains(pidx) && !pidx.addr().within(table.base, table.size.as_nat())
+                ==> #[trigger] s2.mem.read(pidx) == self.mem.read(pidx)
+        // old tables are the same
+        &&& forall|base: PAddr| #[trigger]
+            self.contains_table(base) ==> s2.table_view(base) == self.table_view(
+                base,
+            )
+        // new table doesn't overlap with existing tables
+        &&& forall|i|
+            #![auto]
+            0 <= i < self.tables.len() ==> !PAddr::overlap(
+                self.tables[i].base,
+                self.tables[i].size.as_nat(),
+                table.base,
+                table.size.as_nat(),
+            )
+            // `tables` is updated
+        &&& s2.tables == self.tables.push(table)
+    }
+
+    /// Deallocate a table.
+    ///
+    /// Left uninterpreted, it stands for the table deallocator of the backend in use.
+    pub open spec fn dealloc_table(self, base: PAddr) -> Self
         recommends
             self.invariants(),
-            level < self.arch.level_count(),
+            self.contains_table(base),
+            base != self.root(),
     ;
 
-    /// Facts that `alloc_table` should satisfy.
+    /// If deallocating the table at `base` from `self` may yield `s2`.
+    ///
+    /// The table is removed from `tables`, everything else is unchanged.
+    pub open spec fn deallocates(self, base: PAddr, s2: Self) -> bool {
+        exists|i|
+            0 <= i < self.tables.len() && #[trigger] self.tables[i].base == base && s2 == Self {
+                tables: self.tables.remove(i),
+                ..self
+            }
+    }
+
+    /// Axiom. A table a backend deallocates from `self` is `dealloc_table`.
+    ///
+    /// Sound because `deallocates` has a single result, as table bases are unique.
     #[verifier::external_body]
-    pub broadcast proof fn alloc_table_facts(self, level: nat)
+    pub proof fn axiom_backend_dealloc_table(self, base: PAddr, s2: Self)
         requires
             self.invariants(),
-            level < self.arch.level_count(),
+            self.contains_table(base),
+            base != self.root(),
+            self.deallocates(base, s2),
         ensures
-            ({
-                let (s2, table) = #[trigger] self.alloc_table(level);
-                &&& s2.arch == self.arch
-                // `self` doesn't have the table
-                &&& !self.contains_table(
-                    table.base,
-                )
-                // new table has valid level
-                &&& table.level == level
-                // new table has valid size
-                &&& table.size.as_nat() == self.arch.table_size(
-                    level,
-                )
-                // new table is aligned
-                &&& table.base.aligned(
-                    table.size.as_nat(),
-                )
-                // new table is empty
-                &&& s2.table_view(table.base)
-                    == seq![0u64; self.arch.entry_count(level)]
-                // old tables are the same
-                &&& forall|base: PAddr| #[trigger]
-                    self.contains_table(base) ==> s2.table_view(base) == self.table_view(
-                        base,
-                    )
-                // new table doesn't overlap with existing tables
-                &&& forall|i|
-                    #![auto]
-                    0 <= i < self.tables.len() ==> !PAddr::overlap(
-                        self.tables[i].base,
-                        self.tables[i].size.as_nat(),
-                        table.base,
-                        table.size.as_nat(),
-                    )
-                    // `tables` is updated
-                &&& s2.tables == self.tables.push(table)
-            }),
+            self.dealloc_table(base) == s2,
     {
     }
 
-    /// Deallocate a table.
-    pub open spec fn dealloc_table(self, base: PAddr) -> Self
-        recommends
-            self.invariants(),
-            self.contains_table(base),
-            base != self.root(),
-    ;
-
     /// Facts that `dealloc_table` should satisfy.
     #[verifier::external_body]
     pub broadcast proof fn dealloc_table_facts(self, base: PAddr)
@@ -353,22 +695,59 @@ impl PageTableMem {
                     0 <= i < s2.tables.len() && 0 <= j < s2.tables.len() ==> i == j
                         || s2.tables[i].base
                         != s2.tables[j].base
-                // Table contents are the same
-                &&& forall|base: PAddr| #[trigger]
-                    self.contains_table(base) ==> s2.table_view(base) == self.table_view(base)
+                // Contents of other tables are the same
+                &&& forall|base2: PAddr| #[trigger]
+                    self.contains_table(base2) && base2 != base ==> s2.table_view(base2)
+                        == self.table_view(base2)
+                // Physical memory and the pool are the same
+                &&& s2.mem === self.mem
+                &&& s2.pool == self.pool
+                // Deallocation does not depend on table contents
+                &&& forall|mem: PhysMem| #[trigger]
+                    self.with_mem(mem).dealloc_table(base) == s2.with_mem(mem)
             }),
     {
     }
 
+    /// If writing `entry` at the given index in the given table follows break-before-make.
+    ///
+    /// A valid descriptor is never replaced by another valid descriptor. It must be invalidated
+    /// first, so the walker never observes two different translations for the same entry.
+    ///
+    /// This is only the page table side. The TLB must also be clear of the old translation
+    /// before the entry is made valid again (`HardwareState::write_is_bbm`).
+    pub open spec fn write_is_bbm<G: GhostPTE>(self, base: PAddr, index: nat, entry: u64) -> bool {
+        !G::from_u64(self.read(base, index)).valid() || !G::from_u64(entry).valid()
+    }
+
+    /// If writing `entry` at the given index in the given table only changes the attributes
+    /// of a valid descriptor, which keeps linking the same table or frame.
+    ///
+    /// The memory type and shareability must stay the same. Changing them needs
+    /// break-before-make like any other change of the translation.
+    pub open spec fn write_changes_attr<G: GhostPTE>(
+        self,
+        base: PAddr,
+        index: nat,
+        entry: u64,
+    ) -> bool {
+        let pte = G::from_u64(self.read(base, index));
+        let new_pte = G::from_u64(entry);
+        &&& pte.valid()
+        &&& same_target(pte, new_pte)
+        &&& pte.attr().spec_same_memory_type(new_pte.attr())
+    }
+
     /// Update the entry at the given index in the given table.
     pub open spec fn write(self, base: PAddr, index: nat, entry: u64) -> Self
         recommends
             self.invariants(),
             self.accessible(base, index),
-    ;
+    {
+        self.with_mem(self.mem.write(self.entry_addr(base, index).idx(), entry))
+    }
 
-    /// Facts that `write` should satisfy.
-    #[verifier::external_body]
+    /// Facts that `write` satisfies.
     pub broadcast proof fn write_facts(self, base: PAddr, index: nat, entry: u64)
         requires
             self.invariants(),
@@ -392,6 +771,610 @@ impl PageTableMem {
                     ) == self.table_view(self.tables[i].base)
             }),
     {
+        let s2 = self.write(base, index, entry);
+        let pidx = self.entry_addr(base, index).idx();
+        self.lemma_entry_in_mem(base, index);
+        // The entry is updated
+        assert forall|i: int| 0 <= i < self.table_view(base).len() implies #[trigger] s2.table_view(
+            base,
+        )[i] == self.table_view(base).update(index as int, entry)[i] by {
+            self.lemma_entry_in_mem(base, i as nat);
+            if i != index {
+                self.lemma_entry_idx_unique(base, i as nat, base, index);
+            }
+        }
+        assert(s2.table_view(base) =~= self.table_view(base).update(index as int, entry));
+        // Other tables contents are the same
+        assert forall|i|
+            #![auto]
+            0 <= i < self.tables.len() && self.tables[i].base != base implies s2.table_view(
+            self.tables[i].base,
+        ) == self.table_view(self.tables[i].base) by {
+            let base2 = self.tables[i].base;
+            assert(self.tables.contains(self.tables[i]));
+            assert forall|j: int| 0 <= j < self.table_view(base2).len() implies #[trigger]
+                s2.table_view(base2)[j] == self.table_view(base2)[j] by {
+                self.lemma_entry_in_mem(base2, j as nat);
+                self.lemma_entry_idx_unique(base2, j as nat, base, index);
+            }
+            assert(s2.table_view(base2) =~= self.table_view(base2));
+        }
+    }
+
+    /// Lemma. Writing an entry and deallocating another table commute.
+    pub proof fn lemma_dealloc_write_commute(
+        self,
+        table_base: PAddr,
+        base: PAddr,
+        index: nat,
+        entry: u64,
+    )
+        requires
+            self.invariants(),
+            self.contains_table(table_base),
+            table_base != self.root(),
+        ensures
+            self.dealloc_table(table_base).write(base, index, entry) == self.write(
+                base,
+                index,
+                entry,
+            ).dealloc_table(table_base),
+    {
+        let mem = self.mem.write(self.entry_addr(base, index).idx(), entry);
+        self.dealloc_table_facts(table_base);
+        assert(self.write(base, index, entry) == self.with_mem(mem));
+        assert(self.dealloc_table(table_base).write(base, index, entry) == self.dealloc_table(
+            table_base,
+        ).with_mem(mem));
+    }
+
+    /// Lemma. Writing an entry keeps the other entries.
+    pub proof fn lemma_write_keeps_other_entry(
+        self,
+        b: PAddr,
+        i: nat,
+        entry: u64,
+        base: PAddr,
+        idx: nat,
+    )
+        requires
+            self.invariants(),
+            self.accessible(b, i),
+            self.accessible(base, idx),
+            base != b || idx != i,
+        ensures
+            self.write(b, i, entry).read(base, idx) == self.read(base, idx),
+            self.write(b, i, entry).tables == self.tables,
+    {
+        let post = self.write(b, i, entry);
+        if base != b {
+            let k = choose|k: int| 0 <= k < self.tables.len() && self.tables[k] == self.table(base);
+            assert(post.table_view(self.tables[k].base) == self.table_view(self.tables[k].base));
+        }
+    }
+
+    /// Lemma. An accessible entry is stored within physical memory, inside the word range of
+    /// its table.
+    pub proof fn lemma_entry_in_mem(self, base: PAddr, index: nat)
+        requires
+            self.invariants(),
+            self.accessible(base, index),
+        ensures
+            self.mem.contains(self.entry_addr(base, index).idx()),
+            self.entry_addr(base, index).idx().0 == base.idx().0 + index,
+            self.entry_addr(base, index).idx().addr().within(
+                base,
+                self.table(base).size.as_nat(),
+            ),
+    {
+        let table = self.table(base);
+        let i = choose|i| 0 <= i < self.tables.len() && self.tables[i] == table;
+        assert(self.table_in_mem(self.tables[i]));
+        lemma_entry_idx(base, index);
+    }
+
+    /// Lemma. Different entries are stored in different words of physical memory.
+    pub proof fn lemma_entry_idx_unique(self, base1: PAddr, index1: nat, base2: PAddr, index2: nat)
+        requires
+            self.invariants(),
+            self.accessible(base1, index1),
+            self.accessible(base2, index2),
+            base1 != base2 || index1 != index2,
+        ensures
+            self.entry_addr(base1, index1).idx() != self.entry_addr(base2, index2).idx(),
+    {
+        self.lemma_entry_in_mem(base1, index1);
+        self.lemma_entry_in_mem(base2, index2);
+        if base1 != base2 {
+            let i = choose|i| 0 <= i < self.tables.len() && self.tables[i] == self.table(base1);
+            let j = choose|j| 0 <= j < self.tables.len() && self.tables[j] == self.table(base2);
+            // Tables do not overlap, so neither do their entries
+            assert(i != j);
+            if self.entry_addr(base1, index1).idx() == self.entry_addr(base2, index2).idx() {
+                assert(PAddr::overlap(
+                    self.tables[i].base,
+                    self.tables[i].size.as_nat(),
+                    self.tables[j].base,
+                    self.tables[j].size.as_nat(),
+                ));
+            }
+        }
+    }
+
+    /// Lemma. A table that contains physical word `pidx` makes `in_table(pidx)` true.
+    pub proof fn lemma_entry_in_table(self, base: PAddr, index: nat)
+        requires
+            self.invariants(),
+            self.accessible(base, index),
+        ensures
+            self.in_table(self.entry_addr(base, index).idx()),
+    {
+        self.lemma_entry_in_mem(base, index);
+        let i = choose|i| 0 <= i < self.tables.len() && self.tables[i] == self.table(base);
+        assert(self.entry_addr(base, index).idx().addr().within(
+            self.tables[i].base,
+            self.tables[i].size.as_nat(),
+        ));
+    }
+
+    /// Lemma. Page table memories with the same tables and the same table contents perform
+    /// the same hardware page table walk.
+    pub proof fn lemma_same_tables_same_walk_from<G: GhostPTE>(
+        self,
+        other: Self,
+        vaddr: VAddr,
+        base: PAddr,
+        level: nat,
+    )
+        requires
+            self.arch == other.arch,
+            self.tables == other.tables,
+            forall|base: PAddr| #[trigger] self.table_view(base) == other.table_view(base),
+        ensures
+            self.walk_from::<G>(vaddr, base, level) == other.walk_from::<G>(vaddr, base, level),
+        decreases self.arch.level_count() - level,
+    {
+        let pte = G::from_u64(self.read(base, self.arch.pte_index(vaddr, level)));
+        assert(self.table_view(base) == other.table_view(base));
+        if level + 1 < self.arch.level_count() && pte.valid() && !pte.huge() {
+            self.lemma_same_tables_same_walk_from::<G>(other, vaddr, pte.addr(), level + 1);
+        }
+    }
+
+    /// Lemma. Page table memories with the same tables and the same table contents have the
+    /// same interpretation, no matter what the rest of physical memory contains.
+    pub proof fn lemma_same_tables_same_interpret<G: GhostPTE>(self, other: Self)
+        requires
+            self.arch == other.arch,
+            self.tables == other.tables,
+            forall|base: PAddr|
+                self.contains_table(base) ==> #[trigger] self.table_view(base) == other.table_view(
+                    base,
+                ),
+        ensures
+            self.interpret::<G>() == other.interpret::<G>(),
+    {
+        // Non-existent tables are viewed as empty in both
+        assert forall|base: PAddr| #[trigger]
+            self.table_view(base) == other.table_view(base) by {
+            if !self.contains_table(base) {
+                assert(!other.contains_table(base));
+            }
+        }
+        assert forall|vbase: VAddr, frame: Frame| #[trigger]
+            self.walk::<G>(vbase, frame) == other.walk::<G>(vbase, frame) by {
+            self.lemma_same_tables_same_walk_from::<G>(other, vbase, self.root(), 0);
+        }
+        assert(self.interpret::<G>() =~= other.interpret::<G>());
+    }
+
+    /// Lemma. `walk_entry_from` locates the entry that terminates `walk_from`.
+    pub proof fn lemma_walk_entry_from<G: GhostPTE>(self, vaddr: VAddr, base: PAddr, level: nat)
+        requires
+            self.invariants(),
+            self.walkable::<G>(),
+            self.contains_table(base),
+            self.table(base).level == level,
+            level < self.arch.level_count(),
+        ensures
+            ({
+                let (b, i) = self.walk_entry_from::<G>(vaddr, base, level);
+                let (pte, level2) = self.walk_from::<G>(vaddr, base, level);
+                &&& self.accessible(b, i)
+                &&& self.table(b).level == level2
+                &&& G::from_u64(self.read(b, i)) == pte
+            }),
+        decreases self.arch.level_count() - level,
+    {
+        let index = self.arch.pte_index(vaddr, level);
+        self.arch.lemma_pte_index_bound(vaddr, level);
+        assert(self.accessible(base, index));
+        let pte = G::from_u64(self.read(base, index));
+        if level + 1 < self.arch.level_count() && pte.valid() && !pte.huge() {
+            self.lemma_walk_entry_from::<G>(vaddr, pte.addr(), level + 1);
+        }
+    }
+
+    /// Lemma. A walk from the root terminates on a valid level.
+    pub proof fn lemma_walk_level_bound<G: GhostPTE>(self, vaddr: VAddr)
+        requires
+            self.invariants(),
+            self.walkable::<G>(),
+        ensures
+            self.walk_from::<G>(vaddr, self.root(), 0).1 < self.arch.level_count(),
+    {
+        self.lemma_contains_root();
+        self.lemma_walk_entry_from::<G>(vaddr, self.root(), 0);
+        let (b, _) = self.walk_entry_from::<G>(vaddr, self.root(), 0);
+        // The terminal entry is in a table, whose level is valid
+        let table = self.table(b);
+        assert(self.tables.contains(table));
+        let i = self.tables.index_of(table);
+        assert(self.tables[i].level < self.arch.level_count());
+    }
+
+    /// Lemma. Writing an entry that translates the same way as the old one keeps every walk,
+    /// up to the Access and Dirty flags of the terminal entry.
+    pub proof fn lemma_write_same_translation_walk_from<G: GhostPTE>(
+        self,
+        b: PAddr,
+        i: nat,
+        entry: u64,
+        vaddr: VAddr,
+        base: PAddr,
+        level: nat,
+    )
+        requires
+            self.invariants(),
+            self.walkable::<G>(),
+            self.accessible(b, i),
+            same_translation(G::from_u64(self.read(b, i)), G::from_u64(entry)),
+            self.contains_table(base),
+            self.table(base).level == level,
+            level < self.arch.level_count(),
+        ensures
+            ({
+                let (pte1, level1) = self.walk_from::<G>(vaddr, base, level);
+                let (pte2, level2) = self.write(b, i, entry).walk_from::<G>(vaddr, base, level);
+                &&& level1 == level2
+                &&& same_translation(pte1, pte2)
+            }),
+        decreases self.arch.level_count() - level,
+    {
+        let post = self.write(b, i, entry);
+        let index = self.arch.pte_index(vaddr, level);
+        self.arch.lemma_pte_index_bound(vaddr, level);
+        assert(self.accessible(base, index));
+        if base == b && index == i {
+            assert(post.read(b, i) == entry);
+        } else {
+            self.lemma_write_keeps_other_entry(b, i, entry, base, index);
+        }
+        let pte = G::from_u64(self.read(base, index));
+        if level + 1 < self.arch.level_count() && pte.valid() && !pte.huge() {
+            self.lemma_write_same_translation_walk_from::<G>(
+                b,
+                i,
+                entry,
+                vaddr,
+                pte.addr(),
+                level + 1,
+            );
+        }
+    }
+
+    /// Lemma. Writing an entry that translates the same way as the old one for the MMU keeps
+    /// every walk, up to the global flag, the software bits and the Access and Dirty flags of
+    /// the terminal entry.
+    pub proof fn lemma_write_same_hw_translation_walk_from<G: GhostPTE>(
+        self,
+        b: PAddr,
+        i: nat,
+        entry: u64,
+        vaddr: VAddr,
+        base: PAddr,
+        level: nat,
+    )
+        requires
+            self.invariants(),
+            self.walkable::<G>(),
+            self.accessible(b, i),
+            same_hw_translation(G::from_u64(self.read(b, i)), G::from_u64(entry)),
+            self.contains_table(base),
+            self.table(base).level == level,
+            level < self.arch.level_count(),
+        ensures
+            ({
+                let (pte1, level1) = self.walk_from::<G>(vaddr, base, level);
+                let (pte2, level2) = self.write(b, i, entry).walk_from::<G>(vaddr, base, level);
+                &&& level1 == level2
+                &&& same_hw_translation(pte1, pte2)
+            }),
+        decreases self.arch.level_count() - level,
+    {
+        let post = self.write(b, i, entry);
+        let index = self.arch.pte_index(vaddr, level);
+        self.arch.lemma_pte_index_bound(vaddr, level);
+        assert(self.accessible(base, index));
+        if base == b && index == i {
+            assert(post.read(b, i) == entry);
+        } else {
+            self.lemma_write_keeps_other_entry(b, i, entry, base, index);
+        }
+        let pte = G::from_u64(self.read(base, index));
+        if level + 1 < self.arch.level_count() && pte.valid() && !pte.huge() {
+            self.lemma_write_same_hw_translation_walk_from::<G>(
+                b,
+                i,
+                entry,
+                vaddr,
+                pte.addr(),
+                level + 1,
+            );
+        }
+    }
+
+    /// Lemma. A walk from a table on `level` terminates on `level` or a higher level.
+    pub proof fn lemma_walk_from_level_lower_bound<G: GhostPTE>(
+        self,
+        vaddr: VAddr,
+        base: PAddr,
+        level: nat,
+    )
+        ensures
+            self.walk_from::<G>(vaddr, base, level).1 >= level,
+        decreases self.arch.level_count() - level,
+    {
+        let pte = G::from_u64(self.read(base, self.arch.pte_index(vaddr, level)));
+        if level + 1 < self.arch.level_count() && pte.valid() && !pte.huge() {
+            self.lemma_walk_from_level_lower_bound::<G>(vaddr, pte.addr(), level + 1);
+        }
+    }
+
+    /// Lemma. Addresses in the same page or block on the level where the walk for `v1`
+    /// terminates are walked the same way.
+    pub proof fn lemma_walk_from_same_block<G: GhostPTE>(
+        self,
+        v1: VAddr,
+        v2: VAddr,
+        base: PAddr,
+        level: nat,
+    )
+        requires
+            self.arch.valid(),
+            level < self.arch.level_count(),
+            ({
+                let level1 = self.walk_from::<G>(v1, base, level).1;
+                self.arch.vbase(v1, level1) == self.arch.vbase(v2, level1)
+            }),
+        ensures
+            self.walk_from::<G>(v2, base, level) == self.walk_from::<G>(v1, base, level),
+            self.walk_entry_from::<G>(v2, base, level) == self.walk_entry_from::<G>(
+                v1,
+                base,
+                level,
+            ),
+            self.walk_from::<G>(v1, base, level).1 < self.arch.level_count(),
+        decreases self.arch.level_count() - level,
+    {
+        let level1 = self.walk_from::<G>(v1, base, level).1;
+        let pte = G::from_u64(self.read(base, self.arch.pte_index(v1, level)));
+        if level + 1 < self.arch.level_count() && pte.valid() && !pte.huge() {
+            self.lemma_walk_from_same_block::<G>(v1, v2, pte.addr(), level + 1);
+        }
+        // Both addresses read the same entry on `level`
+        self.lemma_walk_from_level_lower_bound::<G>(v1, base, level);
+        self.arch.lemma_same_vbase_same_pte_index(v1, v2, level1, level);
+    }
+
+    /// Lemma. Writing an entry that links the same table or frame as the old one keeps every
+    /// walk on the same path, so it terminates at the same entry on the same level.
+    pub proof fn lemma_write_same_target_walk_from<G: GhostPTE>(
+        self,
+        b: PAddr,
+        i: nat,
+        entry: u64,
+        vaddr: VAddr,
+        base: PAddr,
+        level: nat,
+    )
+        requires
+            self.invariants(),
+            self.walkable::<G>(),
+            self.accessible(b, i),
+            same_target(G::from_u64(self.read(b, i)), G::from_u64(entry)),
+            self.contains_table(base),
+            self.table(base).level == level,
+            level < self.arch.level_count(),
+        ensures
+            self.write(b, i, entry).walk_entry_from::<G>(vaddr, base, level)
+                == self.walk_entry_from::<G>(vaddr, base, level),
+            self.write(b, i, entry).walk_from::<G>(vaddr, base, level).1 == self.walk_from::<G>(
+                vaddr,
+                base,
+                level,
+            ).1,
+        decreases self.arch.level_count() - level,
+    {
+        let post = self.write(b, i, entry);
+        let index = self.arch.pte_index(vaddr, level);
+        self.arch.lemma_pte_index_bound(vaddr, level);
+        assert(self.accessible(base, index));
+        if base == b && index == i {
+            assert(post.read(b, i) == entry);
+        } else {
+            self.lemma_write_keeps_other_entry(b, i, entry, base, index);
+        }
+        let pte = G::from_u64(self.read(base, index));
+        if level + 1 < self.arch.level_count() && pte.valid() && !pte.huge() {
+            self.lemma_write_same_target_walk_from::<G>(b, i, entry, vaddr, pte.addr(), level + 1);
+        }
+    }
+
+    /// Lemma. Writing an entry that translates the same way as the old one keeps the
+    /// invariants and the interpretation.
+    pub proof fn lemma_write_same_translation_keeps_interpret<G: GhostPTE>(
+        self,
+        b: PAddr,
+        i: nat,
+        entry: u64,
+    )
+        requires
+            self.invariants(),
+            self.walkable::<G>(),
+            self.accessible(b, i),
+            same_translation(G::from_u64(self.read(b, i)), G::from_u64(entry)),
+        ensures
+            self.write(b, i, entry).invariants(),
+            self.write(b, i, entry).walkable::<G>(),
+            self.write(b, i, entry).tables == self.tables,
+            self.write(b, i, entry).interpret::<G>() == self.interpret::<G>(),
+    {
+        let post = self.write(b, i, entry);
+        self.lemma_write_preserves_invariants(b, i, entry);
+        // Every entry decodes to the same translation
+        assert forall|base: PAddr, index: nat| #[trigger]
+            post.accessible(base, index) implies same_translation(
+            G::from_u64(self.read(base, index)),
+            G::from_u64(post.read(base, index)),
+        ) by {
+            if base == b && index == i {
+                assert(post.read(b, i) == entry);
+            } else {
+                self.lemma_write_keeps_other_entry(b, i, entry, base, index);
+            }
+        }
+        assert(post.walkable::<G>());
+        // Every walk reaches an entry with the same translation on the same level
+        self.lemma_contains_root();
+        assert forall|vbase: VAddr, frame: Frame| #[trigger]
+            self.walk::<G>(vbase, frame) == post.walk::<G>(vbase, frame) by {
+            self.lemma_write_same_translation_walk_from::<G>(b, i, entry, vbase, self.root(), 0);
+        }
+        assert(post.interpret::<G>() =~= self.interpret::<G>());
+    }
+
+    /// Lemma. Writing an entry that differs from the old one only in the global flag and the
+    /// software bits keeps the translation of every address.
+    ///
+    /// The same virtual bases stay mapped, to frames that agree up to those bits (`Frame::hw`),
+    /// and every walk terminates on the same level.
+    pub proof fn lemma_write_same_hw_translation_keeps_translation<G: GhostPTE>(
+        self,
+        b: PAddr,
+        i: nat,
+        entry: u64,
+    )
+        requires
+            self.invariants(),
+            self.walkable::<G>(),
+            self.accessible(b, i),
+            same_hw_translation(G::from_u64(self.read(b, i)), G::from_u64(entry)),
+        ensures
+            self.write(b, i, entry).invariants(),
+            self.write(b, i, entry).walkable::<G>(),
+            self.write(b, i, entry).tables == self.tables,
+            self.write(b, i, entry).interpret::<G>().dom() == self.interpret::<G>().dom(),
+            forall|vbase: VAddr| #[trigger]
+                self.interpret::<G>().contains_key(vbase) ==> self.write(
+                    b,
+                    i,
+                    entry,
+                ).interpret::<G>()[vbase].hw() == self.interpret::<G>()[vbase].hw(),
+            forall|vaddr: VAddr| #[trigger]
+                self.write(b, i, entry).walk_from::<G>(vaddr, self.root(), 0).1 == self.walk_from::<
+                    G,
+                >(vaddr, self.root(), 0).1,
+    {
+        let post = self.write(b, i, entry);
+        self.lemma_write_preserves_invariants(b, i, entry);
+        // Every entry links the same table or frame
+        assert forall|base: PAddr, index: nat| #[trigger]
+            post.accessible(base, index) implies same_hw_translation(
+            G::from_u64(self.read(base, index)),
+            G::from_u64(post.read(base, index)),
+        ) by {
+            if base == b && index == i {
+                assert(post.read(b, i) == entry);
+            } else {
+                self.lemma_write_keeps_other_entry(b, i, entry, base, index);
+            }
+        }
+        assert(post.walkable::<G>());
+        // Every walk reaches an entry with the same translation for the MMU on the same level
+        self.lemma_contains_root();
+        assert forall|vbase: VAddr|
+            #![trigger self.interpret::<G>().contains_key(vbase)]
+            #![trigger post.interpret::<G>().contains_key(vbase)]
+            self.interpret::<G>().contains_key(vbase) == post.interpret::<G>().contains_key(vbase)
+                && (self.interpret::<G>().contains_key(vbase) ==> post.interpret::<G>()[vbase].hw()
+                == self.interpret::<G>()[vbase].hw()) by {
+            self.lemma_write_same_hw_translation_walk_from::<G>(b, i, entry, vbase, self.root(), 0);
+            let (pte1, level1) = self.walk_from::<G>(vbase, self.root(), 0);
+            let (pte2, level2) = post.walk_from::<G>(vbase, self.root(), 0);
+            let frame1 = Frame {
+                base: pte1.addr(),
+                size: self.arch.frame_size(level1),
+                attr: pte1.attr(),
+            };
+            let frame2 = Frame {
+                base: pte2.addr(),
+                size: self.arch.frame_size(level2),
+                attr: pte2.attr(),
+            };
+            // A walk maps exactly the frame of its terminal entry
+            assert(self.walk::<G>(vbase, frame1) == post.walk::<G>(vbase, frame2));
+            assert(frame1.hw() == frame2.hw());
+        }
+        assert(post.interpret::<G>().dom() =~= self.interpret::<G>().dom());
+        assert forall|vaddr: VAddr| #[trigger]
+            post.walk_from::<G>(vaddr, self.root(), 0).1 == self.walk_from::<G>(
+                vaddr,
+                self.root(),
+                0,
+            ).1 by {
+            self.lemma_write_same_hw_translation_walk_from::<G>(b, i, entry, vaddr, self.root(), 0);
+        }
+    }
+
+    /// Lemma. The MMU setting the Access and Dirty flags keeps the invariants and the
+    /// interpretation.
+    pub proof fn lemma_set_flags_keeps_interpret<G: GhostPTE>(self, vaddr: VAddr, dirty: bool)
+        requires
+            self.invariants(),
+            self.walkable::<G>(),
+        ensures
+            self.set_flags::<G>(vaddr, dirty).invariants(),
+            self.set_flags::<G>(vaddr, dirty).walkable::<G>(),
+            self.set_flags::<G>(vaddr, dirty).arch == self.arch,
+            self.set_flags::<G>(vaddr, dirty).tables == self.tables,
+            self.set_flags::<G>(vaddr, dirty).mem.lb() == self.mem.lb(),
+            self.set_flags::<G>(vaddr, dirty).mem.ub() == self.mem.ub(),
+            self.set_flags::<G>(vaddr, dirty).interpret::<G>() == self.interpret::<G>(),
+            // Only page table memory is written
+            forall|pidx: PIdx|
+                self.mem.contains(pidx) && !self.in_table(pidx) ==> #[trigger] self.set_flags::<G>(
+                    vaddr,
+                    dirty,
+                ).mem.read(pidx) == self.mem.read(pidx),
+    {
+        broadcast use crate::common::pte::group_pte_lemmas;
+
+        let (b, i) = self.walk_entry_from::<G>(vaddr, self.root(), 0);
+        self.lemma_contains_root();
+        self.lemma_walk_entry_from::<G>(vaddr, self.root(), 0);
+        let pte = G::from_u64(self.read(b, i)).set_accessed();
+        let pte = if dirty {
+            pte.set_dirty()
+        } else {
+            pte
+        };
+        // The flags do not change the translation
+        assert(G::from_u64(pte.to_u64()) == pte);
+        self.lemma_write_same_translation_keeps_interpret::<G>(b, i, pte.to_u64());
+        // The written word lies in a table
+        self.lemma_entry_in_mem(b, i);
+        self.lemma_entry_in_table(b, i);
     }
 
     /// Lemma. Different tables have different base addresses.
@@ -440,40 +1423,77 @@ impl PageTableMem {
     {
     }
 
-    /// Lemma. `alloc_table` preserves invariants.
-    pub broadcast proof fn lemma_alloc_table_preserves_invariants(self, level: nat)
+    /// Lemma. The initial page table memory has no mappings.
+    pub proof fn lemma_init_implies_empty<G: GhostPTE>(self)
+        requires
+            self.init(),
+        ensures
+            self.interpret::<G>() === Map::empty(),
+    {
+        broadcast use crate::common::pte::group_pte_lemmas;
+
+        assert forall|vbase: VAddr, frame: Frame| !#[trigger] self.walk::<G>(vbase, frame) by {
+            // Every root entry is zero, so the walk stops at an invalid entry
+            let idx = self.arch.pte_index(vbase, 0);
+            self.arch.lemma_pte_index_bound(vbase, 0);
+            assert(self.read(self.root(), idx) == 0);
+        }
+        assert(self.interpret::<G>() =~= Map::empty());
+    }
+
+    /// Lemma. Allocating a table preserves invariants.
+    pub broadcast proof fn lemma_alloc_table_preserves_invariants(
+        self,
+        level: nat,
+        s2: Self,
+        table: Table,
+    )
         requires
             self.invariants(),
             level < self.arch.level_count(),
+            #[trigger] self.allocates(level, s2, table),
         ensures
-            #[trigger] self.alloc_table(level).0.invariants(),
+            s2.invariants(),
     {
-        let (s2, table) = self.alloc_table(level);
-        self.alloc_table_facts(level);
         assert forall|table2: Table| #[trigger] s2.tables.contains(table2) implies table2.level
             < s2.arch.level_count() by {
             if table2 != table {
                 assert(self.tables.contains(table2));
             }
         }
+        assert forall|i| 0 <= i < s2.tables.len() implies s2.table_in_mem(
+            #[trigger] s2.tables[i],
+        ) by {
+            if i < self.tables.len() {
+                assert(self.table_in_mem(self.tables[i]));
+            }
+        }
+        assert forall|i| 0 <= i < s2.tables.len() implies s2.table_in_pool(
+            #[trigger] s2.tables[i],
+        ) by {
+            if i < self.tables.len() {
+                assert(self.table_in_pool(self.tables[i]));
+            }
+        }
     }
 
-    /// Lemma. `alloc_table` preserves accessibility.
+    /// Lemma. Allocating a table preserves accessibility.
     pub broadcast proof fn lemma_alloc_table_preserves_accessibility(
         self,
         level: nat,
+        s2: Self,
+        new_table: Table,
         base: PAddr,
         index: nat,
     )
         requires
             self.invariants(),
             level < self.arch.level_count(),
+            #[trigger] self.allocates(level, s2, new_table),
             self.accessible(base, index),
         ensures
-            #[trigger] self.alloc_table(level).0.accessible(base, index),
+            #[trigger] s2.accessible(base, index),
     {
-        let (s2, new_table) = self.alloc_table(level);
-        self.alloc_table_facts(level);
         // s2 contains table with base address `base`
         assert(self.contains_table(base));
         assert forall|table: Table| self.tables.contains(table) implies s2.tables.contains(
@@ -485,43 +1505,44 @@ impl PageTableMem {
         assert(s2.contains_table(base));
 
         // The table with base address `base` is the same as the table in `s1`
-        self.lemma_alloc_table_preserves_invariants(level);
+        self.lemma_alloc_table_preserves_invariants(level, s2, new_table);
         s2.lemma_table_base_unique();
         assert(self.table(base) == s2.table(base));
     }
 
-    /// Lemma. pt_mem after `alloc_table` contains the new table.
-    pub broadcast proof fn lemma_allocated_contains_new_table(self, level: nat)
+    /// Lemma. pt_mem after allocating a table contains the new table.
+    pub broadcast proof fn lemma_allocated_contains_new_table(
+        self,
+        level: nat,
+        s2: Self,
+        table: Table,
+    )
         requires
             self.invariants(),
             level < self.arch.level_count(),
+            #[trigger] self.allocates(level, s2, table),
         ensures
-            ({
-                let (s2, table) = #[trigger] self.alloc_table(level);
-                s2.contains_table(table.base)
-            }),
+            s2.contains_table(table.base),
     {
-        let (s2, table) = self.alloc_table(level);
-        self.alloc_table_facts(level);
-        assert(s2.tables == self.tables.push(table));
         assert(s2.tables.last() == table);
         assert(s2.tables.contains(table));
     }
 
-    /// Lemma. pt_mem after `alloc_table` contains all pre-existing tables.
-    pub broadcast proof fn lemma_allocated_contains_old_tables(self, level: nat)
+    /// Lemma. pt_mem after allocating a table contains all pre-existing tables.
+    pub broadcast proof fn lemma_allocated_contains_old_tables(
+        self,
+        level: nat,
+        s2: Self,
+        table: Table,
+    )
         requires
             self.invariants(),
             level < self.arch.level_count(),
+            #[trigger] self.allocates(level, s2, table),
         ensures
-            ({
-                let (s2, table) = #[trigger] self.alloc_table(level);
-                forall|base: PAddr|
-                    s2.contains_table(base) && base != table.base ==> self.contains_table(base)
-            }),
+            forall|base: PAddr|
+                s2.contains_table(base) && base != table.base ==> self.contains_table(base),
     {
-        let (s2, table) = self.alloc_table(level);
-        self.alloc_table_facts(level);
         assert forall|base: PAddr|
             s2.contains_table(base) && base != table.base implies self.contains_table(base) by {
             let table = choose|table: Table| #[trigger]
@@ -530,19 +1551,15 @@ impl PageTableMem {
         }
     }
 
-    /// Lemma. `self.tables` after `alloc_table` is a superset of before.
-    pub broadcast proof fn lemma_allocated_is_superset(self, level: nat)
+    /// Lemma. `self.tables` after allocating a table is a superset of before.
+    pub broadcast proof fn lemma_allocated_is_superset(self, level: nat, s2: Self, table: Table)
         requires
             self.invariants(),
             level < self.arch.level_count(),
+            #[trigger] self.allocates(level, s2, table),
         ensures
-            ({
-                let (s2, table) = #[trigger] self.alloc_table(level);
-                forall|base: PAddr| self.contains_table(base) ==> s2.contains_table(base)
-            }),
+            forall|base: PAddr| self.contains_table(base) ==> s2.contains_table(base),
     {
-        let (s2, table) = self.alloc_table(level);
-        self.alloc_table_facts(level);
         assert forall|base: PAddr| self.contains_table(base) implies s2.contains_table(base) by {
             let i = choose|i| 0 <= i < self.tables.len() && #[trigger] self.tables[i].base == base;
             assert(s2.tables.contains(s2.tables[i]));
@@ -579,6 +1596,16 @@ impl PageTableMem {
             assert(s2.tables.contains(s2.tables[i]));
             assert(s2.tables.contains(s2.tables[j]));
         }
+        assert forall|i| 0 <= i < s2.tables.len() implies s2.table_in_mem(
+            #[trigger] s2.tables[i],
+        ) by {
+            assert(s2.tables.contains(s2.tables[i]));
+        }
+        assert forall|i| 0 <= i < s2.tables.len() implies s2.table_in_pool(
+            #[trigger] s2.tables[i],
+        ) by {
+            assert(s2.tables.contains(s2.tables[i]));
+        }
     }
 
     /// Lemma. `write` preserves invariants.
@@ -594,14 +1621,38 @@ impl PageTableMem {
         ensures
             #[trigger] self.write(base, index, entry).invariants(),
     {
+        let s2 = self.write(base, index, entry);
         self.write_facts(base, index, entry);
+        // Writing an entry doesn't change physical memory bounds
+        assert(s2.mem.lb() == self.mem.lb() && s2.mem.ub() == self.mem.ub());
+        assert forall|i| 0 <= i < s2.tables.len() implies s2.table_in_mem(
+            #[trigger] s2.tables[i],
+        ) by {
+            assert(self.table_in_mem(self.tables[i]));
+        }
+        assert forall|i| 0 <= i < s2.tables.len() implies s2.table_in_pool(
+            #[trigger] s2.tables[i],
+        ) by {
+            assert(self.table_in_pool(self.tables[i]));
+        }
     }
 }
 
+/// Lemma. Entry `index` of a table at entry-aligned `base` is stored in word `base.idx() + index`.
+pub proof fn lemma_entry_idx(base: PAddr, index: nat)
+    by (nonlinear_arith)
+    requires
+        base.aligned(PTE_SIZE),
+    ensures
+        base.offset(index * PTE_SIZE).idx().0 == base.idx().0 + index,
+        base.offset(index * PTE_SIZE).idx().addr() == base.offset(index * PTE_SIZE),
+        base.idx().addr() == base,
+{
+}
+
 /// Broadcast page table memory related lemmas.
 pub broadcast group group_pt_mem_lemmas {
     PageTableMem::table_view_facts,
-    PageTableMem::alloc_table_facts,
     PageTableMem::dealloc_table_facts,
     PageTableMem::write_facts,
     PageTableMem::lemma_table_base_unique,
@@ -647,6 +1698,27 @@ pub trait PageTableMemExec: Sized {
             res@ == self@.root(),
     ;
 
+    /// Physical memory of the backend, as `(base, size)`.
+    ///
+    /// It also stores the frames page tables map, so it bounds the frames they may map.
+    /// Returns `None` if it does not fit the address space.
+    fn pmem(&self) -> (res: Option<(PAddrExec, usize)>)
+        ensures
+            res matches Some((base, size)) ==> {
+                &&& base@ == self@.mem.lb().addr()
+                &&& base.0 + size == self@.mem.ub().addr().0
+            },
+    ;
+
+    /// Regions of the table pool, as `(base, size)` pairs.
+    fn pool(&self) -> (res: Vec<(PAddrExec, usize)>)
+        ensures
+            res@.len() == self@.pool.len(),
+            forall|i|
+                0 <= i < res@.len() ==> (#[trigger] res@[i]).0@ == self@.pool[i].0 && res@[i].1
+                    == self@.pool[i].1,
+    ;
+
     /// If a table is empty.
     fn is_table_empty(&self, base: PAddrExec) -> (res: bool)
         requires
@@ -656,20 +1728,27 @@ pub trait PageTableMemExec: Sized {
     ;
 
     /// Construct a new page table memory and initialize the root table.
-    fn new_init(arch: PTArchExec) -> (res: Self)
+    ///
+    /// Returns `None` if the backend cannot allocate the root table.
+    fn new_init(arch: PTArchExec) -> (res: Option<Self>)
         requires
             arch@.valid(),
         ensures
-            res@.init(),
+            res is Some ==> res->Some_0@.init(),
+            res is Some ==> res->Some_0@.arch == arch@,
     ;
 
     /// Allocate a new table and returns the table descriptor.
-    fn alloc_table(&mut self, level: usize) -> (res: TableExec)
+    ///
+    /// The backend picks any table `allocates` admits. Returns `None`, changing nothing, if
+    /// the backend is out of memory.
+    fn alloc_table(&mut self, level: usize) -> (res: Option<TableExec>)
         requires
             old(self)@.invariants(),
             level < old(self)@.arch.level_count(),
         ensures
-            (self@, res@) == old(self)@.alloc_table(level as nat),
+            res is Some ==> old(self)@.allocates(level as nat, self@, res->Some_0@),
+            res is None ==> self@ == old(self)@,
     ;
 
     /// Deallocate a table.
@@ -701,4 +1780,40 @@ pub trait PageTableMemExec: Sized {
     ;
 }
 
+/// TLB maintenance interface (exec mode).
+///
+/// The page table issues these operations after removing or changing mappings. `issued`
+/// records every operation issued so far, and the hardware performs each of them as
+/// `HardwareState::tlb_invalidate`.
+pub trait TLBExec: Sized {
+    /// Operations issued so far.
+    spec fn issued(self) -> Seq<TLBOp>;
+
+    /// Invalidate entries of address space `tag` that translate `vaddr`.
+    fn invalidate_va(&mut self, tag: usize, vaddr: VAddrExec)
+        ensures
+            self.issued() == old(self).issued().push(TLBOp::VA(TLBTag(tag as nat), vaddr@)),
+    ;
+
+    /// Invalidate entries of address space `tag` that overlap `(start, size)`.
+    fn invalidate_range(&mut self, tag: usize, start: VAddrExec, size: usize)
+        ensures
+            self.issued() == old(self).issued().push(
+                TLBOp::Range(TLBTag(tag as nat), start@, size as nat),
+            ),
+    ;
+
+    /// Invalidate all entries of address space `tag`.
+    fn invalidate_tag(&mut self, tag: usize)
+        ensures
+            self.issued() == old(self).issued().push(TLBOp::Tag(TLBTag(tag as nat))),
+    ;
+
+    /// Invalidate all entries.
+    fn invalidate_all(&mut self)
+        ensures
+            self.issued() == old(self).issued().push(TLBOp::All),
+    ;
+}
+
 } // verus!
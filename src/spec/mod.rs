@@ -5,8 +5,13 @@
 //! - Hardware. Specifies the harware MMU behavior.
 //! - Page table. Defines the implementation target of the page table.
 //! - Low-level. Acts as a bridge between the implementation and the high-level specification.
+//! - Multi-core. Extends the low-level state machine to CPUs with private TLBs.
+//! - Concurrent. Linearizability of page table operations issued by several CPUs.
+#[cfg(feature = "concurrent")]
+pub mod concurrent;
 pub mod hardware;
 pub mod high_level;
 pub mod low_level;
 pub mod memory;
+pub mod multicore;
 pub mod page_table;
@@ -0,0 +1,299 @@
+//! Multi-core low-level state machine.
+//!
+//! Every CPU has its own TLB, while physical memory and the page table are shared. Seen from a
+//! single CPU, the system behaves as a `LowLevelState`.
+//!
+//! Removing a mapping leaves stale entries in the TLBs of other CPUs, so `unmap` starts a TLB
+//! shootdown: the invalidation is broadcast to all CPUs, and the shootdown completes once every
+//! CPU has performed it.
+use core::marker::PhantomData;
+use vstd::prelude::*;
+
+use super::{
+    hardware::HardwareState,
+    low_level::{LowLevelConstants, LowLevelState},
+    memory::{PageTableMem, PhysMem, TLBOp, TLB},
+    page_table::PageTableState,
+};
+use crate::common::{
+    addr::{AccessSize, VAddr},
+    frame::Frame,
+    pte::GhostPTE,
+    MemoryResult, MmioAccess, PagingResult, Privilege,
+};
+
+verus! {
+
+/// A TLB shootdown in progress.
+pub struct Shootdown {
+    /// Invalidation broadcast to all CPUs.
+    pub op: TLBOp,
+    /// CPUs that have not performed the invalidation yet.
+    pub pending: Set<nat>,
+}
+
+/// Multi-core Memory State.
+///
+/// `G` is the page table descriptor format decoded by the hardware.
+pub struct MultiCoreState<G: GhostPTE> {
+    /// Physical memory.
+    pub mem: PhysMem,
+    /// Page table memory, shared by all CPUs.
+    pub pt: PageTableMem,
+    /// Per-CPU TLBs, indexed by CPU id.
+    pub tlbs: Seq<TLB>,
+    /// TLB shootdown in progress.
+    pub shootdown: Option<Shootdown>,
+    /// Constants.
+    pub constants: LowLevelConstants,
+    /// Phantom data.
+    pub _phantom: PhantomData<G>,
+}
+
+/// State transition specification.
+impl<G> MultiCoreState<G> where G: GhostPTE {
+    /// Initial memory state.
+    ///
+    /// Every CPU starts from the low-level init state.
+    pub open spec fn init(self) -> bool {
+        &&& self.cpu_count() > 0
+        &&& forall|cpu: nat| cpu < self.cpu_count() ==> #[trigger] self.cpu(cpu).init()
+        &&& self.shootdown is None
+    }
+
+    /// State transition - Memory read of `size` bytes on `cpu`.
+    pub open spec fn read(
+        s1: Self,
+        s2: Self,
+        cpu: nat,
+        vaddr: VAddr,
+        size: AccessSize,
+        privilege: Privilege,
+        res: MemoryResult<u64>,
+        mmio: Option<MmioAccess>,
+    ) -> bool {
+        &&& cpu < s1.cpu_count()
+        &&& LowLevelState::read(s1.cpu(cpu), s2.cpu(cpu), vaddr, size, privilege, res, mmio)
+        &&& MultiCoreState::other_tlbs_unchanged(s1, s2, cpu)
+        &&& s1.shootdown == s2.shootdown
+    }
+
+    /// State transition - Fetch of a `size`-byte instruction on `cpu`.
+    pub open spec fn fetch(
+        s1: Self,
+        s2: Self,
+        cpu: nat,
+        vaddr: VAddr,
+        size: AccessSize,
+        privilege: Privilege,
+        res: MemoryResult<u64>,
+    ) -> bool {
+        &&& cpu < s1.cpu_count()
+        &&& LowLevelState::fetch(s1.cpu(cpu), s2.cpu(cpu), vaddr, size, privilege, res)
+        &&& MultiCoreState::other_tlbs_unchanged(s1, s2, cpu)
+        &&& s1.shootdown == s2.shootdown
+    }
+
+    /// State transition - Memory write of `size` bytes on `cpu`.
+    pub open spec fn write(
+        s1: Self,
+        s2: Self,
+        cpu: nat,
+        vaddr: VAddr,
+        size: AccessSize,
+        value: u64,
+        privilege: Privilege,
+        res: MemoryResult<()>,
+        mmio: Option<MmioAccess>,
+    ) -> bool {
+        &&& cpu < s1.cpu_count()
+        &&& LowLevelState::write(
+            s1.cpu(cpu),
+            s2.cpu(cpu),
+            vaddr,
+            size,
+            value,
+            privilege,
+            res,
+            mmio,
+        )
+        &&& MultiCoreState::other_tlbs_unchanged(s1, s2, cpu)
+        &&& s1.shootdown == s2.shootdown
+    }
+
+    /// State transition - Local TLB maintenance on `cpu`.
+    pub open spec fn tlb_invalidate(s1: Self, s2: Self, cpu: nat, op: TLBOp) -> bool {
+        &&& cpu < s1.cpu_count()
+        &&& LowLevelState::tlb_invalidate(s1.cpu(cpu), s2.cpu(cpu), op)
+        &&& MultiCoreState::other_tlbs_unchanged(s1, s2, cpu)
+        &&& s1.shootdown == s2.shootdown
+    }
+
+    /// State transition - Map a frame on `cpu`.
+    ///
+    /// Mapping never leaves stale TLB entries, so no shootdown is needed.
+    pub open spec fn map(
+        s1: Self,
+        s2: Self,
+        cpu: nat,
+        vbase: VAddr,
+        frame: Frame,
+        res: PagingResult,
+    ) -> bool {
+        &&& cpu < s1.cpu_count()
+        &&& LowLevelState::map(s1.cpu(cpu), s2.cpu(cpu), vbase, frame, res)
+        // Other CPUs observe a page table operation
+        &&& MultiCoreState::pt_op(s1, s2)
+        &&& s1.shootdown == s2.shootdown
+    }
+
+    /// State transition - Unmap a frame on `cpu`.
+    ///
+    /// The page table is updated, then the invalidation of the unmapped page is broadcast to
+    /// all CPUs, including `cpu` itself. Only one shootdown may be in progress.
+    pub open spec fn unmap(s1: Self, s2: Self, cpu: nat, vbase: VAddr, res: PagingResult) -> bool {
+        &&& cpu < s1.cpu_count()
+        &&& s1.shootdown is None
+        &&& s1.constants === s2.constants
+        // Page table spec satisfied
+        &&& PageTableState::unmap(
+            s1.cpu(cpu).pt_state(),
+            s2.cpu(cpu).pt_state(),
+            vbase,
+            res,
+        )
+        // The page table is updated one step at a time, as in `LowLevelState::unmap`
+        &&& LowLevelState::unmap_steps(s1.cpu(cpu).hw_state(), s2.cpu(cpu).hw_state())
+        // Every CPU observes the operation as a whole
+        &&& MultiCoreState::pt_op(s1, s2)
+        // Page table memory stays well-formed
+        &&& LowLevelState::pt_mem_op(s1.cpu(cpu), s2.cpu(cpu))
+        // Start the shootdown
+        &&& s2.shootdown == if res is Ok {
+            Some(
+                Shootdown {
+                    op: TLBOp::VA(s1.constants.tag, vbase),
+                    pending: Set::new(|other: nat| other < s1.cpu_count()),
+                },
+            )
+        } else {
+            None
+        }
+    }
+
+    /// State transition - Query a vaddr on `cpu`.
+    pub open spec fn query(
+        s1: Self,
+        s2: Self,
+        cpu: nat,
+        vaddr: VAddr,
+        res: PagingResult<(VAddr, Frame)>,
+    ) -> bool {
+        &&& cpu < s1.cpu_count()
+        &&& LowLevelState::query(s1.cpu(cpu), s2.cpu(cpu), vaddr, res)
+        // Other CPUs observe a page table operation
+        &&& MultiCoreState::pt_op(s1, s2)
+        &&& s1.shootdown == s2.shootdown
+    }
+
+    /// State transition - `cpu` performs the invalidation of the shootdown in progress and
+    /// acknowledges it.
+    pub open spec fn shootdown_ack(s1: Self, s2: Self, cpu: nat) -> bool {
+        &&& cpu < s1.cpu_count()
+        &&& s1.shootdown is Some
+        &&& s1.shootdown->Some_0.pending.contains(cpu)
+        &&& LowLevelState::tlb_invalidate(s1.cpu(cpu), s2.cpu(cpu), s1.shootdown->Some_0.op)
+        &&& MultiCoreState::other_tlbs_unchanged(s1, s2, cpu)
+        &&& s2.shootdown == Some(
+            Shootdown {
+                op: s1.shootdown->Some_0.op,
+                pending: s1.shootdown->Some_0.pending.remove(cpu),
+            },
+        )
+    }
+
+    /// State transition - The shootdown completes after all CPUs have acknowledged it.
+    pub open spec fn shootdown_finish(s1: Self, s2: Self) -> bool {
+        &&& s1.shootdown is Some
+        &&& s1.shootdown->Some_0.pending === Set::empty()
+        &&& s2.shootdown is None
+        &&& s1.mem === s2.mem
+        &&& s1.pt === s2.pt
+        &&& s1.tlbs === s2.tlbs
+        &&& s1.constants === s2.constants
+    }
+
+    /// Every CPU observes a page table operation.
+    pub open spec fn pt_op(s1: Self, s2: Self) -> bool {
+        &&& s1.cpu_count() == s2.cpu_count()
+        &&& forall|cpu: nat|
+            cpu < s1.cpu_count() ==> HardwareState::pt_op(
+                #[trigger] s1.cpu(cpu).hw_state(),
+                s2.cpu(cpu).hw_state(),
+            )
+    }
+
+    /// TLBs of CPUs other than `cpu` are unchanged.
+    pub open spec fn other_tlbs_unchanged(s1: Self, s2: Self, cpu: nat) -> bool {
+        &&& s1.cpu_count() == s2.cpu_count()
+        &&& forall|other: nat|
+            other < s1.cpu_count() && other != cpu ==> #[trigger] s1.tlbs[other as int]
+                === s2.tlbs[other as int]
+    }
+}
+
+/// State Invariants.
+impl<G> MultiCoreState<G> where G: GhostPTE {
+    /// The entry `(vbase, frame)` in the TLB of `cpu` is removed by the shootdown in progress
+    /// once `cpu` acknowledges it.
+    pub open spec fn stale_pending(self, cpu: nat, vbase: VAddr, frame: Frame) -> bool {
+        match self.shootdown {
+            Some(sd) => sd.pending.contains(cpu) && sd.op.covers(self.constants.tag, vbase, frame),
+            None => false,
+        }
+    }
+
+    /// Every TLB entry of this address space is backed by the page table, or is stale and
+    /// waiting for the shootdown.
+    pub open spec fn tlbs_coherent(self) -> bool {
+        forall|cpu: nat, vbase: VAddr, frame: Frame|
+            cpu < self.cpu_count() && #[trigger] self.cpu(cpu).tlb.contains_mapping(
+                self.constants.tag,
+                vbase,
+                frame,
+            ) ==> self.pt.interpret::<G>().contains_pair(vbase, frame) || self.stale_pending(
+                cpu,
+                vbase,
+                frame,
+            )
+    }
+
+    /// Multi-core state invariants.
+    pub open spec fn invariants(self) -> bool {
+        &&& self.cpu_count() > 0
+        &&& self.cpu(0).pt_invariants()
+        &&& forall|cpu: nat| cpu < self.cpu_count() ==> #[trigger] self.cpu(cpu).tlb_unreserved()
+        &&& self.tlbs_coherent()
+    }
+}
+
+/// Helper functions.
+impl<G> MultiCoreState<G> where G: GhostPTE {
+    /// Number of CPUs.
+    pub open spec fn cpu_count(self) -> nat {
+        self.tlbs.len()
+    }
+
+    /// The state observed by `cpu`.
+    pub open spec fn cpu(self, cpu: nat) -> LowLevelState<G> {
+        LowLevelState {
+            mem: self.mem,
+            pt: self.pt,
+            tlb: self.tlbs[cpu as int],
+            constants: self.constants,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+} // verus!
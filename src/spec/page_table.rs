@@ -32,6 +32,11 @@ pub struct PTConstants {
     pub pmem_lb: PAddr,
     /// Physical memory upper bound.
     pub pmem_ub: PAddr,
+    /// Physical regions reserved by the hypervisor, as `(base, size)` pairs.
+    ///
+    /// Reserved memory holds the hypervisor image, stacks, page table pool and per-CPU data.
+    /// It must never be mapped.
+    pub reserved: Seq<(PAddr, nat)>,
 }
 
 /// State transition specification.
@@ -64,6 +69,8 @@ impl PageTableState {
             <= self.constants.pmem_ub.0
         // Frame should not overlap with existing pmem
         &&& !self.overlaps_pmem(frame)
+        // Frame should not overlap with reserved memory
+        &&& !self.overlaps_reserved(frame)
     }
 
     /// State transition - map a virtual address to a physical frame.
@@ -83,11 +90,12 @@ impl PageTableState {
             &&& res is Err
             // Page table should not be updated
             &&& s1.mappings === s2.mappings
+        } else if res is Ok {
+            // Mapping succeeds, update page table
+            s1.mappings.insert(vbase, frame) === s2.mappings
         } else {
-            // Mapping succeeds
-            &&& res is Ok
-            // Update page table
-            &&& s1.mappings.insert(vbase, frame) === s2.mappings
+            // Out of page table memory, page table should not be updated
+            s1.mappings === s2.mappings
         }
     }
 
@@ -167,6 +175,11 @@ impl PageTableState {
             }
     }
 
+    /// If `frame` overlaps with memory reserved by the hypervisor.
+    pub open spec fn overlaps_reserved(self, frame: Frame) -> bool {
+        PAddr::overlap_any(frame.base, frame.size.as_nat(), self.constants.reserved)
+    }
+
     /// If mapping `(vaddr, frame)` overlaps with existing virtual memory.
     pub open spec fn overlaps_vmem(self, vbase: VAddr, frame: Frame) -> bool {
         exists|vbase2: VAddr|